@@ -0,0 +1,187 @@
+// Final sizes rather than timings: the native layout through zstd, this format alone, and this
+// format through zstd, for a few configs shaped like real ones. Compression is left to whatever
+// runs after serialization, so this checks how the bit packing and zstd get along:
+// `cargo bench --bench compressed_size`
+use solution::serializer::{PropertyValue, Serializer};
+
+// zstd's own default
+const LEVEL: i32 = 3;
+
+// xorshift, so the values aren't sequences zstd can trivially predict but the corpus is the same
+// every run
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: u64) -> i64 {
+        (self.next() % bound) as i64
+    }
+
+    fn pick<'a>(&mut self, words: &[&'a str]) -> &'a str {
+        words[self.next() as usize % words.len()]
+    }
+}
+
+const WORDS: &[&str] = &[
+    "api", "auth", "cache", "db", "edge", "events", "gateway", "internal", "metrics", "orders",
+    "payments", "queue", "search", "session", "storage", "users",
+];
+const REGIONS: &[&str] = &["eu-1", "eu-2", "us-east", "us-west", "ap-south"];
+
+struct Sizes {
+    native_zstd: usize,
+    packed: usize,
+    packed_zstd: usize,
+}
+
+fn sizes(serializer: &Serializer) -> Sizes {
+    let mut native = Vec::new();
+    serializer.finish_native(&mut native, 0);
+    let mut packed = Vec::new();
+    serializer.finish(&mut packed, 0);
+    let compressed = |bytes: &[u8]| zstd::bulk::compress(bytes, LEVEL).unwrap().len();
+    Sizes {
+        native_zstd: compressed(&native),
+        packed: packed.len(),
+        packed_zstd: compressed(&packed),
+    }
+}
+
+fn main() {
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+
+    // service configs: host, port, log level, timeout, a couple of flags
+    let services: Vec<([String; 2], [i64; 3], [bool; 2])> = (0..8)
+        .map(|_| {
+            let host = format!("{}.{}.example.com", rng.pick(WORDS), rng.pick(REGIONS));
+            let level = ["debug", "info", "warn"][rng.below(3) as usize].to_owned();
+            let ints = [1024 + rng.below(60_000), rng.below(120), rng.below(16)];
+            ([host, level], ints, [rng.below(2) == 0, rng.below(2) == 0])
+        })
+        .collect();
+
+    // feature flags: a name and an on/off for each
+    let flags: Vec<(String, bool)> = (0..60)
+        .map(|_| {
+            let name = format!("{}_{}_enabled", rng.pick(WORDS), rng.pick(WORDS));
+            (name, rng.below(4) != 0)
+        })
+        .collect();
+
+    // sorted dotted keys with their values, where front coding and zstd both find repetition
+    let mut keys: Vec<String> = (0..120)
+        .map(|_| {
+            let setting =
+                ["timeout_ms", "retries", "pool_size", "max_bytes"][rng.below(4) as usize];
+            format!("{}.{}.{setting}", rng.pick(WORDS), rng.pick(WORDS))
+        })
+        .collect();
+    keys.sort();
+    let key_values: Vec<i64> = keys.iter().map(|_| rng.below(10_000)).collect();
+
+    // readings: timestamps a jittered minute apart, small gauges and an alert flag
+    let readings: Vec<(i64, i64, bool)> = (0..150)
+        .scan(1_760_000_000, |time, _| {
+            *time += 55 + rng.below(10);
+            Some((*time, rng.below(101), rng.below(20) == 0))
+        })
+        .collect();
+
+    // nested arrays of clusters and their nodes
+    let clusters: Vec<PropertyValue> = (0..12)
+        .map(|_| {
+            let region = rng.pick(REGIONS);
+            PropertyValue::Array(
+                (0..1 + rng.below(6))
+                    .map(|node| {
+                        PropertyValue::Array(vec![
+                            PropertyValue::String(format!("node{node}.{region}")),
+                            PropertyValue::Integer(9000 + rng.below(100)),
+                            PropertyValue::Bool(rng.below(3) != 0),
+                        ])
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let mut corpus: Vec<(String, Serializer)> = Vec::new();
+    for (i, ([host, level], ints, bools)) in services.iter().enumerate() {
+        let mut serializer = Serializer::new();
+        serializer.write_string(host);
+        serializer.write_int(ints[0]);
+        serializer.write_string(level);
+        serializer.write_int(ints[1]);
+        serializer.write_int(ints[2]);
+        serializer.write_bool(bools[0]);
+        serializer.write_bool(bools[1]);
+        corpus.push((format!("service {i}"), serializer));
+    }
+
+    let mut serializer = Serializer::new();
+    for (name, enabled) in &flags {
+        serializer.write_string(name);
+        serializer.write_bool(*enabled);
+    }
+    corpus.push(("feature flags".to_owned(), serializer));
+
+    let mut serializer = Serializer::new();
+    serializer.set_front_coding(true);
+    for (key, value) in keys.iter().zip(&key_values) {
+        serializer.write_string(key);
+        serializer.write_int(*value);
+    }
+    corpus.push(("key value pairs".to_owned(), serializer));
+
+    let mut serializer = Serializer::new();
+    for (time, gauge, alert) in &readings {
+        serializer.write_int(*time);
+        serializer.write_int(*gauge);
+        serializer.write_bool(*alert);
+    }
+    corpus.push(("readings".to_owned(), serializer));
+
+    let mut serializer = Serializer::new();
+    serializer.write_array(&clusters);
+    corpus.push(("clusters".to_owned(), serializer));
+
+    println!(
+        "{:<16} {:>13} {:>13} {:>13}",
+        "config", "native+zstd", "format", "format+zstd"
+    );
+    let mut totals = (0, 0, 0);
+    for (name, serializer) in &corpus {
+        let sizes = sizes(serializer);
+        println!(
+            "{name:<16} {:>13} {:>13} {:>13}",
+            sizes.native_zstd, sizes.packed, sizes.packed_zstd
+        );
+        totals.0 += sizes.native_zstd;
+        totals.1 += sizes.packed;
+        totals.2 += sizes.packed_zstd;
+
+        // The claim is about the small messages the format is built for, zstd's frame and the
+        // native layout's fixed widths dominate those. Past a few hundred values drawn from a
+        // small vocabulary zstd finds more in the byte-aligned native layout than in the bit
+        // packed one, which the numbers above show rather than assert.
+        if name.starts_with("service") {
+            assert!(
+                sizes.packed_zstd < sizes.native_zstd,
+                "{name}: the format through zstd ({} bytes) should beat the native layout \
+                 through zstd ({} bytes)",
+                sizes.packed_zstd,
+                sizes.native_zstd
+            );
+        }
+    }
+    println!(
+        "{:<16} {:>13} {:>13} {:>13}",
+        "total", totals.0, totals.1, totals.2
+    );
+}
@@ -0,0 +1,43 @@
+// Plain timing loop like `decode_strings`, over the field-by-field reads a typical config does:
+// `cargo bench --bench decode_config`
+use std::{hint::black_box, time::Instant};
+
+use solution::serializer::{Deserializer, Serializer};
+
+const FIELDS: usize = 50;
+const ITERATIONS: u32 = 100_000;
+
+fn main() {
+    let names: Vec<String> = (0..FIELDS).map(|i| format!("setting_{i}")).collect();
+
+    let mut serializer = Serializer::new();
+    for (i, name) in names.iter().enumerate() {
+        match i % 3 {
+            0 => serializer.write_int(i as i64 * 31),
+            1 => serializer.write_string(name),
+            _ => serializer.write_bool(i % 2 == 0),
+        }
+    }
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, 0);
+
+    let mut deserializer = Deserializer::new();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        deserializer.read_bytes(black_box(&buffer), 0).unwrap();
+        for i in 0..FIELDS {
+            match i % 3 {
+                0 => _ = black_box(deserializer.take_int().unwrap()),
+                1 => _ = black_box(deserializer.take_string().unwrap()),
+                _ => _ = black_box(deserializer.take_bool().unwrap()),
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "decode a {FIELDS} field config ({} bytes): {:?} per buffer",
+        buffer.len(),
+        elapsed / ITERATIONS
+    );
+}
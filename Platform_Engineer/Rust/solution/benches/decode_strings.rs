@@ -0,0 +1,51 @@
+// Plain timing loop rather than a bench framework so it runs on stable without extra dependencies:
+// `cargo bench --bench decode_strings`
+use std::{hint::black_box, time::Instant};
+
+use solution::serializer::{Deserializer, Serializer};
+
+const ITERATIONS: u32 = 2_000;
+
+fn main() {
+    let strings: Vec<String> = (0..500)
+        .map(|i| format!("service.section{}.setting_{i}", i % 13))
+        .collect();
+
+    let mut serializer = Serializer::new();
+    for string in &strings {
+        serializer.write_string(string);
+    }
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, 0);
+
+    let mut deserializer = Deserializer::new();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        deserializer.read_bytes(black_box(&buffer), 0).unwrap();
+        while let Some(string) = deserializer.take_string() {
+            black_box(string);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "decode {} strings ({} bytes): {:?} per buffer",
+        strings.len(),
+        buffer.len(),
+        elapsed / ITERATIONS
+    );
+
+    // the same without an allocation per string
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        deserializer.read_bytes(black_box(&buffer), 0).unwrap();
+        while let Some(string) = deserializer.take_string_cow() {
+            black_box(string);
+        }
+    }
+    println!(
+        "decode {} borrowed strings: {:?} per buffer",
+        strings.len(),
+        start.elapsed() / ITERATIONS
+    );
+}
@@ -0,0 +1,52 @@
+// Plain timing loop like `decode_strings`, over a buffer large enough that filling the columns is
+// most of the work rather than the header: `cargo bench --bench decode_throughput`
+use std::{hint::black_box, time::Instant};
+
+use solution::serializer::{Deserializer, PropertyValue, Serializer};
+
+const VALUES: i64 = 50_000;
+const ITERATIONS: u32 = 100;
+const RUNS: u32 = 10;
+
+fn main() {
+    // ints spread over the narrower widths, an irregular bool pattern and a tagged array mixing
+    // all three kinds
+    let array: Vec<PropertyValue> = (0..VALUES / 2)
+        .map(|i| match i % 3 {
+            0 => PropertyValue::Integer(i * 7 % 1000),
+            1 => PropertyValue::Bool(i % 5 < 2),
+            _ => PropertyValue::Array(vec![PropertyValue::Integer(i)]),
+        })
+        .collect();
+    let mut serializer = Serializer::new();
+    for i in 0..VALUES {
+        serializer.write_int(i * 37 % 5000);
+        serializer.write_bool(i % 7 < 3);
+    }
+    serializer.write_array(&array);
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, 0);
+
+    // the best of a few runs, the machine's noise only ever adds time
+    let mut deserializer = Deserializer::new();
+    let per_buffer = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for _ in 0..ITERATIONS {
+                deserializer.read_bytes(black_box(&buffer), 0).unwrap();
+                black_box(deserializer.remaining_view().counts());
+                deserializer.clear();
+            }
+            start.elapsed() / ITERATIONS
+        })
+        .min()
+        .unwrap();
+    let megabytes = buffer.len() as f64 / 1_000_000.0;
+    println!(
+        "decode {} bytes ({VALUES} ints, {VALUES} bools, {} tagged values): {per_buffer:?} per \
+         buffer, {:.1} MB/s",
+        buffer.len(),
+        array.len(),
+        megabytes / per_buffer.as_secs_f64()
+    );
+}
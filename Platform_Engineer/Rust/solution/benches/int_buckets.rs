@@ -0,0 +1,62 @@
+// Custom int widths against the default ones on integers that come in whole bytes, mostly single
+// bytes with a tail of hashes: `cargo bench --bench int_buckets`
+use std::{hint::black_box, time::Instant};
+
+use solution::{
+    int_plan::{IntHeaderPlan, analyze_ints},
+    serializer::{Deserializer, Serializer},
+};
+
+const INTS: i64 = 10_000;
+const ITERATIONS: u32 = 1_000;
+
+fn skewed() -> Vec<i64> {
+    (0..INTS)
+        .map(|i| match i % 20 {
+            0..12 => i % 256,
+            12..17 => 1_000 + i % 60_000,
+            17..19 => 100_000 + i * 97 % 16_000_000,
+            // hashes, the full 64 bits
+            _ => i.wrapping_mul(0x9e37_79b9_7f4a_7c15_u64 as i64),
+        })
+        .collect()
+}
+
+fn main() {
+    let ints = skewed();
+    let plans = [
+        ("default", IntHeaderPlan::default()),
+        ("best table", analyze_ints(&ints)),
+        (
+            "1/2/3/4/8 bytes",
+            IntHeaderPlan::byte_buckets(&[1, 2, 3, 4, 8]).unwrap(),
+        ),
+    ];
+
+    let mut deserializer = Deserializer::new();
+    for (name, plan) in plans {
+        let mut serializer = Serializer::new();
+        serializer.set_int_header_plan(plan);
+        for int in &ints {
+            serializer.write_int(*int);
+        }
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            deserializer.read_bytes(black_box(&buffer), 0).unwrap();
+            while let Some(int) = deserializer.take_int() {
+                black_box(int);
+            }
+        }
+        let elapsed = start.elapsed();
+
+        println!(
+            "{name:>16}: {:>6} bytes ({} bits of integers), {:?} per decode",
+            buffer.len(),
+            stats.integers.bits,
+            elapsed / ITERATIONS
+        );
+    }
+}
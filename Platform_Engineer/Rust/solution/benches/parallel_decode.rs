@@ -0,0 +1,77 @@
+// `cargo bench --features rayon --bench parallel_decode`
+//
+// `read_bytes` against `read_bytes_parallel` for string columns of a few sizes, on rayon pools of
+// 1, 2, 4 and every available thread. The speedup only shows on a machine with more than one core,
+// the `cores` line says how many this run had. Buffers under `PARALLEL_MIN_BYTES` aren't split, so
+// they measure the cost of asking, and a single thread pool falls back to the serial path.
+use std::{
+    hint::black_box,
+    thread,
+    time::{Duration, Instant},
+};
+
+use solution::serializer::{Deserializer, PARALLEL_MIN_BYTES, Serializer};
+
+const ITERATIONS: u32 = 10;
+const STRING_COUNTS: [usize; 4] = [500, 2_000, 10_000, 100_000];
+
+fn time(mut decode: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        decode();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn buffer(count: usize) -> Vec<u8> {
+    let mut serializer = Serializer::new();
+    serializer.set_column_offsets(true);
+    for i in 0..count {
+        serializer.write_int(i as i64);
+        serializer.write_string_owned(format!(
+            "/var/lib/service/data/shard_{:04}/segment-{i}.log",
+            i % 4096
+        ));
+    }
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, 0);
+    buffer
+}
+
+fn main() {
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get());
+    println!("cores: {cores}, parallel from {PARALLEL_MIN_BYTES} bytes");
+    let mut threads = vec![1, 2, 4, cores];
+    threads.sort();
+    threads.dedup();
+
+    for count in STRING_COUNTS {
+        let buffer = buffer(count);
+        let mut deserializer = Deserializer::new();
+        let serial = time(|| {
+            deserializer.clear();
+            deserializer.read_bytes(black_box(&buffer), 0).unwrap();
+        });
+        println!("{count} strings, {} bytes", buffer.len());
+        println!("  serial:             {serial:?}");
+
+        for threads in &threads {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(*threads)
+                .build()
+                .unwrap();
+            let parallel = pool.install(|| {
+                time(|| {
+                    deserializer.clear();
+                    deserializer
+                        .read_bytes_parallel(black_box(&buffer), 0)
+                        .unwrap();
+                })
+            });
+            println!(
+                "  parallel, {threads:>2} threads: {parallel:?} ({:.2}x)",
+                serial.as_secs_f64() / parallel.as_secs_f64()
+            );
+        }
+    }
+}
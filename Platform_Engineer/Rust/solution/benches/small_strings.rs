@@ -0,0 +1,87 @@
+// `cargo bench --features small-string --bench small_strings`
+//
+// Allocations and time to take a column of short strings out as `String`s against `SmallString`s.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use solution::serializer::{Deserializer, Serializer};
+
+const ITERATIONS: u32 = 2_000;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn main() {
+    // keys and values of a few bytes, all within `INLINE_CAPACITY`
+    let strings: Vec<String> = (0..1000)
+        .map(|i| match i % 3 {
+            0 => format!("key_{i}"),
+            1 => format!("zone-{}", i % 7),
+            _ => format!("{}ms", i * 5),
+        })
+        .collect();
+
+    let mut serializer = Serializer::new();
+    for string in &strings {
+        serializer.write_string(string);
+    }
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, 0);
+
+    let mut deserializer = Deserializer::new();
+    // sizes the deserializer's columns, so what's counted is only the strings themselves
+    deserializer.read_bytes(&buffer, 0).unwrap();
+    deserializer.clear();
+
+    let mut measure = |name: &str, take: &mut dyn FnMut(&mut Deserializer)| {
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            deserializer.read_bytes(black_box(&buffer), 0).unwrap();
+            take(&mut deserializer);
+        }
+        let elapsed = start.elapsed();
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+        println!(
+            "{name:>13}: {} allocations, {:?} per buffer of {} strings",
+            allocations / ITERATIONS as usize,
+            elapsed / ITERATIONS,
+            strings.len()
+        );
+    };
+
+    measure("String", &mut |deserializer| {
+        while let Some(string) = deserializer.take_string() {
+            black_box(string);
+        }
+    });
+    measure("SmallString", &mut |deserializer| {
+        while let Some(string) = deserializer.take_small_string() {
+            black_box(string);
+        }
+    });
+}
@@ -0,0 +1,694 @@
+use std::fmt;
+
+use crate::{
+    bit_packer::{BitPacker, BitUnpacker},
+    columns,
+    error::{DecodeError, Section},
+    format_spec::{TAG_BITS, TAIL_LENGTH_BYTES},
+    limits::{DecodeLimits, DepthExceeded, Limit},
+    serializer::{Deserializer, PropertyType, PropertyValue, value_depth},
+};
+
+// Append-heavy arrays (logs, history, etc.) would otherwise need the whole config re-serialized
+// for every new element since array values are flattened into the shared pools.
+//
+// The tail array trades some compactness for being growable in place:
+// - it is the final section of the buffer and starts on a byte boundary
+// - fixed width u32 length prefix so we can rewrite it without shifting anything after it
+// - each element is encoded inline (tag + value) and padded out to a byte boundary, so new
+//   elements are just concatenated onto the end.
+//
+// The prefix is `TAIL_LENGTH_BYTES` wide.
+
+// strings outside of the pooled string column pick their own codec, so they carry the ascii bit
+pub(crate) fn write_inline_string(packer: &mut BitPacker, string: &str) {
+    let ascii = columns::ascii_encodable(&[string]);
+    packer.write_bit(ascii);
+    if ascii {
+        packer
+            .write_ascii_string_adaptive(string)
+            .expect("checked by ascii_encodable");
+    } else {
+        packer.write_unicode_huffman_string(string);
+    }
+}
+
+pub(crate) fn read_inline_string_into(
+    unpacker: &mut BitUnpacker,
+    bytes: &mut Vec<u8>,
+) -> Option<()> {
+    if !unpacker.read_bit()? {
+        unpacker.read_unicode_huffman_into(bytes)
+    } else if unpacker.read_bit()? {
+        unpacker.read_ascii_huffman_into(bytes)
+    } else {
+        unpacker.read_ascii_ultrapacked_into(bytes)
+    }
+}
+
+pub fn write_inline_value(packer: &mut BitPacker, value: &PropertyValue) {
+    match value {
+        PropertyValue::String(string) => {
+            packer.write_property_type(PropertyType::String);
+            write_inline_string(packer, string);
+        }
+        PropertyValue::Bool(bool) => {
+            packer.write_property_type(PropertyType::Bool);
+            packer.write_bit(*bool);
+        }
+        PropertyValue::Integer(int) => {
+            packer.write_property_type(PropertyType::Integer);
+            packer.write_int(*int);
+        }
+        PropertyValue::Array(values) => {
+            packer.write_property_type(PropertyType::Array);
+            let length = i64::try_from(values.len()).expect("array length doesn't fit in an i64");
+            packer.write_int(length);
+            for value in values {
+                write_inline_value(packer, value);
+            }
+        }
+    }
+}
+
+// `read_inline_value_limited` with no limits, still bounded by the input since every value costs
+// at least a tag
+pub fn read_inline_value(unpacker: &mut BitUnpacker) -> Option<PropertyValue> {
+    let budget = &mut TailBudget::new(DecodeLimits::unlimited(), u64::MAX, u64::MAX);
+    read_inline_value_limited(unpacker, budget, &mut 0).ok()
+}
+
+// What the tail may still cost a reader, from its `DecodeLimits`. The tail array counts as depth
+// 1, so an array element is depth 2 the way it would be inside `take_array`, and every value in
+// it (nested ones included) counts towards `max_column_values` as though the tail were a column.
+// The decoded bytes are whatever the columns left of `max_decoded_bytes`, counted the same way:
+// 8 per integer and array length, 1 per boolean and tag, plus the bytes of every string. The work
+// is whatever the columns left of `max_work`, 1 for the tail's length and then 2 for each value
+// (its tag, and its value or length).
+#[derive(Debug)]
+pub(crate) struct TailBudget {
+    limits: DecodeLimits,
+    values: u64,
+    decoded_bytes: u64,
+    work: u64,
+}
+
+impl TailBudget {
+    pub fn new(limits: DecodeLimits, decoded_bytes: u64, work: u64) -> Self {
+        TailBudget {
+            limits,
+            values: limits.max_column_values,
+            decoded_bytes,
+            work,
+        }
+    }
+
+    pub fn work_left(&self) -> u64 {
+        self.work
+    }
+
+    fn work(&mut self, values: u64) -> Result<(), DecodeError> {
+        self.work = self
+            .work
+            .checked_sub(values)
+            .ok_or(DecodeError::WorkBudgetExceeded {
+                max: self.limits.max_work,
+            })?;
+        Ok(())
+    }
+
+    // one more value (a tag and what follows it) decoding to `bytes`
+    fn value(&mut self, bytes: u64) -> Result<(), DecodeError> {
+        self.work(2)?;
+        let limits = &self.limits;
+        self.values = self
+            .values
+            .checked_sub(1)
+            .ok_or(DecodeError::LimitExceeded {
+                limit: Limit::ColumnValues,
+                max: limits.max_column_values,
+            })?;
+        self.decoded_bytes =
+            self.decoded_bytes
+                .checked_sub(bytes)
+                .ok_or(DecodeError::LimitExceeded {
+                    limit: Limit::DecodedBytes,
+                    max: limits.max_decoded_bytes,
+                })?;
+        Ok(())
+    }
+
+    fn depth(&self, depth: usize) -> Result<(), DecodeError> {
+        match depth > self.limits.max_depth {
+            true => Err(DecodeError::LimitExceeded {
+                limit: Limit::Depth,
+                max: self.limits.max_depth as u64,
+            }),
+            false => Ok(()),
+        }
+    }
+}
+
+// Nested arrays are read with an explicit stack rather than recursion, like `take_array`, so the
+// depth of the input costs heap rather than the call stack. `pending` is how many elements the
+// arrays already started (the tail's own included) are still waiting on, each costs at least a
+// tag's worth of input, so no array reserves more than what's left after them.
+pub(crate) fn read_inline_value_limited(
+    unpacker: &mut BitUnpacker,
+    budget: &mut TailBudget,
+    pending: &mut u64,
+) -> Result<PropertyValue, DecodeError> {
+    let truncated = DecodeError::Truncated(Section::Tail);
+    // arrays being filled (innermost last) and how many values each still needs
+    let mut stack: Vec<(Vec<PropertyValue>, u64)> = Vec::new();
+    loop {
+        let mut value = match unpacker.read_property_type() {
+            // the tags are inline, so running out is the tail's
+            Err(DecodeError::Truncated(_)) => return Err(truncated),
+            tag => match tag? {
+                PropertyType::String => {
+                    let mut bytes = Vec::new();
+                    read_inline_string_into(unpacker, &mut bytes).ok_or(truncated.clone())?;
+                    budget.value(bytes.len() as u64 + 1)?;
+                    Some(PropertyValue::String(
+                        String::from_utf8_lossy(&bytes).into_owned(),
+                    ))
+                }
+                PropertyType::Bool => {
+                    budget.value(2)?;
+                    Some(PropertyValue::Bool(
+                        unpacker.read_bit().ok_or(truncated.clone())?,
+                    ))
+                }
+                PropertyType::Integer => {
+                    budget.value(9)?;
+                    Some(PropertyValue::Integer(
+                        unpacker.read_int().ok_or(truncated.clone())?,
+                    ))
+                }
+                PropertyType::Array => {
+                    budget.value(9)?;
+                    budget.depth(stack.len() + 2)?;
+                    // negative only comes from corrupt input
+                    let length = unpacker.read_int().ok_or(truncated.clone())?;
+                    let length = u64::try_from(length).map_err(|_| truncated.clone())?;
+                    let available = unpacker.remaining_bits().saturating_sub(*pending);
+                    let capacity = usize::try_from(length.min(available)).unwrap_or(usize::MAX);
+                    *pending = pending.saturating_add(length);
+                    stack.push((Vec::with_capacity(capacity), length));
+                    None
+                }
+            },
+        };
+        // hand finished values (and the arrays they finish) up to their parents
+        loop {
+            let Some((values, remaining)) = stack.last_mut() else {
+                return Ok(value.expect("only a finished array leaves the stack empty"));
+            };
+            if let Some(value) = value.take() {
+                values.push(value);
+                *remaining -= 1;
+                *pending -= 1;
+            }
+            if *remaining > 0 {
+                break;
+            }
+            let (values, _) = stack.pop().expect("checked above");
+            value = Some(PropertyValue::Array(values));
+        }
+    }
+}
+
+// Walks a value without building it, with a stack of how many values each array it's in still
+// has rather than recursion. A tag no `PropertyType` maps to is `InvalidPropertyTag`, going past
+// `budget` is `LimitExceeded` and anything else wrong is `Truncated(Tail)`.
+fn skip_inline_value(
+    unpacker: &mut BitUnpacker,
+    scratch: &mut Vec<u8>,
+    budget: &mut TailBudget,
+) -> Result<(), DecodeError> {
+    let truncated = DecodeError::Truncated(Section::Tail);
+    let mut remaining: Vec<u64> = Vec::new();
+    loop {
+        let tag = match unpacker.read_property_type() {
+            // the tags are inline, so running out is the tail's
+            Err(DecodeError::Truncated(_)) => return Err(truncated),
+            tag => tag?,
+        };
+        match tag {
+            PropertyType::String => {
+                scratch.clear();
+                read_inline_string_into(unpacker, scratch).ok_or(truncated.clone())?;
+                budget.value(scratch.len() as u64 + 1)?;
+            }
+            PropertyType::Bool => {
+                unpacker.read_bit().ok_or(truncated.clone())?;
+                budget.value(2)?;
+            }
+            PropertyType::Integer => {
+                unpacker.read_int().ok_or(truncated.clone())?;
+                budget.value(9)?;
+            }
+            PropertyType::Array => {
+                budget.value(9)?;
+                budget.depth(remaining.len() + 2)?;
+                let length = unpacker.read_int().ok_or(truncated.clone())?;
+                remaining.push(u64::try_from(length).map_err(|_| truncated.clone())?);
+            }
+        }
+        while remaining.last() == Some(&0) {
+            remaining.pop();
+        }
+        // the next value is one of the innermost unfinished array's
+        match remaining.last_mut() {
+            Some(left) => *left -= 1,
+            None => return Ok(()),
+        }
+    }
+}
+
+// byte-aligned element, appended to the end of `buffer`
+fn append_element(buffer: &mut Vec<u8>, value: &PropertyValue, tag_bits: u8) {
+    let mut scratch = Vec::new();
+    let mut packer = BitPacker::new(&mut scratch);
+    packer.set_tag_bits(tag_bits);
+    write_inline_value(&mut packer, value);
+    buffer.extend_from_slice(&scratch);
+}
+
+// Why an element couldn't be added to the tail array, nothing was written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppendError {
+    // the length prefix is a u32, which already holds `u32::MAX` elements
+    Full,
+    // nested deeper than the writer's `max_depth`, counting the tail array as depth 1
+    TooDeep(DepthExceeded),
+}
+
+impl fmt::Display for AppendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppendError::Full => write!(f, "the tail array already has {} elements", u32::MAX),
+            AppendError::TooDeep(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for AppendError {}
+
+pub(crate) fn tail_length(tail: &[PropertyValue]) -> Result<u32, AppendError> {
+    u32::try_from(tail.len()).map_err(|_| AppendError::Full)
+}
+
+pub(crate) fn write_tail(buffer: &mut Vec<u8>, tail: &[PropertyValue]) {
+    let length = tail_length(tail).expect("checked by `write_tail_array`");
+    buffer.extend_from_slice(&length.to_le_bytes());
+    for value in tail {
+        append_element(buffer, value, TAG_BITS);
+    }
+}
+
+pub(crate) fn read_tail(
+    unpacker: &mut BitUnpacker,
+    budget: &mut TailBudget,
+) -> Result<Vec<PropertyValue>, DecodeError> {
+    let mut length_bytes = [0u8; TAIL_LENGTH_BYTES];
+    for byte in &mut length_bytes {
+        *byte = unpacker
+            .read_byte()
+            .ok_or(DecodeError::Truncated(Section::Tail))?;
+    }
+    let length = u32::from_le_bytes(length_bytes) as u64;
+    budget.work(1)?;
+    budget.depth(1)?;
+
+    // elements are byte aligned, so there can't be more of them than bytes left
+    let capacity = length.min(unpacker.remaining_bits() / 8) as usize;
+    let mut values = Vec::with_capacity(capacity);
+    let mut pending = length;
+    for _ in 0..length {
+        pending -= 1;
+        values.push(read_inline_value_limited(unpacker, budget, &mut pending)?);
+        unpacker.align_to_byte();
+    }
+    Ok(values)
+}
+
+// `read_tail` without materializing anything, (element count, decoded bytes). Only the depth is
+// limited, to what `Deserializer::new` reads, so `verify` fails rather than taking any amount of
+// time over a tail no default reader would take.
+pub(crate) fn skip_tail(
+    unpacker: &mut BitUnpacker,
+    scratch: &mut Vec<u8>,
+) -> Result<(usize, u64), DecodeError> {
+    let mut length_bytes = [0u8; TAIL_LENGTH_BYTES];
+    for byte in &mut length_bytes {
+        *byte = unpacker
+            .read_byte()
+            .ok_or(DecodeError::Truncated(Section::Tail))?;
+    }
+    let length = u32::from_le_bytes(length_bytes) as usize;
+
+    let limits = DecodeLimits::unlimited().max_depth(DecodeLimits::relaxed().max_depth);
+    let mut budget = TailBudget::new(limits, u64::MAX, u64::MAX);
+    for _ in 0..length {
+        skip_inline_value(unpacker, scratch, &mut budget)?;
+        unpacker.align_to_byte();
+    }
+    Ok((length, u64::MAX - budget.decoded_bytes))
+}
+
+/// Appends elements to the tail array of an already serialized buffer, only touching the length
+/// prefix and the end of the buffer.
+pub struct ArrayAppender<'b> {
+    buffer: &'b mut Vec<u8>,
+    length_offset: usize,
+    length: u32,
+    // elements are appended with the tags of the buffer's revision
+    tag_bits: u8,
+    // see `set_max_depth`
+    max_depth: usize,
+}
+
+impl<'b> ArrayAppender<'b> {
+    // `None` if the buffer can't be read, wasn't written with `write_tail_array` or was padded by
+    // `Serializer::finish_aligned`
+    pub fn new(buffer: &'b mut Vec<u8>, version: u8) -> Option<Self> {
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(buffer, version)?;
+        if deserializer.padded {
+            return None;
+        }
+        let length_offset = deserializer.tail_offset?;
+
+        let length_bytes = buffer.get(length_offset..length_offset + TAIL_LENGTH_BYTES)?;
+        let length = u32::from_le_bytes(length_bytes.try_into().ok()?);
+
+        Some(ArrayAppender {
+            buffer,
+            length_offset,
+            length,
+            tag_bits: deserializer.tag_bits,
+            max_depth: DecodeLimits::relaxed().max_depth,
+        })
+    }
+
+    // The deepest arrays `append` will write, with the tail array itself at depth 1. Defaults to
+    // what `Deserializer::new` reads, like `Serializer::set_max_depth`.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    pub fn len(&self) -> usize {
+        self.length as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn append(&mut self, value: &PropertyValue) -> Result<(), AppendError> {
+        let depth = value_depth(value) + 1;
+        if depth > self.max_depth {
+            return Err(AppendError::TooDeep(DepthExceeded {
+                depth,
+                max: self.max_depth,
+            }));
+        }
+        self.length = self.length.checked_add(1).ok_or(AppendError::Full)?;
+        append_element(self.buffer, value, self.tag_bits);
+
+        let prefix = &mut self.buffer[self.length_offset..self.length_offset + TAIL_LENGTH_BYTES];
+        prefix.copy_from_slice(&self.length.to_le_bytes());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::{IntoFormat, Serializer};
+
+    // a buffer with a single int and a tail of whatever `write` packs
+    fn with_tail(elements: u32, write: impl FnOnce(&mut BitPacker)) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        serializer.write_int(1);
+        serializer.write_tail_array(&[]);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        buffer.truncate(buffer.len() - TAIL_LENGTH_BYTES);
+        buffer.extend_from_slice(&elements.to_le_bytes());
+        let mut tail = Vec::new();
+        write(&mut BitPacker::new(&mut tail));
+        buffer.extend_from_slice(&tail);
+        buffer
+    }
+
+    // one element, `depth` arrays each holding the next
+    fn deep_tail(depth: usize) -> Vec<u8> {
+        with_tail(1, |packer| {
+            for level in 1..=depth {
+                packer.write_property_type(PropertyType::Array);
+                packer.write_int(if level < depth { 1 } else { 0 });
+            }
+        })
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Log {
+        level: i64,
+        entries: Vec<PropertyValue>,
+    }
+
+    impl IntoFormat for Log {
+        fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+            serializer.write_int(self.level);
+            serializer.write_tail_array(self.entries.as_slice());
+        }
+
+        fn take(deserializer: &mut Deserializer) -> Option<Self> {
+            Some(Log {
+                level: deserializer.take_int()?,
+                entries: deserializer.take_tail_array()?,
+            })
+        }
+    }
+
+    fn to_bytes(log: &Log) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        let mut buffer = Vec::new();
+        log.serialize(&mut serializer);
+        serializer.finish(&mut buffer, 0);
+        buffer
+    }
+
+    #[test]
+    pub fn append_matches_single_shot() {
+        let entries = vec![
+            PropertyValue::String("service started".to_owned()),
+            PropertyValue::Integer(404),
+            PropertyValue::Array(vec![
+                PropertyValue::Bool(true),
+                PropertyValue::String("ünïcode".to_owned()),
+            ]),
+        ];
+
+        let mut buffer = to_bytes(&Log {
+            level: 3,
+            entries: Vec::new(),
+        });
+        for entry in &entries {
+            let mut appender = ArrayAppender::new(&mut buffer, 0).unwrap();
+            appender.append(entry).unwrap();
+        }
+
+        let full = Log { level: 3, entries };
+        let single_shot = to_bytes(&full);
+        assert_eq!(buffer, single_shot);
+
+        let mut deserializer = Deserializer::new();
+        assert_eq!(Log::deserialize(&buffer, &mut deserializer, 0), Some(full));
+    }
+
+    #[test]
+    pub fn length_prefix_is_little_endian() {
+        let log = Log {
+            level: 0,
+            entries: vec![PropertyValue::Bool(true); 0x0102],
+        };
+        let buffer = to_bytes(&log);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        let offset = deserializer.tail_offset.unwrap();
+        assert_eq!(buffer[offset..offset + 4], [0x02, 0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    pub fn negative_inline_length_is_rejected() {
+        let mut bytes = Vec::new();
+        let mut packer = BitPacker::new(&mut bytes);
+        packer.write_property_type(PropertyType::Array);
+        packer.write_int(-1);
+        packer.write_property_type(PropertyType::Bool);
+        packer.write_bit(true);
+
+        assert_eq!(read_inline_value(&mut BitUnpacker::new(&bytes)), None);
+        let mut scratch = Vec::new();
+        let budget = &mut TailBudget::new(DecodeLimits::unlimited(), u64::MAX, u64::MAX);
+        assert_eq!(
+            skip_inline_value(&mut BitUnpacker::new(&bytes), &mut scratch, budget),
+            Err(DecodeError::Truncated(Section::Tail))
+        );
+    }
+
+    #[test]
+    pub fn append_requires_tail() {
+        let mut serializer = Serializer::new();
+        let mut buffer = Vec::new();
+        serializer.write_int(1);
+        serializer.finish(&mut buffer, 0);
+
+        assert!(ArrayAppender::new(&mut buffer, 0).is_none());
+    }
+
+    #[test]
+    pub fn deep_tails_are_limited() {
+        // past the depth limit, not past the stack
+        let deep = deep_tail(100_000);
+        let mut deserializer = Deserializer::new();
+        assert_eq!(
+            deserializer.load(&deep, 0),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::Depth,
+                max: 1024
+            })
+        );
+
+        // the tail is depth 1, so its element can nest 1023 more
+        let limits = DecodeLimits::relaxed();
+        let mut deserializer = Deserializer::with_limits(limits);
+        deserializer.load(&deep_tail(1023), 0).unwrap();
+        assert!(deserializer.load(&deep_tail(1024), 0).is_err());
+
+        let mut deserializer = Deserializer::with_limits(DecodeLimits::unlimited());
+        deserializer.load(&deep_tail(10_000), 0).unwrap();
+        let mut value = &deserializer.take_tail_array().unwrap()[0];
+        for _ in 1..10_000 {
+            let PropertyValue::Array(values) = value else {
+                panic!("{value:?}")
+            };
+            value = &values[0];
+        }
+        assert_eq!(*value, PropertyValue::Array(Vec::new()));
+    }
+
+    #[test]
+    pub fn tail_values_are_limited() {
+        let bools = with_tail(4, |packer| {
+            for _ in 0..4 {
+                packer.write_property_type(PropertyType::Bool);
+                packer.write_bit(true);
+                packer.align_to_byte();
+            }
+        });
+        let limits = DecodeLimits::relaxed();
+        let load = |limits| Deserializer::with_limits(limits).load(&bools, 0);
+        load(limits.max_column_values(4)).unwrap();
+        assert_eq!(
+            load(limits.max_column_values(3)),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::ColumnValues,
+                max: 3
+            })
+        );
+
+        // a tag and a bit for each boolean, on top of the 8 for the int column
+        load(limits.max_decoded_bytes(8 + 4 * 2)).unwrap();
+        assert_eq!(
+            load(limits.max_decoded_bytes(8 + 4 * 2 - 1)),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::DecodedBytes,
+                max: 15
+            })
+        );
+    }
+
+    #[test]
+    pub fn full_tails_are_errors() {
+        let mut buffer = to_bytes(&Log {
+            level: 0,
+            entries: Vec::new(),
+        });
+        let mut appender = ArrayAppender::new(&mut buffer, 0).unwrap();
+        appender.length = u32::MAX;
+        let before = appender.buffer.len();
+        assert_eq!(
+            appender.append(&PropertyValue::Bool(true)),
+            Err(AppendError::Full)
+        );
+        assert_eq!(appender.len(), u32::MAX as usize);
+        assert_eq!(appender.buffer.len(), before);
+    }
+
+    #[test]
+    pub fn verify_walks_deep_tails() {
+        assert_eq!(
+            crate::serializer::verify(&deep_tail(1_000_000), 0),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::Depth,
+                max: 1024
+            })
+        );
+        let report = crate::serializer::verify(&deep_tail(1023), 0).unwrap();
+        assert_eq!(report.tail_values, 1);
+        // a tag and a length for each array
+        assert_eq!(report.decoded_bytes, 8 + 1023 * 9);
+    }
+
+    #[test]
+    pub fn strict_refuses_crafted_tails() {
+        let mut deserializer = Deserializer::new_untrusted();
+        let deep = deep_tail(100_000);
+        assert!(deep.len() < 100 * 1024);
+        assert_eq!(
+            deserializer.load(&deep, 0),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::Depth,
+                max: 32
+            })
+        );
+        deserializer.load(&deep_tail(31), 0).unwrap();
+        deserializer.clear();
+
+        // more values than a column may hold, each one a byte
+        let wide = with_tail(1 << 17, |packer| {
+            for _ in 0..1 << 17 {
+                packer.write_property_type(PropertyType::Integer);
+                packer.write_int(0);
+                packer.align_to_byte();
+            }
+        });
+        assert_eq!(
+            deserializer.load(&wide, 0),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::ColumnValues,
+                max: 1 << 16
+            })
+        );
+
+        // a single string past the decoded bytes
+        let long = "x".repeat(1 << 20);
+        let long = with_tail(1, |packer| {
+            packer.write_property_type(PropertyType::String);
+            write_inline_string(packer, &long);
+        });
+        assert_eq!(
+            deserializer.load(&long, 0),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::DecodedBytes,
+                max: 1 << 20
+            })
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::marker::PhantomData;
+
+use crate::{
+    serializer::{Deserializer, IntoFormat, Serializer},
+    stats::EncodeStats,
+};
+
+// Many records of one type in a single buffer, sharing its columns: the record count in the length
+// column and then each record's fields the way `IntoFormat::serialize` writes them, read back with
+// `Deserializer::take_batch`.
+//
+// `extend` takes the records by value, from a database cursor, a channel or anything else that
+// hands them over one at a time. Each one is written into a scratch serializer first and then
+// moved onto the batch's columns with its strings owned, so the record can be dropped straight
+// away rather than kept alive (and collected somewhere) until `finish`. The columns still hold
+// every value until then, it's only the records themselves that don't have to.
+//
+// Records can't use `write_named_*` or `write_tail_array`, those keep borrowing from the record.
+pub struct BatchSerializer<S> {
+    serializer: Serializer<'static>,
+    // borrows from one record at a time, kept for its allocations
+    scratch: Option<Serializer<'static>>,
+    // where the count is in the length column
+    count_index: usize,
+    count: usize,
+    records: PhantomData<fn(S)>,
+}
+
+impl<S: IntoFormat> Default for BatchSerializer<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: IntoFormat> BatchSerializer<S> {
+    pub fn new() -> Self {
+        Self::with_serializer(Serializer::new())
+    }
+
+    // Starts the batch after whatever `serializer` already holds, with its options (front coding,
+    // column offsets and so on).
+    pub fn with_serializer(mut serializer: Serializer<'static>) -> Self {
+        let count_index = serializer.write_length_placeholder();
+        let mut scratch = Serializer::new();
+        // the batch drops them again if it isn't keeping them
+        scratch.set_kind_markers(true);
+        BatchSerializer {
+            serializer,
+            scratch: Some(scratch),
+            count_index,
+            count: 0,
+            records: PhantomData,
+        }
+    }
+
+    pub fn extend<I: IntoIterator<Item = S>>(&mut self, records: I) {
+        for record in records {
+            self.push(record);
+        }
+    }
+
+    pub fn push(&mut self, record: S) {
+        let mut scratch = self
+            .scratch
+            .take()
+            .expect("scratch is put back after each record");
+        record.serialize(&mut scratch);
+        self.serializer.append_owned(&mut scratch);
+        self.scratch = Some(scratch.reuse());
+        self.count += 1;
+        self.serializer.patch_length(self.count_index, self.count);
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn finish(&self, buffer: &mut Vec<u8>, version: u8) -> EncodeStats {
+        self.serializer.finish(buffer, version)
+    }
+
+    // for writing more after the batch
+    pub fn into_serializer(self) -> Serializer<'static> {
+        self.serializer
+    }
+}
+
+impl Deserializer {
+    // The records of a `BatchSerializer`, `None` if any of them can't be taken.
+    pub fn take_batch<S: IntoFormat>(&mut self) -> Option<Vec<S>> {
+        let count = self.take_length()?;
+        // collected without reserving for the count, which comes from the buffer
+        (0..count).map(|_| S::take(self)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        derive_format,
+        serializer::{LoadState, PropertyValue},
+    };
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Config {
+            data: i64,
+            name: String,
+            cool: bool,
+            arr: Vec<PropertyValue>,
+            ports: Vec<i64>,
+        }
+    }
+
+    fn configs() -> impl Iterator<Item = Config> {
+        (0..1000).map(|i| Config {
+            data: i * 37 - 2_000,
+            name: format!("service-{}.internal", i % 40),
+            cool: i % 3 == 0,
+            arr: vec![
+                PropertyValue::String(format!("region-{}", i % 7)),
+                PropertyValue::Integer(i),
+                PropertyValue::Array(vec![PropertyValue::Bool(i % 2 == 0)]),
+            ],
+            ports: (0..i % 4).map(|port| 8000 + port).collect(),
+        })
+    }
+
+    #[test]
+    pub fn streamed_records_decode_in_order() {
+        let mut batch = BatchSerializer::new();
+        batch.extend(configs().take(600));
+        batch.extend(configs().skip(600));
+        assert_eq!(batch.len(), 1000);
+        let mut buffer = Vec::new();
+        batch.finish(&mut buffer, 0);
+
+        // the same columns as writing them out of a vec
+        let collected: Vec<Config> = configs().collect();
+        let mut serializer = Serializer::new();
+        serializer.write_length(collected.len());
+        for config in &collected {
+            config.serialize(&mut serializer);
+        }
+        let mut expected = Vec::new();
+        serializer.finish(&mut expected, 0);
+        assert_eq!(buffer, expected);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_batch::<Config>(), Some(collected));
+        assert_eq!(deserializer.state(), LoadState::Drained);
+    }
+
+    #[test]
+    pub fn batches_keep_the_serializers_options() {
+        let mut serializer = Serializer::new();
+        serializer.set_kind_markers(true);
+        serializer.set_column_offsets(true);
+        serializer.write_string("header");
+        let mut batch = BatchSerializer::with_serializer(serializer);
+        batch.extend(configs().take(10));
+        let mut serializer = batch.into_serializer();
+        serializer.write_int(-1);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_string().as_deref(), Some("header"));
+        let configs: Vec<Config> = configs().take(10).collect();
+        assert_eq!(deserializer.take_batch(), Some(configs));
+        assert_eq!(deserializer.take_int(), Some(-1));
+        assert_eq!(deserializer.state(), LoadState::Drained);
+
+        let empty = BatchSerializer::<Config>::new();
+        assert!(empty.is_empty());
+        empty.finish(&mut buffer, 0);
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_batch::<Config>(), Some(Vec::new()));
+    }
+}
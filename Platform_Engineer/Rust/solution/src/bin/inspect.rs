@@ -0,0 +1,94 @@
+// Prints the layout of a serialized buffer, usage:
+//   inspect <path>
+//   inspect diff <old path> <new path>
+//   inspect spec (the format constants as JSON)
+use std::process::ExitCode;
+
+use solution::{
+    format_spec::FORMAT_SPEC,
+    serializer::{Deserializer, diff, peek_header},
+};
+
+// values per column printed by `inspect <path>`
+const INSPECT_PREVIEW: usize = 8;
+const USAGE: &str = "usage: inspect <path> | inspect diff <old path> <new path> | inspect spec";
+
+fn read(path: &str) -> Option<Vec<u8>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(err) => {
+            eprintln!("failed to read {path}: {err}");
+            None
+        }
+    }
+}
+
+fn inspect(path: &str) -> ExitCode {
+    let Some(bytes) = read(path) else {
+        return ExitCode::FAILURE;
+    };
+
+    let header = match peek_header(&bytes) {
+        Ok(header) => header,
+        Err(err) => {
+            eprintln!("failed to read the header of {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // the version byte is whatever the writer chose, just take it as-is
+    let mut deserializer = Deserializer::new();
+    if deserializer.read_bytes(&bytes, header.version).is_none() {
+        // the header still says what the columns should have held
+        println!("{header}");
+        eprintln!("failed to decode {path}");
+        return ExitCode::FAILURE;
+    }
+
+    println!("version: {}", header.version);
+    println!("revision: {}", header.revision);
+    if let Some(provenance) = deserializer.provenance() {
+        println!("{provenance}");
+    }
+    println!("{}", deserializer.last_stats());
+    let values = deserializer.remaining_view();
+    if values.counts().is_empty() {
+        println!("no values, nothing was written to this buffer");
+    } else {
+        println!("first values: {}", values.preview(INSPECT_PREVIEW));
+    }
+    ExitCode::SUCCESS
+}
+
+fn diff_files(old: &str, new: &str) -> ExitCode {
+    let (Some(old_bytes), Some(new_bytes)) = (read(old), read(new)) else {
+        return ExitCode::FAILURE;
+    };
+
+    match diff(&old_bytes, &new_bytes) {
+        Ok(diff) => {
+            print!("{diff}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("failed to diff {old} and {new}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [command, old, new] if command == "diff" => diff_files(old, new),
+        [command] if command == "spec" => {
+            print!("{}", FORMAT_SPEC.to_json());
+            ExitCode::SUCCESS
+        }
+        [path] => inspect(path),
+        _ => {
+            eprintln!("{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
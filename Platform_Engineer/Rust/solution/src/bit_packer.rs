@@ -1,24 +1,43 @@
-use std::borrow::Cow;
+use std::sync::LazyLock;
 
-use crate::{huffman, serializer::PropertyType, ultra_packer};
+use crate::{
+    error::{DecodeError, Section},
+    format_spec::{INT_HEADER_WIDTHS, SMALL_INT_HEADER_BITS, SMALL_INT_HEADER_WIDTHS, TAG_BITS},
+    huffman::{self, MissingCode},
+    serializer::PropertyType,
+    ultra_packer,
+};
 
-// UTF8-style integer length
+// UTF8-style integer length over `INT_HEADER_WIDTHS` (or one of the `INT_HEADER_TABLES`)
 // prefix: 0, 10, 110, 1110, ...
 // biased towards smaller values
-const INT_WIDTHS: [u8; 7] = [3, 7, 9, 15, 24, 45, 64];
+// negative values only fit the last, full width slot
+fn int_slot_width(int: i64, widths: &[u8]) -> (usize, u8) {
+    let slot = widths
+        .iter()
+        .position(|&w| w >= 64 || (0..1i64 << w).contains(&int))
+        .unwrap_or(widths.len() - 1);
+    (slot, widths[slot])
+}
 
-fn int_slot_width(int: i64) -> (usize, u8) {
-    let slot = INT_WIDTHS
+// 3 bit header indexing into `SMALL_INT_HEADER_WIDTHS`, for values that are almost always tiny
+// (lengths, counts, etc.) where the unary prefix of `write_int` would waste bits on a fixed range.
+fn small_int_slot_width(int: u64) -> (u8, u8) {
+    let slot = SMALL_INT_HEADER_WIDTHS
         .iter()
-        .position(|&w| w >= 64 || int < (1i64 << w))
-        .unwrap_or(INT_WIDTHS.len() - 1);
-    (slot, INT_WIDTHS[slot])
+        .position(|&w| w >= 64 || int < (1u64 << w))
+        .unwrap_or(SMALL_INT_HEADER_WIDTHS.len() - 1);
+    (slot as u8, SMALL_INT_HEADER_WIDTHS[slot])
 }
 
 pub fn int_encoded_bits(int: i64) -> u64 {
-    let (slot, width) = int_slot_width(int);
+    int_encoded_bits_in(int, &INT_HEADER_WIDTHS)
+}
+
+pub fn int_encoded_bits_in(int: i64, widths: &[u8]) -> u64 {
+    let (slot, width) = int_slot_width(int, widths);
     // prefix bits (slot 1s + terminating 0, unless last slot) + data bits
-    let prefix_bits = if slot == INT_WIDTHS.len() - 1 {
+    let prefix_bits = if slot == widths.len() - 1 {
         slot
     } else {
         slot + 1
@@ -26,6 +45,17 @@ pub fn int_encoded_bits(int: i64) -> u64 {
     prefix_bits as u64 + width as u64
 }
 
+// Lengths are written as ints, which anything in memory fits since no slice is longer than
+// `isize::MAX`.
+pub(crate) fn length_int(length: usize) -> i64 {
+    i64::try_from(length).expect("length doesn't fit in an int")
+}
+
+pub fn small_int_encoded_bits(int: u64) -> u64 {
+    let (_, width) = small_int_slot_width(int);
+    SMALL_INT_HEADER_BITS as u64 + width as u64
+}
+
 // Character set bitflags for reducing possible values in packing
 const CHARSETS: u8 = 4;
 const CHARSET_UPPER: u8 = 1;
@@ -58,7 +88,7 @@ pub fn detect_charset_flags(s: &str) -> u8 {
     flags
 }
 
-fn build_charset(flags: u8) -> Vec<u8> {
+fn charset_for(flags: u8) -> Vec<u8> {
     let mut chars = Vec::new();
 
     chars.extend_from_slice(COMMON_PUNCT);
@@ -82,6 +112,14 @@ fn build_charset(flags: u8) -> Vec<u8> {
     chars
 }
 
+// only 16 possible combinations, no reason to rebuild these per string
+static CHARSET_TABLE: LazyLock<Vec<Vec<u8>>> =
+    LazyLock::new(|| (0..1u8 << CHARSETS).map(charset_for).collect());
+
+fn build_charset(flags: u8) -> &'static [u8] {
+    &CHARSET_TABLE[flags as usize]
+}
+
 fn compact_charset(c: u8, charset: &[u8]) -> u8 {
     charset
         .iter()
@@ -93,47 +131,159 @@ fn uncompact_charset(idx: u8, charset: &[u8]) -> u8 {
     charset[idx as usize]
 }
 
+/// Writes values bit by bit into a byte buffer.
+///
+/// Bits fill each byte from the most significant bit down, and multi-bit values are written most
+/// significant bit first, so `write_bits(0b101, 3)` on an empty packer leaves `[0b1010_0000]`.
+/// Unused low bits of the last byte are zero. This ordering is part of the format and won't
+/// change, `BitUnpacker` reads it back the same way.
+///
+/// ```
+/// use solution::bit_packer::{BitPacker, BitUnpacker};
+///
+/// let mut buffer = Vec::new();
+/// let mut packer = BitPacker::new(&mut buffer);
+/// packer.write_bit(true);
+/// packer.write_bits(0b0110, 4);
+/// assert_eq!(packer.bit_position(), 5);
+/// assert_eq!(packer.as_bytes(), [0b1011_0000]);
+///
+/// let mut unpacker = BitUnpacker::new(&buffer);
+/// assert_eq!(unpacker.read_bit(), Some(true));
+/// assert_eq!(unpacker.read_bits(4), Some(0b0110));
+/// ```
 pub struct BitPacker<'a> {
-    pub buffer: &'a mut Vec<u8>,
-    pub bit_offset: u8,
+    // holds exactly the bytes written to so far. `bit_offset` is how many bits of the last one are
+    // used, always less than 8: at 0 every byte is complete and the next write pushes a new one,
+    // otherwise the last byte is partial and the next write ORs into it.
+    buffer: &'a mut Vec<u8>,
+    bit_offset: u8,
+    // width of the property types, see `set_tag_bits`
+    tag_bits: u8,
 }
 
+// Where a placeholder was written, for filling it in once the value is known (a "has more" flag,
+// the length of a body that comes after it).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitPosition(u64);
+
+// byte offset of a byte-aligned placeholder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BytePosition(usize);
+
 impl<'a> BitPacker<'a> {
     pub fn new(buffer: &'a mut Vec<u8>) -> Self {
         buffer.clear();
-        buffer.push(0);
         BitPacker {
             buffer,
             bit_offset: 0,
+            tag_bits: TAG_BITS,
         }
     }
 
-    fn ensure_space(&mut self) {
-        if self.bit_offset == 8 {
+    /// Writes property types `bits` wide from here on, only for appending to a buffer of an older
+    /// revision (see `format_spec::tag_bits`).
+    pub fn set_tag_bits(&mut self, bits: u8) {
+        self.tag_bits = bits;
+    }
+
+    /// Everything written so far, the last byte padded with zero bits if it is partial.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buffer
+    }
+
+    /// Gives the buffer back, e.g. to append bytes after the packed section. A partial last
+    /// byte keeps its zero padding.
+    pub fn into_inner(self) -> &'a mut Vec<u8> {
+        self.buffer
+    }
+
+    /// Number of bits written so far.
+    pub fn bit_position(&self) -> u64 {
+        let unused = (8 - self.bit_offset as u64) % 8;
+        self.buffer.len() as u64 * 8 - unused
+    }
+
+    /// Bytes that no further writes will touch
+    pub fn complete_bytes(&self) -> usize {
+        if self.bit_offset == 0 {
+            self.buffer.len()
+        } else {
+            self.buffer.len() - 1
+        }
+    }
+
+    /// Pads out the current byte, the next write starts on a byte boundary
+    pub fn align_to_byte(&mut self) {
+        self.bit_offset = 0;
+    }
+
+    /// Copies `bytes` in as whole bytes after padding out the current one
+    pub fn write_aligned_bytes(&mut self, bytes: &[u8]) {
+        self.align_to_byte();
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// A false bit that `patch_bit` can overwrite later
+    pub fn write_bit_placeholder(&mut self) -> BitPosition {
+        let position = BitPosition(self.bit_position());
+        self.write_bit(false);
+        position
+    }
+
+    pub fn patch_bit(&mut self, position: BitPosition, bit: bool) {
+        let byte = &mut self.buffer[(position.0 / 8) as usize];
+        let mask = 1 << (7 - position.0 % 8);
+        if bit {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+    }
+
+    /// Pads out the current byte and reserves a little-endian u32 for `patch_u32`, the same layout
+    /// as the tail array's length prefix.
+    pub fn write_u32_placeholder(&mut self) -> BytePosition {
+        self.align_to_byte();
+        let position = BytePosition(self.complete_bytes());
+        self.write_aligned_bytes(&[0; 4]);
+        position
+    }
+
+    pub fn patch_u32(&mut self, position: BytePosition, value: u32) {
+        self.buffer[position.0..position.0 + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    // the byte the next bits go into, pushing a fresh one on a byte boundary
+    fn partial_byte(&mut self) -> &mut u8 {
+        if self.bit_offset == 0 {
             self.buffer.push(0);
-            self.bit_offset = 0;
         }
+        self.buffer.last_mut().unwrap()
     }
 
     pub fn write_bit(&mut self, bit: bool) {
-        self.ensure_space();
-        let last = self.buffer.len() - 1;
-        self.buffer[last] |= (bit as u8) << (7 - self.bit_offset);
-        self.bit_offset += 1;
+        let shift = 7 - self.bit_offset;
+        *self.partial_byte() |= (bit as u8) << shift;
+        self.bit_offset = (self.bit_offset + 1) % 8;
     }
 
+    /// The low `width` bits of `bits`, anything above them is ignored. `width` is at most 8 since
+    /// the value is a byte, use `write_bits_u16` or `write_bytes_width` for wider values.
     pub fn write_bits(&mut self, bits: u8, width: u8) {
-        self.ensure_space();
+        debug_assert!(width <= 8, "write_bits width {width} is wider than a byte");
+        if width == 0 {
+            return;
+        }
         let bits = bits & ((1u16 << width) - 1) as u8;
         let space = 8 - self.bit_offset;
-        let last = self.buffer.len() - 1;
 
         if width <= space {
-            self.buffer[last] |= bits << (space - width);
-            self.bit_offset += width;
+            *self.partial_byte() |= bits << (space - width);
+            self.bit_offset = (self.bit_offset + width) % 8;
         } else {
             let overflow = width - space;
-            self.buffer[last] |= bits >> overflow;
+            *self.partial_byte() |= bits >> overflow;
             self.buffer.push(bits << (8 - overflow));
             self.bit_offset = overflow;
         }
@@ -149,14 +299,10 @@ impl<'a> BitPacker<'a> {
     }
 
     pub fn write_byte(&mut self, byte: u8) {
-        self.ensure_space();
-        let last = self.buffer.len() - 1;
-
         if self.bit_offset == 0 {
-            self.buffer[last] = byte;
-            self.bit_offset = 8;
+            self.buffer.push(byte);
         } else {
-            self.buffer[last] |= byte >> self.bit_offset;
+            *self.partial_byte() |= byte >> self.bit_offset;
             self.buffer.push(byte << (8 - self.bit_offset));
         }
     }
@@ -167,6 +313,9 @@ impl<'a> BitPacker<'a> {
         }
     }
 
+    /// `bytes` are little-endian (from `to_le_bytes`) purely so they can be indexed by
+    /// significance, the packed output is always most significant bit first regardless of the
+    /// host.
     pub fn write_bytes_width(&mut self, bytes: &[u8], width: u8) {
         let high_bits = width % 8;
         let full_bytes = (width / 8) as usize;
@@ -180,34 +329,58 @@ impl<'a> BitPacker<'a> {
     }
 
     pub fn write_int(&mut self, int: i64) {
-        let (slot, width) = int_slot_width(int);
+        self.write_int_in(int, &INT_HEADER_WIDTHS);
+    }
+
+    /// `write_int` over another width table, the last width has to be 64.
+    pub fn write_int_in(&mut self, int: i64, widths: &[u8]) {
+        let (slot, width) = int_slot_width(int, widths);
 
         // prefix: slot 1s followed by a 0 (unless last slot)
         for _ in 0..slot {
             self.write_bit(true);
         }
-        if slot < INT_WIDTHS.len() - 1 {
+        if slot < widths.len() - 1 {
             self.write_bit(false);
         }
 
         self.write_bytes_width(&int.to_le_bytes(), width);
     }
 
-    pub fn write_ascii_string_adaptive(&mut self, string: &Cow<str>) {
+    pub fn write_small_int(&mut self, int: u64) {
+        let (slot, width) = small_int_slot_width(int);
+        self.write_bits(slot, SMALL_INT_HEADER_BITS);
+        self.write_bytes_width(&int.to_le_bytes(), width);
+    }
+
+    /// A set bit and then `write_int(count - 1)`, or a single clear bit for zero. For the header's
+    /// value counts, most columns of a small message are empty.
+    pub fn write_count(&mut self, count: u64) {
+        self.write_bit(count > 0);
+        if count > 0 {
+            self.write_int(i64::try_from(count - 1).expect("count doesn't fit in an int"));
+        }
+    }
+
+    /// Fails without writing anything if `string` has a byte the huffman table has no code for,
+    /// the ultrapacked charsets cover the same printable ascii so neither codec could write it.
+    pub fn write_ascii_string_adaptive(&mut self, string: &str) -> Result<(), MissingCode> {
+        huffman::check(&huffman::HUFFMAN_TABLE, string.as_bytes())?;
         let charset_flags = detect_charset_flags(string);
         let ultrapack_bits = estimate_ultrapack_bits(string, charset_flags);
         let huffman_bits = estimate_huffman_bits(string);
 
         if huffman_bits < ultrapack_bits {
             self.write_bit(true); // 1 = huffman
-            self.write_ascii_huffman_string(string);
+            self.write_ascii_huffman_string(string)
         } else {
             self.write_bit(false); // 0 = ultrapack
             self.write_ascii_ultrapacked_string(string, charset_flags);
+            Ok(())
         }
     }
 
-    pub fn write_ascii_ultrapacked_string(&mut self, string: &Cow<str>, charset_flags: u8) {
+    pub fn write_ascii_ultrapacked_string(&mut self, string: &str, charset_flags: u8) {
         let charset = build_charset(charset_flags);
         let max_value = charset.len() as u64;
 
@@ -220,67 +393,80 @@ impl<'a> BitPacker<'a> {
         let remainder = string.len() % bundle_size as usize;
 
         let length = bundles * bundle_size as usize + remainder;
-        self.write_int(length as i64);
+        self.write_int(length_int(length));
 
-        let mut bundle_buffer = vec![0u64; bundle_size as usize];
+        let mut scratch = [0u64; ultra_packer::MAX_BUNDLE_SIZE as usize];
+        let bundle_buffer = &mut scratch[..bundle_size as usize];
         for _ in 0..bundles {
-            for i in 0..bundle_size as usize {
+            for slot in bundle_buffer.iter_mut() {
                 let byte = bytes.next().expect("should have another byte for bundle");
-                bundle_buffer[i] = compact_charset(byte, &charset) as u64;
+                *slot = compact_charset(byte, charset) as u64;
             }
-            let bundle = ultra_packer::encode(bundle_size, max_value, &bundle_buffer);
+            let bundle = ultra_packer::encode(bundle_size, max_value, bundle_buffer);
             ultra_packer::write_bundle(self, bits_per_bundle, bundle);
         }
 
         if remainder > 0 {
-            let mut remainder_buffer = vec![0u64; remainder];
-            for i in 0..remainder {
+            let remainder_buffer = &mut scratch[..remainder];
+            for slot in remainder_buffer.iter_mut() {
                 let byte = bytes
                     .next()
                     .expect("should have another byte for remainder");
-                remainder_buffer[i] = compact_charset(byte, &charset) as u64;
+                *slot = compact_charset(byte, charset) as u64;
             }
             let remainder_bits = ultra_packer::bits_per_bundle(max_value, remainder as u8);
             let remainder_bundle =
-                ultra_packer::encode(remainder as u8, max_value, &remainder_buffer);
+                ultra_packer::encode(remainder as u8, max_value, remainder_buffer);
             ultra_packer::write_bundle(self, remainder_bits, remainder_bundle);
         }
     }
 
-    pub fn write_ascii_huffman_string(&mut self, string: &Cow<str>) {
-        self.write_int(string.len() as i64);
-        for &c in string.as_bytes() {
-            if let Some(&(code, len)) = huffman::HUFFMAN_TABLE.get(&c) {
-                self.write_bits_u16(code, len);
-            } else {
-                self.write_bits(c & 0x7F, 7);
-            }
+    /// Fails without writing anything if `string` has a byte the huffman table has no code for.
+    pub fn write_ascii_huffman_string(&mut self, string: &str) -> Result<(), MissingCode> {
+        huffman::check(&huffman::HUFFMAN_TABLE, string.as_bytes())?;
+        self.write_int(length_int(string.len()));
+        for c in string.as_bytes() {
+            let (code, len) = huffman::HUFFMAN_TABLE[c];
+            self.write_bits_u16(code, len);
         }
+        Ok(())
     }
 
-    pub fn write_unicode_huffman_string(&mut self, string: &Cow<str>) {
-        self.write_int(string.len() as i64);
+    pub fn write_unicode_huffman_string(&mut self, string: &str) {
+        self.write_int(length_int(string.len()));
         for &c in string.as_bytes() {
-            if let Some(&(code, len)) = huffman::HUFFMAN_TABLE.get(&c) {
-                self.write_bit(false);
-                self.write_bits_u16(code, len);
-            } else {
-                self.write_bit(true);
-                self.write_byte(c);
-            }
+            self.write_unicode_huffman_byte(c);
         }
     }
 
+    /// Its huffman code after a clear bit, or a set bit and the byte as it is if it has none.
+    pub fn write_unicode_huffman_byte(&mut self, byte: u8) {
+        if let Some(&(code, len)) = huffman::HUFFMAN_TABLE.get(&byte) {
+            self.write_bit(false);
+            self.write_bits_u16(code, len);
+        } else {
+            self.write_bit(true);
+            self.write_byte(byte);
+        }
+    }
+
+    /// `TAG_BITS` wide unless `set_tag_bits` said otherwise, the same width `read_property_type`
+    /// reads.
     pub fn write_property_type(&mut self, tag: PropertyType) {
-        let (bits, len) = tag.to_bits();
-        self.write_bits(bits, len);
+        let (bits, _) = tag.to_bits();
+        debug_assert!(bits < 1 << self.tag_bits);
+        self.write_bits(bits, self.tag_bits);
     }
 }
 
+/// Reads back what a `BitPacker` wrote, in the same most significant bit first order.
 pub struct BitUnpacker<'a> {
-    pub buffer: &'a [u8],
-    pub byte_index: usize,
-    pub bit_offset: u8,
+    buffer: &'a [u8],
+    byte_index: usize,
+    // bits of `buffer[byte_index]` already read, always less than 8
+    bit_offset: u8,
+    // width of the property types, see `set_tag_bits`
+    tag_bits: u8,
 }
 
 impl<'a> BitUnpacker<'a> {
@@ -289,9 +475,55 @@ impl<'a> BitUnpacker<'a> {
             buffer,
             byte_index: 0,
             bit_offset: 0,
+            tag_bits: TAG_BITS,
         }
     }
 
+    /// Reads property types `bits` wide from here on, set from the revision by the header so
+    /// buffers of older revisions still read (see `format_spec::tag_bits`).
+    pub fn set_tag_bits(&mut self, bits: u8) {
+        self.tag_bits = bits;
+    }
+
+    pub fn tag_bits(&self) -> u8 {
+        self.tag_bits
+    }
+
+    /// The whole input, including what has already been read.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buffer
+    }
+
+    /// Number of bits read so far.
+    pub fn bit_position(&self) -> u64 {
+        self.byte_index as u64 * 8 + self.bit_offset as u64
+    }
+
+    /// The bytes from the next byte boundary on, i.e. what's left after an `align_to_byte`.
+    pub fn remaining_bytes(&self) -> &'a [u8] {
+        let start = self.byte_index + (self.bit_offset != 0) as usize;
+        self.buffer.get(start..).unwrap_or_default()
+    }
+
+    /// Pads out the current byte and borrows the next `len` whole bytes, the counterpart of
+    /// `BitPacker::write_aligned_bytes`.
+    pub fn read_aligned_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        self.align_to_byte();
+        let bytes = self.buffer.get(self.byte_index..)?.get(..len)?;
+        self.byte_index += len;
+        Some(bytes)
+    }
+
+    pub fn remaining_bits(&self) -> u64 {
+        (self.buffer.len() as u64 * 8).saturating_sub(self.bit_position())
+    }
+
+    // corrupt lengths shouldn't be able to reserve more than the input could possibly hold
+    fn reserve_for(&self, bytes: &mut Vec<u8>, length: usize) {
+        let remaining = usize::try_from(self.remaining_bits()).unwrap_or(usize::MAX);
+        bytes.reserve(length.min(remaining));
+    }
+
     fn advance(&mut self) {
         self.bit_offset += 1;
         if self.bit_offset == 8 {
@@ -307,7 +539,9 @@ impl<'a> BitUnpacker<'a> {
         Some(bit)
     }
 
+    /// Same contract as `BitPacker::write_bits`, `width` is at most 8
     pub fn read_bits(&mut self, width: u8) -> Option<u8> {
+        debug_assert!(width <= 8, "read_bits width {width} is wider than a byte");
         let space = 8 - self.bit_offset;
         let byte = *self.buffer.get(self.byte_index)?;
         let mask = ((1u16 << width) - 1) as u8;
@@ -331,15 +565,21 @@ impl<'a> BitUnpacker<'a> {
     }
 
     pub fn read_byte(&mut self) -> Option<u8> {
+        let byte = self.peek_byte()?;
+        self.byte_index += 1;
+        Some(byte)
+    }
+
+    /// The next 8 bits without moving past them, e.g. to dispatch on the version byte before
+    /// decoding
+    pub fn peek_byte(&self) -> Option<u8> {
         let byte = *self.buffer.get(self.byte_index)?;
 
         if self.bit_offset == 0 {
-            self.byte_index += 1;
             Some(byte)
         } else {
             let space = 8 - self.bit_offset;
-            self.byte_index += 1;
-            let next = *self.buffer.get(self.byte_index)?;
+            let next = *self.buffer.get(self.byte_index + 1)?;
             Some((byte << self.bit_offset) | (next >> space))
         }
     }
@@ -361,56 +601,88 @@ impl<'a> BitUnpacker<'a> {
     }
 
     pub fn read_int(&mut self) -> Option<i64> {
+        self.read_int_in(&INT_HEADER_WIDTHS)
+    }
+
+    pub fn read_int_in(&mut self, widths: &[u8]) -> Option<i64> {
         // Count leading 1s to determine slot
         let mut slot = 0;
-        while slot < 6 && self.read_bit()? {
+        while slot < widths.len() - 1 && self.read_bit()? {
             slot += 1;
         }
 
-        let width = INT_WIDTHS[slot];
+        let width = widths[slot];
         Some(self.read_bytes_width(width)? as i64)
     }
 
+    pub fn read_small_int(&mut self) -> Option<u64> {
+        let slot = self.read_bits(SMALL_INT_HEADER_BITS)?;
+        let width = SMALL_INT_HEADER_WIDTHS[slot as usize];
+        self.read_bytes_width(width)
+    }
+
+    pub fn read_count(&mut self) -> Option<u64> {
+        if !self.read_bit()? {
+            return Some(0);
+        }
+        u64::try_from(self.read_int()?).ok()?.checked_add(1)
+    }
+
     pub fn read_ascii_ultrapacked_string(&mut self) -> Option<String> {
+        let mut bytes = Vec::new();
+        self.read_ascii_ultrapacked_into(&mut bytes)?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Appends the decoded bytes onto `bytes` so a scratch buffer can be reused between strings
+    pub fn read_ascii_ultrapacked_into(&mut self, bytes: &mut Vec<u8>) -> Option<()> {
         let flags = self.read_bits(CHARSETS)?;
         let charset = build_charset(flags);
         let max_value = charset.len() as u64;
 
         let (bundle_size, bits_per_bundle) = ultra_packer::find_optimal_bundle(max_value);
 
-        let length = self.read_int()? as usize;
+        // negative, or past what this target can hold, only comes from corrupt input
+        let length = usize::try_from(self.read_int()?).ok()?;
         let bundles = length / bundle_size as usize;
         let remainder = length % bundle_size as usize;
 
-        let mut bytes = Vec::with_capacity(length);
+        let mut decoded = [0u64; ultra_packer::MAX_BUNDLE_SIZE as usize];
+        self.reserve_for(bytes, length);
         for _ in 0..bundles {
             let bundle = ultra_packer::read_bundle(self, bits_per_bundle)?;
-            let decoded = ultra_packer::decode(bundle_size, max_value, bundle);
-            for idx in decoded {
-                bytes.push(uncompact_charset(idx as u8, &charset));
+            let decoded = &mut decoded[..bundle_size as usize];
+            ultra_packer::decode_into(max_value, bundle, decoded);
+            for &idx in decoded.iter() {
+                bytes.push(uncompact_charset(idx as u8, charset));
             }
         }
 
         if remainder > 0 {
             let remainder_bits = ultra_packer::bits_per_bundle(max_value, remainder as u8);
             let remainder_bundle = ultra_packer::read_bundle(self, remainder_bits)?;
-            let decoded = ultra_packer::decode(remainder as u8, max_value, remainder_bundle);
-            for idx in decoded {
-                bytes.push(uncompact_charset(idx as u8, &charset));
+            let decoded = &mut decoded[..remainder];
+            ultra_packer::decode_into(max_value, remainder_bundle, decoded);
+            for &idx in decoded.iter() {
+                bytes.push(uncompact_charset(idx as u8, charset));
             }
         }
 
-        Some(String::from_utf8_lossy(&bytes).into_owned())
+        Some(())
     }
 
     fn read_huffman_byte(&mut self) -> Option<u8> {
-        let (code, bits_read) = self.read_bits_u16_padded(huffman::HUFFMAN_MAX_LEN);
+        self.read_huffman_byte_with(&huffman::HUFFMAN_DECODE)
+    }
+
+    pub fn read_huffman_byte_with(&mut self, table: &huffman::DecodeTable) -> Option<u8> {
+        let (code, bits_read) = self.read_bits_u16_padded(table.max_len());
 
         if bits_read == 0 {
             return None;
         }
 
-        let (character, actual_len) = huffman::HUFFMAN_DECODE[code as usize];
+        let (character, actual_len) = table.lookup(code);
 
         if actual_len == 0 || actual_len > bits_read {
             return None;
@@ -421,40 +693,62 @@ impl<'a> BitUnpacker<'a> {
     }
 
     pub fn read_ascii_huffman_string(&mut self) -> Option<String> {
-        let length = self.read_int()?;
-        if length < 0 {
-            return None;
-        }
-        let length = length as usize;
-        let mut bytes = Vec::with_capacity(length);
+        let mut bytes = Vec::new();
+        self.read_ascii_huffman_into(&mut bytes)?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    pub fn read_ascii_huffman_into(&mut self, bytes: &mut Vec<u8>) -> Option<()> {
+        let length = usize::try_from(self.read_int()?).ok()?;
+        self.reserve_for(bytes, length);
 
         for _ in 0..length {
             bytes.push(self.read_huffman_byte()?);
         }
 
-        Some(String::from_utf8_lossy(&bytes).into_owned())
+        Some(())
     }
 
     pub fn read_unicode_huffman_string(&mut self) -> Option<String> {
-        let length = self.read_int()? as usize;
-        let mut bytes = Vec::with_capacity(length);
+        let mut bytes = Vec::new();
+        self.read_unicode_huffman_into(&mut bytes)?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    pub fn read_unicode_huffman_into(&mut self, bytes: &mut Vec<u8>) -> Option<()> {
+        let length = usize::try_from(self.read_int()?).ok()?;
+        self.reserve_for(bytes, length);
 
         for _ in 0..length {
-            let is_escaped = self.read_bit()?;
+            bytes.push(self.read_unicode_huffman_byte()?);
+        }
 
-            if is_escaped {
-                bytes.push(self.read_byte()?);
-            } else {
-                bytes.push(self.read_huffman_byte()?);
-            }
+        Some(())
+    }
+
+    pub fn read_unicode_huffman_byte(&mut self) -> Option<u8> {
+        let is_escaped = self.read_bit()?;
+        if is_escaped {
+            self.read_byte()
+        } else {
+            self.read_huffman_byte()
         }
+    }
 
-        Some(String::from_utf8_lossy(&bytes).into_owned())
+    /// Fails with `InvalidPropertyTag` on a value no `PropertyType` maps to rather than guessing
+    /// at one, and with a truncated property type column when the input runs out.
+    pub fn read_property_type(&mut self) -> Result<PropertyType, DecodeError> {
+        let bits = self
+            .read_bits(self.tag_bits)
+            .ok_or(DecodeError::Truncated(Section::PropertyTypes))?;
+        PropertyType::from_bits(bits).ok_or(DecodeError::InvalidPropertyTag(bits))
     }
 
-    pub fn read_property_type(&mut self) -> Option<PropertyType> {
-        let bits = self.read_bits(2)?;
-        PropertyType::from_bits(bits)
+    pub fn align_to_byte(&mut self) {
+        if self.bit_offset != 0 {
+            self.byte_index += 1;
+            self.bit_offset = 0;
+        }
     }
 
     pub fn rewind_bits(&mut self, bits: u8) {
@@ -496,7 +790,7 @@ pub fn estimate_ultrapack_bits(string: &str, charset_flags: u8) -> u64 {
 
     // 1 bit selector + header + length prefix + content
     let mut bits = 1 + CHARSETS as u64;
-    bits += int_encoded_bits(string.len() as i64);
+    bits += int_encoded_bits(length_int(string.len()));
     bits += bundles as u64 * bits_per_bundle as u64;
     if remainder > 0 {
         bits += ultra_packer::bits_per_bundle(max_value, remainder as u8) as u64;
@@ -504,15 +798,33 @@ pub fn estimate_ultrapack_bits(string: &str, charset_flags: u8) -> u64 {
     bits
 }
 
+// what `write_unicode_huffman_byte` writes for `byte`
+pub fn unicode_huffman_byte_bits(byte: u8) -> u64 {
+    1 + huffman::HUFFMAN_TABLE
+        .get(&byte)
+        .map_or(8, |&(_, len)| len as u64)
+}
+
+// what `write_ascii_string_adaptive` writes for `string`, which has a code for every byte
+pub fn ascii_string_adaptive_bits(string: &str) -> u64 {
+    let ultrapack_bits = estimate_ultrapack_bits(string, detect_charset_flags(string));
+    estimate_huffman_bits(string).min(ultrapack_bits)
+}
+
+// what `write_unicode_huffman_string` writes for `string`
+pub fn unicode_huffman_string_bits(string: &str) -> u64 {
+    let bytes = string.bytes().map(unicode_huffman_byte_bits).sum::<u64>();
+    int_encoded_bits(length_int(string.len())) + bytes
+}
+
 pub fn estimate_huffman_bits(string: &str) -> u64 {
     // 1 bit selector + length prefix + huffman codes
-    let mut bits = 1 + int_encoded_bits(string.len() as i64);
+    let mut bits = 1 + int_encoded_bits(length_int(string.len()));
     for &c in string.as_bytes() {
-        if let Some(&(_, len)) = huffman::HUFFMAN_TABLE.get(&c) {
-            bits += len as u64;
-        } else {
-            bits += 7; // fallback for chars not in table
-        }
+        // bytes without a code are rejected before this is used
+        bits += huffman::HUFFMAN_TABLE
+            .get(&c)
+            .map_or(0, |&(_, len)| len as u64);
     }
     bits
 }
@@ -520,6 +832,39 @@ pub fn estimate_huffman_bits(string: &str) -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::roundtrip::assert_bytes_eq;
+
+    #[test]
+    pub fn hostile_string_lengths_fail() {
+        type Read = fn(&mut BitUnpacker, &mut Vec<u8>) -> Option<()>;
+        let readers: [(bool, Read); 3] = [
+            (true, |unpacker, bytes| {
+                unpacker.read_ascii_ultrapacked_into(bytes)
+            }),
+            (false, |unpacker, bytes| {
+                unpacker.read_ascii_huffman_into(bytes)
+            }),
+            (false, |unpacker, bytes| {
+                unpacker.read_unicode_huffman_into(bytes)
+            }),
+        ];
+        // 2^40 wraps to 0 as a 32 bit usize, and -1 to usize::MAX
+        for length in [1 << 40, -1] {
+            for (charsets, read) in readers {
+                let mut buffer = Vec::new();
+                let mut packer = BitPacker::new(&mut buffer);
+                if charsets {
+                    packer.write_bits(0, CHARSETS);
+                }
+                packer.write_int(length);
+                packer.write_bytes(b"a few bytes");
+
+                let mut bytes = Vec::new();
+                assert_eq!(read(&mut BitUnpacker::new(&buffer), &mut bytes), None);
+                assert!(bytes.capacity() <= buffer.len() * 8, "{length}");
+            }
+        }
+    }
 
     #[test]
     pub fn write_bits() {
@@ -530,16 +875,330 @@ mod tests {
         packer.write_bit(true);
         packer.write_bit(true);
         packer.write_bit(true);
-        assert_eq!(*packer.buffer, vec![0b11110000]);
+        assert_eq!(packer.as_bytes(), vec![0b11110000]);
 
         packer.write_bit(true);
         packer.write_bit(true);
         packer.write_bit(true);
         packer.write_bit(true);
-        assert_eq!(*packer.buffer, vec![0b11111111]);
+        assert_eq!(packer.as_bytes(), vec![0b11111111]);
 
         packer.write_bit(false);
-        assert_eq!(*packer.buffer, vec![0b11111111, 0b00000000]);
+        assert_eq!(packer.as_bytes(), vec![0b11111111, 0b00000000]);
+    }
+
+    // the documented ordering other codecs build on, changing any of these breaks existing buffers
+    #[test]
+    pub fn bits_are_most_significant_first() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_bits(0b101, 3);
+        assert_eq!(packer.as_bytes(), [0b1010_0000]);
+        // a byte written mid-byte straddles the boundary, high bits first
+        packer.write_byte(0xC3);
+        assert_eq!(packer.as_bytes(), [0b1011_1000, 0b0110_0000]);
+        packer.write_bits_u16(0b1_0000_0001, 9);
+        assert_eq!(packer.bit_position(), 20);
+        assert_eq!(packer.as_bytes(), [0b1011_1000, 0b0111_0000, 0b0001_0000]);
+
+        // padding is zeros and the bytes handed back are the ones written
+        packer.write_aligned_bytes(&[0xAA]);
+        let buffer = packer.into_inner();
+        buffer.push(0x55);
+        assert_eq!(*buffer, [0b1011_1000, 0b0111_0000, 0b0001_0000, 0xAA, 0x55]);
+
+        let mut unpacker = BitUnpacker::new(buffer);
+        assert_eq!(unpacker.read_bit(), Some(true));
+        assert_eq!(unpacker.read_bits(2), Some(0b01));
+        assert_eq!(unpacker.read_byte(), Some(0xC3));
+        assert_eq!(unpacker.remaining_bytes(), [0b0001_0000, 0xAA, 0x55]);
+        assert_eq!(
+            unpacker.read_aligned_bytes(2),
+            Some(&[0b0001_0000, 0xAA][..])
+        );
+        assert_eq!(unpacker.read_aligned_bytes(2), None);
+        assert_eq!(unpacker.remaining_bytes(), [0x55]);
+        assert_eq!(unpacker.as_bytes().len(), 5);
+    }
+
+    #[test]
+    pub fn first_byte_is_only_content_once_written() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        assert_eq!(packer.bit_position(), 0);
+        assert_eq!(packer.complete_bytes(), 0);
+
+        packer.write_byte(0xAB);
+        assert_eq!(packer.bit_position(), 8);
+        assert_eq!(packer.complete_bytes(), 1);
+        assert_eq!(packer.as_bytes(), vec![0xAB]);
+    }
+
+    #[test]
+    pub fn placeholders_are_patched_in_place() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_bits(0b101, 3);
+        let has_more = packer.write_bit_placeholder();
+        let length = packer.write_u32_placeholder();
+        let start = packer.bit_position();
+        for _ in 0..5 {
+            packer.write_int(300);
+        }
+        let body_bits = packer.bit_position() - start;
+        let last = packer.write_bit_placeholder();
+        packer.patch_bit(has_more, true);
+        packer.patch_u32(length, body_bits as u32);
+        packer.patch_bit(last, true);
+        packer.patch_bit(last, false);
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        assert_eq!(unpacker.read_bits(3), Some(0b101));
+        assert_eq!(unpacker.read_bit(), Some(true));
+        unpacker.align_to_byte();
+        let length: Vec<u8> = (0..4).map(|_| unpacker.read_byte().unwrap()).collect();
+        assert_eq!(
+            u32::from_le_bytes(length.try_into().unwrap()),
+            body_bits as u32
+        );
+        for _ in 0..5 {
+            assert_eq!(unpacker.read_int(), Some(300));
+        }
+        assert_eq!(unpacker.read_bit(), Some(false));
+    }
+
+    #[test]
+    pub fn property_types_use_the_tag_width() {
+        // eight of them end on a byte boundary, so there's no padding to read as one more
+        let tags = [
+            PropertyType::Array,
+            PropertyType::String,
+            PropertyType::Integer,
+            PropertyType::Bool,
+        ]
+        .repeat(2);
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        for tag in tags.iter().copied() {
+            packer.write_property_type(tag);
+        }
+        assert_eq!(packer.bit_position(), tags.len() as u64 * TAG_BITS as u64);
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        for tag in tags {
+            assert_eq!(unpacker.read_property_type(), Ok(tag));
+        }
+        assert_eq!(
+            unpacker.read_property_type(),
+            Err(DecodeError::Truncated(Section::PropertyTypes))
+        );
+
+        // every value of the width either maps to a tag that writes it back or is reserved
+        for bits in 0..1 << TAG_BITS {
+            let buffer = [bits << (8 - TAG_BITS)];
+            let mut unpacker = BitUnpacker::new(&buffer);
+            match unpacker.read_property_type() {
+                Ok(tag) => assert_eq!(tag.to_bits(), (bits, TAG_BITS)),
+                Err(error) => assert_eq!(error, DecodeError::InvalidPropertyTag(bits)),
+            }
+        }
+        assert_eq!(PropertyType::from_bits(1 << TAG_BITS), None);
+    }
+
+    #[test]
+    pub fn counts_roundtrip() {
+        let counts = [0, 1, 8, 9, 128, 129, u32::MAX as u64];
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        for count in counts {
+            packer.write_count(count);
+        }
+        // zero is a single bit, up to 8 fit the narrowest int
+        let mut lengths = Vec::new();
+        let mut packer = BitPacker::new(&mut lengths);
+        packer.write_count(0);
+        assert_eq!(packer.bit_position(), 1);
+        packer.write_count(8);
+        assert_eq!(
+            packer.bit_position(),
+            1 + 1 + 1 + INT_HEADER_WIDTHS[0] as u64
+        );
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        for count in counts {
+            assert_eq!(unpacker.read_count(), Some(count));
+        }
+    }
+
+    // xorshift, enough to shuffle write sequences without pulling in a crate
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+    }
+
+    // one bool per bit, the slowest obviously correct writer
+    #[derive(Default)]
+    struct ReferenceWriter(Vec<bool>);
+
+    impl ReferenceWriter {
+        fn write_bits(&mut self, bits: u64, width: u8) {
+            self.0
+                .extend((0..width).rev().map(|bit| bits >> bit & 1 == 1));
+        }
+
+        fn align_to_byte(&mut self) {
+            while !self.0.len().is_multiple_of(8) {
+                self.0.push(false);
+            }
+        }
+
+        fn bytes(&self) -> Vec<u8> {
+            self.0
+                .chunks(8)
+                .map(|chunk| {
+                    let byte = chunk.iter().fold(0, |byte, &bit| byte << 1 | bit as u8);
+                    byte << (8 - chunk.len())
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    pub fn random_writes_match_the_reference() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let mut buffer = Vec::new();
+            let mut packer = BitPacker::new(&mut buffer);
+            let mut reference = ReferenceWriter::default();
+            let mut ops = Vec::new();
+            for _ in 0..rng.below(24) {
+                let value = rng.next();
+                match rng.below(6) {
+                    0 => {
+                        packer.write_bit(value & 1 == 1);
+                        reference.write_bits(value, 1);
+                        ops.push("bit");
+                    }
+                    1 => {
+                        let width = rng.below(9) as u8;
+                        packer.write_bits(value as u8, width);
+                        reference.write_bits(value, width);
+                        ops.push("bits");
+                    }
+                    2 => {
+                        let bytes = value.to_le_bytes();
+                        let len = rng.below(4) as usize;
+                        packer.write_bytes(&bytes[..len]);
+                        for byte in &bytes[..len] {
+                            reference.write_bits(*byte as u64, 8);
+                        }
+                        ops.push("bytes");
+                    }
+                    3 => {
+                        let width = rng.below(65) as u8;
+                        packer.write_bytes_width(&value.to_le_bytes(), width);
+                        reference.write_bits(value, width);
+                        ops.push("bytes_width");
+                    }
+                    4 => {
+                        packer.align_to_byte();
+                        reference.align_to_byte();
+                        ops.push("align");
+                    }
+                    _ => {
+                        let bytes = value.to_le_bytes();
+                        let len = rng.below(4) as usize;
+                        packer.write_aligned_bytes(&bytes[..len]);
+                        reference.align_to_byte();
+                        for byte in &bytes[..len] {
+                            reference.write_bits(*byte as u64, 8);
+                        }
+                        ops.push("aligned_bytes");
+                    }
+                }
+                assert_eq!(packer.bit_position(), reference.0.len() as u64, "{ops:?}");
+                assert_eq!(packer.complete_bytes(), reference.0.len() / 8, "{ops:?}");
+                assert!(packer.bit_offset < 8, "{ops:?}");
+            }
+            assert_eq!(buffer, reference.bytes(), "{ops:?}");
+        }
+    }
+
+    // What each write adds for empty or zero input, from a byte boundary and from partway through
+    // one. None of them pushes a byte it doesn't put any bits in.
+    #[test]
+    pub fn empty_writes() {
+        type Write = fn(&mut BitPacker);
+        let length_bits = 1 + INT_HEADER_WIDTHS[0] as u64;
+        let writes: [(&str, Write, u64); 12] = [
+            ("bytes", |packer| packer.write_bytes(&[]), 0),
+            ("bits", |packer| packer.write_bits(0xff, 0), 0),
+            ("bits u16", |packer| packer.write_bits_u16(0xffff, 0), 0),
+            (
+                "bytes width",
+                |packer| packer.write_bytes_width(&[0xff; 8], 0),
+                0,
+            ),
+            ("int", |packer| packer.write_int(0), length_bits),
+            (
+                "small int",
+                |packer| packer.write_small_int(0),
+                SMALL_INT_HEADER_BITS as u64,
+            ),
+            ("count", |packer| packer.write_count(0), 1),
+            // only the length
+            (
+                "unicode string",
+                |packer| packer.write_unicode_huffman_string(""),
+                length_bits,
+            ),
+            (
+                "ascii huffman string",
+                |packer| packer.write_ascii_huffman_string("").unwrap(),
+                length_bits,
+            ),
+            (
+                "ultrapacked string",
+                |packer| packer.write_ascii_ultrapacked_string("", 0),
+                CHARSETS as u64 + length_bits,
+            ),
+            // picks huffman, which has no charset
+            (
+                "adaptive string",
+                |packer| packer.write_ascii_string_adaptive("").unwrap(),
+                1 + length_bits,
+            ),
+            // only ends the partial byte
+            ("aligned bytes", |packer| packer.write_aligned_bytes(&[]), 0),
+        ];
+        for offset in [0, 3] {
+            for (name, write, bits) in writes {
+                let mut buffer = Vec::new();
+                let mut packer = BitPacker::new(&mut buffer);
+                packer.write_bits(0, offset);
+                write(&mut packer);
+                let mut expected = offset as u64 + bits;
+                if name == "aligned bytes" {
+                    expected = expected.next_multiple_of(8);
+                }
+                assert_eq!(packer.bit_position(), expected, "{name} at {offset}");
+                assert_eq!(
+                    buffer.len() as u64,
+                    expected.div_ceil(8),
+                    "{name} at {offset}"
+                );
+            }
+        }
     }
 
     #[test]
@@ -548,10 +1207,59 @@ mod tests {
         let mut packer = BitPacker::new(&mut buffer);
 
         packer.write_bytes(&[0b11111001]);
-        assert_eq!(*packer.buffer, vec![0b11111001]);
+        assert_eq!(packer.as_bytes(), vec![0b11111001]);
 
         packer.write_bytes(&[0b00000000]);
-        assert_eq!(*packer.buffer, vec![0b11111001, 0b00000000]);
+        assert_eq!(packer.as_bytes(), vec![0b11111001, 0b00000000]);
+    }
+
+    // A write that ends exactly on a byte boundary leaves `bit_offset` at 0 rather than 8, so the
+    // bytes after it start a new byte instead of ORing into the full one.
+    #[test]
+    pub fn bytes_after_a_filled_byte() {
+        type Fill = fn(&mut BitPacker);
+        let fills: [(&str, Fill); 4] = [
+            ("bits", |packer| {
+                packer.write_bits(0b101, 3);
+                packer.write_bits(0b1_0011, 5);
+            }),
+            ("bit by bit", |packer| {
+                for bit in [true, false, true, true, false, false, true, true] {
+                    packer.write_bit(bit);
+                }
+            }),
+            ("bits u16", |packer| packer.write_bits_u16(0b1011_0011, 8)),
+            // crosses into the second byte and fills it
+            ("across bytes", |packer| {
+                packer.write_bits(0b1_0110, 5);
+                packer.write_bits(0b0111_0110, 8);
+                packer.write_bits(0b011, 3);
+            }),
+        ];
+        for (name, fill) in fills {
+            let mut buffer = Vec::new();
+            let mut packer = BitPacker::new(&mut buffer);
+            fill(&mut packer);
+            let filled = packer.as_bytes().to_vec();
+            assert_eq!(packer.bit_offset, 0, "{name}");
+            assert_eq!(packer.bit_position(), filled.len() as u64 * 8, "{name}");
+
+            packer.write_bytes(&[0xa5, 0x00, 0xff]);
+            packer.write_bit(true);
+            let mut expected = filled.clone();
+            expected.extend([0xa5, 0x00, 0xff, 0b1000_0000]);
+            assert_eq!(buffer, expected, "{name}");
+
+            let mut unpacker = BitUnpacker::new(&buffer);
+            for byte in &filled {
+                assert_eq!(unpacker.read_byte(), Some(*byte), "{name}");
+            }
+            for byte in [0xa5, 0x00, 0xff] {
+                assert_eq!(unpacker.read_byte(), Some(byte), "{name}");
+            }
+            assert_eq!(unpacker.read_bit(), Some(true), "{name}");
+            assert_eq!(unpacker.remaining_bits(), 7, "{name}");
+        }
     }
 
     #[test]
@@ -572,6 +1280,50 @@ mod tests {
         assert_eq!(unpacker.read_bits(4), Some(0b1010));
     }
 
+    #[test]
+    pub fn write_bits_full_byte() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_bits(0xff, 8);
+        packer.write_bits(0b101, 3);
+        packer.write_bits(0xa5, 8);
+        assert_eq!(packer.as_bytes(), vec![0xff, 0b1011_0100, 0b1010_0000]);
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        assert_eq!(unpacker.read_bits(8), Some(0xff));
+        assert_eq!(unpacker.read_bits(3), Some(0b101));
+        assert_eq!(unpacker.read_bits(8), Some(0xa5));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "wider than a byte")]
+    pub fn write_bits_rejects_wide_values() {
+        let mut buffer = Vec::new();
+        BitPacker::new(&mut buffer).write_bits(0xff, 9);
+    }
+
+    #[test]
+    pub fn peek_byte() {
+        let buffer = vec![0b11110000, 0b10101010];
+        let mut unpacker = BitUnpacker::new(&buffer);
+
+        assert_eq!(unpacker.peek_byte(), Some(0b11110000));
+        assert_eq!(unpacker.bit_position(), 0);
+        assert_eq!(unpacker.read_byte(), Some(0b11110000));
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        unpacker.read_bits(4);
+        assert_eq!(unpacker.peek_byte(), Some(0b00001010));
+        assert_eq!(unpacker.bit_position(), 4);
+        assert_eq!(unpacker.read_byte(), Some(0b00001010));
+
+        // only 4 bits left
+        assert_eq!(unpacker.peek_byte(), None);
+        assert_eq!(unpacker.read_byte(), None);
+        assert_eq!(unpacker.bit_position(), 12);
+    }
+
     #[test]
     pub fn sanity() {
         let mut buffer = Vec::new();
@@ -601,4 +1353,71 @@ mod tests {
         assert_eq!(unpacker.read_int(), Some(1000));
         assert_eq!(unpacker.read_int(), Some(100000));
     }
+
+    #[test]
+    pub fn int_byte_order() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+
+        // slot 3 (15 bits): prefix 1110, then 0x1234 most significant bit first
+        packer.write_int(0x1234);
+        assert_bytes_eq(&buffer, &[0b1110_0010, 0b0100_0110, 0b1000_0000]);
+
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        ultra_packer::write_bundle(&mut packer, 16, 0xA1B2);
+        assert_bytes_eq(&buffer, &[0xA1, 0xB2]);
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        assert_eq!(ultra_packer::read_bundle(&mut unpacker, 16), Some(0xA1B2));
+    }
+
+    #[test]
+    pub fn sanity_small_int() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+
+        let values = [0, 1, 3, 4, 200, 65535, 65536, u64::MAX];
+        for value in values {
+            packer.write_small_int(value);
+        }
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        for value in values {
+            assert_eq!(unpacker.read_small_int(), Some(value));
+        }
+    }
+
+    #[test]
+    pub fn bytes_without_a_code_are_rejected() {
+        use crate::serializer::{Deserializer, PropertyValue, Serializer};
+
+        // DEL is within `is_32_127` but has no code, and no ultrapack charset either
+        let string = "ab\x7fc";
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        let missing = MissingCode {
+            byte: 0x7F,
+            position: 2,
+        };
+        assert_eq!(packer.write_ascii_huffman_string(string), Err(missing));
+        assert_eq!(packer.write_ascii_string_adaptive(string), Err(missing));
+        assert_eq!(packer.bit_position(), 0);
+
+        // the whole string column falls back to the unicode codec, inline strings on their own
+        let tail = [PropertyValue::String(string.to_owned())];
+        let mut serializer = Serializer::new();
+        serializer.write_string("plain");
+        serializer.write_string(string);
+        serializer.write_tail_array(&tail);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        assert!(!stats.all_ascii);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.load(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_string().as_deref(), Some("plain"));
+        assert_eq!(deserializer.take_string().as_deref(), Some(string));
+        assert_eq!(deserializer.take_tail_array(), Some(tail.to_vec()));
+    }
 }
@@ -0,0 +1,82 @@
+// CRC-32 (the IEEE polynomial, as in zlib and PNG) for `Serializer::set_section_checksums`.
+// Table driven a byte at a time, which is plenty next to decoding the sections themselves.
+
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => crc >> 1 ^ POLYNOMIAL,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+};
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| {
+        TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ crc >> 8
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::{DecodeError, Section},
+        serializer::{Deserializer, Serializer, check_sections, verify},
+    };
+
+    #[test]
+    pub fn matches_the_reference_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+
+    fn write(checksums: bool) -> (Vec<u8>, usize) {
+        let mut serializer = Serializer::new();
+        serializer.set_column_offsets(true);
+        serializer.set_section_checksums(checksums);
+        for i in 0..64 {
+            serializer.write_int(i * 3);
+            serializer.write_bool(i % 3 == 0);
+            serializer.write_string("replica.zone");
+        }
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        // the boolean section follows the integer one
+        let booleans = (stats.header_bits / 8 + stats.integers.bits.div_ceil(8)) as usize;
+        (buffer, booleans)
+    }
+
+    #[test]
+    pub fn corrupt_section_is_named() {
+        let (mut buffer, booleans) = write(true);
+        assert_eq!(check_sections(&buffer, 0), Ok(Vec::new()));
+        buffer[booleans + 1] ^= 0x10;
+
+        assert_eq!(check_sections(&buffer, 0), Ok(vec![Section::Booleans]));
+        let mismatch = DecodeError::ChecksumMismatch(Section::Booleans);
+        assert_eq!(verify(&buffer, 0).unwrap_err(), mismatch);
+        assert_eq!(Deserializer::new().load(&buffer, 0), Err(mismatch));
+
+        // without them the same flip is just a different boolean
+        let (mut buffer, booleans) = write(false);
+        buffer[booleans + 1] ^= 0x10;
+        assert!(verify(&buffer, 0).is_ok());
+        assert_eq!(check_sections(&buffer, 0), Ok(Vec::new()));
+    }
+}
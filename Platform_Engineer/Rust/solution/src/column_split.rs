@@ -0,0 +1,358 @@
+use std::borrow::Cow;
+
+use crate::{
+    bit_packer::{BitPacker, BitUnpacker},
+    columns::{self, StringFlags},
+    error::{DecodeError, Section},
+    int_plan::IntHeaderPlan,
+    limits::Limit,
+    progress::Progress,
+    serializer::{Deserializer, FORMAT_REVISION, LoadState, PropertyType, Serializer},
+};
+
+// The five columns of a buffer each in a byte buffer of its own, for pipelines that compress them
+// separately (the integers with a codec suited to numbers, the strings with one for text) rather
+// than the whole buffer at once. `Serializer::finish_columns` writes them and
+// `Deserializer::read_columns` takes them back into a deserializer, after which the `take_*`
+// methods read the same values they would from `finish`'s buffer.
+//
+// Each column starts with a small header of its own: the format revision and the column's count,
+// then for the integers whether the widths are a table or custom and which, and for the strings
+// the codec flags `finish` chose (ascii, front coded, lz, deduplicated). The values follow packed
+// exactly as they are in a buffer.
+//
+// Only the columns are covered. Raw strings, named values, kind markers, the tail array, a schema,
+// provenance and the absent marker have nowhere to go, `finish_columns` panics if any were
+// written.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnKind {
+    Integers,
+    Booleans,
+    Strings,
+    PropertyTypes,
+    Lengths,
+}
+
+impl ColumnKind {
+    pub const ALL: [ColumnKind; 5] = [
+        ColumnKind::Integers,
+        ColumnKind::Booleans,
+        ColumnKind::Strings,
+        ColumnKind::PropertyTypes,
+        ColumnKind::Lengths,
+    ];
+
+    fn section(self) -> Section {
+        match self {
+            ColumnKind::Integers => Section::Integers,
+            ColumnKind::Booleans => Section::Booleans,
+            ColumnKind::Strings => Section::Strings,
+            ColumnKind::PropertyTypes => Section::PropertyTypes,
+            ColumnKind::Lengths => Section::Lengths,
+        }
+    }
+}
+
+// `Serializer::as_columns`, everything written to each column before `finish`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Columns<'s> {
+    pub integers: &'s [i64],
+    pub booleans: &'s [bool],
+    pub strings: &'s [Cow<'s, str>],
+    pub property_types: &'s [PropertyType],
+    pub lengths: &'s [u64],
+}
+
+impl Columns<'_> {
+    fn count(&self, kind: ColumnKind) -> usize {
+        match kind {
+            ColumnKind::Integers => self.integers.len(),
+            ColumnKind::Booleans => self.booleans.len(),
+            ColumnKind::Strings => self.strings.len(),
+            ColumnKind::PropertyTypes => self.property_types.len(),
+            ColumnKind::Lengths => self.lengths.len(),
+        }
+    }
+}
+
+impl Serializer<'_> {
+    // Every column in `ColumnKind::ALL` order, empty ones included.
+    pub fn finish_columns(&self) -> Vec<(ColumnKind, Vec<u8>)> {
+        assert!(
+            self.only_columns(),
+            "only the five columns can be written separately"
+        );
+        let columns = self.as_columns();
+        let plan = self.int_plan();
+        let flags = self.chosen_string_flags();
+        ColumnKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let mut buffer = Vec::new();
+                let mut packer = BitPacker::new(&mut buffer);
+                packer.write_small_int(FORMAT_REVISION);
+                packer.write_count(columns.count(kind) as u64);
+                match kind {
+                    ColumnKind::Integers => {
+                        let custom = plan.custom_mask();
+                        packer.write_bit(custom.is_some());
+                        packer.write_small_int(custom.unwrap_or(plan.table() as u64));
+                        columns::write_integers(&mut packer, columns.integers, plan.widths());
+                    }
+                    ColumnKind::Booleans => columns::write_booleans(&mut packer, columns.booleans),
+                    ColumnKind::Strings => {
+                        for flag in [
+                            flags.all_ascii,
+                            flags.front_coded,
+                            flags.lz,
+                            flags.deduplicated,
+                        ] {
+                            packer.write_bit(flag);
+                        }
+                        let seen = &mut self.repeats_scratch();
+                        columns::write_strings(&mut packer, columns.strings, flags, seen)
+                            .expect("all_ascii is only set when every string is encodable");
+                    }
+                    ColumnKind::PropertyTypes => {
+                        columns::write_property_types(&mut packer, columns.property_types)
+                    }
+                    ColumnKind::Lengths => columns::write_lengths(&mut packer, columns.lengths),
+                }
+                (kind, buffer)
+            })
+            .collect()
+    }
+}
+
+impl Deserializer {
+    // Loads the columns `Serializer::finish_columns` wrote, in any order. A column that isn't
+    // there is empty, one given more than once is read onto the end of what came before it.
+    pub fn read_columns(&mut self, columns: &[(ColumnKind, &[u8])]) -> Result<(), DecodeError> {
+        if self.state() == LoadState::Loaded {
+            return Err(DecodeError::AlreadyLoaded {
+                remaining: self.remaining_counts(),
+            });
+        }
+        self.clear();
+        self.start_work();
+        let mut string_budget = self.limits.max_decoded_bytes;
+        for (kind, bytes) in columns {
+            if let Err(error) = self.read_column(*kind, bytes, &mut string_budget) {
+                self.clear();
+                return Err(error);
+            }
+        }
+        self.loaded = true;
+        Ok(())
+    }
+
+    fn read_column(
+        &mut self,
+        kind: ColumnKind,
+        bytes: &[u8],
+        string_budget: &mut u64,
+    ) -> Result<(), DecodeError> {
+        let truncated = DecodeError::Truncated(kind.section());
+        let mut unpacker = BitUnpacker::new(bytes);
+        let revision = unpacker.read_small_int().ok_or(truncated.clone())?;
+        if revision != FORMAT_REVISION {
+            return Err(DecodeError::UnsupportedRevision(revision));
+        }
+        let count = unpacker.read_count().ok_or(truncated.clone())?;
+        let max = self.limits.max_column_values;
+        if count > max {
+            return Err(DecodeError::LimitExceeded {
+                limit: Limit::ColumnValues,
+                max,
+            });
+        }
+        self.charge_work(count)
+            .ok_or_else(|| self.work_budget_exceeded())?;
+
+        let start = unpacker.bit_position();
+        let progress = &mut Progress::default();
+        let read = match kind {
+            ColumnKind::Integers => (|| {
+                let plan = match unpacker.read_bit()? {
+                    true => IntHeaderPlan::from_mask(unpacker.read_small_int()?)?,
+                    false => {
+                        IntHeaderPlan::from_table(u8::try_from(unpacker.read_small_int()?).ok()?)?
+                    }
+                };
+                let flags = (true, plan.widths());
+                columns::read_integers(&mut unpacker, count, flags, &mut self.integers, progress)
+                    .map(|_| ())
+            })(),
+            ColumnKind::Booleans => {
+                columns::read_booleans(&mut unpacker, count, &mut self.booleans, progress)
+            }
+            ColumnKind::Strings => (|| {
+                let mut flag = || unpacker.read_bit();
+                let flags = StringFlags {
+                    all_ascii: flag()?,
+                    front_coded: flag()?,
+                    lz: flag()?,
+                    deduplicated: flag()?,
+                };
+                columns::read_strings(
+                    &mut unpacker,
+                    count,
+                    flags,
+                    &mut self.strings,
+                    &mut self.scratch,
+                    string_budget,
+                    progress,
+                )
+            })(),
+            ColumnKind::PropertyTypes => {
+                let tags = &mut self.property_types;
+                columns::read_property_types(&mut unpacker, count, tags, progress)
+            }
+            ColumnKind::Lengths => {
+                columns::read_lengths(&mut unpacker, count, &mut self.lengths, progress)
+            }
+        };
+        read.ok_or(truncated)?;
+        // the counts `take_*` goes by, and the bits after the column's revision and count
+        let stats = &mut self.stats;
+        let column = match kind {
+            ColumnKind::Integers => &mut stats.integers,
+            ColumnKind::Booleans => &mut stats.booleans,
+            ColumnKind::Strings => &mut stats.strings,
+            ColumnKind::PropertyTypes => &mut stats.property_types,
+            ColumnKind::Lengths => &mut stats.lengths,
+        };
+        column.values += count;
+        column.bits += unpacker.bit_position() - start;
+        // only the padding out to the last byte is left
+        match unpacker.remaining_bits() / 8 {
+            0 => Ok(()),
+            bytes => Err(DecodeError::TrailingBytes(bytes as usize)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{int_plan::IntHeaderPlan, serializer::PropertyValue};
+
+    fn array() -> Vec<PropertyValue> {
+        vec![
+            PropertyValue::Integer(7),
+            PropertyValue::String("ünïcode".to_owned()),
+            PropertyValue::Array(vec![PropertyValue::Bool(false)]),
+        ]
+    }
+
+    fn write<'a>(serializer: &mut Serializer<'a>, array: &'a [PropertyValue]) {
+        for i in 0..200 {
+            serializer.write_int(i * 37 - 1_000);
+            serializer.write_bool(i % 3 == 0);
+            serializer.write_string_owned(format!("metrics.host{:02}.cpu", i % 8));
+        }
+        serializer.write_array(array);
+    }
+
+    fn take(deserializer: &mut Deserializer) -> Vec<PropertyValue> {
+        let mut values = Vec::new();
+        for _ in 0..200 {
+            values.push(PropertyValue::Integer(deserializer.take_int().unwrap()));
+            values.push(PropertyValue::Bool(deserializer.take_bool().unwrap()));
+            values.push(PropertyValue::String(deserializer.take_string().unwrap()));
+        }
+        values.push(PropertyValue::Array(deserializer.take_array().unwrap()));
+        assert_eq!(deserializer.state(), LoadState::Drained);
+        values
+    }
+
+    #[test]
+    pub fn split_columns_read_like_the_buffer() {
+        let options: [fn(&mut Serializer); 4] = [
+            |_| {},
+            |s| s.set_front_coding(true),
+            |s| s.set_lz_strings(true),
+            |s| s.set_int_header_plan(IntHeaderPlan::custom(&[4, 16, 64]).unwrap()),
+        ];
+        let array = array();
+        for set in options {
+            let mut serializer = Serializer::new();
+            set(&mut serializer);
+            write(&mut serializer, &array);
+            let mut buffer = Vec::new();
+            serializer.finish(&mut buffer, 0);
+            let mut deserializer = Deserializer::new();
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            let whole = take(&mut deserializer);
+
+            let split = serializer.finish_columns();
+            assert_eq!(
+                split.iter().map(|(kind, _)| *kind).collect::<Vec<_>>(),
+                ColumnKind::ALL
+            );
+            // in any order
+            let columns: Vec<(ColumnKind, &[u8])> = split
+                .iter()
+                .rev()
+                .map(|(kind, bytes)| (*kind, &bytes[..]))
+                .collect();
+            deserializer.read_columns(&columns).unwrap();
+            assert_eq!(take(&mut deserializer), whole);
+        }
+    }
+
+    #[test]
+    pub fn columns_are_the_written_values() {
+        let mut serializer = Serializer::new();
+        serializer.write_int(-4);
+        serializer.write_string("a");
+        let array = [PropertyValue::Bool(true)];
+        serializer.write_array(&array);
+        let columns = serializer.as_columns();
+        assert_eq!(columns.integers, [-4]);
+        assert_eq!(columns.booleans, [true]);
+        assert_eq!(columns.strings, ["a"]);
+        assert_eq!(columns.property_types, [PropertyType::Bool]);
+        assert_eq!(columns.lengths, [1]);
+    }
+
+    #[test]
+    pub fn bad_columns_are_rejected() {
+        let array = array();
+        let mut serializer = Serializer::new();
+        write(&mut serializer, &array);
+        let split = serializer.finish_columns();
+        let (kind, strings) = &split[2];
+        assert_eq!(*kind, ColumnKind::Strings);
+
+        let mut deserializer = Deserializer::new();
+        let truncated = &strings[..strings.len() - 2];
+        assert_eq!(
+            deserializer.read_columns(&[(*kind, truncated)]),
+            Err(DecodeError::Truncated(Section::Strings))
+        );
+        assert_eq!(deserializer.state(), LoadState::Empty);
+
+        let mut trailing = strings.clone();
+        trailing.push(0);
+        assert_eq!(
+            deserializer.read_columns(&[(*kind, &trailing)]),
+            Err(DecodeError::TrailingBytes(1))
+        );
+
+        let mut other = Vec::new();
+        let mut packer = BitPacker::new(&mut other);
+        packer.write_small_int(FORMAT_REVISION - 1);
+        assert_eq!(
+            deserializer.read_columns(&[(*kind, &other)]),
+            Err(DecodeError::UnsupportedRevision(FORMAT_REVISION - 1))
+        );
+
+        deserializer.read_columns(&[(*kind, strings)]).unwrap();
+        assert!(matches!(
+            deserializer.read_columns(&[]),
+            Err(DecodeError::AlreadyLoaded { .. })
+        ));
+    }
+}
@@ -0,0 +1,855 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    hash::{DefaultHasher, Hasher},
+    mem,
+};
+
+use crate::{
+    appender,
+    bit_packer::{BitPacker, BitUnpacker, int_encoded_bits_in, length_int},
+    checksum,
+    error::{DecodeError, Section},
+    format_spec::STRING_SECTION_LEN,
+    huffman::{self, HUFFMAN_TABLE, MissingCode},
+    lz,
+    progress::Progress,
+    serializer::PropertyType,
+};
+
+// Encoding of each column on its own so the same code can run over the whole buffer in sequence,
+// or over independent byte-aligned sections when the buffer was written with column offsets.
+
+// Strings are written in sections of `STRING_SECTION_LEN` when written with column offsets, the
+// string column is usually the largest so it is split up further to give parallel decoding
+// something to work with.
+
+// returns whether the column was collapsed to a single constant value, `widths` is the
+// `IntHeaderPlan`'s
+pub(crate) fn write_integers(packer: &mut BitPacker, integers: &[i64], widths: &[u8]) -> bool {
+    // a column of a single repeated value (all zeros, the same enum ordinal, etc.) collapses
+    // down to one value, the count is already in the header.
+    if let [first, rest @ ..] = integers
+        && !rest.is_empty()
+    {
+        let constant = rest.iter().all(|int| int == first);
+        packer.write_bit(constant);
+        if constant {
+            packer.write_int_in(*first, widths);
+            return true;
+        }
+
+        // Values clustered away from zero (timestamps, ids, ports) are written as their distance
+        // from the column's minimum, which is written once. The distance is never negative, but
+        // can be past `i64::MAX` and wraps to the 64 bit width with the minimum added back.
+        let min = *integers.iter().min().expect("at least two integers");
+        let framed = framing_saves(integers, min, widths);
+        packer.write_bit(framed);
+        if framed {
+            packer.write_int_in(min, widths);
+            for integer in integers {
+                packer.write_int_in(integer.wrapping_sub(min), widths);
+            }
+            return false;
+        }
+    }
+    for integer in integers {
+        packer.write_int_in(*integer, widths);
+    }
+    false
+}
+
+fn framing_saves(integers: &[i64], min: i64, widths: &[u8]) -> bool {
+    let bits = |int: i64| int_encoded_bits_in(int, widths);
+    let plain: u64 = integers.iter().map(|int| bits(*int)).sum();
+    let framed: u64 = integers.iter().map(|int| bits(int.wrapping_sub(min))).sum();
+    bits(min) + framed < plain
+}
+
+pub(crate) fn write_booleans(packer: &mut BitPacker, booleans: &[bool]) {
+    for boolean in booleans {
+        packer.write_bit(*boolean);
+    }
+}
+
+// How the string column is written, from the header
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct StringFlags {
+    // only for strings the ascii codecs can encode, see `ascii_encodable`
+    pub all_ascii: bool,
+    pub front_coded: bool,
+    // `lz::write_strings`, which the other flags are never set with
+    pub lz: bool,
+    // every string starts with a bit for whether it's a copy of an earlier one, see `repeats`
+    pub deduplicated: bool,
+}
+
+// Strings shorter than this are written out again rather than referred back to, a reference is
+// a bit and the distance as a small int.
+pub(crate) const DEDUP_MIN_LEN: usize = 8;
+
+// For each string, how many strings back the last one equal to it is, when it's at least
+// `DEDUP_MIN_LEN` long. `seen` is scratch the serializer keeps so this doesn't allocate, keyed by
+// a hash of each string rather than borrowing it.
+pub(crate) fn repeats<'s>(
+    strings: &'s [Cow<str>],
+    seen: &'s mut HashMap<u64, usize>,
+) -> impl Iterator<Item = Option<usize>> + use<'s> {
+    seen.clear();
+    strings.iter().enumerate().map(move |(index, string)| {
+        if string.len() < DEDUP_MIN_LEN {
+            return None;
+        }
+        let mut hasher = DefaultHasher::new();
+        hasher.write(string.as_bytes());
+        // a collision is only a repeat that's written out again
+        let earlier = seen.insert(hasher.finish(), index)?;
+        (strings[earlier] == *string).then_some(index - earlier)
+    })
+}
+
+// front coding (and the lz matches and deduplicated references) restart at the start of every
+// call so sections don't depend on each other
+pub(crate) fn write_strings(
+    packer: &mut BitPacker,
+    strings: &[Cow<str>],
+    StringFlags {
+        all_ascii,
+        front_coded,
+        lz,
+        deduplicated,
+    }: StringFlags,
+    seen: &mut HashMap<u64, usize>,
+) -> Result<(), MissingCode> {
+    if lz {
+        lz::write_strings(packer, strings);
+        return Ok(());
+    }
+    let mut repeats = deduplicated.then(|| repeats(strings, seen));
+    let mut previous = "";
+    for string in strings {
+        if let Some(repeats) = &mut repeats {
+            let repeat = repeats.next().flatten();
+            packer.write_bit(repeat.is_some());
+            if let Some(distance) = repeat {
+                packer.write_small_int(distance as u64 - 1);
+                previous = string;
+                continue;
+            }
+        }
+        let mut suffix: &str = string;
+        if front_coded {
+            let shared = shared_prefix(previous, string);
+            packer.write_small_int(shared as u64);
+            suffix = &string[shared..];
+        }
+        if all_ascii {
+            packer.write_ascii_string_adaptive(suffix)?;
+        } else {
+            packer.write_unicode_huffman_string(suffix);
+        }
+        previous = string;
+    }
+    Ok(())
+}
+
+// Whether every string has a huffman code for each of its bytes, which is what the ascii flag
+// promises the reader. The unicode codec escapes anything else, so when this is false the strings
+// fall back to that instead of `write_strings` failing halfway through the column.
+pub(crate) fn ascii_encodable<S: AsRef<str>>(strings: &[S]) -> bool {
+    strings
+        .iter()
+        .all(|string| huffman::check(&HUFFMAN_TABLE, string.as_ref().as_bytes()).is_ok())
+}
+
+pub(crate) fn write_property_types(packer: &mut BitPacker, tags: &[PropertyType]) {
+    for tag in tags {
+        packer.write_property_type(*tag);
+    }
+}
+
+pub(crate) fn write_lengths(packer: &mut BitPacker, lengths: &[u64]) {
+    for length in lengths {
+        packer.write_small_int(*length);
+    }
+}
+
+// name, which pool and where in that pool the named value is
+pub(crate) type FieldName<'a> = (&'a str, PropertyType, usize);
+
+// always written (as a zero count) after the columns, outside of the column offsets table
+pub(crate) fn write_names(packer: &mut BitPacker, names: &[FieldName]) {
+    packer.write_small_int(names.len() as u64);
+    for (name, kind, index) in names {
+        packer.write_property_type(*kind);
+        packer.write_small_int(*index as u64);
+        appender::write_inline_string(packer, name);
+    }
+}
+
+// Strings written with `StringCodec::Raw`. Each is the gap since the previous one's position
+// among all of the strings, then its bytes as they are.
+pub(crate) fn write_raw_strings(packer: &mut BitPacker, strings: &[(usize, Cow<str>)]) {
+    packer.write_small_int(strings.len() as u64);
+    let mut next = 0;
+    for (position, string) in strings {
+        packer.write_small_int((position - next) as u64);
+        packer.write_int(length_int(string.len()));
+        packer.write_bytes(string.as_bytes());
+        next = position + 1;
+    }
+}
+
+// `each` gets the position and bytes of every string in order, returns how many there were
+pub(crate) fn read_raw_strings(
+    unpacker: &mut BitUnpacker,
+    scratch: &mut Vec<u8>,
+    budget: &mut u64,
+    mut each: impl FnMut(usize, &mut Vec<u8>) -> Option<()>,
+) -> Option<u64> {
+    let count = unpacker.read_small_int()?;
+    let mut next = 0usize;
+    for _ in 0..count {
+        let position = next.checked_add(usize::try_from(unpacker.read_small_int()?).ok()?)?;
+        let len = u64::try_from(unpacker.read_int()?).ok()?;
+        if len > unpacker.remaining_bits() / 8 {
+            return None;
+        }
+        *budget = budget.checked_sub(len)?;
+        scratch.clear();
+        for _ in 0..len {
+            scratch.push(unpacker.read_byte()?);
+        }
+        each(position, scratch)?;
+        next = position + 1;
+    }
+    Some(count)
+}
+
+// the first value written under a name wins
+pub(crate) fn read_names(
+    unpacker: &mut BitUnpacker,
+    names: &mut HashMap<String, (PropertyType, usize)>,
+) -> Option<()> {
+    let count = unpacker.read_small_int()?;
+    let mut bytes = Vec::new();
+    for _ in 0..count {
+        let kind = unpacker.read_property_type().ok()?;
+        let index = usize::try_from(unpacker.read_small_int()?).ok()?;
+        bytes.clear();
+        appender::read_inline_string_into(unpacker, &mut bytes)?;
+        let name = String::from_utf8_lossy(&bytes).into_owned();
+        names.entry(name).or_insert((kind, index));
+    }
+    Some(())
+}
+
+pub(crate) fn skip_names(unpacker: &mut BitUnpacker, scratch: &mut Vec<u8>) -> Option<u64> {
+    let count = unpacker.read_small_int()?;
+    for _ in 0..count {
+        unpacker.read_property_type().ok()?;
+        unpacker.read_small_int()?;
+        scratch.clear();
+        appender::read_inline_string_into(unpacker, scratch)?;
+    }
+    Some(count)
+}
+
+// in bytes, backed off to a char boundary so the suffix is still a `str`
+pub(crate) fn shared_prefix(previous: &str, string: &str) -> usize {
+    let mut shared = previous
+        .bytes()
+        .zip(string.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !string.is_char_boundary(shared) {
+        shared -= 1;
+    }
+    shared
+}
+
+// A decoded column read front to back. Values stay where they were decoded and `read` moves past
+// them, which is cheaper than a `VecDeque` for the handful of values a typical message has per
+// column, and leaves the whole allocation to be reused by the next `read_bytes`.
+pub(crate) struct Column<T> {
+    values: Vec<T>,
+    read: usize,
+}
+
+impl<T> Default for Column<T> {
+    fn default() -> Self {
+        Column {
+            values: Vec::new(),
+            read: 0,
+        }
+    }
+}
+
+impl<T> Column<T> {
+    pub fn pop_front(&mut self) -> Option<T>
+    where
+        T: Copy,
+    {
+        let value = *self.values.get(self.read)?;
+        self.read += 1;
+        Some(value)
+    }
+
+    // the values that haven't been read yet
+    pub fn as_slice(&self) -> &[T] {
+        &self.values[self.read..]
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len() - self.read
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.as_slice().last()
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        self.values.push(value);
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Option<()> {
+        self.values.try_reserve(additional).ok()
+    }
+
+    // Undoes `Serializer::append_transposed` for the next `items * stride` values, putting them
+    // back in record order. `None` if there aren't that many.
+    pub fn interleave_front(&mut self, items: usize, stride: usize) -> Option<()>
+    where
+        T: Copy,
+    {
+        let end = items.checked_mul(stride)?;
+        let front = self.values.get_mut(self.read..)?.get_mut(..end)?;
+        let fields = front.to_vec();
+        for (index, value) in front.iter_mut().enumerate() {
+            *value = fields[index % stride * items + index / stride];
+        }
+        Some(())
+    }
+
+    // the unread values, leaves the column empty
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        let read = mem::take(&mut self.read);
+        self.values.drain(..).skip(read)
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.read = 0;
+    }
+}
+
+// Decoded strings back to back in one buffer, read front to back like a `Column`. `clear` keeps
+// both allocations, so once a deserializer has decoded a message its size the strings of the next
+// one don't allocate at all.
+#[derive(Default)]
+pub(crate) struct TextColumn {
+    text: String,
+    // byte range of each string in `text`
+    spans: Column<(usize, usize)>,
+}
+
+impl TextColumn {
+    // invalid UTF-8 is replaced rather than rejected, the same as the rest of the strings
+    pub fn push(&mut self, bytes: &[u8]) {
+        let start = self.text.len();
+        match std::str::from_utf8(bytes) {
+            Ok(string) => self.text.push_str(string),
+            Err(_) => self.text.push_str(&String::from_utf8_lossy(bytes)),
+        }
+        self.spans.push_back((start, self.text.len()));
+    }
+
+    fn span(&self, (start, end): (usize, usize)) -> &str {
+        &self.text[start..end]
+    }
+
+    pub fn pop_front(&mut self) -> Option<&str> {
+        let span = self.spans.pop_front()?;
+        Some(self.span(span))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.spans.get(index).map(|span| self.span(*span))
+    }
+
+    pub fn back(&self) -> Option<&str> {
+        self.spans.back().map(|span| self.span(*span))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.spans.iter().map(|span| self.span(*span))
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn try_reserve(&mut self, additional: usize) -> Option<()> {
+        self.spans.try_reserve(additional)
+    }
+
+    pub fn interleave_front(&mut self, items: usize, stride: usize) -> Option<()> {
+        self.spans.interleave_front(items, stride)
+    }
+
+    pub fn append(&mut self, other: &mut TextColumn) {
+        let offset = self.text.len();
+        self.text.push_str(&other.text);
+        self.spans.extend(
+            other
+                .spans
+                .drain()
+                .map(|(start, end)| (start + offset, end + offset)),
+        );
+        other.text.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.spans.clear();
+    }
+}
+
+impl fmt::Debug for TextColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Extend<T> for Column<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        self.values.extend(values);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Column<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// returns whether the column was a single constant value, `framed` is whether the buffer's
+// revision has the frame of reference flag
+pub(crate) fn read_integers(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    (framed, widths): (bool, &[u8]),
+    integers: &mut Column<i64>,
+    progress: &mut Progress,
+) -> Option<bool> {
+    if count > 1 && unpacker.read_bit()? {
+        let value = unpacker.read_int_in(widths)?;
+        // the only count that isn't bounded by the input size, fail rather than abort on a
+        // corrupt one that can't be allocated
+        let count = usize::try_from(count).ok()?;
+        integers.try_reserve(count)?;
+        integers.extend(std::iter::repeat_n(value, count));
+        return Some(true);
+    }
+    let min = match count > 1 && framed && unpacker.read_bit()? {
+        true => unpacker.read_int_in(widths)?,
+        false => 0,
+    };
+    for _ in 0..count {
+        integers.push_back(unpacker.read_int_in(widths)?.wrapping_add(min));
+        progress.values(1, unpacker.bit_position())?;
+    }
+    Some(false)
+}
+
+pub(crate) fn read_booleans(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    booleans: &mut Column<bool>,
+    progress: &mut Progress,
+) -> Option<()> {
+    // up to a byte of them per read rather than a bit at a time
+    let mut left = count;
+    while left > 0 {
+        let width = left.min(8) as u8;
+        let bits = unpacker.read_bits(width)?;
+        booleans.extend((0..width).rev().map(|shift| bits >> shift & 1 == 1));
+        left -= width as u64;
+        progress.values(width as u64, unpacker.bit_position())?;
+    }
+    Some(())
+}
+
+pub(crate) fn read_strings(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    flags: StringFlags,
+    strings: &mut TextColumn,
+    scratch: &mut Vec<u8>,
+    // bytes the decoded strings may add up to, reduced by each one
+    budget: &mut u64,
+    progress: &mut Progress,
+) -> Option<()> {
+    let StringFlags {
+        all_ascii,
+        front_coded,
+        lz,
+        deduplicated,
+    } = flags;
+    if lz {
+        return lz::read_strings(unpacker, count, strings, scratch, budget, progress);
+    }
+    let first = strings.len();
+    for _ in 0..count {
+        scratch.clear();
+        if deduplicated && unpacker.read_bit()? {
+            let distance = usize::try_from(unpacker.read_small_int()?).ok()?;
+            let earlier = strings.len().checked_sub(distance.checked_add(1)?)?;
+            if earlier < first {
+                return None;
+            }
+            scratch.extend_from_slice(strings.get(earlier)?.as_bytes());
+            *budget = budget.checked_sub(scratch.len() as u64)?;
+            strings.push(scratch);
+            progress.values(1, unpacker.bit_position())?;
+            continue;
+        }
+        if front_coded {
+            let shared = usize::try_from(unpacker.read_small_int()?).ok()?;
+            let previous = match strings.back() {
+                Some(string) if strings.len() > first => string,
+                _ => "",
+            };
+            scratch.extend_from_slice(previous.as_bytes().get(..shared)?);
+        }
+        read_suffix(unpacker, all_ascii, scratch)?;
+        *budget = budget.checked_sub(scratch.len() as u64)?;
+        strings.push(scratch);
+        progress.values(1, unpacker.bit_position())?;
+    }
+    Some(())
+}
+
+fn read_suffix(unpacker: &mut BitUnpacker, all_ascii: bool, bytes: &mut Vec<u8>) -> Option<()> {
+    if !all_ascii {
+        unpacker.read_unicode_huffman_into(bytes)
+    } else if unpacker.read_bit()? {
+        unpacker.read_ascii_huffman_into(bytes)
+    } else {
+        unpacker.read_ascii_ultrapacked_into(bytes)
+    }
+}
+
+pub(crate) fn read_property_types(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    tags: &mut Column<PropertyType>,
+    progress: &mut Progress,
+) -> Option<()> {
+    // as many whole tags as fit in a byte per read, the width depends on the buffer's revision
+    let tag_bits = unpacker.tag_bits();
+    let per_read = 8 / tag_bits;
+    let mut left = count;
+    while left > 0 {
+        let read = left.min(per_read as u64) as u8;
+        let bits = unpacker.read_bits(read * tag_bits)?;
+        for index in (0..read).rev() {
+            let tag = bits >> (index * tag_bits) & ((1 << tag_bits) - 1);
+            tags.push_back(PropertyType::from_bits(tag)?);
+        }
+        left -= read as u64;
+        progress.values(read as u64, unpacker.bit_position())?;
+    }
+    Some(())
+}
+
+pub(crate) fn read_lengths(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    lengths: &mut Column<u64>,
+    progress: &mut Progress,
+) -> Option<()> {
+    for _ in 0..count {
+        lengths.push_back(unpacker.read_small_int()?);
+        progress.values(1, unpacker.bit_position())?;
+    }
+    Some(())
+}
+
+pub(crate) fn skip_integers(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    (framed, widths): (bool, &[u8]),
+) -> Option<()> {
+    if count > 1 && unpacker.read_bit()? {
+        unpacker.read_int_in(widths)?;
+        return Some(());
+    }
+    if count > 1 && framed && unpacker.read_bit()? {
+        unpacker.read_int_in(widths)?;
+    }
+    for _ in 0..count {
+        unpacker.read_int_in(widths)?;
+    }
+    Some(())
+}
+
+pub(crate) fn skip_booleans(unpacker: &mut BitUnpacker, count: u64) -> Option<()> {
+    for _ in 0..count {
+        unpacker.read_bit()?;
+    }
+    Some(())
+}
+
+// returns the decoded size of the strings, `first` is the index of the first one for errors
+pub(crate) fn skip_strings(
+    unpacker: &mut BitUnpacker,
+    first: usize,
+    count: u64,
+    flags: StringFlags,
+    scratch: &mut Vec<u8>,
+) -> Result<u64, DecodeError> {
+    let truncated = DecodeError::Truncated(crate::error::Section::Strings);
+    let StringFlags {
+        all_ascii,
+        front_coded,
+        lz,
+        deduplicated,
+    } = flags;
+    if lz {
+        return lz::skip_strings(unpacker, count, scratch).ok_or(truncated);
+    }
+    let mut decoded = 0;
+    let mut previous_len = 0;
+    let count = usize::try_from(count).map_err(|_| truncated.clone())?;
+    // the length of every string so far, for the references to look back at
+    let mut lengths = Vec::new();
+    for string in first..first.saturating_add(count) {
+        if deduplicated && unpacker.read_bit().ok_or(truncated.clone())? {
+            let distance = unpacker.read_small_int().ok_or(truncated.clone())?;
+            let earlier = usize::try_from(distance)
+                .ok()
+                .and_then(|distance| lengths.len().checked_sub(distance.checked_add(1)?));
+            previous_len = *earlier
+                .and_then(|earlier| lengths.get(earlier))
+                .ok_or(truncated.clone())?;
+            decoded += previous_len as u64;
+            lengths.push(previous_len);
+            continue;
+        }
+        let mut shared = 0;
+        if front_coded {
+            let prefix = unpacker.read_small_int().ok_or(truncated.clone())?;
+            shared = usize::try_from(prefix).map_err(|_| DecodeError::InvalidPrefix { string })?;
+            if shared > previous_len {
+                return Err(DecodeError::InvalidPrefix { string });
+            }
+        }
+        scratch.clear();
+        read_suffix(unpacker, all_ascii, scratch).ok_or(truncated.clone())?;
+        previous_len = shared + scratch.len();
+        decoded += previous_len as u64;
+        if deduplicated {
+            lengths.push(previous_len);
+        }
+    }
+    Ok(decoded)
+}
+
+pub(crate) fn skip_property_types(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+) -> Result<(), DecodeError> {
+    for _ in 0..count {
+        unpacker.read_property_type()?;
+    }
+    Ok(())
+}
+
+pub(crate) fn skip_lengths(unpacker: &mut BitUnpacker, count: u64) -> Option<()> {
+    for _ in 0..count {
+        unpacker.read_small_int()?;
+    }
+    Some(())
+}
+
+// Every column's section back to back, kept by the serializer so that `finish` with column
+// offsets packs into the same buffers each time rather than a fresh one per section.
+#[derive(Debug, Default)]
+pub(crate) struct SectionScratch {
+    // the section being packed, `BitPacker::new` clears it
+    packing: Vec<u8>,
+    bytes: Vec<u8>,
+    // where each section ends in `bytes`
+    ends: Vec<usize>,
+}
+
+impl SectionScratch {
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.ends.clear();
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.packing.capacity() + self.bytes.capacity()
+    }
+
+    // packs a column into its own section, returns the number of bits actually used
+    pub fn pack(&mut self, write: impl FnOnce(&mut BitPacker)) -> u64 {
+        let mut packer = BitPacker::new(&mut self.packing);
+        write(&mut packer);
+        let bits = packer.bit_position();
+        self.bytes.extend_from_slice(&self.packing);
+        self.ends.push(self.bytes.len());
+        bits
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &[u8]> + Clone {
+        let starts = [0].into_iter().chain(self.ends.iter().copied());
+        starts
+            .zip(&self.ends)
+            .map(|(start, end)| &self.bytes[start..*end])
+    }
+}
+
+// how many of `strings` land in section `index`
+pub(crate) fn section_strings(strings: u64, index: usize) -> u64 {
+    let start = (index * STRING_SECTION_LEN) as u64;
+    strings.saturating_sub(start).min(STRING_SECTION_LEN as u64)
+}
+
+// Byte ranges of each column, written as a table of byte lengths after the header followed by the
+// byte-aligned columns. With section checksums the table is followed by the CRC-32 of each
+// section, 4 little endian bytes each, see `Serializer::set_section_checksums`.
+pub(crate) struct Sections<'b> {
+    pub integers: &'b [u8],
+    pub booleans: &'b [u8],
+    pub strings: Vec<&'b [u8]>,
+    pub property_types: &'b [u8],
+    pub lengths: &'b [u8],
+    // in the same order as the sections, empty when the buffer has none
+    pub checksums: Vec<u32>,
+}
+
+impl<'b> Sections<'b> {
+    pub fn write<'s>(
+        packer: &mut BitPacker,
+        sections: impl Iterator<Item = &'s [u8]> + Clone,
+        checksums: bool,
+    ) {
+        for section in sections.clone() {
+            packer.write_int(length_int(section.len()));
+        }
+        packer.align_to_byte();
+        if checksums {
+            for section in sections {
+                packer.write_aligned_bytes(&checksum::crc32(section).to_le_bytes());
+            }
+        }
+    }
+
+    // leaves `unpacker` at the end of the last section
+    pub fn read(unpacker: &mut BitUnpacker<'b>, strings: u64, checksums: bool) -> Option<Self> {
+        let string_sections = strings.div_ceil(STRING_SECTION_LEN as u64);
+        let mut lengths = Vec::new();
+        for _ in 0..string_sections + 4 {
+            lengths.push(usize::try_from(unpacker.read_int()?).ok()?);
+        }
+        let mut crcs = Vec::new();
+        if checksums {
+            for _ in 0..lengths.len() {
+                let bytes = unpacker.read_aligned_bytes(4)?;
+                crcs.push(u32::from_le_bytes(bytes.try_into().ok()?));
+            }
+        }
+
+        let mut sections = Vec::with_capacity(lengths.len());
+        for length in lengths {
+            sections.push(unpacker.read_aligned_bytes(length)?);
+        }
+
+        let lengths = sections.pop()?;
+        let property_types = sections.pop()?;
+        let mut sections = sections.into_iter();
+        let integers = sections.next()?;
+        let booleans = sections.next()?;
+        Some(Sections {
+            integers,
+            booleans,
+            strings: sections.collect(),
+            property_types,
+            lengths,
+            checksums: crcs,
+        })
+    }
+
+    // The columns whose section doesn't match its checksum, in the order they're written. A
+    // corrupt string section reports the whole string column.
+    pub fn corrupt(&self) -> Vec<Section> {
+        let sections = [
+            (Section::Integers, self.integers),
+            (Section::Booleans, self.booleans),
+        ]
+        .into_iter()
+        .chain(self.strings.iter().map(|bytes| (Section::Strings, *bytes)))
+        .chain([
+            (Section::PropertyTypes, self.property_types),
+            (Section::Lengths, self.lengths),
+        ]);
+        let mut corrupt = Vec::new();
+        for ((section, bytes), checksum) in sections.zip(&self.checksums) {
+            if checksum::crc32(bytes) != *checksum && corrupt.last() != Some(&section) {
+                corrupt.push(section);
+            }
+        }
+        corrupt
+    }
+}
+
+// runs both on the rayon pool when `parallel`, otherwise one after the other
+pub(crate) fn join<A: Send, B: Send>(
+    parallel: bool,
+    a: impl FnOnce() -> A + Send,
+    b: impl FnOnce() -> B + Send,
+) -> (A, B) {
+    #[cfg(feature = "rayon")]
+    if parallel {
+        return rayon::join(a, b);
+    }
+    #[cfg(not(feature = "rayon"))]
+    let _ = parallel;
+    (a(), b())
+}
+
+// `f` over each section (with its index), stopping at the first failure
+pub(crate) fn map_sections<T: Send>(
+    parallel: bool,
+    sections: &[&[u8]],
+    f: impl Fn(usize, &[u8]) -> Option<T> + Send + Sync,
+) -> Option<Vec<T>> {
+    #[cfg(feature = "rayon")]
+    if parallel {
+        use rayon::prelude::*;
+        return sections
+            .par_iter()
+            .enumerate()
+            .map(|(index, section)| f(index, section))
+            .collect();
+    }
+    #[cfg(not(feature = "rayon"))]
+    let _ = parallel;
+    sections
+        .iter()
+        .enumerate()
+        .map(|(index, section)| f(index, section))
+        .collect()
+}
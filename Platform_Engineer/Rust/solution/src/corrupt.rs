@@ -0,0 +1,158 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{
+    error::{DecodeError, Section},
+    serializer::{Deserializer, verify},
+};
+
+// Test-only mutations of a valid buffer, for checking that whatever the input the decoder either
+// reads it or fails with an error. A mutation may well still decode (a flipped bit in a value is
+// just a different value), so what's checked is that nothing panics and that `load` never accepts
+// a buffer `verify` rejects or the other way around.
+//
+// Each kind of mutation is capped at `MUTATIONS` evenly spaced positions to keep the tests fast,
+// `CORRUPT_EXHAUSTIVE=1 cargo test corrupt` tries every position instead.
+const MUTATIONS: usize = 256;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Mutation {
+    Truncate(usize),
+    FlipBit(usize),
+    // all ones or all zeros, which turns whatever count or length the byte is part of into a huge
+    // or an empty one
+    SetByte(usize, u8),
+}
+
+impl Mutation {
+    pub fn apply(self, buffer: &[u8]) -> Vec<u8> {
+        let mut mutated = buffer.to_vec();
+        match self {
+            Mutation::Truncate(len) => mutated.truncate(len),
+            Mutation::FlipBit(bit) => mutated[bit / 8] ^= 0x80 >> (bit % 8),
+            Mutation::SetByte(index, byte) => mutated[index] = byte,
+        }
+        mutated
+    }
+}
+
+// up to `MUTATIONS` of each kind for a buffer of `len` bytes
+pub fn mutations(len: usize) -> Vec<Mutation> {
+    let exhaustive = std::env::var_os("CORRUPT_EXHAUSTIVE").is_some();
+    let spaced = |count: usize| {
+        let step = match exhaustive {
+            true => 1,
+            false => count.div_ceil(MUTATIONS).max(1),
+        };
+        (0..count).step_by(step)
+    };
+    let mut mutations: Vec<Mutation> = spaced(len).map(Mutation::Truncate).collect();
+    mutations.extend(spaced(len * 8).map(Mutation::FlipBit));
+    for byte in [0x00, 0xFF] {
+        mutations.extend(spaced(len).map(|index| Mutation::SetByte(index, byte)));
+    }
+    mutations
+}
+
+// Decodes every mutation of `buffer`, panicking with the mutation if decoding panicked or `load`
+// and `verify` disagree. Returns how many of them still decoded.
+pub fn check(buffer: &[u8], version: u8) -> usize {
+    let mut decoded = 0;
+    for mutation in mutations(buffer.len()) {
+        let mutated = mutation.apply(buffer);
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| decode(&mutated, version)));
+        let (loaded, verified) = match outcome {
+            Ok(outcome) => outcome,
+            Err(_) => panic!("{mutation:?} panicked while decoding {mutated:?}"),
+        };
+        match (&loaded, &verified) {
+            (Ok(()), Ok(())) => decoded += 1,
+            // `read_bytes` stops after the last section, only `verify` looks past it
+            (
+                Ok(()),
+                Err(DecodeError::TrailingBytes(_) | DecodeError::Truncated(Section::Padding)),
+            ) => decoded += 1,
+            // limits are the deserializer's, `verify` only checks the buffer is well formed
+            (Err(DecodeError::LimitExceeded { .. }), Ok(())) => {}
+            (Err(_), Err(_)) => {}
+            _ => panic!(
+                "{mutation:?}: load gave {loaded:?} but verify gave {verified:?} for {mutated:?}"
+            ),
+        }
+    }
+    decoded
+}
+
+// `load` and then every value in the buffer, arrays first so their lengths and tags are checked
+// against each other
+fn decode(bytes: &[u8], version: u8) -> (Result<(), DecodeError>, Result<(), DecodeError>) {
+    let verified = verify(bytes, version).map(|_| ());
+    let mut deserializer = Deserializer::new();
+    let loaded = deserializer.load(bytes, version);
+    if loaded.is_ok() {
+        while !deserializer.remaining_view().counts().is_empty()
+            && deserializer.take_array_checked().is_ok()
+        {}
+        deserializer.take_remaining();
+        deserializer.take_tail_array();
+    }
+    (loaded, verified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::{PropertyValue, Serializer, StringCodec};
+
+    fn values() -> Vec<PropertyValue> {
+        vec![
+            PropertyValue::String("ünïcode".to_owned()),
+            PropertyValue::Array(vec![
+                PropertyValue::Integer(1 << 40),
+                PropertyValue::Bool(true),
+                PropertyValue::Array(Vec::new()),
+            ]),
+            PropertyValue::Integer(3),
+        ]
+    }
+
+    #[test]
+    pub fn corrupt_buffers_fail_cleanly() {
+        let values = values();
+        let tail = vec![PropertyValue::String("tail".to_owned())];
+        let mut serializer = Serializer::new();
+        serializer.set_front_coding(true);
+        serializer.set_kind_markers(true);
+        serializer.set_format_id(9);
+        serializer.set_provenance(1_760_000_000, "corrupt").unwrap();
+        serializer.write_int(1920);
+        serializer.write_bool(false);
+        serializer.write_string("window.title");
+        serializer.write_string("window.width");
+        serializer.write_string_with("3f2b8c1e-9d4a", StringCodec::Raw);
+        serializer.write_named_int("height", 1080);
+        serializer.write_array(&values);
+        serializer.write_tail_array(&tail);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 3);
+
+        // the unmutated buffer is never among the mutations, but a few of them should still decode
+        let decoded = check(&buffer, 3);
+        assert!(decoded > 0);
+    }
+
+    #[test]
+    pub fn corrupt_sectioned_buffers_fail_cleanly() {
+        let values = values();
+        let strings: Vec<String> = (0..40).map(|i| format!("service.key_{i}")).collect();
+        let mut serializer = Serializer::new();
+        serializer.set_section_checksums(true);
+        for (i, string) in strings.iter().enumerate() {
+            serializer.write_int(i as i64);
+            serializer.write_string(string);
+        }
+        serializer.write_array(&values);
+        let mut buffer = Vec::new();
+        serializer.finish_aligned(&mut buffer, 0, 8);
+        check(&buffer, 0);
+    }
+}
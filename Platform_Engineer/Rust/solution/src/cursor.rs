@@ -0,0 +1,353 @@
+use crate::{
+    error::DecodeError,
+    remaining::RemainingValues,
+    serializer::{Deserializer, PropertyType, PropertyValue, Serializer},
+};
+
+// Fields go in (and come out) in document order through these, counting as they go so a reader
+// that doesn't line up with the writer gets told which field went wrong instead of silently
+// shifted values.
+//
+// serializer.cursor().int(self.data).str(&self.name).boolean(self.cool);
+//
+// let mut cursor = deserializer.cursor();
+// let config = Config { data: cursor.int()?, name: cursor.str()?, cool: cursor.boolean()? };
+// cursor.finish()?;
+pub struct FieldCursor<'s, 'a> {
+    serializer: &'s mut Serializer<'a>,
+    fields: usize,
+}
+
+impl<'s, 'a> FieldCursor<'s, 'a> {
+    pub(crate) fn new(serializer: &'s mut Serializer<'a>) -> Self {
+        FieldCursor {
+            serializer,
+            fields: 0,
+        }
+    }
+
+    pub fn int(self, value: i64) -> Self {
+        self.field(|serializer| serializer.write_int(value))
+    }
+
+    pub fn str(self, value: &'a str) -> Self {
+        self.field(|serializer| serializer.write_string(value))
+    }
+
+    pub fn boolean(self, value: bool) -> Self {
+        self.field(|serializer| serializer.write_bool(value))
+    }
+
+    pub fn array(self, value: &'a [PropertyValue]) -> Self {
+        self.field(|serializer| serializer.write_array(value))
+    }
+
+    pub fn fields(&self) -> usize {
+        self.fields
+    }
+
+    fn field(mut self, write: impl FnOnce(&mut Serializer<'a>)) -> Self {
+        write(self.serializer);
+        self.fields += 1;
+        self
+    }
+}
+
+// values per column shown in `DecodeError::UnconsumedFields`
+pub(crate) const LEFTOVER_PREVIEW: usize = 4;
+
+pub struct ReadCursor<'d> {
+    deserializer: &'d mut Deserializer,
+    fields: usize,
+    // (field index, value) read in place of fields the writer didn't have, see `Migrator`
+    defaults: &'d [(usize, PropertyValue)],
+}
+
+impl<'d> ReadCursor<'d> {
+    pub(crate) fn new(deserializer: &'d mut Deserializer) -> Self {
+        Self::with_defaults(deserializer, &[])
+    }
+
+    pub(crate) fn with_defaults(
+        deserializer: &'d mut Deserializer,
+        defaults: &'d [(usize, PropertyValue)],
+    ) -> Self {
+        ReadCursor {
+            deserializer,
+            fields: 0,
+            defaults,
+        }
+    }
+
+    pub fn int(&mut self) -> Result<i64, DecodeError> {
+        self.field(
+            PropertyType::Integer,
+            Deserializer::take_int,
+            |value| match value {
+                PropertyValue::Integer(int) => Some(*int),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn str(&mut self) -> Result<String, DecodeError> {
+        self.field(
+            PropertyType::String,
+            Deserializer::take_string,
+            |value| match value {
+                PropertyValue::String(string) => Some(string.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn boolean(&mut self) -> Result<bool, DecodeError> {
+        self.field(
+            PropertyType::Bool,
+            Deserializer::take_bool,
+            |value| match value {
+                PropertyValue::Bool(bool) => Some(*bool),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn array(&mut self) -> Result<Vec<PropertyValue>, DecodeError> {
+        self.field(
+            PropertyType::Array,
+            Deserializer::take_array,
+            |value| match value {
+                PropertyValue::Array(values) => Some(values.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    pub fn fields(&self) -> usize {
+        self.fields
+    }
+
+    // The forward compatible counterpart to `finish`: a writer on a newer schema may have added
+    // fields after the ones this reader knows, which are left over in their pools and returned
+    // rather than treated as an error. Additions are only read past cleanly when each comes after
+    // every known field of the same kind (the pools are per kind, not per field), anything else
+    // shifts the known fields and needs a version bump or a `Migrator`.
+    pub fn remaining(self) -> Result<RemainingValues, DecodeError> {
+        self.deserializer.check_loaded()?;
+        Ok(self.deserializer.take_remaining())
+    }
+
+    // every value the writer put in should have been read by now
+    pub fn finish(self) -> Result<(), DecodeError> {
+        self.deserializer.check_loaded()?;
+        let remaining = self.deserializer.remaining_counts();
+        if remaining.is_empty() {
+            Ok(())
+        } else {
+            Err(DecodeError::UnconsumedFields {
+                fields: self.fields,
+                remaining,
+                leftover: Box::new(self.deserializer.remaining_view().preview(LEFTOVER_PREVIEW)),
+            })
+        }
+    }
+
+    // a default of the wrong kind is as missing as no value at all
+    fn field<T>(
+        &mut self,
+        kind: PropertyType,
+        take: impl FnOnce(&mut Deserializer) -> Option<T>,
+        from_default: impl FnOnce(&PropertyValue) -> Option<T>,
+    ) -> Result<T, DecodeError> {
+        self.deserializer.check_loaded()?;
+        let index = self.fields;
+        self.fields += 1;
+        let value = match self.defaults.iter().find(|(field, _)| *field == index) {
+            Some((_, default)) => from_default(default),
+            None => take(self.deserializer),
+        };
+        value.ok_or_else(|| match self.deserializer.take_kind_mismatch() {
+            // counted in fields rather than values, an array field is all of its values
+            Some(DecodeError::KindMismatch {
+                expected, found, ..
+            }) => DecodeError::KindMismatch {
+                index,
+                expected,
+                found,
+            },
+            _ => DecodeError::MissingField { index, kind },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::ColumnCounts, serializer::IntoFormat};
+
+    fn written() -> Vec<u8> {
+        let arr = vec![PropertyValue::Bool(false)];
+        let mut serializer = Serializer::new();
+        let cursor = serializer
+            .cursor()
+            .int(4)
+            .str("Nice")
+            .boolean(true)
+            .array(&arr);
+        assert_eq!(cursor.fields(), 4);
+
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        buffer
+    }
+
+    #[test]
+    pub fn matching_cursors() {
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&written(), 0).unwrap();
+
+        let mut cursor = deserializer.cursor();
+        assert_eq!(cursor.int(), Ok(4));
+        assert_eq!(cursor.str().as_deref(), Ok("Nice"));
+        assert_eq!(cursor.boolean(), Ok(true));
+        assert_eq!(cursor.array(), Ok(vec![PropertyValue::Bool(false)]));
+        assert_eq!(cursor.finish(), Ok(()));
+    }
+
+    #[test]
+    pub fn mismatched_kind() {
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&written(), 0).unwrap();
+
+        let mut cursor = deserializer.cursor();
+        assert_eq!(cursor.int(), Ok(4));
+        let err = cursor.int().unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::MissingField {
+                index: 1,
+                kind: PropertyType::Integer
+            }
+        );
+        assert!(err.to_string().starts_with("field 1 expected Integer"));
+    }
+
+    #[test]
+    pub fn skipped_field() {
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&written(), 0).unwrap();
+
+        let mut cursor = deserializer.cursor();
+        cursor.int().unwrap();
+        cursor.boolean().unwrap();
+        cursor.array().unwrap();
+        let err = cursor.finish().unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::UnconsumedFields {
+                fields: 3,
+                remaining: ColumnCounts {
+                    strings: 1,
+                    ..Default::default()
+                },
+                leftover: Box::new(RemainingValues {
+                    strings: vec!["Nice".to_owned()],
+                    ..Default::default()
+                })
+            }
+        );
+        assert!(
+            err.to_string()
+                .ends_with("starting with strings [\"Nice\"]"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    pub fn newer_trailing_fields_are_left_over() {
+        // v8 of the schema added a retry count after the fields v7 readers know
+        let mut serializer = Serializer::new();
+        serializer.cursor().int(4).str("Nice").boolean(true).int(3);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 8);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 8).unwrap();
+        let mut cursor = deserializer.cursor();
+        assert_eq!(cursor.int(), Ok(4));
+        assert_eq!(cursor.str().as_deref(), Ok("Nice"));
+        assert_eq!(cursor.boolean(), Ok(true));
+        assert_eq!(
+            cursor.remaining(),
+            Ok(RemainingValues {
+                ints: vec![3],
+                ..Default::default()
+            })
+        );
+        assert!(deserializer.remaining_view().counts().is_empty());
+
+        // the same through `IntoFormat`, which reads what it knows and leaves the rest
+        #[derive(Debug, PartialEq)]
+        struct V7 {
+            id: i64,
+            name: String,
+            enabled: bool,
+        }
+        impl IntoFormat for V7 {
+            fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+                serializer
+                    .cursor()
+                    .int(self.id)
+                    .str(&self.name)
+                    .boolean(self.enabled);
+            }
+
+            fn take(deserializer: &mut Deserializer) -> Option<Self> {
+                let mut cursor = deserializer.cursor();
+                Some(V7 {
+                    id: cursor.int().ok()?,
+                    name: cursor.str().ok()?,
+                    enabled: cursor.boolean().ok()?,
+                })
+            }
+        }
+        let decoded = V7::deserialize(&buffer, &mut deserializer, 8);
+        assert_eq!(
+            decoded,
+            Some(V7 {
+                id: 4,
+                name: "Nice".to_owned(),
+                enabled: true
+            })
+        );
+        assert_eq!(deserializer.take_remaining().ints, [3]);
+
+        let deserializer = &mut Deserializer::new();
+        assert_eq!(
+            deserializer.cursor().remaining(),
+            Err(DecodeError::NotLoaded)
+        );
+    }
+
+    #[test]
+    pub fn swapped_fields_with_kind_markers() {
+        let mut serializer = Serializer::new();
+        serializer.set_kind_markers(true);
+        serializer.cursor().int(4).boolean(true).int(9);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        let mut cursor = deserializer.cursor();
+        assert_eq!(cursor.int(), Ok(4));
+        assert_eq!(
+            cursor.int(),
+            Err(DecodeError::KindMismatch {
+                index: 1,
+                expected: PropertyType::Integer,
+                found: PropertyType::Bool,
+            })
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+
+use crate::{
+    error::{DecodeError, Section},
+    serializer::{Deserializer, PropertyType, PropertyValue, verify},
+};
+
+// Structural diff of two buffers, column by column, so "what changed between these two config
+// versions" doesn't need a hexdump. Values are compared in pool order, which is the order the
+// fields were written in.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change<T> {
+    Added { index: usize, value: T },
+    Removed { index: usize, value: T },
+    Changed { index: usize, from: T, to: T },
+}
+
+// something that applies to the whole buffer rather than a value, e.g. the version or a codec flag
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeaderChange {
+    pub field: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FormatDiff {
+    pub header: Vec<HeaderChange>,
+    pub integers: Vec<Change<i64>>,
+    pub booleans: Vec<Change<bool>>,
+    pub strings: Vec<Change<String>>,
+    pub property_types: Vec<Change<PropertyType>>,
+    pub lengths: Vec<Change<u64>>,
+    pub tail: Vec<Change<PropertyValue>>,
+}
+
+impl FormatDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == FormatDiff::default()
+    }
+}
+
+// Both buffers are checked with `verify` first, each is read with its own version byte.
+pub fn diff(a: &[u8], b: &[u8]) -> Result<FormatDiff, DecodeError> {
+    let (a_version, a) = decode(a)?;
+    let (b_version, b) = decode(b)?;
+    let (a_stats, b_stats) = (a.last_stats(), b.last_stats());
+
+    let mut header = Vec::new();
+    let mut compare = |field, from: &dyn ToString, to: &dyn ToString| {
+        let (from, to) = (from.to_string(), to.to_string());
+        if from != to {
+            header.push(HeaderChange { field, from, to });
+        }
+    };
+    compare("version", &a_version, &b_version);
+    compare("format id", &a.format_id(), &b.format_id());
+    compare(
+        "provenance",
+        &format!("{:?}", a.provenance()),
+        &format!("{:?}", b.provenance()),
+    );
+    compare(
+        "schema",
+        &format!("{:?}", a.schema()),
+        &format!("{:?}", b.schema()),
+    );
+    compare("all ascii", &a_stats.all_ascii, &b_stats.all_ascii);
+    compare("front coded", &a_stats.front_coded, &b_stats.front_coded);
+    compare("lz strings", &a_stats.lz_strings, &b_stats.lz_strings);
+    compare(
+        "deduplicated strings",
+        &a_stats.deduplicated_strings,
+        &b_stats.deduplicated_strings,
+    );
+    compare(
+        "constant integers",
+        &a_stats.constant_integers,
+        &b_stats.constant_integers,
+    );
+    compare("tail", &a.tail.is_some(), &b.tail.is_some());
+
+    let strings = |deserializer: &Deserializer| -> Vec<String> {
+        deserializer
+            .remaining_view()
+            .strings()
+            .map(str::to_owned)
+            .collect()
+    };
+    let no_tail = Vec::new();
+    Ok(FormatDiff {
+        header,
+        integers: column(a.integers.iter(), b.integers.iter()),
+        booleans: column(a.booleans.iter(), b.booleans.iter()),
+        strings: column(strings(&a).iter(), strings(&b).iter()),
+        property_types: column(a.property_types.iter(), b.property_types.iter()),
+        lengths: column(a.lengths.iter(), b.lengths.iter()),
+        tail: column(
+            a.tail.as_ref().unwrap_or(&no_tail).iter(),
+            b.tail.as_ref().unwrap_or(&no_tail).iter(),
+        ),
+    })
+}
+
+fn decode(bytes: &[u8]) -> Result<(u8, Deserializer), DecodeError> {
+    let version = *bytes
+        .first()
+        .ok_or(DecodeError::Truncated(Section::Header))?;
+    verify(bytes, version)?;
+    let mut deserializer = Deserializer::new();
+    deserializer
+        .read_bytes(bytes, version)
+        .ok_or(DecodeError::Truncated(Section::Header))?;
+    Ok((version, deserializer))
+}
+
+// Skips the shared prefix and suffix, whatever is left in the middle is paired up as changes
+// with the excess added or removed. Not a minimal edit script, but a single insertion or removal
+// shows up as exactly that rather than shifting every value after it.
+fn column<'v, T: PartialEq + Clone + 'v>(
+    a: impl ExactSizeIterator<Item = &'v T> + DoubleEndedIterator + Clone,
+    b: impl ExactSizeIterator<Item = &'v T> + DoubleEndedIterator + Clone,
+) -> Vec<Change<T>> {
+    let prefix = a.clone().zip(b.clone()).take_while(|(a, b)| a == b).count();
+    let max_suffix = a.len().min(b.len()) - prefix;
+    let suffix = a
+        .clone()
+        .rev()
+        .zip(b.clone().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let a: Vec<&T> = a.collect();
+    let b: Vec<&T> = b.collect();
+    let a = &a[prefix..a.len() - suffix];
+    let b = &b[prefix..b.len() - suffix];
+
+    let mut changes = Vec::new();
+    for (offset, (from, to)) in a.iter().zip(b).enumerate() {
+        changes.push(Change::Changed {
+            index: prefix + offset,
+            from: (*from).clone(),
+            to: (*to).clone(),
+        });
+    }
+    let paired = a.len().min(b.len());
+    for (offset, value) in a[paired..].iter().enumerate() {
+        changes.push(Change::Removed {
+            index: prefix + paired + offset,
+            value: (*value).clone(),
+        });
+    }
+    for (offset, value) in b[paired..].iter().enumerate() {
+        changes.push(Change::Added {
+            index: prefix + paired + offset,
+            value: (*value).clone(),
+        });
+    }
+    changes
+}
+
+fn write_column<T: fmt::Debug>(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    changes: &[Change<T>],
+) -> fmt::Result {
+    for change in changes {
+        match change {
+            Change::Added { index, value } => writeln!(f, "+ {name}[{index}]: {value:?}")?,
+            Change::Removed { index, value } => writeln!(f, "- {name}[{index}]: {value:?}")?,
+            Change::Changed { index, from, to } => {
+                writeln!(f, "~ {name}[{index}]: {from:?} -> {to:?}")?
+            }
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for FormatDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no differences");
+        }
+        for HeaderChange { field, from, to } in &self.header {
+            writeln!(f, "~ {field}: {from} -> {to}")?;
+        }
+        write_column(f, "integers", &self.integers)?;
+        write_column(f, "booleans", &self.booleans)?;
+        write_column(f, "strings", &self.strings)?;
+        write_column(f, "property types", &self.property_types)?;
+        write_column(f, "lengths", &self.lengths)?;
+        write_column(f, "tail", &self.tail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::Serializer;
+
+    fn to_bytes(version: u8, ints: &[i64], name: &str, array: &[PropertyValue]) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        for int in ints {
+            serializer.write_int(*int);
+        }
+        serializer.write_string(name);
+        serializer.write_array(array);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, version);
+        buffer
+    }
+
+    #[test]
+    pub fn identical_buffers() {
+        let array = [PropertyValue::Bool(true)];
+        let a = to_bytes(1, &[1, 2], "api", &array);
+        let diff = diff(&a, &a).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no differences\n");
+    }
+
+    #[test]
+    pub fn value_changes() {
+        let array = [PropertyValue::Bool(true)];
+        let a = to_bytes(1, &[1, 2, 3], "api", &array);
+        let b = to_bytes(2, &[1, 5, 3], "web", &array);
+
+        let diff = diff(&a, &b).unwrap();
+        assert_eq!(
+            diff.header,
+            [HeaderChange {
+                field: "version",
+                from: "1".to_owned(),
+                to: "2".to_owned()
+            }]
+        );
+        assert_eq!(
+            diff.integers,
+            [Change::Changed {
+                index: 1,
+                from: 2,
+                to: 5
+            }]
+        );
+        assert_eq!(
+            diff.strings,
+            [Change::Changed {
+                index: 0,
+                from: "api".to_owned(),
+                to: "web".to_owned()
+            }]
+        );
+        assert!(diff.booleans.is_empty());
+        assert_eq!(
+            diff.to_string(),
+            "~ version: 1 -> 2\n~ integers[1]: 2 -> 5\n~ strings[0]: \"api\" -> \"web\"\n"
+        );
+    }
+
+    #[test]
+    pub fn array_insertion() {
+        let a = to_bytes(
+            0,
+            &[],
+            "api",
+            &[PropertyValue::Integer(1), PropertyValue::Integer(3)],
+        );
+        let b = to_bytes(
+            0,
+            &[],
+            "api",
+            &[
+                PropertyValue::Integer(1),
+                PropertyValue::Integer(2),
+                PropertyValue::Integer(3),
+            ],
+        );
+
+        let diff = diff(&a, &b).unwrap();
+        assert_eq!(diff.integers, [Change::Added { index: 1, value: 2 }]);
+        assert_eq!(
+            diff.property_types,
+            [Change::Added {
+                index: 2,
+                value: PropertyType::Integer
+            }]
+        );
+        assert_eq!(
+            diff.lengths,
+            [Change::Changed {
+                index: 0,
+                from: 2,
+                to: 3
+            }]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+
+use crate::serializer::{Deserializer, Serializer};
+
+// Durations as nanoseconds are numbers in the tens of billions for a timeout of a few seconds,
+// past every narrow int width. They're stored as a count of the coarsest unit that holds them
+// exactly instead, so a 30s timeout is the int 30, along with a 2 bit unit tag in the boolean
+// column (most significant bit first).
+//
+// The count is a u64 in the int column's bits, so only durations of centuries with sub-second
+// precision don't fit, `write_duration` panics on those.
+
+// nanoseconds in each unit, indexed by the unit tag, coarsest first
+const UNIT_NANOS: [u128; 4] = [1_000_000_000, 1_000_000, 1_000, 1];
+
+impl Serializer<'_> {
+    pub fn write_duration(&mut self, duration: Duration) {
+        let nanos = duration.as_nanos();
+        let (unit, count) = UNIT_NANOS
+            .iter()
+            .enumerate()
+            .find(|(_, unit)| nanos.is_multiple_of(**unit))
+            .map(|(unit, unit_nanos)| (unit, nanos / unit_nanos))
+            .expect("every duration is a whole number of nanoseconds");
+        let count = u64::try_from(count).expect("duration is too long for its precision");
+        self.write_int(count as i64);
+        self.write_bool(unit & 0b10 != 0);
+        self.write_bool(unit & 0b01 != 0);
+    }
+}
+
+impl Deserializer {
+    pub fn take_duration(&mut self) -> Option<Duration> {
+        let count = self.take_int()? as u64;
+        let unit = (self.take_bool()? as usize) << 1 | self.take_bool()? as usize;
+        let nanos = count as u128 * UNIT_NANOS[unit];
+        let secs = u64::try_from(nanos / UNIT_NANOS[0]).ok()?;
+        Some(Duration::new(secs, (nanos % UNIT_NANOS[0]) as u32))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(durations: &[Duration]) -> u64 {
+        let mut serializer = Serializer::new();
+        for duration in durations {
+            serializer.write_duration(*duration);
+        }
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        for duration in durations {
+            assert_eq!(deserializer.take_duration(), Some(*duration));
+        }
+        stats.integers.bits
+    }
+
+    #[test]
+    pub fn durations_roundtrip() {
+        roundtrip(&[
+            Duration::ZERO,
+            Duration::from_secs(30),
+            Duration::from_millis(1500),
+            Duration::from_micros(250),
+            Duration::from_nanos(1_000_000_001),
+            Duration::new(u64::MAX, 0),
+            Duration::new(1 << 30, 7),
+        ]);
+    }
+
+    #[test]
+    pub fn whole_seconds_are_compact() {
+        // 30, against 30_000_000_000 in the nanosecond int the duration would otherwise be
+        let timeout = roundtrip(&[Duration::from_secs(30)]);
+        assert_eq!(timeout, 9);
+        let millis = roundtrip(&[Duration::from_millis(30_250)]);
+        assert!(millis > timeout && millis < 45, "{millis} bits");
+        let nanos = roundtrip(&[Duration::new(30, 1)]);
+        assert!(nanos > millis, "{nanos} bits");
+    }
+
+    #[test]
+    #[should_panic(expected = "too long for its precision")]
+    pub fn long_precise_durations_panic() {
+        Serializer::new().write_duration(Duration::new(u64::MAX, 1));
+    }
+}
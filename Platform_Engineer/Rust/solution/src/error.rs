@@ -0,0 +1,270 @@
+use std::fmt;
+
+use crate::{limits::Limit, remaining::RemainingValues, serializer::PropertyType};
+
+// Number of values still queued up in each of the deserializer's pools
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColumnCounts {
+    pub integers: usize,
+    pub booleans: usize,
+    pub strings: usize,
+    pub property_types: usize,
+    pub lengths: usize,
+}
+
+impl ColumnCounts {
+    pub fn is_empty(&self) -> bool {
+        *self == ColumnCounts::default()
+    }
+}
+
+impl fmt::Display for ColumnCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} integers, {} booleans, {} strings, {} property types, {} array lengths",
+            self.integers, self.booleans, self.strings, self.property_types, self.lengths
+        )
+    }
+}
+
+// Sections of the buffer in the order they're written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    Integers,
+    Booleans,
+    Strings,
+    PropertyTypes,
+    Lengths,
+    RawStrings,
+    KindMarkers,
+    Names,
+    Provenance,
+    Tail,
+    Padding,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Section::Header => "header",
+            Section::Integers => "integer column",
+            Section::Booleans => "boolean column",
+            Section::Strings => "string column",
+            Section::PropertyTypes => "property type column",
+            Section::Lengths => "array length column",
+            Section::RawStrings => "raw strings",
+            Section::KindMarkers => "kind markers",
+            Section::Names => "field names",
+            Section::Provenance => "provenance trailer",
+            Section::Tail => "tail array",
+            Section::Padding => "alignment padding",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    VersionMismatch {
+        expected: u8,
+        found: u8,
+    },
+    UnsupportedRevision(u64),
+    // the header sets option bits this revision doesn't define, it was written by something newer
+    ReservedOptions(u64),
+    // the header declares more values than the rest of the buffer could hold even at their
+    // smallest encoding
+    CountsExceedInput {
+        minimum_bits: u64,
+        available_bits: u64,
+    },
+    // ran out of input (or hit an invalid code) partway through `section`
+    Truncated(Section),
+    // a front coded string claimed to share more than the previous string had
+    InvalidPrefix {
+        string: usize,
+    },
+    // a property type tag `PropertyType::from_bits` doesn't map, reserved for types added later
+    InvalidPropertyTag(u8),
+    TrailingBytes(usize),
+    // the section doesn't match its checksum, see `Serializer::set_section_checksums`
+    ChecksumMismatch(Section),
+    // the array tag at `index` has no length left for it, see `Deserializer::validate_structure`
+    InconsistentTag {
+        index: usize,
+        kind: PropertyType,
+    },
+    // the property types reference more of `column`'s values than it has, the buffer is corrupt
+    // whatever order they're taken in, see `Deserializer::validate_structure`
+    ColumnTagMismatch {
+        column: Section,
+        declared: usize,
+        referenced: usize,
+    },
+    // an array claims more values than there are property types left to describe them
+    ArrayLength {
+        declared: u64,
+        available: usize,
+    },
+    // the buffer is valid but decoding it would go past the deserializer's `DecodeLimits`
+    LimitExceeded {
+        limit: Limit,
+        max: u64,
+    },
+    // the reader asked for a field of `kind` but that pool was already empty
+    MissingField {
+        index: usize,
+        kind: PropertyType,
+    },
+    // the reader took a `expected` for field `index` where the writer wrote a `found`, only caught
+    // in buffers written with `Serializer::set_kind_markers`
+    KindMismatch {
+        index: usize,
+        expected: PropertyType,
+        found: PropertyType,
+    },
+    // the reader finished while values the writer wrote were still queued
+    UnconsumedFields {
+        fields: usize,
+        remaining: ColumnCounts,
+        // the first few values left in each column, see `Deserializer::remaining_view`
+        leftover: Box<RemainingValues>,
+    },
+    // `Deserializer::load` while values from the previous buffer were still unread
+    AlreadyLoaded {
+        remaining: ColumnCounts,
+    },
+    // read from a deserializer no buffer was loaded into
+    NotLoaded,
+    // `IntoFormat::deserialize_optional` of a buffer another type wrote, see `Registry` for
+    // buffers that can be either
+    FormatIdMismatch {
+        expected: u16,
+        found: u16,
+    },
+    // `IntoFormat::take` gave up, with what was left in each column when it did
+    TakeFailed {
+        remaining: ColumnCounts,
+    },
+    // the `Deserializer::set_progress` hook stopped the decode
+    Cancelled,
+    // decoding the buffer, or taking arrays out of it, would go past `DecodeLimits::max_work`
+    WorkBudgetExceeded {
+        max: u64,
+    },
+    // the buffer is marked absent but `section` has values in it, `Serializer::write_absent`
+    // never writes those
+    AbsentWithValues(Section),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::VersionMismatch { expected, found } => {
+                write!(f, "expected version {expected} but found {found}")
+            }
+            DecodeError::UnsupportedRevision(revision) => {
+                write!(f, "unsupported format revision {revision}")
+            }
+            DecodeError::ReservedOptions(bits) => {
+                write!(f, "header sets reserved option bits {bits:#b}")
+            }
+            DecodeError::CountsExceedInput {
+                minimum_bits,
+                available_bits,
+            } => write!(
+                f,
+                "header declares at least {minimum_bits} bits of values but only \
+                 {available_bits} bits remain"
+            ),
+            DecodeError::Truncated(section) => write!(f, "{section} is truncated or corrupt"),
+            DecodeError::InvalidPrefix { string } => write!(
+                f,
+                "string {string} shares more bytes than the previous string has"
+            ),
+            DecodeError::InvalidPropertyTag(tag) => write!(f, "invalid property type tag {tag}"),
+            DecodeError::TrailingBytes(bytes) => {
+                write!(f, "{bytes} unexpected bytes after the last section")
+            }
+            DecodeError::ChecksumMismatch(section) => {
+                write!(f, "{section} doesn't match its checksum")
+            }
+            DecodeError::InconsistentTag { index, kind } => write!(
+                f,
+                "property type {index} is {kind:?} but there's no {kind:?} value left for it"
+            ),
+            DecodeError::ColumnTagMismatch {
+                column,
+                declared,
+                referenced,
+            } => write!(
+                f,
+                "property types reference {referenced} values in the {column} but it only has \
+                 {declared}"
+            ),
+            DecodeError::ArrayLength {
+                declared,
+                available,
+            } => write!(
+                f,
+                "array declares {declared} values but only {available} property types remain"
+            ),
+            DecodeError::LimitExceeded { limit, max } => {
+                write!(f, "decoding would exceed the {limit} limit of {max}")
+            }
+            DecodeError::MissingField { index, kind } => write!(
+                f,
+                "field {index} expected {kind:?} but there were none left, \
+                 the writer and reader field order likely differ"
+            ),
+            DecodeError::KindMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "field {index} was read as {expected:?} but written as {found:?}"
+            ),
+            DecodeError::UnconsumedFields {
+                fields,
+                remaining,
+                leftover,
+            } => write!(
+                f,
+                "read {fields} fields but {remaining} were left unconsumed, starting with \
+                 {leftover}"
+            ),
+            DecodeError::AlreadyLoaded { remaining } => write!(
+                f,
+                "the previous buffer still has {remaining} unread, clear the deserializer first"
+            ),
+            DecodeError::NotLoaded => write!(f, "no buffer has been read"),
+            DecodeError::FormatIdMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected format {expected} but the buffer holds format {found}"
+                )
+            }
+            DecodeError::TakeFailed { remaining } => {
+                write!(f, "take failed with {remaining} left")
+            }
+            DecodeError::Cancelled => write!(f, "decoding was cancelled"),
+            DecodeError::WorkBudgetExceeded { max } => {
+                write!(
+                    f,
+                    "decoding would take more than the work budget of {max} values"
+                )
+            }
+            DecodeError::AbsentWithValues(section) => {
+                write!(
+                    f,
+                    "the buffer is marked absent but its {section} has values"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
@@ -0,0 +1,210 @@
+use crate::{
+    error::{DecodeError, Section},
+    serializer::{Deserializer, PropertyType, PropertyValue},
+};
+
+// An array as a stream of events instead of a `Vec<PropertyValue>` tree, for buffers too large to
+// hold decoded all at once. Only a count per open array is kept, so memory is bounded by how
+// deeply the arrays nest rather than by how many values they hold.
+//
+// for event in deserializer.events() {
+//     match event? {
+//         Event::ArrayStart(len) => ..,
+//         Event::Integer(value) => ..,
+//         ..
+//     }
+// }
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    // the number of values before the matching `ArrayEnd`
+    ArrayStart(usize),
+    ArrayEnd,
+    Integer(i64),
+    Bool(bool),
+    String(String),
+}
+
+pub struct PropertyValueReader<'d> {
+    deserializer: &'d mut Deserializer,
+    // values each open array still has to yield, innermost last
+    stack: Vec<usize>,
+    // see `Deserializer::take_array_length`
+    pending: usize,
+    started: bool,
+    finished: bool,
+}
+
+impl Deserializer {
+    // the next array, as `take_array` would read it
+    pub fn events(&mut self) -> PropertyValueReader<'_> {
+        PropertyValueReader {
+            deserializer: self,
+            stack: Vec::new(),
+            pending: 0,
+            started: false,
+            finished: false,
+        }
+    }
+}
+
+impl PropertyValueReader<'_> {
+    fn next_event(&mut self) -> Result<Option<Event>, DecodeError> {
+        if !self.started {
+            self.started = true;
+            self.deserializer.check_loaded()?;
+            self.deserializer.check_depth(1)?;
+            return self.start_array().map(Some);
+        }
+        let Some(remaining) = self.stack.last_mut() else {
+            return Ok(None);
+        };
+        if *remaining == 0 {
+            self.stack.pop();
+            return Ok(Some(Event::ArrayEnd));
+        }
+
+        *remaining -= 1;
+        self.pending -= 1;
+        let tag = self
+            .deserializer
+            .take_property_type()
+            .ok_or(DecodeError::Truncated(Section::PropertyTypes))?;
+        let event = match tag {
+            PropertyType::Array => {
+                self.deserializer.check_depth(self.stack.len() + 1)?;
+                return self.start_array().map(Some);
+            }
+            tag => match self.deserializer.take_tagged_value(tag)? {
+                PropertyValue::Integer(value) => Event::Integer(value),
+                PropertyValue::Bool(value) => Event::Bool(value),
+                PropertyValue::String(value) => Event::String(value),
+                PropertyValue::Array(_) => unreachable!("arrays are started above"),
+            },
+        };
+        Ok(Some(event))
+    }
+
+    fn start_array(&mut self) -> Result<Event, DecodeError> {
+        let length = self.deserializer.take_array_length(&mut self.pending)?;
+        self.stack.push(length);
+        Ok(Event::ArrayStart(length))
+    }
+}
+
+// stops after the outermost `ArrayEnd` or the first error
+impl Iterator for PropertyValueReader<'_> {
+    type Item = Result<Event, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let event = self.next_event().transpose();
+        if !matches!(event, Some(Ok(_))) {
+            self.finished = true;
+        }
+        event
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{limits::DecodeLimits, serializer::Serializer};
+
+    fn values() -> Vec<PropertyValue> {
+        use PropertyValue::{Array, Bool, Integer};
+        vec![
+            Integer(8080),
+            Array(vec![Bool(true), Array(Vec::new())]),
+            PropertyValue::String("host".to_owned()),
+        ]
+    }
+
+    fn written(values: &[PropertyValue]) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        serializer.write_array(values);
+        serializer.write_int(7);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        buffer
+    }
+
+    // the tree the events describe, `None` if they don't nest properly
+    fn assemble(events: impl IntoIterator<Item = Event>) -> Option<Vec<PropertyValue>> {
+        let mut stack: Vec<Vec<PropertyValue>> = Vec::new();
+        for event in events {
+            let value = match event {
+                Event::ArrayStart(length) => {
+                    stack.push(Vec::with_capacity(length));
+                    continue;
+                }
+                Event::ArrayEnd => {
+                    let array = stack.pop()?;
+                    if stack.is_empty() {
+                        return Some(array);
+                    }
+                    PropertyValue::Array(array)
+                }
+                Event::Integer(value) => PropertyValue::Integer(value),
+                Event::Bool(value) => PropertyValue::Bool(value),
+                Event::String(value) => PropertyValue::String(value),
+            };
+            stack.last_mut()?.push(value);
+        }
+        None
+    }
+
+    #[test]
+    pub fn events_follow_the_tree() {
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&written(&values()), 0).unwrap();
+        let events: Vec<Event> = deserializer.events().map(Result::unwrap).collect();
+        assert_eq!(
+            events,
+            [
+                Event::ArrayStart(3),
+                Event::Integer(8080),
+                Event::ArrayStart(2),
+                Event::Bool(true),
+                Event::ArrayStart(0),
+                Event::ArrayEnd,
+                Event::ArrayEnd,
+                Event::String("host".to_owned()),
+                Event::ArrayEnd,
+            ]
+        );
+        // only the array was consumed
+        assert_eq!(deserializer.take_int(), Some(7));
+
+        assert_eq!(assemble(events), Some(values()));
+    }
+
+    #[test]
+    pub fn errors_end_the_stream() {
+        // the outer array claims more values than there are tags
+        let mut serializer = Serializer::new();
+        serializer.write_length(2);
+        serializer.write_property_type(PropertyType::Integer);
+        serializer.write_int(1);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        let events: Vec<_> = deserializer.events().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [Err(DecodeError::ArrayLength { .. })]
+        ));
+
+        let nested = [PropertyValue::Array(vec![PropertyValue::Array(Vec::new())])];
+        let mut deserializer = Deserializer::with_limits(DecodeLimits::relaxed().max_depth(2));
+        deserializer.read_bytes(&written(&nested), 0).unwrap();
+        let events: Vec<_> = deserializer.events().collect();
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            events.last(),
+            Some(Err(DecodeError::LimitExceeded { .. }))
+        ));
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    serializer::{Deserializer, PropertyValue, Serializer, verify},
+    stats::EncodeStats,
+};
+
+// Encodes the same values under several combinations of the serializer's opt-in flags, for
+// answering "would this flag help on our data" without patching the serializer locally. Each
+// buffer is decoded and verified again so a report never describes a buffer that can't be read.
+//
+// Only flags that are written into the header are covered, everything else the serializer decides
+// per buffer (ascii vs unicode strings, constant integer columns) and shows up in `stats`.
+
+const VERSION: u8 = 0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EncodingConfig {
+    pub front_coding: bool,
+    pub column_offsets: bool,
+}
+
+impl EncodingConfig {
+    // every combination of the flags, starting with the default
+    pub fn all() -> Vec<EncodingConfig> {
+        let mut configs = Vec::new();
+        for column_offsets in [false, true] {
+            for front_coding in [false, true] {
+                configs.push(EncodingConfig {
+                    front_coding,
+                    column_offsets,
+                });
+            }
+        }
+        configs
+    }
+}
+
+impl fmt::Display for EncodingConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.front_coding, self.column_offsets) {
+            (false, false) => write!(f, "default"),
+            (true, false) => write!(f, "front coding"),
+            (false, true) => write!(f, "column offsets"),
+            (true, true) => write!(f, "front coding + column offsets"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EncodingReport {
+    pub config: EncodingConfig,
+    pub bytes: usize,
+    pub stats: EncodeStats,
+    pub encode_time: Duration,
+    pub decode_time: Duration,
+    // decoded back to the input and passed `verify`
+    pub roundtrips: bool,
+}
+
+impl fmt::Display for EncodingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} bytes, encode {:?}, decode {:?}{}",
+            self.config,
+            self.bytes,
+            self.encode_time,
+            self.decode_time,
+            if self.roundtrips { "" } else { ", FAILED" }
+        )
+    }
+}
+
+// `values` are written as a single array, one serializer is reused across every config
+pub fn compare_encodings(
+    values: &[PropertyValue],
+    configs: &[EncodingConfig],
+) -> Vec<EncodingReport> {
+    let mut serializer = Serializer::new();
+    let mut deserializer = Deserializer::new();
+    let mut buffer = Vec::new();
+    let mut reports = Vec::with_capacity(configs.len());
+
+    for &config in configs {
+        serializer.set_front_coding(config.front_coding);
+        serializer.set_column_offsets(config.column_offsets);
+        buffer.clear();
+
+        let start = Instant::now();
+        serializer.write_array(values);
+        let stats = serializer.finish(&mut buffer, VERSION);
+        let encode_time = start.elapsed();
+        serializer = serializer.reuse();
+
+        deserializer.clear();
+        let start = Instant::now();
+        let decoded = deserializer
+            .read_bytes(&buffer, VERSION)
+            .and_then(|()| deserializer.take_array());
+        let decode_time = start.elapsed();
+
+        reports.push(EncodingReport {
+            config,
+            bytes: buffer.len(),
+            stats,
+            encode_time,
+            decode_time,
+            roundtrips: decoded.as_deref() == Some(values) && verify(&buffer, VERSION).is_ok(),
+        });
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus() -> Vec<(&'static str, Vec<PropertyValue>)> {
+        use PropertyValue::{Array, Bool, Integer};
+        let config = vec![
+            Integer(8080),
+            PropertyValue::String("service.example.internal".to_owned()),
+            Bool(true),
+            Array(vec![Integer(1), Integer(2), Integer(3)]),
+        ];
+        let sorted_keys = (0..2_000)
+            .map(|i| PropertyValue::String(format!("metrics.host{:03}.cpu_{i}", i % 40)))
+            .collect();
+        let unicode = vec![
+            PropertyValue::String("ünïcode".to_owned()),
+            PropertyValue::String("日本語".to_owned()),
+            PropertyValue::String("a\0b".to_owned()),
+        ];
+        let nested = vec![Array(vec![
+            Array(vec![Bool(false), Array(Vec::new())]),
+            Array(vec![PropertyValue::String(String::new()), Integer(0)]),
+        ])];
+        vec![
+            ("config", config),
+            ("sorted keys", sorted_keys),
+            ("unicode", unicode),
+            ("nested", nested),
+            ("empty", Vec::new()),
+        ]
+    }
+
+    #[test]
+    pub fn every_config_roundtrips() {
+        let configs = EncodingConfig::all();
+        for (name, values) in corpus() {
+            let reports = compare_encodings(&values, &configs);
+            assert_eq!(reports.len(), configs.len());
+            for report in &reports {
+                assert!(report.roundtrips, "{name}, {report}");
+                assert_eq!(report.stats.total_bytes, report.bytes as u64);
+            }
+        }
+    }
+
+    crate::derive_format! {
+        #[derive(Debug, PartialEq)]
+        struct Fixture {
+            values: Vec<PropertyValue>,
+        }
+    }
+
+    #[test]
+    pub fn corpus_roundtrips_canonically() {
+        for (_, values) in corpus() {
+            crate::serializer::assert_roundtrip(&Fixture { values });
+        }
+    }
+
+    #[test]
+    pub fn front_coding_shows_up_on_sorted_keys() {
+        let (_, values) = &corpus()[1];
+        let reports = compare_encodings(values, &EncodingConfig::all()[..2]);
+        assert!(!reports[0].stats.front_coded);
+        assert!(reports[1].stats.front_coded);
+        assert!(reports[1].bytes < reports[0].bytes, "{}", reports[1]);
+    }
+}
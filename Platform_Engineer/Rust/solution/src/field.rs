@@ -0,0 +1,280 @@
+use crate::serializer::{Deserializer, IntoFormat, PropertyValue, Serializer};
+
+// Per-type serialization of a single struct field, this is what `derive_format!` dispatches
+// through so the field types decide which pool they go into.
+pub trait FormatField: Sized {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>);
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self>;
+}
+
+impl FormatField for i64 {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_int(*self);
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_int()
+    }
+}
+
+impl FormatField for bool {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_bool(*self);
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_bool()
+    }
+}
+
+impl FormatField for String {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_string(self.as_str());
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_string()
+    }
+}
+
+impl FormatField for Vec<PropertyValue> {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_array(self.as_slice());
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_array()
+    }
+}
+
+// a length and the values, without the per element property types of `Vec<PropertyValue>`
+impl FormatField for Vec<i64> {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_int_vec(self);
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_int_vec()
+    }
+}
+
+impl FormatField for Vec<bool> {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_bool_vec(self);
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_bool_vec()
+    }
+}
+
+impl FormatField for Vec<String> {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_string_vec(self);
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_string_vec()
+    }
+}
+
+// nested `Option<Option<T>>` just stacks presence bits
+impl<T: FormatField> FormatField for Option<T> {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_optional(self.as_ref(), |serializer, value| {
+            value.write_field(serializer)
+        });
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_optional(T::take_field)
+    }
+}
+
+// Tuples and fixed size arrays have their length in the type, so unlike `Vec<PropertyValue>`
+// there's no length or property types written, each element just goes into its own pool.
+macro_rules! tuple_field {
+    ($($element:ident),+) => {
+        impl<$($element: FormatField),+> FormatField for ($($element,)+) {
+            #[allow(non_snake_case)]
+            fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+                let ($($element,)+) = self;
+                $($element.write_field(serializer);)+
+            }
+
+            fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+                Some(($($element::take_field(deserializer)?,)+))
+            }
+        }
+    };
+}
+
+tuple_field!(A);
+tuple_field!(A, B);
+tuple_field!(A, B, C);
+tuple_field!(A, B, C, D);
+tuple_field!(A, B, C, D, E);
+tuple_field!(A, B, C, D, E, F);
+tuple_field!(A, B, C, D, E, F, G);
+tuple_field!(A, B, C, D, E, F, G, H);
+
+impl<T: FormatField, const N: usize> FormatField for [T; N] {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        for element in self {
+            element.write_field(serializer);
+        }
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        let elements: Vec<T> = (0..N)
+            .map(|_| T::take_field(deserializer))
+            .collect::<Option<_>>()?;
+        elements.try_into().ok()
+    }
+}
+
+// nested structs are flattened into the same pools
+impl<T: IntoFormat> FormatField for T {
+    fn write_field<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        self.serialize(serializer);
+    }
+
+    fn take_field(deserializer: &mut Deserializer) -> Option<Self> {
+        T::take(deserializer)
+    }
+}
+
+// Declares a struct and implements `IntoFormat` for it, writing fields in declaration order. An
+// optional leading `#![format_id = N]` sets `IntoFormat::format_id`.
+//
+// derive_format! {
+//     #![format_id = 1]
+//     #[derive(Debug)]
+//     pub struct Config {
+//         pub data: i64,
+//         pub name: Option<String>,
+//     }
+// }
+#[macro_export]
+macro_rules! derive_format {
+    (
+        $(#![format_id = $format_id:expr])?
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty),*
+        }
+
+        impl $crate::serializer::IntoFormat for $name {
+            $(
+                fn format_id() -> u16 {
+                    $format_id
+                }
+            )?
+
+            fn serialize<'a>(&'a self, serializer: &mut $crate::serializer::Serializer<'a>) {
+                $($crate::field::FormatField::write_field(&self.$field, serializer);)*
+            }
+
+            fn take(deserializer: &mut $crate::serializer::Deserializer) -> Option<Self> {
+                Some($name {
+                    $($field: <$ty as $crate::field::FormatField>::take_field(deserializer)?,)*
+                })
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        pool::to_bytes,
+        serializer::{Deserializer, IntoFormat, PropertyValue, Serializer},
+    };
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Inner {
+            retries: Option<i64>,
+        }
+    }
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Optional {
+            port: Option<i64>,
+            name: String,
+            label: Option<Option<String>>,
+            extra: Option<Vec<PropertyValue>>,
+            inner: Inner,
+        }
+    }
+
+    fn roundtrip(value: &Optional) -> Option<Optional> {
+        let mut deserializer = Deserializer::new();
+        Optional::deserialize(&to_bytes(value, 0), &mut deserializer, 0)
+    }
+
+    #[test]
+    pub fn optional_fields_roundtrip() {
+        let present = Optional {
+            port: Some(8080),
+            name: "api".to_owned(),
+            label: Some(Some("primary".to_owned())),
+            extra: Some(vec![PropertyValue::Bool(true)]),
+            inner: Inner { retries: Some(3) },
+        };
+        assert_eq!(roundtrip(&present).as_ref(), Some(&present));
+
+        let absent = Optional {
+            port: None,
+            name: "api".to_owned(),
+            label: Some(None),
+            extra: None,
+            inner: Inner { retries: None },
+        };
+        assert_eq!(roundtrip(&absent).as_ref(), Some(&absent));
+
+        let outer_none = Optional {
+            label: None,
+            ..absent
+        };
+        assert_eq!(roundtrip(&outer_none).as_ref(), Some(&outer_none));
+    }
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Fixed {
+            coords: (i64, i64, i64),
+            flags: [bool; 4],
+            label: (String, Option<i64>),
+        }
+    }
+
+    #[test]
+    pub fn tuples_and_arrays_have_no_length() {
+        let fixed = Fixed {
+            coords: (10, 20, 30),
+            flags: [true, false, false, true],
+            label: ("origin".to_owned(), Some(1)),
+        };
+        let mut serializer = Serializer::new();
+        fixed.serialize(&mut serializer);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        assert_eq!(stats.integers.values, 4);
+        assert_eq!(stats.booleans.values, 5);
+        assert_eq!(stats.lengths.values, 0);
+        assert_eq!(stats.property_types.values, 0);
+
+        let mut deserializer = Deserializer::new();
+        assert_eq!(
+            Fixed::deserialize(&buffer, &mut deserializer, 0),
+            Some(fixed)
+        );
+    }
+}
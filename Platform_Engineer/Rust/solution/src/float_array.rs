@@ -0,0 +1,145 @@
+use crate::serializer::{Deserializer, Serializer};
+
+// Arrays of floats that sit close together (sensor readings, coordinates in a small area) share
+// their sign, exponent and high mantissa bits. Every value after the first is stored as its bits
+// XORed against the first one's, so those shared bits become leading zeros the integer widths
+// skip over, leaving only the bits that actually differ.
+//
+// Each 64 bit pattern is split into its top 63 bits in the integer column, which keeps the int
+// non-negative, and its lowest bit in the boolean column. The count goes in the length column
+// like an array's. Values come back bit for bit, including -0.0 and NaN payloads.
+
+impl Serializer<'_> {
+    pub fn write_float_array(&mut self, values: &[f64]) {
+        self.write_length(values.len());
+        let Some(first) = values.first().map(|value| value.to_bits()) else {
+            return;
+        };
+        self.write_float_bits(first);
+        for value in &values[1..] {
+            self.write_float_bits(value.to_bits() ^ first);
+        }
+    }
+
+    fn write_float_bits(&mut self, bits: u64) {
+        self.write_int((bits >> 1) as i64);
+        self.write_bool(bits & 1 == 1);
+    }
+}
+
+impl Deserializer {
+    pub fn take_float_array(&mut self) -> Option<Vec<f64>> {
+        let count = usize::try_from(self.take_length()?).ok()?;
+        if count > self.integers.len() {
+            return None;
+        }
+
+        let mut values = Vec::with_capacity(count);
+        if count == 0 {
+            return Some(values);
+        }
+        let first = self.take_float_bits()?;
+        values.push(f64::from_bits(first));
+        for _ in 1..count {
+            values.push(f64::from_bits(self.take_float_bits()? ^ first));
+        }
+        Some(values)
+    }
+
+    fn take_float_bits(&mut self) -> Option<u64> {
+        let high = u64::try_from(self.take_int()?).ok()?;
+        let low = self.take_bool()? as u64;
+        Some(high << 1 | low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // bits of information per byte, over the bytes as they'd be handed to a general purpose
+    // compressor
+    fn byte_entropy(words: impl IntoIterator<Item = u64>) -> f64 {
+        let mut counts = [0u32; 256];
+        let mut total = 0;
+        for word in words {
+            for byte in word.to_le_bytes() {
+                counts[byte as usize] += 1;
+                total += 1;
+            }
+        }
+        counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|&count| {
+                let p = count as f64 / total as f64;
+                -p * p.log2()
+            })
+            .sum()
+    }
+
+    #[test]
+    pub fn near_equal_floats_lose_their_shared_bits() {
+        let readings: Vec<f64> = (0..100).map(|i| 21.5 + (i % 7) as f64 * 0.013).collect();
+
+        let first = readings[0].to_bits();
+        let raw = byte_entropy(readings.iter().map(|value| value.to_bits()));
+        let xored = byte_entropy(readings.iter().map(|value| value.to_bits() ^ first));
+        assert!(xored < raw, "{xored} bits per byte XORed, {raw} raw");
+
+        let mut serializer = Serializer::new();
+        serializer.write_float_array(&readings);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+
+        // the same split without the XOR
+        let mut serializer = Serializer::new();
+        for value in &readings {
+            serializer.write_int((value.to_bits() >> 1) as i64);
+        }
+        let plain = serializer.finish(&mut Vec::new(), 0);
+        assert!(
+            stats.integers.bits < plain.integers.bits,
+            "{} bits XORed, {} plain",
+            stats.integers.bits,
+            plain.integers.bits
+        );
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        let decoded = deserializer.take_float_array().unwrap();
+        let bits = |values: &[f64]| {
+            values
+                .iter()
+                .map(|value| value.to_bits())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(bits(&decoded), bits(&readings));
+    }
+
+    #[test]
+    pub fn any_bit_pattern_roundtrips() {
+        let values = [
+            -0.0,
+            0.0,
+            f64::NAN,
+            -f64::INFINITY,
+            f64::MIN_POSITIVE,
+            -1.5e300,
+            f64::from_bits(u64::MAX),
+        ];
+        let mut serializer = Serializer::new();
+        serializer.write_float_array(&values);
+        serializer.write_float_array(&[]);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        let decoded = deserializer.take_float_array().unwrap();
+        for (decoded, value) in decoded.iter().zip(values) {
+            assert_eq!(decoded.to_bits(), value.to_bits());
+        }
+        assert_eq!(deserializer.take_float_array(), Some(Vec::new()));
+    }
+}
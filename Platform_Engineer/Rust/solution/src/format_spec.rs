@@ -0,0 +1,373 @@
+use std::fmt::Write;
+
+use crate::serializer::{FORMAT_REVISION, PropertyType};
+
+// The numbers the wire format is built from, for other implementations and tooling to read rather
+// than copy by hand. The packer and unpacker use these same constants, so they can't drift apart.
+
+// width of each property type tag, see `PropertyType::to_bits`
+pub const TAG_BITS: u8 = 3;
+// what the tags were before revision 15, room for exactly the first four kinds
+pub const LEGACY_TAG_BITS: u8 = 2;
+
+// the tag width a buffer of `revision` was written with
+pub const fn tag_bits(revision: u64) -> u8 {
+    match revision {
+        ..15 => LEGACY_TAG_BITS,
+        _ => TAG_BITS,
+    }
+}
+
+// Widths selected by the unary prefix of `write_int` (0, 10, 110, ...), the last one has no
+// terminating 0.
+pub const INT_HEADER_WIDTHS: [u8; 7] = [3, 7, 9, 15, 24, 45, 64];
+
+// Width tables the integer column can use instead, chosen per buffer by an `IntHeaderPlan` and
+// written as the index into this. The first is `INT_HEADER_WIDTHS`, and every table ends in the
+// full 64 bits so any value (and every negative one) still fits.
+pub const INT_HEADER_TABLES: [&[u8]; 4] = [
+    &INT_HEADER_WIDTHS,
+    // mostly single digits
+    &[4, 8, 16, 32, 64],
+    // mostly flags and tiny counts
+    &[1, 3, 6, 12, 24, 64],
+    // ids, offsets and sizes that are rarely small
+    &[8, 16, 24, 32, 48, 64],
+];
+
+// Widths selected by the fixed size header of `write_small_int`.
+pub const SMALL_INT_HEADER_BITS: u8 = 3;
+pub const SMALL_INT_HEADER_WIDTHS: [u8; 8] = [0, 2, 4, 6, 8, 16, 32, 64];
+
+// Bits of the options small int after the revision, each an optional part of the buffer. Every
+// other bit is reserved, a buffer setting one was written by something newer and is rejected
+// rather than misread.
+pub const OPTION_FORMAT_ID: u64 = 1 << 0;
+pub const OPTION_COLUMN_OFFSETS: u64 = 1 << 1;
+pub const OPTION_SCHEMA: u64 = 1 << 2;
+pub const OPTION_PROVENANCE: u64 = 1 << 3;
+pub const OPTION_RAW_STRINGS: u64 = 1 << 4;
+pub const OPTION_PADDED: u64 = 1 << 5;
+pub const OPTION_KIND_MARKERS: u64 = 1 << 6;
+pub const OPTION_INT_TABLE: u64 = 1 << 7;
+pub const OPTION_SECTION_CHECKSUMS: u64 = 1 << 8;
+pub const OPTION_LZ_STRINGS: u64 = 1 << 9;
+pub const OPTION_DEDUPLICATED_STRINGS: u64 = 1 << 10;
+pub const OPTION_ABSENT: u64 = 1 << 11;
+pub const OPTION_INT_WIDTHS: u64 = 1 << 12;
+pub const OPTIONS: [(&str, u64); 13] = [
+    ("format id", OPTION_FORMAT_ID),
+    ("column offsets", OPTION_COLUMN_OFFSETS),
+    ("schema", OPTION_SCHEMA),
+    ("provenance", OPTION_PROVENANCE),
+    ("raw strings", OPTION_RAW_STRINGS),
+    ("padded", OPTION_PADDED),
+    ("kind markers", OPTION_KIND_MARKERS),
+    ("int table", OPTION_INT_TABLE),
+    ("section checksums", OPTION_SECTION_CHECKSUMS),
+    ("lz strings", OPTION_LZ_STRINGS),
+    ("deduplicated strings", OPTION_DEDUPLICATED_STRINGS),
+    ("absent", OPTION_ABSENT),
+    ("int widths", OPTION_INT_WIDTHS),
+];
+pub const KNOWN_OPTIONS: u64 = (1 << OPTIONS.len()) - 1;
+
+// longest code in the static ascii huffman table
+pub const MAX_HUFFMAN_CODE_LEN: u8 = 12;
+
+// strings per section when written with column offsets
+pub const STRING_SECTION_LEN: usize = 1024;
+
+// the little endian length prefix of a tail array
+pub const TAIL_LENGTH_BYTES: usize = 4;
+
+// longest producer id in a provenance trailer, in bytes
+pub const MAX_PRODUCER_LEN: usize = 64;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FieldEncoding {
+    Byte,
+    Bit,
+    Int,
+    SmallInt,
+    // a small int only when its bit in the options is set
+    OptionalSmallInt,
+    // a clear bit for zero, otherwise a set bit and an int of one less
+    Count,
+}
+
+impl FieldEncoding {
+    fn name(self) -> &'static str {
+        match self {
+            FieldEncoding::Byte => "byte",
+            FieldEncoding::Bit => "bit",
+            FieldEncoding::Int => "int",
+            FieldEncoding::SmallInt => "small int",
+            FieldEncoding::OptionalSmallInt => "optional small int",
+            FieldEncoding::Count => "count",
+        }
+    }
+}
+
+// The header in the order it's written. The counts are variable width so the flags don't sit at
+// fixed bit offsets, a flag's position is its place in this sequence.
+pub const HEADER_FIELDS: [(&str, FieldEncoding); 13] = [
+    ("version", FieldEncoding::Byte),
+    ("revision", FieldEncoding::SmallInt),
+    ("options", FieldEncoding::SmallInt),
+    ("format id", FieldEncoding::OptionalSmallInt),
+    ("int table", FieldEncoding::OptionalSmallInt),
+    ("int widths", FieldEncoding::OptionalSmallInt),
+    ("integers", FieldEncoding::Count),
+    ("booleans", FieldEncoding::Count),
+    ("all ascii", FieldEncoding::Bit),
+    ("front coded", FieldEncoding::Bit),
+    ("strings", FieldEncoding::Count),
+    ("property types", FieldEncoding::Count),
+    ("lengths", FieldEncoding::Count),
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatSpec {
+    pub revision: u64,
+    pub tag_bits: u8,
+    pub legacy_tag_bits: u8,
+    pub tags: [(PropertyType, u8); 4],
+    pub int_header_widths: &'static [u8],
+    pub int_header_tables: &'static [&'static [u8]],
+    pub small_int_header_bits: u8,
+    pub small_int_header_widths: &'static [u8],
+    pub max_huffman_code_len: u8,
+    pub string_section_len: usize,
+    pub tail_length_bytes: usize,
+    pub max_producer_len: usize,
+    pub header_fields: &'static [(&'static str, FieldEncoding)],
+    pub options: &'static [(&'static str, u64)],
+}
+
+pub const FORMAT_SPEC: FormatSpec = FormatSpec {
+    revision: FORMAT_REVISION,
+    tag_bits: TAG_BITS,
+    legacy_tag_bits: LEGACY_TAG_BITS,
+    tags: [
+        (PropertyType::String, PropertyType::String.to_bits().0),
+        (PropertyType::Bool, PropertyType::Bool.to_bits().0),
+        (PropertyType::Integer, PropertyType::Integer.to_bits().0),
+        (PropertyType::Array, PropertyType::Array.to_bits().0),
+    ],
+    int_header_widths: &INT_HEADER_WIDTHS,
+    int_header_tables: &INT_HEADER_TABLES,
+    small_int_header_bits: SMALL_INT_HEADER_BITS,
+    small_int_header_widths: &SMALL_INT_HEADER_WIDTHS,
+    max_huffman_code_len: MAX_HUFFMAN_CODE_LEN,
+    string_section_len: STRING_SECTION_LEN,
+    tail_length_bytes: TAIL_LENGTH_BYTES,
+    max_producer_len: MAX_PRODUCER_LEN,
+    header_fields: &HEADER_FIELDS,
+    options: &OPTIONS,
+};
+
+impl FormatSpec {
+    // Written by hand so it doesn't need the `serde_json` feature, every string in here is a plain
+    // identifier so nothing needs escaping.
+    pub fn to_json(&self) -> String {
+        let widths = |widths: &[u8]| {
+            let widths: Vec<String> = widths.iter().map(u8::to_string).collect();
+            format!("[{}]", widths.join(", "))
+        };
+        let tags: Vec<String> = self
+            .tags
+            .iter()
+            .map(|(tag, bits)| format!("\"{tag:?}\": {bits}"))
+            .collect();
+        let header: Vec<String> = self
+            .header_fields
+            .iter()
+            .map(|(name, encoding)| {
+                format!(
+                    "{{\"name\": \"{name}\", \"encoding\": \"{}\"}}",
+                    encoding.name()
+                )
+            })
+            .collect();
+
+        let mut json = String::from("{\n");
+        let mut field = |name: &str, value: String| {
+            let separator = if json.len() > 2 { ",\n" } else { "" };
+            let _ = write!(json, "{separator}  \"{name}\": {value}");
+        };
+        field("revision", self.revision.to_string());
+        field("tag_bits", self.tag_bits.to_string());
+        field("legacy_tag_bits", self.legacy_tag_bits.to_string());
+        field("tags", format!("{{{}}}", tags.join(", ")));
+        field("int_header_widths", widths(self.int_header_widths));
+        let tables: Vec<String> = self
+            .int_header_tables
+            .iter()
+            .map(|table| widths(table))
+            .collect();
+        field("int_header_tables", format!("[{}]", tables.join(", ")));
+        field(
+            "small_int_header_bits",
+            self.small_int_header_bits.to_string(),
+        );
+        field(
+            "small_int_header_widths",
+            widths(self.small_int_header_widths),
+        );
+        field(
+            "max_huffman_code_len",
+            self.max_huffman_code_len.to_string(),
+        );
+        field("string_section_len", self.string_section_len.to_string());
+        field("tail_length_bytes", self.tail_length_bytes.to_string());
+        field("max_producer_len", self.max_producer_len.to_string());
+        field("header_fields", format!("[{}]", header.join(", ")));
+        let options: Vec<String> = self
+            .options
+            .iter()
+            .map(|(name, bit)| format!("\"{name}\": {bit}"))
+            .collect();
+        field("options", format!("{{{}}}", options.join(", ")));
+        json.push_str("\n}\n");
+        json
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bit_packer::{BitPacker, BitUnpacker},
+        int_plan::IntHeaderPlan,
+        serializer::{PropertyValue, Serializer},
+    };
+
+    // reads an int with nothing but the exported table
+    fn read_int_by_spec(unpacker: &mut BitUnpacker) -> (usize, u64) {
+        let last = INT_HEADER_WIDTHS.len() - 1;
+        let mut slot = 0;
+        while slot < last && unpacker.read_bit().unwrap() {
+            slot += 1;
+        }
+        (slot, read_msb_first(unpacker, INT_HEADER_WIDTHS[slot]))
+    }
+
+    fn read_small_int_by_spec(unpacker: &mut BitUnpacker) -> u64 {
+        let slot = read_msb_first(unpacker, SMALL_INT_HEADER_BITS);
+        read_msb_first(unpacker, SMALL_INT_HEADER_WIDTHS[slot as usize])
+    }
+
+    fn read_count_by_spec(unpacker: &mut BitUnpacker) -> u64 {
+        match unpacker.read_bit().unwrap() {
+            true => read_int_by_spec(unpacker).1 + 1,
+            false => 0,
+        }
+    }
+
+    fn read_msb_first(unpacker: &mut BitUnpacker, width: u8) -> u64 {
+        (0..width).fold(0, |value, _| {
+            value << 1 | unpacker.read_bit().unwrap() as u64
+        })
+    }
+
+    #[test]
+    pub fn ints_use_the_exported_buckets() {
+        let values = [0, 7, 8, 127, 128, 500, 40_000, 1 << 30, 1 << 50, i64::MAX];
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        for value in values {
+            packer.write_int(value);
+        }
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        for value in values {
+            let (slot, read) = read_int_by_spec(&mut unpacker);
+            assert_eq!(read, value as u64);
+            // the narrowest bucket the value fits in
+            let width = INT_HEADER_WIDTHS[slot];
+            assert!(width == 64 || value < 1 << width, "{value} in {width} bits");
+            if slot > 0 {
+                assert!(
+                    value >= 1 << INT_HEADER_WIDTHS[slot - 1],
+                    "{value} too wide"
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn header_follows_the_exported_layout() {
+        let array = vec![PropertyValue::Bool(true), PropertyValue::Integer(3)];
+        let mut serializer = Serializer::new();
+        serializer.set_format_id(9);
+        serializer.set_int_header_plan(IntHeaderPlan::from_table(2).unwrap());
+        serializer.write_int(300);
+        serializer.write_string("spec");
+        serializer.write_array(&array);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 4);
+
+        let mut unpacker = BitUnpacker::new(&buffer);
+        let mut fields = Vec::new();
+        let mut options = 0;
+        for (name, encoding) in HEADER_FIELDS {
+            let value = match encoding {
+                FieldEncoding::Byte => read_msb_first(&mut unpacker, 8),
+                FieldEncoding::Bit => read_msb_first(&mut unpacker, 1),
+                FieldEncoding::Int => read_int_by_spec(&mut unpacker).1,
+                FieldEncoding::SmallInt => read_small_int_by_spec(&mut unpacker),
+                FieldEncoding::OptionalSmallInt => {
+                    let option = match name {
+                        "format id" => OPTION_FORMAT_ID,
+                        "int table" => OPTION_INT_TABLE,
+                        "int widths" => OPTION_INT_WIDTHS,
+                        _ => unreachable!("{name} has no option"),
+                    };
+                    match options & option != 0 {
+                        true => read_small_int_by_spec(&mut unpacker),
+                        false => 0,
+                    }
+                }
+                FieldEncoding::Count => read_count_by_spec(&mut unpacker),
+            };
+            if name == "options" {
+                options = value;
+            }
+            fields.push((name, value));
+        }
+        assert_eq!(
+            fields,
+            [
+                ("version", 4),
+                ("revision", FORMAT_REVISION),
+                ("options", OPTION_FORMAT_ID | OPTION_INT_TABLE),
+                ("format id", 9),
+                ("int table", 2),
+                ("int widths", 0),
+                ("integers", 2),
+                ("booleans", 1),
+                ("all ascii", 1),
+                ("front coded", 0),
+                ("strings", 1),
+                ("property types", 2),
+                ("lengths", 1),
+            ]
+        );
+        assert_eq!(unpacker.bit_position(), stats.header_bits);
+    }
+
+    #[test]
+    pub fn tags_fit_the_exported_width() {
+        for (tag, bits) in FORMAT_SPEC.tags {
+            assert_eq!(tag.to_bits(), (bits, TAG_BITS));
+            assert!(bits < 1 << TAG_BITS);
+        }
+        let json = FORMAT_SPEC.to_json();
+        assert!(json.contains("\"int_header_widths\": [3, 7, 9, 15, 24, 45, 64]"));
+        assert!(json.contains("{\"name\": \"all ascii\", \"encoding\": \"bit\"}"));
+        assert!(json.contains("\"schema\": 4"));
+        #[cfg(feature = "serde_json")]
+        serde_json::from_str::<serde_json::Value>(&json).unwrap();
+    }
+}
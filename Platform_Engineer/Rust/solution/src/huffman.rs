@@ -1,6 +1,12 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::LazyLock;
 
+use crate::format_spec::MAX_HUFFMAN_CODE_LEN;
+
+// NUL has no entry: strings are length prefixed rather than joined on a separator, so it only
+// shows up inside strings and takes the escape like any other control byte.
 const CHAR_FREQUENCIES: &[(u8, u32)] = &[
     // Lowercase
     (b'e', 710),
@@ -118,6 +124,14 @@ impl Symbol {
     fn kraft_cost_to_shorten(&self) -> f64 {
         2.0_f64.powi(-((self.len - 1) as i32)) - self.kraft_contribution()
     }
+
+    // equal frequencies are broken by byte value so the lengths only depend on the counts and not
+    // on the order they were passed in.
+    fn rarer(&self, other: &Symbol) -> Ordering {
+        self.probability
+            .total_cmp(&other.probability)
+            .then(other.byte.cmp(&self.byte))
+    }
 }
 
 fn kraft_sum(symbols: &[Symbol]) -> f64 {
@@ -143,13 +157,13 @@ fn build_optimal_lengths(frequencies: &[(u8, u32)], max_len: u8) -> Vec<(u8, u8)
         })
         .collect();
 
-    symbols.sort_by(|a, b| b.probability.partial_cmp(&a.probability).unwrap());
+    symbols.sort_by(|a, b| b.rarer(a));
 
     while kraft_sum(&symbols) > 1.0 + 1e-9 {
         let lowest_prob_shortenable = symbols
             .iter_mut()
             .filter(|s| s.len < max_len)
-            .min_by(|a, b| a.probability.partial_cmp(&b.probability).unwrap());
+            .min_by(|a, b| a.rarer(b));
 
         match lowest_prob_shortenable {
             Some(symbol) => symbol.len += 1,
@@ -163,7 +177,7 @@ fn build_optimal_lengths(frequencies: &[(u8, u32)], max_len: u8) -> Vec<(u8, u8)
         let best_to_shorten = symbols
             .iter_mut()
             .filter(|s| s.len > 1 && s.kraft_cost_to_shorten() <= slack + 1e-9)
-            .max_by(|a, b| a.probability.partial_cmp(&b.probability).unwrap());
+            .max_by(|a, b| a.rarer(b));
 
         match best_to_shorten {
             Some(symbol) => symbol.len -= 1,
@@ -205,8 +219,6 @@ fn build_canonical_codes(lengths: &[(u8, u8)]) -> HashMap<u8, (u16, u8)> {
     table
 }
 
-pub const HUFFMAN_MAX_LEN: u8 = 12;
-
 // build a LUT of every u16 that matches the 12 bit suffix
 // basically just fill the last 4 bits with every possibility
 // e.g.
@@ -214,27 +226,194 @@ pub const HUFFMAN_MAX_LEN: u8 = 12;
 // 0b10011011_110001 => 'e'
 // 0b10011011_110010 => 'e'
 // ...
-fn build_decode_table(encode_table: &HashMap<u8, (u16, u8)>) -> Vec<(u8, u8)> {
-    let table_size = 1usize << HUFFMAN_MAX_LEN;
-    let mut table = vec![(0u8, 0u8); table_size];
+//
+// `max_len` is the width of the index, the flat table always uses `MAX_HUFFMAN_CODE_LEN` but small
+// alphabets only ever need as many bits as their longest code.
+#[derive(Debug)]
+pub struct DecodeTable {
+    max_len: u8,
+    entries: Vec<(u8, u8)>,
+}
+
+impl DecodeTable {
+    pub fn flat(encode_table: &HashMap<u8, (u16, u8)>) -> Self {
+        Self::with_max_len(encode_table, MAX_HUFFMAN_CODE_LEN)
+    }
+
+    // sized to the longest code actually in use, so memory scales with the alphabet
+    pub fn compact(encode_table: &HashMap<u8, (u16, u8)>) -> Self {
+        let max_len = encode_table
+            .values()
+            .map(|&(_, len)| len)
+            .max()
+            .unwrap_or(1);
+        Self::with_max_len(encode_table, max_len)
+    }
 
-    for (&character, &(code, len)) in encode_table {
-        let suffix_count = 1usize << (HUFFMAN_MAX_LEN - len);
-        let base_index = (code as usize) << (HUFFMAN_MAX_LEN - len);
+    fn with_max_len(encode_table: &HashMap<u8, (u16, u8)>, max_len: u8) -> Self {
+        let table_size = 1usize << max_len;
+        let mut entries = vec![(0u8, 0u8); table_size];
 
-        for suffix in 0..suffix_count {
-            table[base_index | suffix] = (character, len);
+        for (&character, &(code, len)) in encode_table {
+            let suffix_count = 1usize << (max_len - len);
+            let base_index = (code as usize) << (max_len - len);
+
+            for suffix in 0..suffix_count {
+                entries[base_index | suffix] = (character, len);
+            }
         }
+
+        DecodeTable { max_len, entries }
     }
 
-    table
+    pub fn max_len(&self) -> u8 {
+        self.max_len
+    }
+
+    // index with max_len bits, get (char, actual_length)
+    pub fn lookup(&self, code: u16) -> (u8, u8) {
+        self.entries[code as usize]
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.entries.len() * size_of::<(u8, u8)>()
+    }
 }
 
-pub static HUFFMAN_TABLE: LazyLock<HashMap<u8, (u16, u8)>> = LazyLock::new(|| {
-    let lengths = build_optimal_lengths(CHAR_FREQUENCIES, 12);
+pub fn build_table(frequencies: &[(u8, u32)], max_len: u8) -> HashMap<u8, (u16, u8)> {
+    let lengths = build_optimal_lengths(frequencies, max_len);
     build_canonical_codes(&lengths)
-});
+}
+
+pub static HUFFMAN_TABLE: LazyLock<HashMap<u8, (u16, u8)>> =
+    LazyLock::new(|| build_table(CHAR_FREQUENCIES, MAX_HUFFMAN_CODE_LEN));
+
+pub static HUFFMAN_DECODE: LazyLock<DecodeTable> =
+    LazyLock::new(|| DecodeTable::flat(&HUFFMAN_TABLE));
+
+/// A byte the table has no code for, and where it is in the input. Writing nothing in its place
+/// would leave the reader, which decodes a count of codes, out of step for everything after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MissingCode {
+    pub byte: u8,
+    pub position: usize,
+}
 
-/// index with max_len bits, get (char, actual_length)
-pub static HUFFMAN_DECODE: LazyLock<Vec<(u8, u8)>> =
-    LazyLock::new(|| build_decode_table(&HUFFMAN_TABLE));
+impl fmt::Display for MissingCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no huffman code for byte {:#04x} at {}",
+            self.byte, self.position
+        )
+    }
+}
+
+impl std::error::Error for MissingCode {}
+
+// checked before anything is written, so a caller that gets an error can still pick another codec
+pub fn check(table: &HashMap<u8, (u16, u8)>, bytes: &[u8]) -> Result<(), MissingCode> {
+    match bytes.iter().position(|byte| !table.contains_key(byte)) {
+        Some(position) => Err(MissingCode {
+            byte: bytes[position],
+            position,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bit_packer::{BitPacker, BitUnpacker},
+        columns::{self, StringFlags},
+        roundtrip::assert_bytes_eq,
+    };
+    use std::borrow::Cow;
+
+    #[test]
+    pub fn compact_table_matches_flat() {
+        let table = build_table(
+            &[(b'a', 50), (b'b', 25), (b'c', 15), (b'd', 10)],
+            MAX_HUFFMAN_CODE_LEN,
+        );
+        let flat = DecodeTable::flat(&table);
+        let compact = DecodeTable::compact(&table);
+        assert!(compact.memory_bytes() < flat.memory_bytes());
+        assert!(compact.max_len() < flat.max_len());
+
+        let message = b"abacabadcbbdaaab";
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        for c in message {
+            let (code, len) = table[c];
+            packer.write_bits_u16(code, len);
+        }
+
+        for decode_table in [&flat, &compact] {
+            let mut unpacker = BitUnpacker::new(&buffer);
+            let decoded: Vec<u8> = message
+                .iter()
+                .map(|_| unpacker.read_huffman_byte_with(decode_table).unwrap())
+                .collect();
+            assert_eq!(decoded, message);
+        }
+    }
+
+    #[test]
+    pub fn equal_frequencies_are_order_independent() {
+        // one of the three gets the single 1 bit code
+        let frequencies = [(b'x', 1), (b'a', 1), (b'm', 1)];
+        let mut reversed = frequencies;
+        reversed.reverse();
+
+        let mut lengths = build_optimal_lengths(&frequencies, MAX_HUFFMAN_CODE_LEN);
+        let mut reversed_lengths = build_optimal_lengths(&reversed, MAX_HUFFMAN_CODE_LEN);
+        assert_eq!(
+            lengths,
+            build_optimal_lengths(&frequencies, MAX_HUFFMAN_CODE_LEN)
+        );
+
+        lengths.sort();
+        reversed_lengths.sort();
+        assert_eq!(lengths, reversed_lengths);
+        assert_eq!(
+            build_table(&frequencies, MAX_HUFFMAN_CODE_LEN),
+            build_table(&reversed, MAX_HUFFMAN_CODE_LEN)
+        );
+    }
+
+    #[test]
+    pub fn joined_strings_pay_no_separator() {
+        let strings: Vec<Cow<str>> = (0..20).map(|i| Cow::Owned(format!("ключ{i}"))).collect();
+        let column = |strings: &[Cow<str>]| {
+            let mut buffer = Vec::new();
+            let mut packer = BitPacker::new(&mut buffer);
+            columns::write_strings(
+                &mut packer,
+                strings,
+                StringFlags::default(),
+                &mut Default::default(),
+            )
+            .unwrap();
+            (packer.bit_position(), buffer)
+        };
+
+        assert!(!HUFFMAN_TABLE.contains_key(&0));
+        let separate: u64 = strings
+            .iter()
+            .map(|s| column(std::slice::from_ref(s)).0)
+            .sum();
+        let (bits, joined) = column(&strings);
+        assert_eq!(bits, separate);
+
+        // and it's nothing but the strings one after another
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        for string in &strings {
+            packer.write_unicode_huffman_string(string);
+        }
+        assert_bytes_eq(&joined, &buffer);
+    }
+}
@@ -0,0 +1,128 @@
+use std::fmt;
+
+use crate::serializer::{Deserializer, Serializer};
+
+// Sorted sets of indices ("which slots are active") written as the first index followed by the
+// gaps between neighbours, which stay small and land in the narrow integer widths no matter how
+// large the indices themselves get. The count goes in the length column like an array's.
+//
+// Gaps are stored minus one since a set never repeats an index.
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnsortedIndices {
+    // the first index that isn't greater than the one before it
+    pub position: usize,
+}
+
+impl fmt::Display for UnsortedIndices {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "index {} isn't greater than the one before it",
+            self.position
+        )
+    }
+}
+
+impl std::error::Error for UnsortedIndices {}
+
+impl Serializer<'_> {
+    // nothing is written if `sorted_indices` isn't strictly increasing
+    pub fn write_index_set(&mut self, sorted_indices: &[u32]) -> Result<(), UnsortedIndices> {
+        if let Some(position) = sorted_indices
+            .windows(2)
+            .position(|pair| pair[0] >= pair[1])
+        {
+            return Err(UnsortedIndices {
+                position: position + 1,
+            });
+        }
+
+        self.write_length(sorted_indices.len());
+        let mut previous = None;
+        for &index in sorted_indices {
+            let stored = match previous {
+                Some(previous) => index - previous - 1,
+                None => index,
+            };
+            self.write_int(stored as i64);
+            previous = Some(index);
+        }
+        Ok(())
+    }
+}
+
+impl Deserializer {
+    pub fn take_index_set(&mut self) -> Option<Vec<u32>> {
+        let count = usize::try_from(self.take_length()?).ok()?;
+        if count > self.integers.len() {
+            return None;
+        }
+
+        let mut indices = Vec::with_capacity(count);
+        let mut previous: Option<u32> = None;
+        for _ in 0..count {
+            let stored = u32::try_from(self.take_int()?).ok()?;
+            let index = match previous {
+                Some(previous) => previous.checked_add(stored)?.checked_add(1)?,
+                None => stored,
+            };
+            indices.push(index);
+            previous = Some(index);
+        }
+        Some(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn gaps_are_smaller_than_indices() {
+        let indices = [3, 7, 8, 100, 1000];
+
+        let mut serializer = Serializer::new();
+        serializer.write_index_set(&indices).unwrap();
+        serializer.write_index_set(&[]).unwrap();
+        let mut buffer = Vec::new();
+        let gaps = serializer.finish(&mut buffer, 0);
+
+        let mut serializer = Serializer::new();
+        for index in indices {
+            serializer.write_int(index as i64);
+        }
+        let plain = serializer.finish(&mut Vec::new(), 0);
+        assert!(
+            gaps.integers.bits < plain.integers.bits,
+            "{} bits as gaps, {} as indices",
+            gaps.integers.bits,
+            plain.integers.bits
+        );
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_index_set(), Some(indices.to_vec()));
+        assert_eq!(deserializer.take_index_set(), Some(Vec::new()));
+    }
+
+    #[test]
+    pub fn unsorted_indices_are_rejected() {
+        let mut serializer = Serializer::new();
+        assert_eq!(
+            serializer.write_index_set(&[1, 5, 4]),
+            Err(UnsortedIndices { position: 2 })
+        );
+        assert_eq!(
+            serializer.write_index_set(&[1, 1]),
+            Err(UnsortedIndices { position: 1 })
+        );
+        serializer.write_index_set(&[u32::MAX]).unwrap();
+
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_index_set(), Some(vec![u32::MAX]));
+    }
+}
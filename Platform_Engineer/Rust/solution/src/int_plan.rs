@@ -0,0 +1,243 @@
+use std::fmt;
+
+use crate::{bit_packer::int_encoded_bits_in, format_spec::INT_HEADER_TABLES};
+
+// The default int widths are a guess at what integers usually look like. Columns that are mostly
+// one shape (single digits, flags, large ids) can be written with whichever of the
+// `INT_HEADER_TABLES` suits them, picked from a sample with `analyze_ints` and passed to
+// `Serializer::set_int_header_plan`. The table's index goes in the header, so reading doesn't need
+// the plan.
+//
+// Only the integer column uses the plan, counts and string lengths keep the default widths.
+//
+// A producer that knows its integers better than any of the tables can bring its own widths with
+// `custom` (or `byte_buckets`, for widths in whole bytes). Those go in the header in place of the
+// table index, as a mask with bit `width - 1` set for each width below 64.
+
+// More widths than this make the unary prefix of the last ones longer than what they save.
+pub const MAX_INT_WIDTHS: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IntHeaderPlan {
+    table: u8,
+    // the custom widths' mask, 0 for a table
+    mask: u64,
+    widths: [u8; MAX_INT_WIDTHS],
+    len: u8,
+}
+
+// Custom widths that aren't 1 to `MAX_INT_WIDTHS` strictly increasing widths ending in 64.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidIntWidths;
+
+impl fmt::Display for InvalidIntWidths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "int widths have to be 1 to {MAX_INT_WIDTHS} increasing widths ending in 64 bits"
+        )
+    }
+}
+
+impl std::error::Error for InvalidIntWidths {}
+
+impl Default for IntHeaderPlan {
+    fn default() -> Self {
+        IntHeaderPlan {
+            table: 0,
+            mask: 0,
+            widths: [0; MAX_INT_WIDTHS],
+            len: 0,
+        }
+    }
+}
+
+impl IntHeaderPlan {
+    // `None` for a table this build doesn't have
+    pub fn from_table(table: u8) -> Option<Self> {
+        ((table as usize) < INT_HEADER_TABLES.len()).then_some(IntHeaderPlan {
+            table,
+            ..IntHeaderPlan::default()
+        })
+    }
+
+    // Each width the prefix can select, in bits. Negative values only fit the last, so it has to
+    // be 64.
+    pub fn custom(widths: &[u8]) -> Result<Self, InvalidIntWidths> {
+        let increasing = widths.windows(2).all(|pair| pair[0] < pair[1]);
+        let valid = increasing
+            && (1..=MAX_INT_WIDTHS).contains(&widths.len())
+            && widths.first().is_some_and(|width| *width >= 1)
+            && widths.last() == Some(&64);
+        if !valid {
+            return Err(InvalidIntWidths);
+        }
+        let mut plan = IntHeaderPlan {
+            len: widths.len() as u8,
+            ..IntHeaderPlan::default()
+        };
+        plan.widths[..widths.len()].copy_from_slice(widths);
+        plan.mask = widths[..widths.len() - 1]
+            .iter()
+            .fold(0, |mask, width| mask | 1 << (width - 1));
+        Ok(plan)
+    }
+
+    // `custom` in whole bytes, `[1, 2, 3, 4, 8]` is 8, 16, 24, 32 and 64 bits
+    pub fn byte_buckets(bytes: &[u8]) -> Result<Self, InvalidIntWidths> {
+        let mut widths = [0; MAX_INT_WIDTHS];
+        if bytes.len() > MAX_INT_WIDTHS || bytes.iter().any(|bytes| *bytes > 8) {
+            return Err(InvalidIntWidths);
+        }
+        for (width, bytes) in widths.iter_mut().zip(bytes) {
+            *width = bytes * 8;
+        }
+        IntHeaderPlan::custom(&widths[..bytes.len()])
+    }
+
+    // `None` for a mask that isn't one `custom` writes, 0 is only the 64 bit width
+    pub(crate) fn from_mask(mask: u64) -> Option<Self> {
+        if mask >> 63 != 0 {
+            return None;
+        }
+        let mut widths = [0; MAX_INT_WIDTHS + 1];
+        let mut len = 0;
+        for width in (1..64).filter(|width| mask & 1 << (width - 1) != 0) {
+            *widths.get_mut(len)? = width;
+            len += 1;
+        }
+        widths[len] = 64;
+        IntHeaderPlan::custom(&widths[..=len]).ok()
+    }
+
+    // the mask written for custom widths, `None` for a table
+    pub(crate) fn custom_mask(self) -> Option<u64> {
+        (self.len != 0).then_some(self.mask)
+    }
+
+    // index into `INT_HEADER_TABLES`, 0 is the default widths and isn't written (and is what
+    // custom widths have)
+    pub fn table(self) -> u8 {
+        self.table
+    }
+
+    pub fn widths(&self) -> &[u8] {
+        match self.len {
+            0 => INT_HEADER_TABLES[self.table as usize],
+            len => &self.widths[..len as usize],
+        }
+    }
+
+    // what `ints` cost written with this plan's widths, without the column's own flags
+    pub fn encoded_bits(self, ints: &[i64]) -> u64 {
+        ints.iter()
+            .map(|int| int_encoded_bits_in(*int, self.widths()))
+            .sum()
+    }
+}
+
+// The plan that writes `sample` in the fewest bits, the default on a tie (and for no sample).
+pub fn analyze_ints(sample: &[i64]) -> IntHeaderPlan {
+    (0..INT_HEADER_TABLES.len() as u8)
+        .filter_map(IntHeaderPlan::from_table)
+        .min_by_key(|plan| plan.encoded_bits(sample))
+        .expect("there's always the default table")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::{Deserializer, Serializer};
+
+    fn write(ints: &[i64], plan: IntHeaderPlan) -> (Vec<u8>, u64) {
+        let mut serializer = Serializer::new();
+        serializer.set_int_header_plan(plan);
+        for int in ints {
+            serializer.write_int(*int);
+        }
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        (buffer, stats.integers.bits)
+    }
+
+    fn read(buffer: &[u8]) -> Vec<i64> {
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(buffer, 0).unwrap();
+        std::iter::from_fn(|| deserializer.take_int()).collect()
+    }
+
+    // 70% of them fit in 4 bits but half of those are past the default's 3 bit first width
+    fn skewed() -> Vec<i64> {
+        (0..1000)
+            .map(|i| match i % 10 {
+                0..7 => i % 16,
+                7..9 => 200 + i % 50,
+                _ => 40_000 + i,
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn trained_plan_beats_the_default() {
+        let ints = skewed();
+        let plan = analyze_ints(&ints);
+        assert_ne!(plan, IntHeaderPlan::default());
+        assert!(plan.encoded_bits(&ints) < IntHeaderPlan::default().encoded_bits(&ints));
+
+        let (planned, planned_bits) = write(&ints, plan);
+        let (default, default_bits) = write(&ints, IntHeaderPlan::default());
+        assert!(
+            planned_bits < default_bits,
+            "{planned_bits} bits against {default_bits}"
+        );
+        assert!(planned.len() < default.len());
+        assert_eq!(read(&planned), ints);
+
+        assert_eq!(analyze_ints(&[]), IntHeaderPlan::default());
+    }
+
+    #[test]
+    pub fn every_table_roundtrips_any_value() {
+        let ints = [0, 1, -1, 255, 256, 1 << 40, i64::MIN, i64::MAX, 3, -40_000];
+        for table in 0..INT_HEADER_TABLES.len() as u8 {
+            let plan = IntHeaderPlan::from_table(table).unwrap();
+            let (buffer, _) = write(&ints, plan);
+            assert_eq!(read(&buffer), ints, "table {table}");
+        }
+        assert_eq!(
+            IntHeaderPlan::from_table(INT_HEADER_TABLES.len() as u8),
+            None
+        );
+    }
+
+    #[test]
+    pub fn custom_widths_roundtrip() {
+        let ints = skewed();
+        let buckets = IntHeaderPlan::byte_buckets(&[1, 2, 3, 4, 8]).unwrap();
+        assert_eq!(buckets.widths(), [8, 16, 24, 32, 64]);
+        let (buffer, _) = write(&ints, buckets);
+        assert_eq!(read(&buffer), ints);
+
+        let edges = [0, 1, -1, 255, 256, 1 << 40, i64::MIN, i64::MAX];
+        let plans = [&[64][..], &[1, 2, 3, 4, 5, 6, 7, 64], &[63, 64], &[5, 64]];
+        for widths in plans {
+            let plan = IntHeaderPlan::custom(widths).unwrap();
+            let (buffer, _) = write(&edges, plan);
+            assert_eq!(read(&buffer), edges, "{widths:?}");
+        }
+
+        let invalid = [
+            &[][..],
+            &[8, 16],
+            &[16, 8, 64],
+            &[8, 8, 64],
+            &[0, 64],
+            &[65],
+        ];
+        for widths in invalid {
+            assert_eq!(IntHeaderPlan::custom(widths), Err(InvalidIntWidths));
+        }
+        assert!(IntHeaderPlan::custom(&[1, 2, 3, 4, 5, 6, 7, 8, 64]).is_err());
+        assert!(IntHeaderPlan::byte_buckets(&[1, 9]).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::serializer::{PropertyType, PropertyValue, Serializer};
+
+// JSON -> property mapping:
+// - bools and strings map directly
+// - numbers must be integral and fit in an i64, floats and u64s above i64::MAX are rejected
+//   rather than silently rounded/wrapped
+// - arrays map to arrays
+// - objects map to an array of `[key, value]` pairs in the map's iteration order
+// - null has no equivalent and is rejected
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonError {
+    Null,
+    Float(f64),
+    OutOfRange(u64),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonError::Null => write!(f, "null has no property equivalent"),
+            JsonError::Float(float) => write!(f, "{float} is not an integer"),
+            JsonError::OutOfRange(int) => write!(f, "{int} doesn't fit in an i64"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+fn json_int(number: &serde_json::Number) -> Result<i64, JsonError> {
+    if let Some(int) = number.as_i64() {
+        Ok(int)
+    } else if let Some(int) = number.as_u64() {
+        Err(JsonError::OutOfRange(int))
+    } else {
+        Err(JsonError::Float(number.as_f64().unwrap_or(f64::NAN)))
+    }
+}
+
+// checks the whole tree up front so a failure doesn't leave half a value in the serializer
+fn validate(value: &Value) -> Result<(), JsonError> {
+    match value {
+        Value::Null => Err(JsonError::Null),
+        Value::Bool(_) | Value::String(_) => Ok(()),
+        Value::Number(number) => json_int(number).map(|_| ()),
+        Value::Array(values) => values.iter().try_for_each(validate),
+        Value::Object(map) => map.values().try_for_each(validate),
+    }
+}
+
+impl PropertyValue {
+    pub fn from_json(value: &Value) -> Result<PropertyValue, JsonError> {
+        Ok(match value {
+            Value::Null => return Err(JsonError::Null),
+            Value::Bool(bool) => PropertyValue::Bool(*bool),
+            Value::Number(number) => PropertyValue::Integer(json_int(number)?),
+            Value::String(string) => PropertyValue::String(string.clone()),
+            Value::Array(values) => PropertyValue::Array(
+                values
+                    .iter()
+                    .map(PropertyValue::from_json)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(map) => PropertyValue::Array(
+                map.iter()
+                    .map(|(key, value)| {
+                        Ok(PropertyValue::Array(vec![
+                            PropertyValue::String(key.clone()),
+                            PropertyValue::from_json(value)?,
+                        ]))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+}
+
+impl<'a> Serializer<'a> {
+    // same output as `write_value(&PropertyValue::from_json(value)?)`, but strings are borrowed
+    // straight out of the json instead of being copied into an intermediate tree
+    pub fn write_json_value(&mut self, value: &'a Value) -> Result<(), JsonError> {
+        validate(value)?;
+        self.write_json_unchecked(value);
+        Ok(())
+    }
+
+    fn write_json_unchecked(&mut self, value: &'a Value) {
+        match value {
+            Value::Null => unreachable!("rejected by validate"),
+            Value::Bool(bool) => {
+                self.write_property_type(PropertyType::Bool);
+                self.write_bool(*bool);
+            }
+            Value::Number(number) => {
+                self.write_property_type(PropertyType::Integer);
+                self.write_int(number.as_i64().expect("rejected by validate"));
+            }
+            Value::String(string) => {
+                self.write_property_type(PropertyType::String);
+                self.write_string(string);
+            }
+            Value::Array(values) => {
+                self.write_property_type(PropertyType::Array);
+                self.write_length(values.len());
+                for value in values {
+                    self.write_json_unchecked(value);
+                }
+            }
+            Value::Object(map) => {
+                self.write_property_type(PropertyType::Array);
+                self.write_length(map.len());
+                for (key, value) in map {
+                    self.write_property_type(PropertyType::Array);
+                    self.write_length(2);
+                    self.write_property_type(PropertyType::String);
+                    self.write_string(key);
+                    self.write_json_unchecked(value);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::Deserializer;
+
+    #[test]
+    pub fn nested_object_matches_from_json() {
+        let json: Value = serde_json::from_str(
+            r#"{
+                "property 1": "a",
+                "property 2": 100,
+                "property 3": false,
+                "property 4": ["hello", 200, true, {"nested": [1, 2]}]
+            }"#,
+        )
+        .unwrap();
+
+        let mut serializer = Serializer::new();
+        serializer.write_json_value(&json).unwrap();
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_value(),
+            Some(PropertyValue::from_json(&json).unwrap())
+        );
+    }
+
+    #[test]
+    pub fn rejects_unrepresentable() {
+        for (json, err) in [
+            ("[1, null]", JsonError::Null),
+            ("[1.5]", JsonError::Float(1.5)),
+            ("18446744073709551615", JsonError::OutOfRange(u64::MAX)),
+        ] {
+            let json: Value = serde_json::from_str(json).unwrap();
+            let mut serializer = Serializer::new();
+            assert_eq!(serializer.write_json_value(&json), Err(err.clone()));
+            assert_eq!(PropertyValue::from_json(&json), Err(err));
+            let stats = serializer.finish(&mut Vec::new(), 0);
+            assert_eq!(stats.property_types.values, 0);
+        }
+    }
+}
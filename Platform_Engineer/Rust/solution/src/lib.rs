@@ -0,0 +1,45 @@
+pub mod appender;
+pub mod batch;
+pub mod bit_packer;
+pub mod checksum;
+pub mod column_split;
+mod columns;
+pub mod cursor;
+pub mod diff;
+pub mod duration;
+pub mod error;
+pub mod events;
+pub mod experiment;
+pub mod field;
+pub mod float_array;
+pub mod format_spec;
+pub mod huffman;
+pub mod index_set;
+pub mod int_plan;
+#[cfg(feature = "serde_json")]
+pub mod json;
+pub mod limits;
+mod lz;
+pub mod migrate;
+pub mod num;
+pub mod pool;
+mod progress;
+pub mod provenance;
+pub mod record;
+mod recycled;
+pub mod registry;
+pub mod remaining;
+pub mod roundtrip;
+pub mod serializer;
+#[cfg(feature = "small-string")]
+pub mod small_string;
+pub mod stats;
+pub mod struct_array;
+pub mod structure;
+pub mod typed_vec;
+pub mod ultra_packer;
+
+#[cfg(test)]
+mod alloc_counter;
+#[cfg(test)]
+mod corrupt;
@@ -0,0 +1,378 @@
+use std::fmt;
+
+// Caps on how much a single buffer can make the deserializer do, checked against the header
+// before anything is allocated for the columns, and against the tail array as it's read (see
+// `appender::TailBudget`). `Deserializer::new` uses `relaxed`, anything reading input it doesn't
+// control should use `Deserializer::new_untrusted` (`strict`).
+//
+// let limits = DecodeLimits::strict().max_depth(4);
+// let mut deserializer = Deserializer::with_limits(limits);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+    pub(crate) max_column_values: u64,
+    pub(crate) max_decoded_bytes: u64,
+    pub(crate) max_depth: usize,
+    pub(crate) max_work: u64,
+}
+
+impl DecodeLimits {
+    // for untrusted network input, small messages that don't nest much
+    pub const fn strict() -> Self {
+        DecodeLimits {
+            max_column_values: 1 << 16,
+            max_decoded_bytes: 1 << 20,
+            max_depth: 32,
+            max_work: 1 << 18,
+        }
+    }
+
+    // for trusted data from internal services, only there to stop a corrupt buffer asking for
+    // more memory than a process would reasonably have
+    pub const fn relaxed() -> Self {
+        DecodeLimits {
+            max_column_values: 1 << 24,
+            max_decoded_bytes: 1 << 30,
+            max_depth: 1024,
+            max_work: u64::MAX,
+        }
+    }
+
+    // whatever the buffer declares, still bounded by its size since every value costs input (a few
+    // hundred times its size for strings written with `Serializer::set_lz_strings`)
+    pub const fn unlimited() -> Self {
+        DecodeLimits {
+            max_column_values: u64::MAX,
+            max_decoded_bytes: u64::MAX,
+            max_depth: usize::MAX,
+            max_work: u64::MAX,
+        }
+    }
+
+    // values any one column may declare
+    pub const fn max_column_values(mut self, values: u64) -> Self {
+        self.max_column_values = values;
+        self
+    }
+
+    // Size of everything decoded from a buffer: 8 bytes per integer and array length, 1 per
+    // boolean and property type, plus the bytes of every string.
+    pub const fn max_decoded_bytes(mut self, bytes: u64) -> Self {
+        self.max_decoded_bytes = bytes;
+        self
+    }
+
+    // arrays within arrays, the outermost array is depth 1
+    pub const fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    // Values decoded from a buffer, each integer, boolean, string, property type and array length
+    // (kind markers and raw strings included) costs 1, and then 1 more for each value
+    // `take_array` puts into an array, and in the tail array 1 for its length and 2 for each value
+    // (the tag, then the value or the array's length). Counted rather than timed, so the same
+    // buffer always gets as far. `strict` allows less than a buffer filling every column to its
+    // limit would take, the other presets are unbounded.
+    pub const fn max_work(mut self, values: u64) -> Self {
+        self.max_work = values;
+        self
+    }
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits::relaxed()
+    }
+}
+
+// An array nested deeper than `Serializer::set_max_depth` allows, which a reader with the same
+// `max_depth` would refuse. Nothing of it was written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DepthExceeded {
+    pub depth: usize,
+    pub max: usize,
+}
+
+impl fmt::Display for DepthExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "arrays are nested {} deep, past the limit of {}",
+            self.depth, self.max
+        )
+    }
+}
+
+impl std::error::Error for DepthExceeded {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    ColumnValues,
+    DecodedBytes,
+    Depth,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Limit::ColumnValues => "column values",
+            Limit::DecodedBytes => "decoded bytes",
+            Limit::Depth => "array depth",
+        };
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::DecodeError,
+        serializer::{Deserializer, PropertyType, PropertyValue, Serializer},
+    };
+
+    fn buffer(write: impl FnOnce(&mut Serializer)) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        write(&mut serializer);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        buffer
+    }
+
+    // `depth` arrays each holding the next
+    fn nested(depth: usize) -> Vec<u8> {
+        buffer(|serializer| {
+            serializer.write_length(1);
+            for level in 1..depth {
+                serializer.write_property_type(PropertyType::Array);
+                serializer.write_length(if level + 1 < depth { 1 } else { 0 });
+            }
+        })
+    }
+
+    fn decodes(limits: DecodeLimits, bytes: &[u8]) -> bool {
+        Deserializer::with_limits(limits)
+            .read_bytes(bytes, 0)
+            .is_some()
+    }
+
+    #[test]
+    pub fn column_values_are_enforced() {
+        let ints = |count| buffer(|serializer| (0..count).for_each(|i| serializer.write_int(i)));
+        let strict = DecodeLimits::strict();
+        assert!(decodes(strict, &ints(1 << 16)));
+        assert!(!decodes(strict, &ints((1 << 16) + 1)));
+        assert!(decodes(DecodeLimits::relaxed(), &ints((1 << 16) + 1)));
+        assert!(!decodes(strict.max_column_values(3), &ints(4)));
+    }
+
+    #[test]
+    pub fn decoded_bytes_are_enforced() {
+        // a constant column is a few bits whatever its length, only the budget catches it
+        let zeros = buffer(|serializer| (0..60_000).for_each(|_| serializer.write_int(0)));
+        assert!(zeros.len() < 32);
+        let budget = DecodeLimits::unlimited().max_decoded_bytes(60_000 * 8);
+        assert!(decodes(budget, &zeros));
+        assert!(!decodes(budget.max_decoded_bytes(60_000 * 8 - 1), &zeros));
+
+        let strings = buffer(|serializer| {
+            for i in 0..2_000 {
+                serializer.write_string_owned(format!("{i:0>1000}"));
+            }
+        });
+        assert!(!decodes(DecodeLimits::strict(), &strings));
+        assert!(decodes(DecodeLimits::relaxed(), &strings));
+    }
+
+    #[test]
+    pub fn depth_is_enforced() {
+        let mut deserializer = Deserializer::new_untrusted();
+        deserializer.read_bytes(&nested(32), 0).unwrap();
+        assert!(deserializer.take_array().is_some());
+        deserializer.read_bytes(&nested(33), 0).unwrap();
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::Depth,
+                max: 32
+            })
+        );
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&nested(33), 0).unwrap();
+        assert!(deserializer.take_array().is_some());
+        deserializer.read_bytes(&nested(1025), 0).unwrap();
+        assert!(deserializer.take_array().is_none());
+    }
+
+    // `depth` arrays each holding the next, as a value
+    fn nested_value(depth: usize) -> PropertyValue {
+        (1..depth).fold(PropertyValue::Array(Vec::new()), |inner, _| {
+            PropertyValue::Array(vec![inner])
+        })
+    }
+
+    #[test]
+    pub fn depth_is_enforced_when_writing() {
+        let limits = DecodeLimits::relaxed().max_depth(4);
+        let mut serializer = Serializer::new();
+        serializer.set_max_depth(4);
+        let at_limit = nested_value(4);
+        serializer.write_value_checked(&at_limit).unwrap();
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::with_limits(limits);
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_value(), Some(at_limit));
+
+        let past = DepthExceeded { depth: 5, max: 4 };
+        let mut serializer = Serializer::new();
+        serializer.set_max_depth(4);
+        assert_eq!(serializer.write_value_checked(&nested_value(5)), Err(past));
+        let array = [PropertyValue::Integer(1), nested_value(4)];
+        assert_eq!(serializer.write_array_checked(&array), Err(past));
+        assert!(serializer.is_empty());
+
+        // and what a writer allowing more writes, the reader refuses at the same depth
+        serializer.set_max_depth(5);
+        serializer.write_array_checked(&array).unwrap();
+        serializer.finish(&mut buffer, 0);
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::Depth,
+                max: 4
+            })
+        );
+    }
+
+    #[test]
+    pub fn default_depths_match() {
+        let max = DecodeLimits::default().max_depth;
+        let mut serializer = Serializer::new();
+        let past = nested_value(max + 1);
+        assert_eq!(
+            serializer.write_value_checked(&past),
+            Err(DepthExceeded {
+                depth: max + 1,
+                max
+            })
+        );
+        let at_limit = nested_value(max);
+        serializer.write_value(&at_limit);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_value(), Some(at_limit));
+    }
+
+    #[test]
+    #[should_panic(expected = "arrays are nested 3 deep, past the limit of 2")]
+    pub fn write_array_panics_past_the_depth() {
+        let mut serializer = Serializer::new();
+        serializer.set_max_depth(2);
+        serializer.write_array(&[nested_value(2)]);
+    }
+
+    #[test]
+    pub fn work_is_enforced() {
+        let work = |max| DecodeLimits::relaxed().max_work(max);
+        let ints = buffer(|serializer| (0..100).for_each(|i| serializer.write_int(i)));
+        assert!(decodes(work(100), &ints));
+        let mut deserializer = Deserializer::with_limits(work(99));
+        assert_eq!(
+            deserializer.load(&ints, 0),
+            Err(DecodeError::WorkBudgetExceeded { max: 99 })
+        );
+        // a constant column costs its count like any other
+        let zeros = buffer(|serializer| (0..60_000).for_each(|_| serializer.write_int(0)));
+        assert!(!decodes(work(59_999), &zeros));
+
+        // 33 lengths and 32 tags decoded, then 32 more for the values taken back out
+        let array = nested(33);
+        let mut deserializer = Deserializer::with_limits(work(97));
+        deserializer.load(&array, 0).unwrap();
+        assert!(deserializer.take_array().is_some());
+        let mut deserializer = Deserializer::with_limits(work(96));
+        deserializer.load(&array, 0).unwrap();
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::WorkBudgetExceeded { max: 96 })
+        );
+    }
+
+    #[test]
+    pub fn tails_cost_work() {
+        let mut serializer = Serializer::new();
+        serializer.write_int(1);
+        let tail = vec![PropertyValue::Bool(true); 10];
+        serializer.write_tail_array(&tail);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        // the int, the tail's length, then a tag and a bit for each element
+        let work = 1 + 1 + 10 * 2;
+        let limits = DecodeLimits::relaxed();
+        assert!(decodes(limits.max_work(work), &buffer));
+        assert_eq!(
+            Deserializer::with_limits(limits.max_work(work - 1)).load(&buffer, 0),
+            Err(DecodeError::WorkBudgetExceeded { max: work - 1 })
+        );
+    }
+
+    #[test]
+    pub fn strict_work_is_finite() {
+        // four columns at the limit (the strings empty, to stay under the decoded bytes) is all
+        // the work strict allows
+        let full = |length: bool| {
+            buffer(|serializer| {
+                for i in 0..1 << 16 {
+                    serializer.write_int(i);
+                    serializer.write_bool(true);
+                    serializer.write_property_type(PropertyType::Bool);
+                    serializer.write_string("");
+                }
+                if length {
+                    serializer.write_length(0);
+                }
+            })
+        };
+        assert!(decodes(DecodeLimits::strict(), &full(false)));
+        assert_eq!(
+            Deserializer::new_untrusted().load(&full(true), 0),
+            Err(DecodeError::WorkBudgetExceeded { max: 1 << 18 })
+        );
+    }
+
+    #[test]
+    pub fn tail_depth_is_enforced_when_writing() {
+        use crate::appender::{AppendError, ArrayAppender};
+
+        let limits = DecodeLimits::relaxed().max_depth(4);
+        let past = AppendError::TooDeep(DepthExceeded { depth: 5, max: 4 });
+        // the tail array is depth 1, like the array `write_array` writes
+        let tail = [nested_value(3)];
+        let deep = [nested_value(4)];
+        let mut serializer = Serializer::new();
+        serializer.set_max_depth(4);
+        assert_eq!(serializer.write_tail_array_checked(&deep), Err(past));
+        serializer.write_tail_array_checked(&tail).unwrap();
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut appender = ArrayAppender::new(&mut buffer, 0).unwrap();
+        appender.set_max_depth(4);
+        assert_eq!(appender.append(&nested_value(4)), Err(past));
+        appender.append(&nested_value(3)).unwrap();
+        assert_eq!(appender.len(), 2);
+
+        let mut deserializer = Deserializer::with_limits(limits);
+        deserializer.load(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_tail_array(),
+            Some(vec![nested_value(3), nested_value(3)])
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::{
+    bit_packer::{
+        BitPacker, BitUnpacker, length_int, small_int_encoded_bits, unicode_huffman_byte_bits,
+    },
+    columns::TextColumn,
+    progress::Progress,
+};
+
+// Back references for the string column, for buffers that don't go through a general purpose
+// compressor afterwards (see `Serializer::set_lz_strings`). Config strings repeat a lot more of
+// each other than a shared prefix, `service.auth.timeout_ms` and `service.cache.timeout_ms`, which
+// front coding only catches at the start of neighbouring strings.
+//
+// Each string is its length as an int and then tokens adding up to exactly that many bytes:
+// - a clear bit and a literal byte, the way `write_unicode_huffman_byte` writes it
+// - a set bit and a match: small ints of the distance back less 1 and the length less `MIN_MATCH`
+//
+// Matches reach back into the earlier strings of the same call (a section with column offsets,
+// which are independent of each other) but never past the end of the string they're in. A match
+// may overlap the bytes it produces, a run is a match at distance 1.
+
+const MIN_MATCH: usize = 3;
+// Longer matches are split, and longer ones from a reader are corrupt. The writer's matches don't
+// get near this in practice, it's what keeps a buffer from decoding to more than about 250 times
+// its size (with `DecodeLimits::unlimited`, the other limits cap the strings well before that).
+const MAX_MATCH: usize = MIN_MATCH + 255;
+// how far back the writer looks, and how many of the earlier positions starting with the same
+// `MIN_MATCH` bytes it compares against
+const WINDOW: usize = 1 << 15;
+const MAX_CANDIDATES: usize = 16;
+
+const NO_POSITION: usize = usize::MAX;
+
+pub(crate) fn write_strings(packer: &mut BitPacker, strings: &[Cow<str>]) {
+    // every string so far back to back, and for each position the previous one that started with
+    // the same bytes (a hash chain)
+    let mut blob = Vec::new();
+    let mut heads: HashMap<[u8; MIN_MATCH], usize> = HashMap::new();
+    let mut chain = Vec::new();
+    for string in strings {
+        let start = blob.len();
+        blob.extend_from_slice(string.as_bytes());
+        chain.resize(blob.len(), NO_POSITION);
+        let end = blob.len();
+
+        packer.write_int(length_int(string.len()));
+        let mut position = start;
+        while position < end {
+            let found = longest_match(&blob[..end], position, &heads, &chain).filter(
+                |(distance, length)| {
+                    match_bits(*distance, *length) < literal_bits(&blob[position..][..*length])
+                },
+            );
+            let advance = match found {
+                Some((distance, length)) => {
+                    packer.write_bit(true);
+                    packer.write_small_int((distance - 1) as u64);
+                    packer.write_small_int((length - MIN_MATCH) as u64);
+                    length
+                }
+                None => {
+                    packer.write_bit(false);
+                    packer.write_unicode_huffman_byte(blob[position]);
+                    1
+                }
+            };
+            let added = chain.iter_mut().enumerate().skip(position).take(advance);
+            for (position, previous) in added {
+                if let Some(key) = key(&blob[..end], position) {
+                    *previous = heads.insert(key, position).unwrap_or(NO_POSITION);
+                }
+            }
+            position += advance;
+        }
+    }
+}
+
+// the first `MIN_MATCH` bytes at `position`, if the string has that many left
+fn key(blob: &[u8], position: usize) -> Option<[u8; MIN_MATCH]> {
+    blob.get(position..position + MIN_MATCH)?.try_into().ok()
+}
+
+// (distance, length) of the longest earlier match for what's at `position`, up to the end of `blob`
+fn longest_match(
+    blob: &[u8],
+    position: usize,
+    heads: &HashMap<[u8; MIN_MATCH], usize>,
+    chain: &[usize],
+) -> Option<(usize, usize)> {
+    let mut candidate = *heads.get(&key(blob, position)?)?;
+    let longest = (blob.len() - position).min(MAX_MATCH);
+    let mut best: Option<(usize, usize)> = None;
+    for _ in 0..MAX_CANDIDATES {
+        if candidate == NO_POSITION || position - candidate > WINDOW {
+            break;
+        }
+        let length = (0..longest)
+            .take_while(|i| blob[candidate + i] == blob[position + i])
+            .count();
+        if length >= MIN_MATCH && best.is_none_or(|(_, best)| length > best) {
+            best = Some((position - candidate, length));
+        }
+        candidate = chain[candidate];
+    }
+    best
+}
+
+fn match_bits(distance: usize, length: usize) -> u64 {
+    1 + small_int_encoded_bits((distance - 1) as u64)
+        + small_int_encoded_bits((length - MIN_MATCH) as u64)
+}
+
+fn literal_bits(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .map(|byte| 1 + unicode_huffman_byte_bits(*byte))
+        .sum()
+}
+
+// `blob` is scratch space for the strings of this call, which the matches refer back into
+pub(crate) fn read_strings(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    strings: &mut TextColumn,
+    blob: &mut Vec<u8>,
+    budget: &mut u64,
+    progress: &mut Progress,
+) -> Option<()> {
+    blob.clear();
+    for _ in 0..count {
+        let start = blob.len();
+        read_string(unpacker, blob, budget)?;
+        strings.push(&blob[start..]);
+        progress.values(1, unpacker.bit_position())?;
+    }
+    Some(())
+}
+
+// returns the decoded size of the strings
+pub(crate) fn skip_strings(
+    unpacker: &mut BitUnpacker,
+    count: u64,
+    blob: &mut Vec<u8>,
+) -> Option<u64> {
+    blob.clear();
+    let mut budget = u64::MAX;
+    for _ in 0..count {
+        read_string(unpacker, blob, &mut budget)?;
+    }
+    Some(blob.len() as u64)
+}
+
+fn read_string(unpacker: &mut BitUnpacker, blob: &mut Vec<u8>, budget: &mut u64) -> Option<()> {
+    let length = u64::try_from(unpacker.read_int()?).ok()?;
+    *budget = budget.checked_sub(length)?;
+    let end = blob.len().checked_add(usize::try_from(length).ok()?)?;
+    while blob.len() < end {
+        if !unpacker.read_bit()? {
+            blob.push(unpacker.read_unicode_huffman_byte()?);
+            continue;
+        }
+        let distance = usize::try_from(unpacker.read_small_int()?)
+            .ok()?
+            .checked_add(1)?;
+        let length = usize::try_from(unpacker.read_small_int()?)
+            .ok()?
+            .checked_add(MIN_MATCH)?;
+        let from = blob.len().checked_sub(distance)?;
+        if length > MAX_MATCH || length > end - blob.len() {
+            return None;
+        }
+        // byte by byte, the match can overlap what it's copying
+        for index in from..from + length {
+            blob.push(blob[index]);
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        columns::{StringFlags, write_strings as write_column},
+        serializer::{Deserializer, Serializer},
+    };
+
+    fn paths() -> Vec<String> {
+        let services = ["auth", "cache", "billing", "search"];
+        let settings = ["timeout_ms", "max_connections", "retry_backoff_ms"];
+        services
+            .iter()
+            .flat_map(|service| {
+                settings
+                    .iter()
+                    .map(move |setting| format!("/etc/platform/{service}/{setting}"))
+            })
+            .collect()
+    }
+
+    fn column_bits(strings: &[Cow<str>], flags: StringFlags) -> u64 {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        write_column(&mut packer, strings, flags, &mut HashMap::new()).unwrap();
+        packer.bit_position()
+    }
+
+    #[test]
+    pub fn repeated_substrings_beat_huffman_alone() {
+        let paths: Vec<Cow<str>> = paths().into_iter().map(Cow::Owned).collect();
+        let huffman = column_bits(
+            &paths,
+            StringFlags {
+                all_ascii: true,
+                ..StringFlags::default()
+            },
+        );
+        let front_coded = column_bits(
+            &paths,
+            StringFlags {
+                all_ascii: true,
+                front_coded: true,
+                ..StringFlags::default()
+            },
+        );
+        let lz = column_bits(
+            &paths,
+            StringFlags {
+                lz: true,
+                ..StringFlags::default()
+            },
+        );
+        assert!(lz * 2 < huffman, "{lz} bits against {huffman}");
+        assert!(lz < front_coded, "{lz} bits against {front_coded}");
+    }
+
+    #[test]
+    pub fn lz_strings_roundtrip() {
+        let mut strings = paths();
+        strings.extend(["", "aaaaaaaaaaaaaaaaaaaaaaaa", "ключключключ", "x"].map(String::from));
+        strings.push("ab".repeat(400));
+
+        for column_offsets in [false, true] {
+            let mut serializer = Serializer::new();
+            serializer.set_lz_strings(true);
+            serializer.set_column_offsets(column_offsets);
+            for string in &strings {
+                serializer.write_string(string);
+            }
+            let mut buffer = Vec::new();
+            let stats = serializer.finish(&mut buffer, 0);
+            assert!(stats.lz_strings);
+
+            crate::serializer::verify(&buffer, 0).unwrap();
+            let mut deserializer = Deserializer::new();
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            assert!(deserializer.last_stats().lz_strings);
+            for string in &strings {
+                assert_eq!(deserializer.take_string().as_ref(), Some(string));
+            }
+        }
+    }
+
+    #[test]
+    pub fn corrupt_matches_are_rejected() {
+        let strings = |write: &dyn Fn(&mut BitPacker)| {
+            let mut buffer = Vec::new();
+            let mut packer = BitPacker::new(&mut buffer);
+            write(&mut packer);
+            let mut strings = TextColumn::default();
+            let mut budget = u64::MAX;
+            let mut unpacker = BitUnpacker::new(&buffer);
+            let progress = &mut Progress::default();
+            read_strings(
+                &mut unpacker,
+                1,
+                &mut strings,
+                &mut Vec::new(),
+                &mut budget,
+                progress,
+            )
+            .map(|()| strings.pop_front().unwrap().to_owned())
+        };
+        let literal = |packer: &mut BitPacker, byte| {
+            packer.write_bit(false);
+            packer.write_unicode_huffman_byte(byte);
+        };
+        let matched = |packer: &mut BitPacker, distance: u64, length: u64| {
+            packer.write_bit(true);
+            packer.write_small_int(distance - 1);
+            packer.write_small_int(length - MIN_MATCH as u64);
+        };
+
+        // a run is a match overlapping itself
+        let run = strings(&|packer| {
+            packer.write_int(6);
+            literal(packer, b'z');
+            literal(packer, b'y');
+            matched(packer, 2, 4);
+        });
+        assert_eq!(run.as_deref(), Some("zyzyzy"));
+        // from before the start
+        let before = strings(&|packer| {
+            packer.write_int(4);
+            literal(packer, b'z');
+            matched(packer, 2, 3);
+        });
+        assert_eq!(before, None);
+        // past the end of the string
+        let past = strings(&|packer| {
+            packer.write_int(3);
+            literal(packer, b'z');
+            matched(packer, 1, 3);
+        });
+        assert_eq!(past, None);
+        // longer than any match the writer makes, however long the string
+        let long = strings(&|packer| {
+            packer.write_int(1 << 20);
+            literal(packer, b'z');
+            matched(packer, 1, MAX_MATCH as u64 + 1);
+        });
+        assert_eq!(long, None);
+    }
+}
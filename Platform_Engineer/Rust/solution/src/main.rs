@@ -1,9 +1,4 @@
-mod bit_packer;
-mod huffman;
-mod serializer;
-mod ultra_packer;
-
-use serializer::{Deserializer, IntoFormat, PropertyValue, Serializer};
+use solution::serializer::{Deserializer, IntoFormat, PropertyValue, Serializer, assert_roundtrip};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Config {
@@ -172,8 +167,105 @@ fn main() {
         (1.0 - buffer.len() as f64 / native_buffer.len() as f64) * 100.0
     );
 
+    if let Some(path) = std::env::args().nth(1) {
+        std::fs::write(&path, &buffer).expect("failed to write buffer");
+    }
+
     let deser_config = Config::deserialize(&buffer, &mut deserializer, PROTOCOL_VERSION);
     println!("expected: {:?}", config);
     println!("got:      {:?}", deser_config);
     assert_eq!(Some(&config), deser_config.as_ref(), "round-trip failed");
+    // and nothing was left unread or written differently the second time
+    assert_roundtrip(&config);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solution::{
+        error::DecodeError,
+        format_spec::OPTION_ABSENT,
+        pool::{optional_to_bytes, to_bytes},
+        serializer::{assert_bytes_eq, peek_header},
+    };
+
+    fn config() -> Config {
+        Config {
+            data: -12,
+            name: "service.example.internal".to_owned(),
+            cool: false,
+            arr: vec![
+                PropertyValue::String("1920x1080".to_owned()),
+                PropertyValue::Integer(4096),
+                PropertyValue::Array(vec![PropertyValue::Bool(true)]),
+            ],
+            nested: NestedConfig { nested: 7 },
+        }
+    }
+
+    #[test]
+    pub fn config_roundtrips() {
+        assert_roundtrip(&config());
+    }
+
+    #[test]
+    pub fn nested_configs_write_in_place() {
+        // the same as writing every field by hand
+        let config = config();
+        let mut serializer = Serializer::new();
+        serializer.write_int(config.data);
+        serializer.write_string(&config.name);
+        serializer.write_bool(config.cool);
+        serializer.write_array(&config.arr);
+        serializer.write_int(config.nested.nested);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        assert_bytes_eq(&to_bytes(&config, 0), &buffer);
+    }
+
+    // a record with nothing in it, which is still there
+    #[derive(Debug, PartialEq)]
+    struct Empty;
+
+    impl IntoFormat for Empty {
+        fn serialize<'a>(&'a self, _: &mut Serializer<'a>) {}
+
+        fn take(_: &mut Deserializer) -> Option<Self> {
+            Some(Empty)
+        }
+    }
+
+    #[test]
+    pub fn absent_configs_are_not_errors() {
+        let mut deserializer = Deserializer::new();
+        let absent = optional_to_bytes::<Config>(None, 0);
+        assert!(peek_header(&absent).unwrap().has(OPTION_ABSENT));
+        assert_eq!(
+            Config::deserialize_optional(&absent, &mut deserializer, 0),
+            Ok(None)
+        );
+        // the plain `deserialize` has no way to say so, it just finds nothing to take
+        assert_eq!(Config::deserialize(&absent, &mut deserializer, 0), None);
+
+        let config = config();
+        let present = optional_to_bytes(Some(&config), 0);
+        assert_bytes_eq(&present, &to_bytes(&config, 0));
+        assert_eq!(
+            Config::deserialize_optional(&present, &mut deserializer, 0),
+            Ok(Some(config))
+        );
+
+        let empty = optional_to_bytes(Some(&Empty), 0);
+        assert_ne!(empty, optional_to_bytes::<Empty>(None, 0));
+        assert_eq!(
+            Empty::deserialize_optional(&empty, &mut deserializer, 0),
+            Ok(Some(Empty))
+        );
+        assert_eq!(
+            Config::deserialize_optional(&empty, &mut deserializer, 0),
+            Err(DecodeError::TakeFailed {
+                remaining: Default::default()
+            })
+        );
+    }
 }
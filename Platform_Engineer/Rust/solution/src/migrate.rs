@@ -0,0 +1,212 @@
+use crate::{
+    cursor::ReadCursor,
+    serializer::{Deserializer, PropertyValue},
+};
+
+// Fills in fields an older version of a type didn't write, so one `ReadCursor` based decode
+// handles both:
+//
+// deserializer.read_bytes(bytes, bytes[0])?;
+// let migrator = Migrator::for_version(7)
+//     .default_bool(2, true)
+//     .default_array(3, Vec::new());
+// let mut cursor = migrator.apply(&mut deserializer);
+// let config = Config { data: cursor.int()?, name: cursor.str()?, cool: cursor.boolean()?, .. };
+//
+// Defaults are keyed by the field's index in the current read order and handed out by the cursor
+// in place of that field without touching the pools, so the fields around them still line up no
+// matter which pools the missing fields would have used.
+#[derive(Clone, Debug)]
+pub struct Migrator {
+    version: u8,
+    defaults: Vec<(usize, PropertyValue)>,
+}
+
+impl Migrator {
+    // only applies to buffers written with `version`
+    pub fn for_version(version: u8) -> Self {
+        Migrator {
+            version,
+            defaults: Vec::new(),
+        }
+    }
+
+    pub fn default_int(self, field: usize, value: i64) -> Self {
+        self.default(field, PropertyValue::Integer(value))
+    }
+
+    pub fn default_bool(self, field: usize, value: bool) -> Self {
+        self.default(field, PropertyValue::Bool(value))
+    }
+
+    pub fn default_string(self, field: usize, value: impl Into<String>) -> Self {
+        self.default(field, PropertyValue::String(value.into()))
+    }
+
+    pub fn default_array(self, field: usize, values: Vec<PropertyValue>) -> Self {
+        self.default(field, PropertyValue::Array(values))
+    }
+
+    // a later default for the same field replaces the earlier one
+    fn default(mut self, field: usize, value: PropertyValue) -> Self {
+        self.defaults.retain(|(existing, _)| *existing != field);
+        self.defaults.push((field, value));
+        self
+    }
+
+    // a cursor over `deserializer` with the defaults filled in, or a plain one for other versions
+    pub fn apply<'d>(&'d self, deserializer: &'d mut Deserializer) -> ReadCursor<'d> {
+        let defaults = match deserializer.version() == self.version {
+            true => self.defaults.as_slice(),
+            false => &[],
+        };
+        ReadCursor::with_defaults(deserializer, defaults)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::DecodeError,
+        serializer::{IntoFormat, PropertyType, Serializer},
+    };
+
+    // v7 of `Config`, before `cool` and `arr` were added
+    struct ConfigV7 {
+        data: i64,
+        name: String,
+        nested: i64,
+    }
+
+    impl IntoFormat for ConfigV7 {
+        fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+            serializer
+                .cursor()
+                .int(self.data)
+                .str(&self.name)
+                .int(self.nested);
+        }
+
+        fn take(_: &mut Deserializer) -> Option<Self> {
+            unreachable!("only written")
+        }
+    }
+
+    // v8, the new fields sit between existing ones and the array has an int like `nested`
+    #[derive(Debug, PartialEq, Eq)]
+    struct Config {
+        data: i64,
+        name: String,
+        cool: bool,
+        arr: Vec<PropertyValue>,
+        nested: i64,
+    }
+
+    impl IntoFormat for Config {
+        fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+            serializer
+                .cursor()
+                .int(self.data)
+                .str(&self.name)
+                .boolean(self.cool)
+                .array(&self.arr)
+                .int(self.nested);
+        }
+
+        fn take(deserializer: &mut Deserializer) -> Option<Self> {
+            decode(&migrator(), deserializer).ok()
+        }
+    }
+
+    fn decode(migrator: &Migrator, deserializer: &mut Deserializer) -> Result<Config, DecodeError> {
+        let mut cursor = migrator.apply(deserializer);
+        let config = Config {
+            data: cursor.int()?,
+            name: cursor.str()?,
+            cool: cursor.boolean()?,
+            arr: cursor.array()?,
+            nested: cursor.int()?,
+        };
+        cursor.finish()?;
+        Ok(config)
+    }
+
+    fn migrator() -> Migrator {
+        Migrator::for_version(7)
+            .default_bool(2, true)
+            .default_array(3, vec![PropertyValue::Integer(5)])
+    }
+
+    fn written(value: &impl IntoFormat, version: u8) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        value.serialize(&mut serializer);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, version);
+        buffer
+    }
+
+    #[test]
+    pub fn old_versions_get_defaults() {
+        let old = ConfigV7 {
+            data: 4,
+            name: "Nice".to_owned(),
+            nested: 9,
+        };
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&written(&old, 7), 7).unwrap();
+        assert_eq!(
+            decode(&migrator(), &mut deserializer),
+            Ok(Config {
+                data: 4,
+                name: "Nice".to_owned(),
+                cool: true,
+                arr: vec![PropertyValue::Integer(5)],
+                nested: 9,
+            })
+        );
+
+        // without the migrator the reads shift and run out
+        deserializer.read_bytes(&written(&old, 7), 7).unwrap();
+        assert_eq!(
+            decode(&Migrator::for_version(6), &mut deserializer),
+            Err(DecodeError::MissingField {
+                index: 2,
+                kind: PropertyType::Bool
+            })
+        );
+    }
+
+    #[test]
+    pub fn current_versions_are_untouched() {
+        let current = Config {
+            data: 1,
+            name: "new".to_owned(),
+            cool: false,
+            arr: Vec::new(),
+            nested: 2,
+        };
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&written(&current, 8), 8).unwrap();
+        assert_eq!(decode(&migrator(), &mut deserializer), Ok(current));
+    }
+
+    #[test]
+    pub fn default_of_the_wrong_kind() {
+        let old = ConfigV7 {
+            data: 4,
+            name: "Nice".to_owned(),
+            nested: 9,
+        };
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&written(&old, 7), 7).unwrap();
+        let migrator = migrator().default_int(2, 1);
+        assert_eq!(
+            decode(&migrator, &mut deserializer),
+            Err(DecodeError::MissingField {
+                index: 2,
+                kind: PropertyType::Bool
+            })
+        );
+    }
+}
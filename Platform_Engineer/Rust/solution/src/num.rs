@@ -0,0 +1,147 @@
+use std::fmt;
+
+use crate::serializer::{Deserializer, Serializer};
+
+// Every integer lands in the i64 pool, these let callers write their own widths without an `as`
+// cast that would silently wrap u64s above i64::MAX (or narrow an i64 on the way back out).
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+pub trait IntoI64Checked: sealed::Sealed + Copy {
+    fn into_i64(self) -> Option<i64>;
+    fn from_i64(value: i64) -> Option<Self>;
+    // lossless for every implementor, only used for errors
+    fn widen(self) -> i128;
+}
+
+macro_rules! checked_num {
+    ($($ty:ty),+) => {
+        $(
+            impl sealed::Sealed for $ty {}
+
+            impl IntoI64Checked for $ty {
+                fn into_i64(self) -> Option<i64> {
+                    i64::try_from(self).ok()
+                }
+
+                fn from_i64(value: i64) -> Option<Self> {
+                    <$ty>::try_from(value).ok()
+                }
+
+                fn widen(self) -> i128 {
+                    self as i128
+                }
+            }
+        )+
+    };
+}
+
+checked_num!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NumError {
+    // `value` doesn't fit in `target`
+    OutOfRange { value: i128, target: &'static str },
+    // the integer pool was already empty
+    Missing,
+}
+
+impl fmt::Display for NumError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NumError::OutOfRange { value, target } => write!(f, "{value} doesn't fit in {target}"),
+            NumError::Missing => write!(f, "no integers left"),
+        }
+    }
+}
+
+impl std::error::Error for NumError {}
+
+fn out_of_range<T>(value: impl IntoI64Checked) -> NumError {
+    NumError::OutOfRange {
+        value: value.widen(),
+        target: std::any::type_name::<T>(),
+    }
+}
+
+impl Serializer<'_> {
+    // nothing is written if `value` doesn't fit in an i64
+    pub fn write_num<T: IntoI64Checked>(&mut self, value: T) -> Result<(), NumError> {
+        let int = value.into_i64().ok_or_else(|| out_of_range::<i64>(value))?;
+        self.write_int(int);
+        Ok(())
+    }
+}
+
+impl Deserializer {
+    // the value is still consumed if it doesn't fit in `T`
+    pub fn take_num<T: IntoI64Checked>(&mut self) -> Result<T, NumError> {
+        let int = self.take_int().ok_or(NumError::Missing)?;
+        T::from_i64(int).ok_or_else(|| out_of_range::<T>(int))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn every_width_roundtrips() {
+        let mut serializer = Serializer::new();
+        serializer.write_num(i8::MAX).unwrap();
+        serializer.write_num(i16::MAX).unwrap();
+        serializer.write_num(i32::MAX).unwrap();
+        serializer.write_num(i64::MAX).unwrap();
+        serializer.write_num(u8::MAX).unwrap();
+        serializer.write_num(u16::MAX).unwrap();
+        serializer.write_num(u32::MAX).unwrap();
+        serializer.write_num(i64::MAX as u64).unwrap();
+        serializer.write_num(1234usize).unwrap();
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_num::<i8>(), Ok(i8::MAX));
+        assert_eq!(deserializer.take_num::<i16>(), Ok(i16::MAX));
+        assert_eq!(deserializer.take_num::<i32>(), Ok(i32::MAX));
+        assert_eq!(deserializer.take_num::<i64>(), Ok(i64::MAX));
+        assert_eq!(deserializer.take_num::<u8>(), Ok(u8::MAX));
+        assert_eq!(deserializer.take_num::<u16>(), Ok(u16::MAX));
+        assert_eq!(deserializer.take_num::<u32>(), Ok(u32::MAX));
+        assert_eq!(deserializer.take_num::<u64>(), Ok(i64::MAX as u64));
+        assert_eq!(deserializer.take_num::<usize>(), Ok(1234));
+        assert_eq!(deserializer.take_num::<u8>(), Err(NumError::Missing));
+    }
+
+    #[test]
+    pub fn overflow_is_rejected() {
+        let mut serializer = Serializer::new();
+        assert_eq!(
+            serializer.write_num(u64::MAX),
+            Err(NumError::OutOfRange {
+                value: u64::MAX as i128,
+                target: "i64"
+            })
+        );
+        assert!(serializer.write_num(i64::MAX as usize + 1).is_err());
+        serializer.write_num(300u16).unwrap();
+        serializer.write_num(u32::MAX).unwrap();
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        assert_eq!(stats.integers.values, 2);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_num::<u8>(),
+            Err(NumError::OutOfRange {
+                value: 300,
+                target: "u8"
+            })
+        );
+        assert!(deserializer.take_num::<i32>().is_err());
+    }
+}
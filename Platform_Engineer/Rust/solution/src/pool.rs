@@ -0,0 +1,159 @@
+use std::cell::RefCell;
+
+use crate::serializer::{IntoFormat, Serializer};
+
+// Per-thread stash of serializers so the convenience functions get the same allocation reuse as
+// holding onto a `Serializer` and calling `reuse()` by hand.
+//
+// Kept small, nested `with_serializer` calls are the only way to have more than one checked out.
+const MAX_POOLED: usize = 4;
+
+thread_local! {
+    static POOL: RefCell<Vec<Serializer<'static>>> = const { RefCell::new(Vec::new()) };
+}
+
+// Checks a cleared serializer out of the thread's pool for the duration of `f`.
+//
+// The serializer is only returned if `f` completes, if it panics the serializer is dropped along
+// with whatever half-written state it had.
+pub fn with_serializer<'a, R>(f: impl FnOnce(&mut Serializer<'a>) -> R) -> R {
+    let pooled = POOL
+        .with(|pool| pool.borrow_mut().pop())
+        .unwrap_or_default();
+    let mut serializer = pooled.reuse();
+
+    let result = f(&mut serializer);
+
+    let serializer = serializer.reuse();
+    POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        if pool.len() < MAX_POOLED {
+            pool.push(serializer);
+        }
+    });
+    result
+}
+
+pub fn to_bytes<T: IntoFormat>(value: &T, version: u8) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    to_bytes_into(value, version, &mut buffer);
+    buffer
+}
+
+// `buffer` is cleared first, so passing the same one in each time avoids allocating entirely
+pub fn to_bytes_into<T: IntoFormat>(value: &T, version: u8, buffer: &mut Vec<u8>) {
+    with_serializer(|serializer| {
+        serializer.set_format_id(T::format_id());
+        value.serialize(serializer);
+        serializer.finish(buffer, version);
+    });
+}
+
+// `to_bytes` for a top-level value that may not be there, `None` is written with
+// `Serializer::write_absent` and read back by `IntoFormat::deserialize_optional`
+pub fn optional_to_bytes<T: IntoFormat>(value: Option<&T>, version: u8) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    with_serializer(|serializer| {
+        serializer.set_format_id(T::format_id());
+        match value {
+            Some(value) => value.serialize(serializer),
+            None => serializer.write_absent(),
+        }
+        serializer.finish(&mut buffer, version);
+    });
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc_counter,
+        serializer::{Deserializer, PropertyValue},
+    };
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Entry {
+        id: i64,
+        name: String,
+        enabled: bool,
+        tags: Vec<PropertyValue>,
+    }
+
+    impl IntoFormat for Entry {
+        fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+            serializer.write_int(self.id);
+            serializer.write_string(&self.name);
+            serializer.write_bool(self.enabled);
+            serializer.write_array(&self.tags);
+        }
+
+        fn take(deserializer: &mut Deserializer) -> Option<Self> {
+            Some(Entry {
+                id: deserializer.take_int()?,
+                name: deserializer.take_string()?,
+                enabled: deserializer.take_bool()?,
+                tags: deserializer.take_array()?,
+            })
+        }
+    }
+
+    fn entry() -> Entry {
+        Entry {
+            id: 12,
+            name: "primary".to_owned(),
+            enabled: true,
+            tags: vec![
+                PropertyValue::String("eu-west".to_owned()),
+                PropertyValue::Integer(3),
+            ],
+        }
+    }
+
+    #[test]
+    pub fn pooled_serializers_are_reused() {
+        let entry = entry();
+        let mut buffer = Vec::new();
+        to_bytes_into(&entry, 0, &mut buffer);
+
+        let allocations = alloc_counter::count(|| {
+            for _ in 0..1000 {
+                to_bytes_into(&entry, 0, &mut buffer);
+            }
+        });
+        assert_eq!(allocations, 0);
+
+        // only the returned buffer itself (and its growth) is allocated
+        let allocations = alloc_counter::count(|| {
+            for _ in 0..1000 {
+                std::hint::black_box(to_bytes(&entry, 0));
+            }
+        });
+        assert!(allocations <= 4 * 1000, "{allocations} allocations");
+
+        let mut deserializer = Deserializer::new();
+        let decoded = Entry::deserialize(&to_bytes(&entry, 0), &mut deserializer, 0);
+        assert_eq!(decoded, Some(entry));
+    }
+
+    #[test]
+    pub fn panicking_serializer_is_not_returned() {
+        POOL.with(|pool| pool.borrow_mut().clear());
+
+        let result = std::panic::catch_unwind(|| {
+            with_serializer(|serializer| {
+                serializer.write_int(1);
+                panic!("mid-serialize");
+            })
+        });
+        assert!(result.is_err());
+        assert_eq!(POOL.with(|pool| pool.borrow().len()), 0);
+
+        with_serializer(|serializer| {
+            let mut buffer = Vec::new();
+            serializer.finish(&mut buffer, 0);
+            assert_eq!(serializer.constant_integer(), None);
+        });
+        assert_eq!(POOL.with(|pool| pool.borrow().len()), 1);
+    }
+}
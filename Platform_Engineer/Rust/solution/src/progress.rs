@@ -0,0 +1,80 @@
+use std::{fmt, ops::ControlFlow};
+
+// `Deserializer::set_progress`, called with (columns done, bits consumed, bits in the buffer)
+pub(crate) type ProgressHook = Box<dyn FnMut(usize, u64, u64) -> ControlFlow<()> + Send>;
+
+// values read within a column between calls to the hook
+pub(crate) const PROGRESS_INTERVAL: u64 = 4096;
+
+// Where the deserializer has got to in the buffer it's reading, for the hook. Without a hook every
+// call is a branch on the `Option` and nothing else.
+#[derive(Default)]
+pub(crate) struct Progress {
+    hook: Option<ProgressHook>,
+    columns_done: usize,
+    total_bits: u64,
+    // values since the hook was last called
+    values: u64,
+    // the hook returned `Break` during the last read
+    cancelled: bool,
+}
+
+impl Progress {
+    pub fn set(&mut self, hook: Option<ProgressHook>) {
+        self.hook = hook;
+    }
+
+    pub fn start(&mut self, total_bits: u64) {
+        self.columns_done = 0;
+        self.total_bits = total_bits;
+        self.values = 0;
+        self.cancelled = false;
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    // `None` once the hook cancels, which the column readers pass on like any other failure
+    #[inline]
+    pub fn values(&mut self, count: u64, bits: u64) -> Option<()> {
+        if self.hook.is_none() {
+            return Some(());
+        }
+        self.values += count;
+        if self.values < PROGRESS_INTERVAL {
+            return Some(());
+        }
+        self.values = 0;
+        self.report(bits)
+    }
+
+    pub fn column_done(&mut self, bits: u64) -> Option<()> {
+        if self.hook.is_none() {
+            return Some(());
+        }
+        self.columns_done += 1;
+        self.values = 0;
+        self.report(bits)
+    }
+
+    fn report(&mut self, bits: u64) -> Option<()> {
+        let hook = self.hook.as_mut()?;
+        match hook(self.columns_done, bits, self.total_bits) {
+            ControlFlow::Continue(()) => Some(()),
+            ControlFlow::Break(()) => {
+                self.cancelled = true;
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("hook", &self.hook.is_some())
+            .field("columns_done", &self.columns_done)
+            .finish()
+    }
+}
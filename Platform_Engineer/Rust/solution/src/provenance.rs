@@ -0,0 +1,157 @@
+use std::fmt;
+
+use crate::{
+    bit_packer::{BitPacker, BitUnpacker},
+    format_spec::MAX_PRODUCER_LEN,
+};
+
+// Who wrote a buffer and when, for tracking down stale configs in the field. Lives in its own
+// trailer after the field names (behind a header flag) rather than in the pools, so it never
+// shifts a reader's fields and decoders that don't care never see it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Provenance {
+    // seconds since the unix epoch
+    pub timestamp: u64,
+    pub producer: String,
+}
+
+impl fmt::Display for Provenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "written at {} by {}", self.timestamp, self.producer)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProducerTooLong {
+    pub len: usize,
+}
+
+impl fmt::Display for ProducerTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "producer id is {} bytes, at most {MAX_PRODUCER_LEN} are allowed",
+            self.len
+        )
+    }
+}
+
+impl std::error::Error for ProducerTooLong {}
+
+impl Provenance {
+    pub(crate) fn new(timestamp: u64, producer: &str) -> Result<Self, ProducerTooLong> {
+        if producer.len() > MAX_PRODUCER_LEN {
+            return Err(ProducerTooLong {
+                len: producer.len(),
+            });
+        }
+        Ok(Provenance {
+            timestamp,
+            producer: producer.to_owned(),
+        })
+    }
+
+    pub(crate) fn write(&self, packer: &mut BitPacker) {
+        packer.write_small_int(self.timestamp);
+        packer.write_unicode_huffman_string(&self.producer);
+    }
+
+    // an oversized producer means the buffer wasn't written by `Serializer::set_provenance`
+    pub(crate) fn read(unpacker: &mut BitUnpacker, scratch: &mut Vec<u8>) -> Option<Self> {
+        let timestamp = unpacker.read_small_int()?;
+        scratch.clear();
+        unpacker.read_unicode_huffman_into(scratch)?;
+        let producer = String::from_utf8_lossy(scratch);
+        Provenance::new(timestamp, &producer).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        error::{DecodeError, Section},
+        format_spec::{FORMAT_SPEC, OPTION_PROVENANCE},
+        serializer::{Deserializer, Serializer, verify},
+    };
+
+    fn written(provenance: Option<(u64, &str)>) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        if let Some((timestamp, producer)) = provenance {
+            serializer.set_provenance(timestamp, producer).unwrap();
+        }
+        serializer.write_int(4);
+        serializer.write_string("Nice");
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        buffer
+    }
+
+    #[test]
+    pub fn provenance_roundtrips_beside_the_data() {
+        let buffer = written(Some((1_760_000_000, "config-service@eu-1")));
+        verify(&buffer, 0).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.provenance(),
+            Some(&Provenance {
+                timestamp: 1_760_000_000,
+                producer: "config-service@eu-1".to_owned()
+            })
+        );
+        // the fields are where they'd be without it
+        assert_eq!(deserializer.take_int(), Some(4));
+        assert_eq!(deserializer.take_string().as_deref(), Some("Nice"));
+        assert!(deserializer.remaining_counts().is_empty());
+    }
+
+    #[test]
+    pub fn absent_provenance() {
+        let plain = written(None);
+        verify(&plain, 0).unwrap();
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&plain, 0).unwrap();
+        assert_eq!(deserializer.provenance(), None);
+        // nothing past its option bit is written
+        assert!(plain.len() < written(Some((1_760_000_000, "config-service"))).len());
+        assert!(
+            FORMAT_SPEC
+                .options
+                .contains(&("provenance", OPTION_PROVENANCE))
+        );
+    }
+
+    #[test]
+    pub fn oversized_producers_are_rejected() {
+        let producer = "x".repeat(MAX_PRODUCER_LEN + 1);
+        let mut serializer = Serializer::new();
+        assert_eq!(
+            serializer.set_provenance(1, &producer),
+            Err(ProducerTooLong {
+                len: MAX_PRODUCER_LEN + 1
+            })
+        );
+        serializer.set_provenance(1, &producer[1..]).unwrap();
+
+        // one written by hand past the limit doesn't decode
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        Provenance {
+            timestamp: 1,
+            producer,
+        }
+        .write(&mut packer);
+        let mut unpacker = BitUnpacker::new(&buffer);
+        assert_eq!(Provenance::read(&mut unpacker, &mut Vec::new()), None);
+
+        // and truncating one fails `verify` in the trailer
+        let mut buffer = written(Some((1, "short")));
+        buffer.truncate(buffer.len() - 2);
+        assert_eq!(
+            verify(&buffer, 0),
+            Err(DecodeError::Truncated(Section::Provenance))
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use std::fmt;
+
+use crate::serializer::{Deserializer, PropertyType, PropertyValue, Serializer};
+
+// Arrays of maps that all share the same keys and value types (rows of a table, lists of
+// objects from json) repeat every key and tag per element. A record array writes that shape once
+// as a template and then each field's values back to back without any tags, so it's the same
+// trade the pools make for struct fields but for dynamically typed values.
+//
+// Records use the same map representation as `PropertyValue::from_json`, an array of
+// `[key, value]` pairs, and must list their keys in template order.
+//
+// The same `Schema` describes a whole buffer's top level values when it's written with
+// `Serializer::with_schema`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Schema {
+    pub fields: Vec<(String, PropertyType)>,
+}
+
+impl Schema {
+    pub fn new<K: Into<String>>(fields: impl IntoIterator<Item = (K, PropertyType)>) -> Self {
+        Schema {
+            fields: fields
+                .into_iter()
+                .map(|(key, kind)| (key.into(), kind))
+                .collect(),
+        }
+    }
+
+    // the shape of a single record, `None` if it isn't a map
+    pub fn infer(record: &PropertyValue) -> Option<Self> {
+        let PropertyValue::Array(pairs) = record else {
+            return None;
+        };
+        let fields = pairs
+            .iter()
+            .map(|pair| map_entry(pair).map(|(key, value)| (key.to_owned(), value.kind())))
+            .collect::<Option<_>>()?;
+        Some(Schema { fields })
+    }
+
+    fn check(&self, index: usize, record: &PropertyValue) -> Result<(), RecordError> {
+        let PropertyValue::Array(pairs) = record else {
+            return Err(RecordError::NotAMap { record: index });
+        };
+        if pairs.len() != self.fields.len() {
+            return Err(RecordError::FieldCount {
+                record: index,
+                expected: self.fields.len(),
+                found: pairs.len(),
+            });
+        }
+        for (field, ((expected_key, expected_kind), pair)) in
+            self.fields.iter().zip(pairs).enumerate()
+        {
+            let (key, value) = map_entry(pair).ok_or(RecordError::NotAMap { record: index })?;
+            if key != expected_key {
+                return Err(RecordError::Key {
+                    record: index,
+                    field,
+                    expected: expected_key.clone(),
+                    found: key.to_owned(),
+                });
+            }
+            if value.kind() != *expected_kind {
+                return Err(RecordError::Kind {
+                    record: index,
+                    field,
+                    expected: *expected_kind,
+                    found: value.kind(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn map_entry(pair: &PropertyValue) -> Option<(&str, &PropertyValue)> {
+    match pair {
+        PropertyValue::Array(pair) => match pair.as_slice() {
+            [PropertyValue::String(key), value] => Some((key.as_str(), value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordError {
+    // not an array of `[key, value]` pairs
+    NotAMap {
+        record: usize,
+    },
+    FieldCount {
+        record: usize,
+        expected: usize,
+        found: usize,
+    },
+    Key {
+        record: usize,
+        field: usize,
+        expected: String,
+        found: String,
+    },
+    Kind {
+        record: usize,
+        field: usize,
+        expected: PropertyType,
+        found: PropertyType,
+    },
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::NotAMap { record } => {
+                write!(f, "record {record} is not an array of [key, value] pairs")
+            }
+            RecordError::FieldCount {
+                record,
+                expected,
+                found,
+            } => write!(
+                f,
+                "record {record} has {found} fields but the template has {expected}"
+            ),
+            RecordError::Key {
+                record,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "record {record} field {field} is {found:?} but the template expects {expected:?}"
+            ),
+            RecordError::Kind {
+                record,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "record {record} field {field} is {found:?} but the template expects {expected:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+impl<'a> Serializer<'a> {
+    // Layout: field count, each key and its type, record count, then every record's value for the
+    // first field, every record's value for the second, etc. Values are untagged since the
+    // template already has their type.
+    //
+    // Every record is checked before anything is written so a mismatch doesn't leave half an array
+    // in the pools.
+    pub fn write_record_array(
+        &mut self,
+        template: &Schema,
+        records: &'a [PropertyValue],
+    ) -> Result<(), RecordError> {
+        for (index, record) in records.iter().enumerate() {
+            template.check(index, record)?;
+        }
+
+        self.write_length(template.fields.len());
+        for (key, kind) in &template.fields {
+            self.write_string_owned(key.clone());
+            self.write_property_type(*kind);
+        }
+
+        self.write_length(records.len());
+        for field in 0..template.fields.len() {
+            for record in records {
+                let PropertyValue::Array(pairs) = record else {
+                    unreachable!("checked against the template");
+                };
+                let (_, value) = map_entry(&pairs[field]).expect("checked against the template");
+                match value {
+                    PropertyValue::String(string) => self.write_string(string.as_str()),
+                    PropertyValue::Bool(bool) => self.write_bool(*bool),
+                    PropertyValue::Integer(int) => self.write_int(*int),
+                    PropertyValue::Array(values) => self.write_array(values.as_slice()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Deserializer {
+    // counterpart to `Serializer::write_record_array`, the template is read back out of the buffer
+    pub fn take_record_array(&mut self) -> Option<(Schema, Vec<PropertyValue>)> {
+        let fields = self.take_length()?;
+        let mut template = Schema::default();
+        for _ in 0..fields {
+            let key = self.take_string()?;
+            let kind = self.take_property_type()?;
+            template.fields.push((key, kind));
+        }
+
+        let count = usize::try_from(self.take_length()?).ok()?;
+        let mut columns = Vec::with_capacity(template.fields.len());
+        for (_, kind) in &template.fields {
+            let mut column = Vec::new();
+            for _ in 0..count {
+                column.push(match kind {
+                    PropertyType::String => PropertyValue::String(self.take_string()?),
+                    PropertyType::Bool => PropertyValue::Bool(self.take_bool()?),
+                    PropertyType::Integer => PropertyValue::Integer(self.take_int()?),
+                    PropertyType::Array => PropertyValue::Array(self.take_array()?),
+                });
+            }
+            columns.push(column.into_iter());
+        }
+
+        // nothing bounds the count of records without any fields, don't abort on a corrupt one
+        let mut records = Vec::new();
+        records.try_reserve(count).ok()?;
+        for _ in 0..count {
+            let pairs = template
+                .fields
+                .iter()
+                .zip(&mut columns)
+                .map(|((key, _), column)| {
+                    PropertyValue::Array(vec![
+                        PropertyValue::String(key.clone()),
+                        column.next().expect("every column has `count` values"),
+                    ])
+                })
+                .collect();
+            records.push(PropertyValue::Array(pairs));
+        }
+        Some((template, records))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: i64, name: String, active: bool) -> PropertyValue {
+        let pair = |key: &str, value| {
+            PropertyValue::Array(vec![PropertyValue::String(key.to_owned()), value])
+        };
+        PropertyValue::Array(vec![
+            pair("id", PropertyValue::Integer(id)),
+            pair("name", PropertyValue::String(name)),
+            pair("active", PropertyValue::Bool(active)),
+        ])
+    }
+
+    #[test]
+    pub fn records_share_a_template() {
+        let records: Vec<PropertyValue> = (0..50)
+            .map(|i| record(i, format!("user{i}"), i % 3 == 0))
+            .collect();
+        let template = Schema::infer(&records[0]).unwrap();
+        assert_eq!(
+            template,
+            Schema::new([
+                ("id", PropertyType::Integer),
+                ("name", PropertyType::String),
+                ("active", PropertyType::Bool),
+            ])
+        );
+
+        let mut serializer = Serializer::new();
+        serializer.write_record_array(&template, &records).unwrap();
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut serializer = Serializer::new();
+        serializer.write_array(&records);
+        let mut maps = Vec::new();
+        serializer.finish(&mut maps, 0);
+        assert!(
+            buffer.len() * 2 < maps.len(),
+            "{} bytes as records, {} as maps",
+            buffer.len(),
+            maps.len()
+        );
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_record_array(), Some((template, records)));
+    }
+
+    #[test]
+    pub fn mismatched_records_are_rejected() {
+        let template = Schema::infer(&record(1, "a".to_owned(), true)).unwrap();
+        let records = vec![
+            record(1, "a".to_owned(), true),
+            PropertyValue::Array(vec![PropertyValue::Integer(1)]),
+        ];
+        let mut serializer = Serializer::new();
+        assert_eq!(
+            serializer.write_record_array(&template, &records),
+            Err(RecordError::FieldCount {
+                record: 1,
+                expected: 3,
+                found: 1
+            })
+        );
+
+        let mut wrong_kind = record(2, "b".to_owned(), false);
+        if let PropertyValue::Array(pairs) = &mut wrong_kind {
+            pairs[2] = PropertyValue::Array(vec![
+                PropertyValue::String("active".to_owned()),
+                PropertyValue::Integer(0),
+            ]);
+        }
+        let records = vec![wrong_kind];
+        assert_eq!(
+            serializer.write_record_array(&template, &records),
+            Err(RecordError::Kind {
+                record: 0,
+                field: 2,
+                expected: PropertyType::Bool,
+                found: PropertyType::Integer
+            })
+        );
+
+        // nothing was written by either attempt
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        assert_eq!(stats.lengths.values, 0);
+    }
+
+    // labels every value by walking the schema, without knowing the type that wrote it
+    fn generic_decode(bytes: &[u8]) -> Option<Vec<(String, PropertyValue)>> {
+        let mut deserializer = Deserializer::new();
+        let schema = deserializer.read_schema(bytes, 0)?;
+        schema
+            .fields
+            .into_iter()
+            .map(|(name, kind)| {
+                let value = match kind {
+                    PropertyType::String => PropertyValue::String(deserializer.take_string()?),
+                    PropertyType::Bool => PropertyValue::Bool(deserializer.take_bool()?),
+                    PropertyType::Integer => PropertyValue::Integer(deserializer.take_int()?),
+                    PropertyType::Array => PropertyValue::Array(deserializer.take_array()?),
+                };
+                Some((name, value))
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn schema_labels_a_generic_decode() {
+        let schema = Schema::new([
+            ("name", PropertyType::String),
+            ("tags", PropertyType::Array),
+            ("retries", PropertyType::Integer),
+            ("enabled", PropertyType::Bool),
+        ]);
+        let tags = vec![PropertyValue::String("fast".to_owned())];
+
+        let mut serializer = Serializer::with_schema(&schema);
+        serializer.write_string("service");
+        serializer.write_array(&tags);
+        serializer.write_int(3);
+        serializer.write_bool(true);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        assert_eq!(
+            generic_decode(&buffer),
+            Some(vec![
+                (
+                    "name".to_owned(),
+                    PropertyValue::String("service".to_owned())
+                ),
+                ("tags".to_owned(), PropertyValue::Array(tags.clone())),
+                ("retries".to_owned(), PropertyValue::Integer(3)),
+                ("enabled".to_owned(), PropertyValue::Bool(true)),
+            ])
+        );
+
+        // the schema is taken off the front, typed reads see only the data
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.schema(), Some(&schema));
+        assert_eq!(deserializer.take_string().as_deref(), Some("service"));
+
+        let mut serializer = Serializer::new();
+        serializer.write_int(3);
+        let mut plain = Vec::new();
+        serializer.finish(&mut plain, 0);
+        assert_eq!(deserializer.read_schema(&plain, 0), None);
+        assert_eq!(deserializer.take_int(), Some(3));
+    }
+}
@@ -0,0 +1,67 @@
+// An emptied `Vec` on its way to holding another element type with the same layout.
+// `Serializer::reuse` needs this to keep its string and name columns' allocations when it moves
+// to a new lifetime: to the type system `Cow<'a, str>` and `Cow<'b, str>` are different types,
+// even though they're laid out identically.
+//
+// There's no unsafe code in here. `into_vec` collects the empty vec's `into_iter` into the new
+// type, and the standard library does that in place whenever the layouts match. Being empty is
+// what makes this sound: there are no elements to convert, so no value is ever reinterpreted as
+// another type. If the in-place path ever stopped applying, the result would still be correct but
+// would be a fresh allocation, and `capacity_survives_a_new_lifetime` would fail.
+//
+// Miri checks this and the `reuse` tests with
+// `cargo +nightly miri test --lib -- recycled serializer::tests::reuse`.
+
+pub(crate) struct RecycledVec<T>(Vec<T>);
+
+impl<T> RecycledVec<T> {
+    // drops whatever `vec` held, only the allocation is kept
+    pub(crate) fn new(mut vec: Vec<T>) -> Self {
+        vec.clear();
+        RecycledVec(vec)
+    }
+
+    // Only for a `U` the same size and alignment as `T`, which is checked at compile time for each
+    // pair it's used with. Any other pair could never reuse the allocation, so it doesn't compile.
+    pub(crate) fn into_vec<U>(self) -> Vec<U> {
+        const {
+            assert!(size_of::<T>() == size_of::<U>());
+            assert!(align_of::<T>() == align_of::<U>());
+        }
+        self.0
+            .into_iter()
+            .map(|_| unreachable!("recycled vecs are empty"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    #[test]
+    pub fn capacity_survives_a_new_lifetime() {
+        let owned = String::from("owned");
+        let mut strings: Vec<Cow<str>> = Vec::with_capacity(64);
+        strings.push(Cow::Borrowed(&owned));
+        strings.push(Cow::Owned("also owned".to_owned()));
+        let pointer = strings.as_ptr() as usize;
+
+        let strings: Vec<Cow<'static, str>> = RecycledVec::new(strings).into_vec();
+        // nothing borrows from it any more
+        drop(owned);
+        assert!(strings.is_empty());
+        assert_eq!(strings.capacity(), 64);
+        assert_eq!(strings.as_ptr() as usize, pointer);
+    }
+
+    #[test]
+    pub fn empty_vecs_recycle() {
+        let recycled = RecycledVec::new(Vec::<(usize, &str)>::new());
+        let raw_strings = recycled.into_vec::<(usize, &str)>();
+        assert!(raw_strings.is_empty());
+        assert_eq!(raw_strings.capacity(), 0);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, fmt};
+
+use crate::serializer::{Deserializer, IntoFormat, PropertyValue, peek_header};
+
+// Decodes buffers of any registered type by the format id in their header, for services that
+// handle many `IntoFormat` types under one version byte and need to route each buffer somewhere
+// without knowing its type up front.
+
+type Decoder = Box<dyn Fn(&mut Deserializer) -> Option<PropertyValue> + Send + Sync>;
+
+#[derive(Default)]
+pub struct Registry {
+    decoders: HashMap<u16, Decoder>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RegistryError {
+    // the buffer itself couldn't be read, or didn't hold what its type expects
+    Decode,
+    // written without a format id
+    Unidentified,
+    UnknownFormat(u16),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegistryError::Decode => write!(f, "failed to decode the buffer"),
+            RegistryError::Unidentified => write!(f, "buffer has no format id"),
+            RegistryError::UnknownFormat(id) => write!(f, "no decoder registered for format {id}"),
+        }
+    }
+}
+
+impl std::error::Error for RegistryError {}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `into_value` turns the decoded `T` into the tree handed back by `decode`.
+    //
+    // Panics if `T` has no format id or another type already registered it, both are mistakes in
+    // how the types were declared rather than anything about the input.
+    pub fn register<T: IntoFormat>(
+        &mut self,
+        into_value: impl Fn(T) -> PropertyValue + Send + Sync + 'static,
+    ) {
+        let id = T::format_id();
+        assert_ne!(id, 0, "{} has no format id", std::any::type_name::<T>());
+        let decoder: Decoder = Box::new(move |deserializer| T::take(deserializer).map(&into_value));
+        let previous = self.decoders.insert(id, decoder);
+        assert!(previous.is_none(), "format id {id} registered twice");
+    }
+
+    // returns the buffer's format id along with its value
+    pub fn decode(
+        &self,
+        bytes: &[u8],
+        version: u8,
+        deserializer: &mut Deserializer,
+    ) -> Result<(u16, PropertyValue), RegistryError> {
+        // Routed by the header alone, a buffer nothing is registered for isn't decoded. Either way
+        // the last buffer's values aren't left in the way of the next one.
+        let Ok(header) = peek_header(bytes) else {
+            deserializer.clear();
+            return Err(RegistryError::Decode);
+        };
+        let id = header.format_id;
+        let Some(decoder) = self.decoders.get(&id) else {
+            deserializer.clear();
+            return Err(match id {
+                0 => RegistryError::Unidentified,
+                id => RegistryError::UnknownFormat(id),
+            });
+        };
+        deserializer
+            .read_bytes(bytes, version)
+            .ok_or(RegistryError::Decode)?;
+        let value = decoder(deserializer).ok_or(RegistryError::Decode)?;
+        Ok((id, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{derive_format, pool::to_bytes};
+
+    derive_format! {
+        #![format_id = 1]
+        #[derive(Debug, PartialEq, Eq)]
+        struct User {
+            id: i64,
+            name: String,
+        }
+    }
+
+    derive_format! {
+        #![format_id = 2]
+        #[derive(Debug, PartialEq, Eq)]
+        struct Flag {
+            enabled: bool,
+        }
+    }
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Anonymous {
+            value: i64,
+        }
+    }
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(|user: User| {
+            PropertyValue::Array(vec![
+                PropertyValue::Integer(user.id),
+                PropertyValue::String(user.name),
+            ])
+        });
+        registry.register(|flag: Flag| PropertyValue::Bool(flag.enabled));
+        registry
+    }
+
+    #[test]
+    pub fn buffers_route_by_format_id() {
+        let registry = registry();
+        let mut deserializer = Deserializer::new();
+
+        let user = to_bytes(
+            &User {
+                id: 7,
+                name: "ada".to_owned(),
+            },
+            0,
+        );
+        assert_eq!(
+            registry.decode(&user, 0, &mut deserializer),
+            Ok((
+                1,
+                PropertyValue::Array(vec![
+                    PropertyValue::Integer(7),
+                    PropertyValue::String("ada".to_owned()),
+                ])
+            ))
+        );
+
+        let flag = to_bytes(&Flag { enabled: true }, 0);
+        assert_eq!(
+            registry.decode(&flag, 0, &mut deserializer),
+            Ok((2, PropertyValue::Bool(true)))
+        );
+
+        let anonymous = to_bytes(&Anonymous { value: 3 }, 0);
+        assert_eq!(
+            registry.decode(&anonymous, 0, &mut deserializer),
+            Err(RegistryError::Unidentified)
+        );
+
+        // a typed decode refuses a buffer of a different type
+        assert_eq!(User::deserialize(&flag, &mut deserializer, 0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "registered twice")]
+    pub fn duplicate_ids_panic() {
+        let mut registry = registry();
+        registry.register(|flag: Flag| PropertyValue::Bool(flag.enabled));
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::{
+    columns::Column,
+    error::ColumnCounts,
+    serializer::{Deserializer, PropertyType},
+};
+
+// What's still queued in each pool, for working out where a reader stopped lining up with the
+// writer. `Deserializer::take_remaining` drains it, `remaining_view` just looks.
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RemainingValues {
+    pub ints: Vec<i64>,
+    pub strings: Vec<String>,
+    pub bools: Vec<bool>,
+    pub tags: Vec<PropertyType>,
+    pub lengths: Vec<u64>,
+}
+
+impl RemainingValues {
+    pub fn is_empty(&self) -> bool {
+        *self == RemainingValues::default()
+    }
+}
+
+// only the columns that have something in them, e.g. `strings ["Nice"], booleans [true]`
+impl fmt::Display for RemainingValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut separator = "";
+        let mut column = |f: &mut fmt::Formatter<'_>, name, values: &dyn fmt::Debug, empty| {
+            if empty {
+                return Ok(());
+            }
+            write!(f, "{separator}{name} {values:?}")?;
+            separator = ", ";
+            Ok(())
+        };
+        column(f, "integers", &self.ints, self.ints.is_empty())?;
+        column(f, "strings", &self.strings, self.strings.is_empty())?;
+        column(f, "booleans", &self.bools, self.bools.is_empty())?;
+        column(f, "property types", &self.tags, self.tags.is_empty())?;
+        column(f, "array lengths", &self.lengths, self.lengths.is_empty())?;
+        if separator.is_empty() {
+            write!(f, "nothing")?;
+        }
+        Ok(())
+    }
+}
+
+pub struct RemainingView<'d> {
+    deserializer: &'d Deserializer,
+}
+
+impl<'d> RemainingView<'d> {
+    pub fn ints(&self) -> impl Iterator<Item = i64> + 'd {
+        self.deserializer.integers.iter().copied()
+    }
+
+    pub fn strings(&self) -> impl Iterator<Item = &'d str> + 'd {
+        let deserializer = self.deserializer;
+        (0..deserializer.pooled_strings()).map_while(|index| deserializer.string_at(index))
+    }
+
+    pub fn bools(&self) -> impl Iterator<Item = bool> + 'd {
+        self.deserializer.booleans.iter().copied()
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = PropertyType> + 'd {
+        self.deserializer.property_types.iter().copied()
+    }
+
+    pub fn lengths(&self) -> impl Iterator<Item = u64> + 'd {
+        self.deserializer.lengths.iter().copied()
+    }
+
+    pub fn counts(&self) -> ColumnCounts {
+        self.deserializer.remaining_counts()
+    }
+
+    // the next `limit` values of each column, copied out
+    pub fn preview(&self, limit: usize) -> RemainingValues {
+        RemainingValues {
+            ints: self.ints().take(limit).collect(),
+            strings: self.strings().take(limit).map(str::to_owned).collect(),
+            bools: self.bools().take(limit).collect(),
+            tags: self.tags().take(limit).collect(),
+            lengths: self.lengths().take(limit).collect(),
+        }
+    }
+}
+
+impl Deserializer {
+    pub fn take_remaining(&mut self) -> RemainingValues {
+        #[allow(clippy::useless_conversion)]
+        fn drain<T, U: From<T>>(pool: &mut Column<T>) -> Vec<U> {
+            pool.drain().map(U::from).collect()
+        }
+        // everything goes regardless of order, so there's nothing for kind markers to check
+        self.kinds.clear();
+        // in the order they were written, raw strings are kept apart from the rest
+        let strings = std::iter::from_fn(|| self.take_string()).collect();
+        RemainingValues {
+            ints: drain(&mut self.integers),
+            strings,
+            bools: drain(&mut self.booleans),
+            tags: drain(&mut self.property_types),
+            lengths: drain(&mut self.lengths),
+        }
+    }
+
+    pub fn remaining_view(&self) -> RemainingView<'_> {
+        RemainingView { deserializer: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::{PropertyValue, Serializer};
+
+    #[test]
+    pub fn skipped_field_is_left_over() {
+        let array = vec![PropertyValue::Integer(5)];
+        let mut serializer = Serializer::new();
+        serializer.write_int(4);
+        serializer.write_bool(true);
+        serializer.write_string("Nice");
+        serializer.write_array(&array);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        deserializer.take_int().unwrap();
+        deserializer.take_string().unwrap();
+
+        let view = deserializer.remaining_view();
+        assert_eq!(view.bools().collect::<Vec<_>>(), [true]);
+        assert_eq!(
+            view.counts(),
+            ColumnCounts {
+                booleans: 1,
+                integers: 1,
+                property_types: 1,
+                lengths: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            view.preview(8).to_string(),
+            "integers [5], booleans [true], property types [Integer], array lengths [1]"
+        );
+
+        let remaining = deserializer.take_remaining();
+        assert_eq!(remaining.bools, [true]);
+        assert_eq!(remaining.ints, [5]);
+        assert!(deserializer.take_remaining().is_empty());
+        assert_eq!(
+            deserializer.remaining_view().preview(8).to_string(),
+            "nothing"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use std::fmt;
+
+use crate::{
+    error::{ColumnCounts, DecodeError},
+    pool::to_bytes,
+    serializer::{Deserializer, IntoFormat, verify},
+};
+
+// For downstream test suites, checks an `IntoFormat` impl agrees with itself:
+//
+// #[test]
+// fn config_roundtrips() {
+//     assert_roundtrip(&Config::default());
+// }
+//
+// and `assert_bytes_eq` for encodings that have to match another one bit for bit.
+
+const VERSION: u8 = 0;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RoundtripError {
+    // what `serialize` wrote doesn't pass `verify`
+    Invalid(DecodeError),
+    // `take` gave up, with what was left in each column when it did
+    Take { remaining: ColumnCounts },
+    // `take` succeeded without reading everything `serialize` wrote
+    Leftover { remaining: ColumnCounts },
+    // both are `Debug` output
+    Mismatch { expected: String, decoded: String },
+    // the decoded value serializes to different bytes, starting at `offset`
+    NonCanonical { offset: usize },
+}
+
+impl fmt::Display for RoundtripError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundtripError::Invalid(err) => write!(f, "serialized buffer is invalid: {err}"),
+            RoundtripError::Take { remaining } => {
+                write!(f, "take failed with {remaining} left")
+            }
+            RoundtripError::Leftover { remaining } => {
+                write!(f, "take left {remaining} unread")
+            }
+            RoundtripError::Mismatch { expected, decoded } => {
+                write!(f, "decoded {decoded} but wrote {expected}")
+            }
+            RoundtripError::NonCanonical { offset } => write!(
+                f,
+                "decoded value serializes differently from byte {offset} on"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RoundtripError {}
+
+// Serializes `value`, verifies and decodes the buffer, requires every value to be read and the
+// result to equal `value`. With `canonical` the decoded value is serialized again and has to give
+// the same bytes, which catches impls that write something `take` throws away.
+pub fn check_roundtrip<T>(value: &T, canonical: bool) -> Result<(), RoundtripError>
+where
+    T: IntoFormat + PartialEq + fmt::Debug,
+{
+    let buffer = to_bytes(value, VERSION);
+    verify(&buffer, VERSION).map_err(RoundtripError::Invalid)?;
+
+    let mut deserializer = Deserializer::new();
+    let decoded = T::deserialize(&buffer, &mut deserializer, VERSION).ok_or_else(|| {
+        RoundtripError::Take {
+            remaining: deserializer.remaining_counts(),
+        }
+    })?;
+    let remaining = deserializer.remaining_counts();
+    if !remaining.is_empty() {
+        return Err(RoundtripError::Leftover { remaining });
+    }
+    if decoded != *value {
+        return Err(RoundtripError::Mismatch {
+            expected: format!("{value:?}"),
+            decoded: format!("{decoded:?}"),
+        });
+    }
+
+    if canonical {
+        let again = to_bytes(&decoded, VERSION);
+        if let Some(offset) = first_difference(&again, &buffer) {
+            return Err(RoundtripError::NonCanonical { offset });
+        }
+    }
+    Ok(())
+}
+
+// `check_roundtrip` including the canonical check, panicking with which stage failed
+#[track_caller]
+pub fn assert_roundtrip<T>(value: &T)
+where
+    T: IntoFormat + PartialEq + fmt::Debug,
+{
+    if let Err(err) = check_roundtrip(value, true) {
+        panic!("{value:?} didn't roundtrip: {err}");
+    }
+}
+
+// the first byte that differs, or the length of the shorter one if it's the start of the other
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(a, b)| a != b) {
+        Some(offset) => Some(offset),
+        None => (a.len() != b.len()).then(|| a.len().min(b.len())),
+    }
+}
+
+// bytes either side of the first difference shown in a failed `assert_bytes_eq`
+const CONTEXT_BYTES: usize = 4;
+
+fn hex_around(bytes: &[u8], offset: usize) -> String {
+    let start = offset.saturating_sub(CONTEXT_BYTES);
+    let end = bytes.len().min(offset + CONTEXT_BYTES + 1);
+    let mut hex = match start {
+        0 => String::new(),
+        _ => "..".to_owned(),
+    };
+    for (index, byte) in bytes.iter().enumerate().take(end).skip(start) {
+        match index == offset {
+            true => hex += &format!(" [{byte:02x}]"),
+            false => hex += &format!(" {byte:02x}"),
+        }
+    }
+    if end < bytes.len() {
+        hex += " ..";
+    }
+    hex
+}
+
+// `assert_eq!` for encoded buffers, failing with the first byte and bit (most significant first,
+// the order `BitPacker` writes them in) that differ and the bytes around them rather than both
+// buffers in full.
+#[track_caller]
+pub fn assert_bytes_eq(actual: &[u8], expected: &[u8]) {
+    let Some(offset) = first_difference(actual, expected) else {
+        return;
+    };
+    let (Some(left), Some(right)) = (actual.get(offset), expected.get(offset)) else {
+        panic!(
+            "buffers are {} and {} bytes, equal up to the end of the shorter one\n  \
+             actual:  {}\n  expected: {}",
+            actual.len(),
+            expected.len(),
+            hex_around(actual, offset),
+            hex_around(expected, offset)
+        );
+    };
+    let bit = (left ^ right).leading_zeros() as usize;
+    panic!(
+        "buffers differ at byte {offset} bit {bit} (bit {} overall), {left:08b} against \
+         {right:08b}\n  actual:   {}\n  expected: {}",
+        offset * 8 + bit,
+        hex_around(actual, offset),
+        hex_around(expected, offset)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::{PropertyValue, Serializer};
+
+    #[derive(Debug, PartialEq)]
+    struct Sloppy {
+        id: i64,
+        // written twice, read once
+        name: String,
+    }
+
+    impl IntoFormat for Sloppy {
+        fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+            serializer.write_int(self.id);
+            serializer.write_string(&self.name);
+            serializer.write_string(&self.name);
+        }
+
+        fn take(deserializer: &mut Deserializer) -> Option<Self> {
+            Some(Sloppy {
+                id: deserializer.take_int()?,
+                name: deserializer.take_string()?,
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Misread {
+        id: i64,
+    }
+
+    impl IntoFormat for Misread {
+        fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+            serializer.write_int(self.id);
+        }
+
+        fn take(deserializer: &mut Deserializer) -> Option<Self> {
+            deserializer.take_bool()?;
+            None
+        }
+    }
+
+    crate::derive_format! {
+        #[derive(Debug, PartialEq)]
+        struct Tagged {
+            id: i64,
+            tags: Vec<PropertyValue>,
+        }
+    }
+
+    #[test]
+    pub fn stages_are_reported() {
+        let tagged = Tagged {
+            id: 3,
+            tags: vec![PropertyValue::String("a".to_owned())],
+        };
+        assert_roundtrip(&tagged);
+
+        let sloppy = Sloppy {
+            id: 1,
+            name: "x".to_owned(),
+        };
+        assert_eq!(
+            check_roundtrip(&sloppy, false),
+            Err(RoundtripError::Leftover {
+                remaining: ColumnCounts {
+                    strings: 1,
+                    ..ColumnCounts::default()
+                }
+            })
+        );
+        assert_eq!(
+            check_roundtrip(&Misread { id: 1 }, true),
+            Err(RoundtripError::Take {
+                remaining: ColumnCounts {
+                    integers: 1,
+                    ..ColumnCounts::default()
+                }
+            })
+        );
+    }
+
+    #[test]
+    pub fn byte_differences_are_located() {
+        assert_bytes_eq(&[1, 2, 3], &[1, 2, 3]);
+        assert_eq!(first_difference(&[1, 2, 3], &[1, 2, 7]), Some(2));
+        assert_eq!(first_difference(&[1, 2], &[1, 2, 3]), Some(2));
+        assert_eq!(first_difference(&[], &[]), None);
+        assert_eq!(
+            hex_around(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10], 5),
+            ".. 01 02 03 04 [05] 06 07 08 09 .."
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "differ at byte 1 bit 5 (bit 13 overall), 00000010 against 00000110")]
+    pub fn assert_bytes_eq_names_the_bit() {
+        assert_bytes_eq(&[0xff, 0b0000_0010], &[0xff, 0b0000_0110]);
+    }
+
+    #[test]
+    #[should_panic(expected = "didn't roundtrip")]
+    pub fn assert_panics_on_failure() {
+        assert_roundtrip(&Sloppy {
+            id: 1,
+            name: "x".to_owned(),
+        });
+    }
+}
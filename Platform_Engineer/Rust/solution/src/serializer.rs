@@ -1,8 +1,78 @@
-use std::{borrow::Cow, collections::VecDeque};
+use std::{
+    borrow::Cow,
+    cell::{RefCell, RefMut},
+    collections::HashMap,
+    fmt,
+    hash::Hasher,
+    ops::ControlFlow,
+};
 
-use crate::bit_packer::{BitPacker, BitUnpacker};
+use crate::{
+    appender::{self, AppendError, TailBudget},
+    bit_packer::{
+        BitPacker, BitUnpacker, ascii_string_adaptive_bits, length_int, small_int_encoded_bits,
+        unicode_huffman_string_bits,
+    },
+    column_split::Columns,
+    columns::{
+        self, Column, FieldName, SectionScratch, Sections, StringFlags, TextColumn, shared_prefix,
+    },
+    cursor::{FieldCursor, LEFTOVER_PREVIEW, ReadCursor},
+    error::{ColumnCounts, DecodeError, Section},
+    format_spec::{
+        self, INT_HEADER_WIDTHS, KNOWN_OPTIONS, OPTION_ABSENT, OPTION_COLUMN_OFFSETS,
+        OPTION_DEDUPLICATED_STRINGS, OPTION_FORMAT_ID, OPTION_INT_TABLE, OPTION_INT_WIDTHS,
+        OPTION_KIND_MARKERS, OPTION_LZ_STRINGS, OPTION_PADDED, OPTION_PROVENANCE,
+        OPTION_RAW_STRINGS, OPTION_SCHEMA, OPTION_SECTION_CHECKSUMS, OPTIONS,
+        SMALL_INT_HEADER_BITS, STRING_SECTION_LEN, TAG_BITS,
+    },
+    int_plan::{self, IntHeaderPlan},
+    limits::{DecodeLimits, DepthExceeded, Limit},
+    pool,
+    progress::Progress,
+    provenance::{ProducerTooLong, Provenance},
+    record::Schema,
+    recycled::RecycledVec,
+    stats::{ColumnCapacities, ColumnStats, DecodeStats, EncodeStats, VerifyReport},
+};
 
-#[derive(Debug, Default)]
+// Layout of the header and columns, bumped whenever `finish` changes what it writes.
+//
+// 1: array lengths moved out of the integer pool into their own column.
+// 2: constant flag on the integer column.
+// 3: front coding flag on the string column.
+// 4: huffman code lengths no longer depend on the order of equal frequencies.
+// 5: optional column offsets table.
+// 6: field names after the columns.
+// 7: format id after the revision.
+// 8: schema flag after the column offsets flag.
+// 9: provenance flag after the schema flag, and its trailer after the field names.
+// 10: raw strings flag after the provenance flag, and their section after the columns.
+// 11: padded flag after the raw strings flag.
+// 12: header counts written with `write_count`, a single bit when the column is empty.
+// 13: the format id, column offsets, schema, provenance, raw strings and padded flags gathered
+//     into an options small int after the revision, see `format_spec::OPTIONS`.
+// 14: frame of reference flag on the integer column after the constant flag, and negative ints
+//     in the full width slot (earlier revisions wrote them truncated to their low bits).
+// 15: property types `TAG_BITS` (3) wide rather than `LEGACY_TAG_BITS` (2), room for more kinds.
+//
+// Buffers from before the revision was written (the bare version byte) aren't readable.
+pub const FORMAT_REVISION: u64 = 15;
+
+// buffers smaller than this `Deserializer::read_bytes_parallel` decodes on the calling thread
+#[cfg(feature = "rayon")]
+pub const PARALLEL_MIN_BYTES: usize = 64 * 1024;
+
+// The oldest revision `read_bytes` and `verify` still accept. Revision 11 wrote its header counts
+// as plain ints (and a small int for the lengths), and before 13 each option was a bit of its own
+// where it's read in `ColumnHeader::read`.
+pub const OLDEST_READABLE_REVISION: u64 = 11;
+
+// What `finish` produces when nothing was written: the version byte and a header of zero counts
+// and unset flags. Every valid buffer is at least this long.
+pub const EMPTY_BUFFER_LEN: usize = 4;
+
+#[derive(Debug)]
 pub struct Serializer<'a> {
     // each property is order-dependent, arrays are flattened into this structure and theoretically
     // nested structs would do the same.
@@ -18,7 +88,13 @@ pub struct Serializer<'a> {
     //
     // Encode this as either english frequency based huffman codes or use ultrapacker for even distribution of
     // probable values.
+    //
+    // Each string is length prefixed rather than separated, so there is no reserved byte and NUL or
+    // any other control character is valid content (it just falls back to the unicode path).
     strings: Vec<Cow<'a, str>>,
+    // strings written with `StringCodec::Raw` and their position among all of the strings, kept out
+    // of `strings` so they have no say in how that column is encoded.
+    raw_strings: Vec<(usize, Cow<'a, str>)>,
     // booleans can just be bitpacked directly, RLE *may* help sometimes, but given mostly random booleans
     // it'll just bloat this size.
     booleans: Vec<bool>,
@@ -26,11 +102,64 @@ pub struct Serializer<'a> {
     //
     // length prefixed and an enum of each property inside of it.
     //
-    // 2 bits per tag
+    // `TAG_BITS` per tag
     property_types: Vec<PropertyType>,
+    // array lengths get their own column so they don't skew the integer pool, and so the structure
+    // of the arrays can be walked without touching any values.
+    lengths: Vec<u64>,
+    // optional trailing array that lives outside of the pools in its own byte-aligned section so it
+    // can be grown in place, see `appender::ArrayAppender`.
+    tail: Option<&'a [PropertyValue]>,
+    // names for values written with `write_named_*` so they can be looked up regardless of order,
+    // see `Deserializer::take_named_int`.
+    names: Vec<FieldName<'a>>,
+    // opt-in since it only pays off for runs of similar strings (sorted keys, paths), see
+    // `set_front_coding`.
+    front_coding: bool,
+    // opt-in since it's slower to write and a compressor after the format does the same job, see
+    // `set_lz_strings`.
+    lz_strings: bool,
+    // on by default, refers repeats of longer strings back to their first copy, see
+    // `set_dedup_strings`.
+    dedup_strings: bool,
+    // opt-in since byte aligning every column costs a few bits each, see `set_column_offsets`.
+    column_offsets: bool,
+    // a CRC-32 per section with the column offsets, see `set_section_checksums`.
+    section_checksums: bool,
+    // the kind of every positional value in the order it was written, only recorded when
+    // `kind_markers` is set, see `set_kind_markers`.
+    kind_markers: bool,
+    kinds: Vec<PropertyType>,
+    // widths for the integer column, see `set_int_header_plan`.
+    int_plan: IntHeaderPlan,
+    // deepest array `write_array` and `write_value` take, see `set_max_depth`.
+    max_depth: usize,
+    // which type the buffer holds, 0 when unidentified, see `IntoFormat::format_id`.
+    format_id: u16,
+    // whether the front of the pools is a schema descriptor, see `with_schema`.
+    schema: bool,
+    // written after the field names when set, see `set_provenance`.
+    provenance: Option<Provenance>,
+    // the buffer stands for no record at all, see `write_absent`.
+    absent: bool,
+    // what `finish` packs the column offsets sections into, kept across `reuse`.
+    sections: RefCell<SectionScratch>,
+    // `columns::repeats` of the strings, kept for the same reason
+    repeats: RefCell<HashMap<u64, usize>>,
+}
+
+// How a single string is written, see `Serializer::write_string_with`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum StringCodec {
+    // in the string column, with whichever encoding `finish` picks for it
+    #[default]
+    Auto,
+    // Byte for byte in a section of its own. For tokens, hashes and the like that huffman can only
+    // make larger, which would otherwise also push the rest of the column off the ascii path.
+    Raw,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum PropertyType {
     String,
     Bool,
@@ -39,12 +168,12 @@ pub enum PropertyType {
 }
 
 impl PropertyType {
-    pub fn to_bits(&self) -> (u8, u8) {
+    pub const fn to_bits(self) -> (u8, u8) {
         match self {
-            PropertyType::String => (0, 2),
-            PropertyType::Bool => (1, 2),
-            PropertyType::Integer => (2, 2),
-            PropertyType::Array => (3, 2),
+            PropertyType::String => (0, TAG_BITS),
+            PropertyType::Bool => (1, TAG_BITS),
+            PropertyType::Integer => (2, TAG_BITS),
+            PropertyType::Array => (3, TAG_BITS),
         }
     }
 
@@ -67,16 +196,46 @@ pub enum PropertyValue {
     Array(Vec<PropertyValue>),
 }
 
-// hacky way to get the compiler to re-use the allocated Vec for differing lifetimes
-// worst case the optimization fails and we end up with the naive allocating solution.
-#[inline]
-fn reuse_vec<T, U>(mut v: Vec<T>) -> Vec<U> {
-    const {
-        assert!(size_of::<T>() == size_of::<U>());
-        assert!(align_of::<T>() == align_of::<U>());
+impl PropertyValue {
+    pub fn kind(&self) -> PropertyType {
+        match self {
+            PropertyValue::String(_) => PropertyType::String,
+            PropertyValue::Bool(_) => PropertyType::Bool,
+            PropertyValue::Integer(_) => PropertyType::Integer,
+            PropertyValue::Array(_) => PropertyType::Array,
+        }
+    }
+
+    // The value as the whole message, for value trees that aren't wrapped in a struct. Written as
+    // `write_value` writes it, a tag and then the value in the usual pools, through the same
+    // `IntoFormat` path as any struct.
+    pub fn to_bytes(&self, version: u8) -> Vec<u8> {
+        pool::to_bytes(self, version)
+    }
+
+    // A buffer with anything left after the value wasn't written as a bare value, so that's an
+    // error rather than ignored.
+    pub fn from_bytes(bytes: &[u8], expected_version: u8) -> Result<PropertyValue, DecodeError> {
+        let mut deserializer = Deserializer::new();
+        deserializer.load(bytes, expected_version)?;
+        let value = deserializer.take_value_checked()?;
+        let remaining = deserializer.remaining_counts();
+        if !remaining.is_empty() {
+            return Err(DecodeError::UnconsumedFields {
+                fields: 1,
+                remaining,
+                leftover: Box::new(deserializer.remaining_view().preview(LEFTOVER_PREVIEW)),
+            });
+        }
+        Ok(value)
+    }
+}
+
+// the same options as `new`, which a derived default would leave at zero
+impl Default for Serializer<'_> {
+    fn default() -> Self {
+        Serializer::new()
     }
-    v.clear();
-    v.into_iter().map(|_| unreachable!()).collect()
 }
 
 impl<'a> Serializer<'a> {
@@ -84,55 +243,415 @@ impl<'a> Serializer<'a> {
         Self {
             integers: Vec::new(),
             strings: Vec::new(),
+            raw_strings: Vec::new(),
             booleans: Vec::new(),
             property_types: Vec::new(),
+            lengths: Vec::new(),
+            tail: None,
+            names: Vec::new(),
+            front_coding: false,
+            lz_strings: false,
+            dedup_strings: true,
+            column_offsets: false,
+            section_checksums: false,
+            kind_markers: false,
+            kinds: Vec::new(),
+            int_plan: IntHeaderPlan::default(),
+            max_depth: DecodeLimits::relaxed().max_depth,
+            format_id: 0,
+            schema: false,
+            provenance: None,
+            absent: false,
+            sections: RefCell::default(),
+            repeats: RefCell::default(),
         }
     }
 
-    // should generally hint to the compiler enough that we can re-use this serializer for a
-    // different lifetime.
-    pub fn reuse<'b>(mut self) -> Serializer<'b> {
+    // Clears everything written for the next message, keeping the options (like `reuse`) and every
+    // column's allocation, so a message no bigger than the ones before it writes without growing
+    // any of them (see `capacities`). The next message has to borrow for the same lifetime, use
+    // `reuse` for one that doesn't.
+    pub fn clear(&mut self) {
         self.integers.clear();
+        self.strings.clear();
+        self.raw_strings.clear();
         self.booleans.clear();
         self.property_types.clear();
+        self.lengths.clear();
+        self.tail = None;
+        self.names.clear();
+        self.kinds.clear();
+        self.format_id = 0;
+        self.schema = false;
+        self.provenance = None;
+        self.absent = false;
+    }
+
+    pub fn capacities(&self) -> ColumnCapacities {
+        ColumnCapacities {
+            integers: self.integers.capacity(),
+            booleans: self.booleans.capacity(),
+            strings: self.strings.capacity(),
+            raw_strings: self.raw_strings.capacity(),
+            property_types: self.property_types.capacity(),
+            lengths: self.lengths.capacity(),
+            names: self.names.capacity(),
+            kinds: self.kinds.capacity(),
+            sections: self.sections.borrow().capacity(),
+        }
+    }
+
+    // What's been written to each of the five columns so far, see `column_split`.
+    pub fn as_columns(&self) -> Columns<'_> {
+        Columns {
+            integers: &self.integers,
+            booleans: &self.booleans,
+            strings: &self.strings,
+            property_types: &self.property_types,
+            lengths: &self.lengths,
+        }
+    }
+
+    // nothing was written outside of the five columns, see `column_split`
+    pub(crate) fn only_columns(&self) -> bool {
+        self.raw_strings.is_empty()
+            && self.names.is_empty()
+            && self.tail.is_none()
+            && !self.kind_markers
+            && !self.schema
+            && self.provenance.is_none()
+            && !self.absent
+    }
+
+    pub(crate) fn int_plan(&self) -> IntHeaderPlan {
+        self.int_plan
+    }
+
+    pub(crate) fn repeats_scratch(&self) -> RefMut<'_, HashMap<u64, usize>> {
+        self.repeats.borrow_mut()
+    }
+
+    // Clears everything written for the next message, which can borrow for a different lifetime.
+    // Every column keeps its allocation, the ones that borrow through `RecycledVec`.
+    pub fn reuse<'b>(mut self) -> Serializer<'b> {
+        self.clear();
         Serializer {
             integers: self.integers,
-            strings: reuse_vec(self.strings),
+            strings: RecycledVec::new(self.strings).into_vec(),
+            raw_strings: RecycledVec::new(self.raw_strings).into_vec(),
             booleans: self.booleans,
             property_types: self.property_types,
+            lengths: self.lengths,
+            tail: None,
+            names: RecycledVec::new(self.names).into_vec(),
+            front_coding: self.front_coding,
+            lz_strings: self.lz_strings,
+            dedup_strings: self.dedup_strings,
+            column_offsets: self.column_offsets,
+            section_checksums: self.section_checksums,
+            kind_markers: self.kind_markers,
+            kinds: self.kinds,
+            int_plan: self.int_plan,
+            max_depth: self.max_depth,
+            format_id: 0,
+            schema: false,
+            provenance: None,
+            absent: false,
+            sections: self.sections,
+            repeats: self.repeats,
+        }
+    }
+
+    // Starts the buffer with a description of the values that follow (names and kinds) so generic
+    // viewers and editors can label them without the type, see `Deserializer::read_schema`. The
+    // names go in the string column and the kinds in the tag column ahead of any of the data.
+    pub fn with_schema(schema: &Schema) -> Self {
+        let mut serializer = Self::new();
+        serializer.schema = true;
+        serializer.write_length(schema.fields.len());
+        for (name, kind) in &schema.fields {
+            serializer.write_string_owned(name.clone());
+            serializer.write_property_type(*kind);
+        }
+        serializer
+    }
+
+    // Each string is written as the length of the prefix it shares with the previous string
+    // followed by the rest of it. `finish` still only uses it when it shrinks the strings before
+    // they hit the huffman/ultrapack stage, so unsorted input just costs the header bit.
+    pub fn set_front_coding(&mut self, enabled: bool) {
+        self.front_coding = enabled;
+    }
+
+    // Writes the string column with back references to the bytes of earlier strings (see `lz`),
+    // for buffers stored or sent without a general purpose compressor after them. Replaces the
+    // ascii codecs and front coding rather than adding to them, every string is written this way
+    // whether or not it repeats anything.
+    pub fn set_lz_strings(&mut self, enabled: bool) {
+        self.lz_strings = enabled;
+    }
+
+    // On by default. A string of at least `DEDUP_MIN_LEN` (8) bytes that's equal to an earlier one
+    // in the same column (or column offsets section) is written as how far back that one is.
+    // `finish` only does it when the repeats save more than the bit it adds to every string, and
+    // never with `set_lz_strings`, whose matches already cover repeats.
+    pub fn set_dedup_strings(&mut self, enabled: bool) {
+        self.dedup_strings = enabled;
+    }
+
+    // Writes a table of byte lengths after the header and byte aligns every column (and every
+    // `STRING_SECTION_LEN` strings) so each one can be decoded on its own, see
+    // `Deserializer::read_bytes_parallel`. Only worth it for large buffers.
+    pub fn set_column_offsets(&mut self, enabled: bool) {
+        self.column_offsets = enabled;
+    }
+
+    // Adds a CRC-32 of every column section to the column offsets table (turning column offsets on
+    // when enabled), 4 bytes per section. A corrupt buffer then fails with the column it's in,
+    // `DecodeError::ChecksumMismatch`, and `check_sections` lists every corrupt column so the
+    // rest can still be trusted.
+    //
+    // Only the columns are covered, not the header or anything written after them.
+    pub fn set_section_checksums(&mut self, enabled: bool) {
+        self.section_checksums = enabled;
+        self.column_offsets |= enabled;
+    }
+
+    // A debugging aid for readers that take values in a different order than they were written,
+    // which otherwise goes unnoticed when the kinds line up per column. Every positional value
+    // written from here on gets a `TAG_BITS` wide marker of its kind, and the deserializer's
+    // `take_*` check them in order, see `Deserializer::take_kind_mismatch`. Buffers written
+    // without it carry no markers and are read without any checks.
+    //
+    // Named values aren't marked since they're read by name rather than in order.
+    pub fn set_kind_markers(&mut self, enabled: bool) {
+        self.kind_markers = enabled;
+    }
+
+    // Writes the integer column with the plan's widths instead of `INT_HEADER_WIDTHS`, see
+    // `int_plan::analyze_ints`. Kept by `reuse`, the same type tends to write the same shape of
+    // integers.
+    pub fn set_int_header_plan(&mut self, plan: IntHeaderPlan) {
+        self.int_plan = plan;
+    }
+
+    // The deepest arrays `write_array`, `write_value`, `write_map_sorted_keys` and
+    // `write_tail_array` will write, with
+    // the outermost array at depth 1 the way `DecodeLimits::max_depth` counts. Defaults to what
+    // `Deserializer::new` reads, so the writer doesn't produce buffers its own default reader
+    // refuses. Kept by `reuse`.
+    pub fn set_max_depth(&mut self, depth: usize) {
+        self.max_depth = depth;
+    }
+
+    fn check_depth(&self, depth: usize) -> Result<(), DepthExceeded> {
+        match depth > self.max_depth {
+            true => Err(DepthExceeded {
+                depth,
+                max: self.max_depth,
+            }),
+            false => Ok(()),
+        }
+    }
+
+    fn mark(&mut self, kind: PropertyType) {
+        if self.kind_markers {
+            self.kinds.push(kind);
         }
     }
 
+    // Marks the buffer as holding no record at all, rather than one with no fields, for an
+    // optional top-level value (see `IntoFormat::deserialize_optional`). Nothing else can be
+    // written alongside it, `finish` panics if anything was. Reset by `reuse`.
+    pub fn write_absent(&mut self) {
+        self.absent = true;
+    }
+
+    // written in the header so a `Registry` can tell which type to decode the buffer as, reset by
+    // `reuse`.
+    pub fn set_format_id(&mut self, id: u16) {
+        self.format_id = id;
+    }
+
+    // Records who wrote the buffer and when (seconds since the unix epoch), readable with
+    // `Deserializer::provenance` without touching the values. Reset by `reuse`.
+    pub fn set_provenance(
+        &mut self,
+        timestamp: u64,
+        producer: &str,
+    ) -> Result<(), ProducerTooLong> {
+        self.provenance = Some(Provenance::new(timestamp, producer)?);
+        Ok(())
+    }
+
     pub fn write_int(&mut self, value: i64) {
+        self.mark(PropertyType::Integer);
         self.integers.push(value);
     }
 
     pub fn write_string<'b: 'a>(&mut self, value: &'b str) {
+        self.mark(PropertyType::String);
         self.strings.push(Cow::Borrowed(value));
     }
 
+    // for strings built while serializing that don't live as long as the serializer
+    pub fn write_string_owned(&mut self, value: String) {
+        self.mark(PropertyType::String);
+        self.strings.push(Cow::Owned(value));
+    }
+
+    // Read back with `take_string` like any other string, in the order they were written.
+    pub fn write_string_with<'b: 'a>(&mut self, value: &'b str, codec: StringCodec) {
+        match codec {
+            StringCodec::Auto => self.write_string(value),
+            StringCodec::Raw => {
+                self.mark(PropertyType::String);
+                self.raw_strings
+                    .push((self.string_count(), Cow::Borrowed(value)));
+            }
+        }
+    }
+
+    fn string_count(&self) -> usize {
+        self.strings.len() + self.raw_strings.len()
+    }
+
     pub fn write_bool(&mut self, value: bool) {
+        self.mark(PropertyType::Bool);
+        self.booleans.push(value);
+    }
+
+    // Written into the usual pools, plus a name so a reader with a different field order can still
+    // find it with `take_named_*`.
+    pub fn write_named_int(&mut self, name: &'a str, value: i64) {
+        self.names
+            .push((name, PropertyType::Integer, self.integers.len()));
+        self.integers.push(value);
+    }
+
+    pub fn write_named_string<'b: 'a>(&mut self, name: &'a str, value: &'b str) {
+        self.names
+            .push((name, PropertyType::String, self.string_count()));
+        self.strings.push(Cow::Borrowed(value));
+    }
+
+    pub fn write_named_bool(&mut self, name: &'a str, value: bool) {
+        self.names
+            .push((name, PropertyType::Bool, self.booleans.len()));
         self.booleans.push(value);
     }
 
-    pub fn write_value<'r: 'a>(&mut self, value: &'r PropertyValue) {
+    pub fn cursor(&mut self) -> FieldCursor<'_, 'a> {
+        FieldCursor::new(self)
+    }
+
+    // presence goes into the boolean pool, the value is only written if there is one
+    pub fn write_optional<T: ?Sized>(
+        &mut self,
+        value: Option<&'a T>,
+        write: impl FnOnce(&mut Self, &'a T),
+    ) {
+        self.write_bool(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    /// Copies the strings out of `value` so it only has to live for the call, `write_array` keeps
+    /// borrowing them for values that outlive the serializer.
+    ///
+    /// ```
+    /// use solution::serializer::{Deserializer, IntoFormat, PropertyValue, Serializer};
+    ///
+    /// struct Point {
+    ///     x: i64,
+    ///     y: i64,
+    /// }
+    ///
+    /// impl IntoFormat for Point {
+    ///     fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+    ///         let pair = PropertyValue::Array(vec![
+    ///             PropertyValue::Integer(self.x),
+    ///             PropertyValue::String(format!("{}", self.y)),
+    ///         ]);
+    ///         serializer.write_value(&pair);
+    ///     }
+    ///
+    ///     fn take(deserializer: &mut Deserializer) -> Option<Self> {
+    ///         let PropertyValue::Array(pair) = deserializer.take_value()? else {
+    ///             return None;
+    ///         };
+    ///         match pair.as_slice() {
+    ///             [PropertyValue::Integer(x), PropertyValue::String(y)] => Some(Point {
+    ///                 x: *x,
+    ///                 y: y.parse().ok()?,
+    ///             }),
+    ///             _ => None,
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `value` nests arrays deeper than `set_max_depth`, see `write_value_checked`.
+    pub fn write_value(&mut self, value: &PropertyValue) {
+        if let Err(error) = self.write_value_checked(value) {
+            panic!("{error}");
+        }
+    }
+
+    // `write_value` that writes nothing and fails for arrays nested deeper than `set_max_depth`
+    pub fn write_value_checked(&mut self, value: &PropertyValue) -> Result<(), DepthExceeded> {
+        self.check_depth(value_depth(value))?;
+        self.write_value_unchecked(value);
+        Ok(())
+    }
+
+    // the depth was checked for the whole value up front
+    fn write_value_unchecked(&mut self, value: &PropertyValue) {
         match value {
+            PropertyValue::String(string) => {
+                self.write_property_type(PropertyType::String);
+                self.write_string_owned(string.clone());
+            }
+            PropertyValue::Array(values) => {
+                self.write_property_type(PropertyType::Array);
+                self.write_length(values.len());
+                for value in values {
+                    self.write_value_unchecked(value);
+                }
+            }
             PropertyValue::Bool(bool) => {
                 self.write_property_type(PropertyType::Bool);
                 self.write_bool(*bool);
             }
+            PropertyValue::Integer(int) => {
+                self.write_property_type(PropertyType::Integer);
+                self.write_int(*int);
+            }
+        }
+    }
+
+    // zero-copy counterpart of `write_value`
+    fn write_borrowed_value<'r: 'a>(&mut self, value: &'r PropertyValue) {
+        match value {
             PropertyValue::String(string) => {
                 self.write_property_type(PropertyType::String);
                 self.write_string(string.as_str());
             }
+            PropertyValue::Array(values) => {
+                self.write_property_type(PropertyType::Array);
+                self.write_array_unchecked(values.as_slice());
+            }
+            PropertyValue::Bool(bool) => {
+                self.write_property_type(PropertyType::Bool);
+                self.write_bool(*bool);
+            }
             PropertyValue::Integer(int) => {
                 self.write_property_type(PropertyType::Integer);
                 self.write_int(*int);
             }
-            PropertyValue::Array(values) => {
-                self.write_property_type(PropertyType::Array);
-                self.write_array(values.as_slice());
-            }
         }
     }
 
@@ -140,33 +659,288 @@ impl<'a> Serializer<'a> {
         self.property_types.push(tag);
     }
 
+    pub(crate) fn write_length(&mut self, length: usize) {
+        // the column is u64 rather than i64 so there's no sign to lose, this only fails on a
+        // hypothetical target with a wider usize
+        let length = u64::try_from(length).expect("array length doesn't fit in a u64");
+        self.mark(PropertyType::Array);
+        self.lengths.push(length);
+    }
+
+    // `write_length` of however many values `write` goes on to write, see `write_ints`
+    pub(crate) fn write_counted_length(&mut self, write: impl FnOnce(&mut Self) -> usize) {
+        let index = self.write_length_placeholder();
+        let length = write(self);
+        self.patch_length(index, length);
+    }
+
+    // a length of 0 for `patch_length` to fill in once it's known, returns its index in the column
+    pub(crate) fn write_length_placeholder(&mut self) -> usize {
+        self.write_length(0);
+        self.lengths.len() - 1
+    }
+
+    pub(crate) fn patch_length(&mut self, index: usize, length: usize) {
+        self.lengths[index] = u64::try_from(length).expect("array length doesn't fit in a u64");
+    }
+
+    // Moves everything `record` wrote onto the end of the columns with its strings owned, so
+    // whatever `record` borrowed from can go, and leaves `record` empty (see `BatchSerializer`).
+    // Named values and the tail array borrow for as long as the serializer lives and can't be
+    // moved.
+    pub(crate) fn append_owned(&mut self, record: &mut Serializer<'_>) {
+        assert!(
+            record.names.is_empty() && record.tail.is_none(),
+            "named values and tail arrays can't be moved to another serializer"
+        );
+        let strings = self.string_count();
+        self.integers.append(&mut record.integers);
+        self.booleans.append(&mut record.booleans);
+        let owned = |string: Cow<str>| Cow::Owned(string.into_owned());
+        self.strings.extend(record.strings.drain(..).map(owned));
+        let raw_strings = record.raw_strings.drain(..);
+        self.raw_strings
+            .extend(raw_strings.map(|(position, string)| (strings + position, owned(string))));
+        self.property_types.append(&mut record.property_types);
+        self.lengths.append(&mut record.lengths);
+        match self.kind_markers {
+            true => self.kinds.append(&mut record.kinds),
+            false => record.kinds.clear(),
+        }
+    }
+
+    // an empty serializer with this one's kind markers and depth limit, see `write_struct_array`
+    pub(crate) fn scratch(&self) -> Serializer<'a> {
+        let mut scratch = Serializer::new();
+        scratch.kind_markers = self.kind_markers;
+        scratch.max_depth = self.max_depth;
+        scratch
+    }
+
+    pub(crate) fn written_counts(&self) -> ColumnCounts {
+        ColumnCounts {
+            integers: self.integers.len(),
+            booleans: self.booleans.len(),
+            strings: self.string_count(),
+            property_types: self.property_types.len(),
+            lengths: self.lengths.len(),
+        }
+    }
+
+    // Moves the columns of `items` records written one after the other in `fields` onto the end
+    // of this serializer's. A column with a stride, where every record wrote that many values, is
+    // moved field by field: every record's first value, then every record's second and so on. The
+    // kind markers stay in record order, they're checked once the reader has put the values back.
+    pub(crate) fn append_transposed(
+        &mut self,
+        fields: &mut Serializer<'a>,
+        items: usize,
+        [integers, booleans, strings, property_types, lengths]: [Option<usize>; 5],
+    ) {
+        assert!(
+            fields.names.is_empty() && fields.tail.is_none() && fields.raw_strings.is_empty(),
+            "named values, tail arrays and raw strings can't be transposed"
+        );
+        transpose_onto(&mut self.integers, &mut fields.integers, items, integers);
+        transpose_onto(&mut self.booleans, &mut fields.booleans, items, booleans);
+        transpose_onto(&mut self.strings, &mut fields.strings, items, strings);
+        let tags = &mut fields.property_types;
+        transpose_onto(&mut self.property_types, tags, items, property_types);
+        transpose_onto(&mut self.lengths, &mut fields.lengths, items, lengths);
+        self.kinds.append(&mut fields.kinds);
+    }
+
+    /// Writes the array's length and then each value with its tag, borrowing the strings until
+    /// `finish`.
+    ///
+    /// # Panics
+    ///
+    /// If `array` nests arrays deeper than `set_max_depth`, see `write_array_checked`.
     pub fn write_array<'arr: 'a>(&mut self, array: &'arr [PropertyValue]) {
-        self.write_int(array.len() as i64);
+        if let Err(error) = self.write_array_checked(array) {
+            panic!("{error}");
+        }
+    }
+
+    // `write_array` that writes nothing and fails for arrays nested deeper than `set_max_depth`
+    pub fn write_array_checked<'arr: 'a>(
+        &mut self,
+        array: &'arr [PropertyValue],
+    ) -> Result<(), DepthExceeded> {
+        self.check_depth(array_depth(array))?;
+        self.write_array_unchecked(array);
+        Ok(())
+    }
+
+    fn write_array_unchecked<'arr: 'a>(&mut self, array: &'arr [PropertyValue]) {
+        self.write_length(array.len());
         for value in array {
-            self.write_value(value);
+            self.write_borrowed_value(value);
+        }
+    }
+
+    /// A map as its length, then its keys in sorted order and then each key's value, read back
+    /// with `Deserializer::take_map`. Sorting puts keys with shared prefixes next to each other in
+    /// the string column, so this turns on front coding for the buffer, which `finish` still only
+    /// uses when it comes out smaller.
+    ///
+    /// # Panics
+    ///
+    /// If any value nests arrays deeper than `set_max_depth`.
+    pub fn write_map_sorted_keys<'m: 'a>(
+        &mut self,
+        entries: impl IntoIterator<Item = (&'m str, &'m PropertyValue)>,
+    ) {
+        let mut entries: Vec<_> = entries.into_iter().collect();
+        let depth = entries.iter().map(|(_, value)| value_depth(value)).max();
+        if let Err(error) = self.check_depth(depth.unwrap_or(0)) {
+            panic!("{error}");
+        }
+        entries.sort_by_key(|(key, _)| *key);
+        self.front_coding = true;
+        self.write_length(entries.len());
+        for (key, _) in &entries {
+            self.write_string(key);
+        }
+        for (_, value) in entries {
+            self.write_borrowed_value(value);
+        }
+    }
+
+    /// Must be the last field written, everything inside of it is encoded inline rather than
+    /// pooled so that appending doesn't require rewriting the rest of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// If the array has more than `u32::MAX` elements or nests arrays deeper than
+    /// `set_max_depth` (the tail array itself is depth 1), see `write_tail_array_checked`.
+    pub fn write_tail_array<'arr: 'a>(&mut self, array: &'arr [PropertyValue]) {
+        if let Err(error) = self.write_tail_array_checked(array) {
+            panic!("{error}");
         }
     }
 
+    // `write_tail_array` that leaves the tail as it was and fails for arrays that won't fit or are
+    // nested too deep
+    pub fn write_tail_array_checked<'arr: 'a>(
+        &mut self,
+        array: &'arr [PropertyValue],
+    ) -> Result<(), AppendError> {
+        appender::tail_length(array)?;
+        self.check_depth(array_depth(array))
+            .map_err(AppendError::TooDeep)?;
+        self.tail = Some(array);
+        Ok(())
+    }
+
+    // Nothing was written, `finish` would produce `EMPTY_BUFFER_LEN` bytes. The format id and
+    // provenance describe the buffer rather than its contents, so they don't count.
+    pub fn is_empty(&self) -> bool {
+        self.integers.is_empty()
+            && self.strings.is_empty()
+            && self.raw_strings.is_empty()
+            && self.booleans.is_empty()
+            && self.property_types.is_empty()
+            && self.lengths.is_empty()
+            && self.names.is_empty()
+            && self.tail.is_none()
+    }
+
+    pub fn constant_integer(&self) -> Option<i64> {
+        let (first, rest) = self.integers.split_first()?;
+        rest.iter().all(|int| int == first).then_some(*first)
+    }
+
     // are we ascii & are we above the "control" characters?
     pub fn all_32_127(&self) -> bool {
-        self.strings
-            .iter()
-            .all(|string| string.chars().all(|c| c as u32 >= 32 && c as u32 <= 127))
+        self.strings.iter().all(|string| is_32_127(string))
+    }
+
+    // the repeats as they'd be written otherwise (a front coded repeat of the string just before
+    // is nearly free) against their references + a bit for every string, with references only
+    // within a section when there are column offsets
+    fn dedup_saves(&self, all_ascii: bool, front_coded: bool) -> bool {
+        let section = match self.column_offsets {
+            true => STRING_SECTION_LEN,
+            false => self.strings.len().max(1),
+        };
+        let mut saved = 0;
+        let seen = &mut self.repeats.borrow_mut();
+        for chunk in self.strings.chunks(section) {
+            let mut previous = "";
+            for (string, repeat) in chunk.iter().zip(columns::repeats(chunk, seen)) {
+                if let Some(distance) = repeat {
+                    let shared = if front_coded {
+                        shared_prefix(previous, string)
+                    } else {
+                        0
+                    };
+                    let suffix = &string[shared..];
+                    let mut written = match all_ascii {
+                        true => ascii_string_adaptive_bits(suffix),
+                        false => unicode_huffman_string_bits(suffix),
+                    };
+                    if front_coded {
+                        written += small_int_encoded_bits(shared as u64);
+                    }
+                    saved += written.saturating_sub(small_int_encoded_bits(distance as u64 - 1));
+                }
+                previous = string;
+            }
+        }
+        saved > self.strings.len() as u64
+    }
+
+    // how `finish` writes the string column
+    pub(crate) fn chosen_string_flags(&self) -> StringFlags {
+        // strings with a byte the ascii codecs can't write take the unicode codec's escapes instead
+        let all_ascii = !self.lz_strings && columns::ascii_encodable(&self.strings);
+        let front_coded = !self.lz_strings && self.front_coding && self.front_coding_saves();
+        let deduplicated =
+            self.dedup_strings && !self.lz_strings && self.dedup_saves(all_ascii, front_coded);
+        StringFlags {
+            all_ascii,
+            front_coded,
+            lz: self.lz_strings,
+            deduplicated,
+        }
+    }
+
+    // raw string bytes against front coded suffix bytes + shared prefix lengths
+    fn front_coding_saves(&self) -> bool {
+        let mut plain = 0;
+        let mut front_coded = 0;
+        let mut previous = "";
+        for string in &self.strings {
+            let shared = shared_prefix(previous, string);
+            plain += string.len() as u64 * 8;
+            front_coded +=
+                (string.len() - shared) as u64 * 8 + small_int_encoded_bits(shared as u64);
+            previous = string;
+        }
+        front_coded < plain
     }
 
     pub fn finish_native(&self, buffer: &mut Vec<u8>, version: u8) {
         let mut packer = BitPacker::new(buffer);
         packer.write_byte(version);
-        packer.write_bytes(&(self.integers.len() as i64).to_le_bytes());
-        packer.write_bytes(&(self.booleans.len() as i64).to_le_bytes());
-        packer.write_bytes(&(self.strings.len() as i64).to_le_bytes());
-        packer.write_bytes(&(self.property_types.len() as i64).to_le_bytes());
+        packer.write_bytes(&length_int(self.integers.len()).to_le_bytes());
+        packer.write_bytes(&length_int(self.booleans.len()).to_le_bytes());
+        packer.write_bytes(&length_int(self.string_count()).to_le_bytes());
+        packer.write_bytes(&length_int(self.property_types.len()).to_le_bytes());
+        packer.write_bytes(&length_int(self.lengths.len()).to_le_bytes());
 
         for integer in &self.integers {
             packer.write_bytes(&integer.to_le_bytes());
         }
 
-        for string in &self.strings {
+        let mut raw = self.raw_strings.iter().peekable();
+        let mut coded = self.strings.iter();
+        for position in 0..self.string_count() {
+            let string = match raw.next_if(|(raw, _)| *raw == position) {
+                Some((_, string)) => string,
+                None => coded.next().expect("positions are within the string count"),
+            };
             packer.write_bytes(string.as_bytes());
         }
 
@@ -178,160 +952,1937 @@ impl<'a> Serializer<'a> {
             let (byte, _) = tag.to_bits();
             packer.write_bytes(&[byte]);
         }
+
+        for length in &self.lengths {
+            packer.write_bytes(&length.to_le_bytes());
+        }
     }
 
-    pub fn finish(&self, buffer: &mut Vec<u8>, version: u8) {
+    pub fn finish(&self, buffer: &mut Vec<u8>, version: u8) -> EncodeStats {
+        self.finish_with(buffer, version, &mut IncrementalHash(None), false)
+    }
+
+    // For embedding in a larger structure that needs its fields aligned. Whatever `buffer` already
+    // holds is kept and zero padded to a multiple of `align` bytes, then the message is appended
+    // and padded out to a multiple of `align` as well, so both ends of it are aligned. Read it back
+    // from `&buffer[buffer.len() - stats.total_bytes as usize..]`.
+    //
+    // The last byte of the padding is its length, so `align` is at most 255. `verify` checks the
+    // padding like any other section, and a tail array can't be appended to afterwards since that
+    // would undo the alignment.
+    pub fn finish_aligned(&self, buffer: &mut Vec<u8>, version: u8, align: usize) -> EncodeStats {
+        assert!(
+            (1..=u8::MAX as usize).contains(&align),
+            "alignment {align} doesn't fit the padding length byte"
+        );
+        let mut message = Vec::new();
+        let mut stats = self.finish_with(&mut message, version, &mut IncrementalHash(None), true);
+        // always at least the length byte
+        let padding = align - message.len() % align;
+        message.resize(message.len() + padding - 1, 0);
+        message.push(padding as u8);
+        stats.total_bytes = message.len() as u64;
+
+        buffer.resize(buffer.len().next_multiple_of(align), 0);
+        buffer.extend_from_slice(&message);
+        stats
+    }
+
+    // Same as `finish`, but bytes are fed into `hasher` as each column completes so callers keying
+    // on the output don't need a second pass. `stats.digest` is `hasher.finish()`, which matches
+    // `hasher.write(&buffer)` for hashers that don't care how the input is chunked (e.g. SipHash
+    // / `DefaultHasher`).
+    pub fn finish_hashed(
+        &self,
+        buffer: &mut Vec<u8>,
+        version: u8,
+        hasher: &mut impl Hasher,
+    ) -> EncodeStats {
+        let mut stats = self.finish_with(
+            buffer,
+            version,
+            &mut IncrementalHash(Some((hasher, 0))),
+            false,
+        );
+        stats.digest = Some(hasher.finish());
+        stats
+    }
+
+    fn finish_with(
+        &self,
+        buffer: &mut Vec<u8>,
+        version: u8,
+        hash: &mut IncrementalHash,
+        padded: bool,
+    ) -> EncodeStats {
+        assert!(
+            !self.absent || self.is_empty(),
+            "values were written to an absent buffer"
+        );
+        let mut stats = EncodeStats::default();
+        let StringFlags {
+            all_ascii,
+            front_coded,
+            deduplicated,
+            ..
+        } = self.chosen_string_flags();
         let mut packer = BitPacker::new(buffer);
         packer.write_byte(version);
+        packer.write_small_int(FORMAT_REVISION);
+        // most buffers have none of these, which is only the small int's 3 bit header
+        let options = [
+            (OPTION_FORMAT_ID, self.format_id != 0),
+            (OPTION_COLUMN_OFFSETS, self.column_offsets),
+            (OPTION_SCHEMA, self.schema),
+            (OPTION_PROVENANCE, self.provenance.is_some()),
+            (OPTION_RAW_STRINGS, !self.raw_strings.is_empty()),
+            (OPTION_PADDED, padded),
+            (OPTION_KIND_MARKERS, self.kind_markers),
+            (OPTION_INT_TABLE, self.int_plan.table() != 0),
+            (
+                OPTION_SECTION_CHECKSUMS,
+                self.column_offsets && self.section_checksums,
+            ),
+            (OPTION_LZ_STRINGS, self.lz_strings),
+            (OPTION_DEDUPLICATED_STRINGS, deduplicated),
+            (OPTION_ABSENT, self.absent),
+            (OPTION_INT_WIDTHS, self.int_plan.custom_mask().is_some()),
+        ];
+        let options = options
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .fold(0, |options, (option, _)| options | option);
+        packer.write_small_int(options);
+        if self.format_id != 0 {
+            packer.write_small_int(self.format_id as u64);
+        }
+        if self.int_plan.table() != 0 {
+            packer.write_small_int(self.int_plan.table() as u64);
+        }
+        if let Some(mask) = self.int_plan.custom_mask() {
+            packer.write_small_int(mask);
+        }
 
         // per type headers
-        packer.write_int(self.integers.len() as i64);
-        packer.write_int(self.booleans.len() as i64);
+        packer.write_count(self.integers.len() as u64);
+        packer.write_count(self.booleans.len() as u64);
 
-        let all_ascii = self.all_32_127();
         packer.write_bit(all_ascii);
-        packer.write_int(self.strings.len() as i64);
+        packer.write_bit(front_coded);
+        packer.write_count(self.strings.len() as u64);
 
-        packer.write_int(self.property_types.len() as i64);
+        packer.write_count(self.property_types.len() as u64);
+        packer.write_count(self.lengths.len() as u64);
+        stats.all_ascii = all_ascii;
+        stats.front_coded = front_coded;
+        stats.lz_strings = self.lz_strings;
+        stats.deduplicated_strings = deduplicated;
 
-        for integer in &self.integers {
-            packer.write_int(*integer);
+        if self.column_offsets {
+            self.write_sections(&mut packer, &mut stats, hash);
+        } else {
+            stats.header_bits = packer.bit_position();
+            hash.feed(&packer);
+            self.write_columns(&mut packer, &mut stats, hash);
         }
-
-        for boolean in &self.booleans {
-            packer.write_bit(*boolean);
+        if !self.raw_strings.is_empty() {
+            let start = packer.bit_position();
+            columns::write_raw_strings(&mut packer, &self.raw_strings);
+            stats.raw_strings = column_stats(self.raw_strings.len(), start, packer.bit_position());
+        }
+        if self.kind_markers {
+            packer.write_count(self.kinds.len() as u64);
+            columns::write_property_types(&mut packer, &self.kinds);
         }
 
-        if all_ascii {
-            for string in &self.strings {
-                packer.write_ascii_string_adaptive(string);
-            }
-        } else {
-            for string in &self.strings {
-                packer.write_unicode_huffman_string(string);
-            }
+        let start = packer.bit_position();
+        columns::write_names(&mut packer, &self.names);
+        stats.names = column_stats(self.names.len(), start, packer.bit_position());
+        if let Some(provenance) = &self.provenance {
+            provenance.write(&mut packer);
         }
+        hash.feed(&packer);
 
-        for tag in &self.property_types {
-            packer.write_property_type(*tag);
+        let start = packer.bit_position();
+        packer.write_bit(self.tail.is_some());
+        let mut end = packer.bit_position();
+        let buffer = packer.into_inner();
+        let tail_len = self.tail.map_or(0, <[_]>::len);
+        if let Some(tail) = self.tail {
+            appender::write_tail(buffer, tail);
+            end = buffer.len() as u64 * 8;
         }
+        stats.tail = column_stats(tail_len, start, end);
+
+        stats.total_bytes = buffer.len() as u64;
+        hash.feed_rest(buffer);
+        stats
     }
-}
 
-#[derive(Debug)]
-pub struct Deserializer {
-    integers: VecDeque<i64>,
-    strings: VecDeque<String>,
-    booleans: VecDeque<bool>,
-    property_types: VecDeque<PropertyType>,
-}
+    fn write_columns(
+        &self,
+        packer: &mut BitPacker,
+        stats: &mut EncodeStats,
+        hash: &mut IncrementalHash,
+    ) {
+        let start = packer.bit_position();
+        stats.constant_integers =
+            columns::write_integers(packer, &self.integers, self.int_plan.widths());
+        stats.integers = column_stats(self.integers.len(), start, packer.bit_position());
+        hash.feed(packer);
 
-impl Deserializer {
-    pub fn new() -> Self {
-        Self {
-            integers: Default::default(),
-            strings: Default::default(),
-            booleans: Default::default(),
-            property_types: Default::default(),
-        }
-    }
+        let start = packer.bit_position();
+        columns::write_booleans(packer, &self.booleans);
+        stats.booleans = column_stats(self.booleans.len(), start, packer.bit_position());
+        hash.feed(packer);
 
-    fn clear(&mut self) {
-        self.integers.clear();
-        self.strings.clear();
-        self.booleans.clear();
-        self.property_types.clear();
-    }
+        let start = packer.bit_position();
+        let seen = &mut self.repeats.borrow_mut();
+        columns::write_strings(packer, &self.strings, string_flags(stats), seen)
+            .expect("all_ascii is only set when every string is encodable");
+        stats.strings = column_stats(self.strings.len(), start, packer.bit_position());
+        hash.feed(packer);
 
-    // ideally a `Result`
-    pub fn read_bytes(&mut self, bytes: &[u8], version: u8) -> Option<()> {
-        self.clear();
-        let mut unpacker = BitUnpacker::new(bytes);
+        let start = packer.bit_position();
+        columns::write_property_types(packer, &self.property_types);
+        stats.property_types =
+            column_stats(self.property_types.len(), start, packer.bit_position());
+        hash.feed(packer);
 
-        let read_version = unpacker.read_byte()?;
-        assert_eq!(read_version, version);
+        let start = packer.bit_position();
+        columns::write_lengths(packer, &self.lengths);
+        stats.lengths = column_stats(self.lengths.len(), start, packer.bit_position());
+        hash.feed(packer);
+    }
 
-        let int_len = unpacker.read_int()?;
-        let bool_len = unpacker.read_int()?;
+    // column bits in the stats don't include the padding out to each section's last byte
+    fn write_sections(
+        &self,
+        packer: &mut BitPacker,
+        stats: &mut EncodeStats,
+        hash: &mut IncrementalHash,
+    ) {
+        let mut scratch = self.sections.borrow_mut();
+        scratch.clear();
 
-        let all_ascii = unpacker.read_bit()?;
-        let string_len = unpacker.read_int()?;
+        let mut constant = false;
+        let bits = scratch.pack(|packer| {
+            constant = columns::write_integers(packer, &self.integers, self.int_plan.widths())
+        });
+        stats.constant_integers = constant;
+        stats.integers = column_stats(self.integers.len(), 0, bits);
 
-        let tags_len = unpacker.read_int()?;
+        let bits = scratch.pack(|packer| columns::write_booleans(packer, &self.booleans));
+        stats.booleans = column_stats(self.booleans.len(), 0, bits);
 
-        for _ in 0..int_len {
-            self.integers.push_back(unpacker.read_int()?);
+        let mut string_bits = 0;
+        let seen = &mut self.repeats.borrow_mut();
+        for chunk in self.strings.chunks(STRING_SECTION_LEN) {
+            string_bits += scratch.pack(|packer| {
+                columns::write_strings(packer, chunk, string_flags(stats), seen)
+                    .expect("all_ascii is only set when every string is encodable")
+            });
         }
+        stats.strings = column_stats(self.strings.len(), 0, string_bits);
 
-        for _ in 0..bool_len {
-            self.booleans.push_back(unpacker.read_bit()?);
-        }
+        let bits =
+            scratch.pack(|packer| columns::write_property_types(packer, &self.property_types));
+        stats.property_types = column_stats(self.property_types.len(), 0, bits);
 
-        if all_ascii {
-            for _ in 0..string_len {
-                let is_huffman = unpacker.read_bit()?;
-                if is_huffman {
-                    self.strings
-                        .push_back(unpacker.read_ascii_huffman_string()?);
-                } else {
-                    self.strings
-                        .push_back(unpacker.read_ascii_ultrapacked_string()?);
-                }
-            }
-        } else {
-            for _ in 0..string_len {
-                self.strings
-                    .push_back(unpacker.read_unicode_huffman_string()?);
-            }
-        }
+        let bits = scratch.pack(|packer| columns::write_lengths(packer, &self.lengths));
+        stats.lengths = column_stats(self.lengths.len(), 0, bits);
 
-        for _ in 0..tags_len {
-            self.property_types
-                .push_back(unpacker.read_property_type()?);
+        Sections::write(packer, scratch.sections(), self.section_checksums);
+        stats.header_bits = packer.bit_position();
+        hash.feed(packer);
+
+        for section in scratch.sections() {
+            packer.write_aligned_bytes(section);
+            hash.feed(packer);
         }
+    }
+}
 
-        Some(())
+// hasher + how many bytes of the buffer it has already seen
+struct IncrementalHash<'h>(Option<(&'h mut dyn Hasher, usize)>);
+
+impl IncrementalHash<'_> {
+    // only bytes the packer is done with, the last one may still have bits OR'd into it
+    fn feed(&mut self, packer: &BitPacker) {
+        if let Some((hasher, fed)) = &mut self.0 {
+            let complete = packer.complete_bytes();
+            hasher.write(&packer.as_bytes()[*fed..complete]);
+            *fed = complete;
+        }
     }
 
-    pub fn take_int(&mut self) -> Option<i64> {
-        self.integers.pop_front()
+    fn feed_rest(&mut self, buffer: &[u8]) {
+        if let Some((hasher, fed)) = &mut self.0 {
+            hasher.write(&buffer[*fed..]);
+            *fed = buffer.len();
+        }
     }
+}
 
-    pub fn take_bool(&mut self) -> Option<bool> {
-        self.booleans.pop_front()
+// How deep an array holding `values` goes, 1 when none of them are arrays. A stack rather than
+// recursion, like `Deserializer::take_array_checked`, so it can measure however deep the caller
+// built the value. Only arrays within arrays go on the stack, a flat array doesn't allocate.
+fn array_depth(values: &[PropertyValue]) -> usize {
+    let mut deepest = 1;
+    let mut stack = Vec::new();
+    let mut next = Some((values, 1));
+    while let Some((values, depth)) = next.take().or_else(|| stack.pop()) {
+        deepest = deepest.max(depth);
+        for value in values {
+            if let PropertyValue::Array(values) = value {
+                stack.push((values.as_slice(), depth + 1));
+            }
+        }
     }
+    deepest
+}
 
-    pub fn take_string(&mut self) -> Option<String> {
-        self.strings.pop_front()
+pub(crate) fn value_depth(value: &PropertyValue) -> usize {
+    match value {
+        PropertyValue::Array(values) => array_depth(values),
+        _ => 0,
     }
+}
 
-    pub fn take_property_type(&mut self) -> Option<PropertyType> {
-        self.property_types.pop_front()
+fn string_flags(stats: &EncodeStats) -> StringFlags {
+    StringFlags {
+        all_ascii: stats.all_ascii,
+        front_coded: stats.front_coded,
+        lz: stats.lz_strings,
+        deduplicated: stats.deduplicated_strings,
     }
+}
 
-    pub fn take_array(&mut self) -> Option<Vec<PropertyValue>> {
-        let length = self.take_int()? as usize;
+// stats only count values that were in memory at once, so they always fit back in a usize
+fn stat_count(values: u64) -> usize {
+    usize::try_from(values).expect("counted from a usize")
+}
 
-        let mut values = Vec::with_capacity(length);
-        for _ in 0..length {
-            let tag = self.take_property_type()?;
+// a constant column's count isn't bounded by the input, and can be past what this target holds
+fn column_count(values: u64) -> Result<usize, DecodeError> {
+    usize::try_from(values).map_err(|_| DecodeError::LimitExceeded {
+        limit: Limit::ColumnValues,
+        max: usize::MAX as u64,
+    })
+}
 
-            let value = match tag {
-                PropertyType::String => PropertyValue::String(self.take_string()?),
-                PropertyType::Bool => PropertyValue::Bool(self.take_bool()?),
-                PropertyType::Integer => PropertyValue::Integer(self.take_int()?),
-                PropertyType::Array => PropertyValue::Array(self.take_array()?),
-            };
-            values.push(value);
-        }
+fn transpose_onto<T>(to: &mut Vec<T>, from: &mut Vec<T>, items: usize, stride: Option<usize>) {
+    let Some(stride) = stride.filter(|stride| *stride > 1) else {
+        to.append(from);
+        return;
+    };
+    let mut values: Vec<Option<T>> = from.drain(..).map(Some).collect();
+    let fields = (0..stride).flat_map(|field| (0..items).map(move |item| item * stride + field));
+    to.extend(fields.map(|index| values[index].take().expect("each value is moved once")));
+}
 
-        Some(values)
+fn column_stats(values: usize, start: u64, end: u64) -> ColumnStats {
+    ColumnStats {
+        values: values as u64,
+        bits: end - start,
     }
 }
 
-pub trait IntoFormat {
-    fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>)
-    where
+// format id, value counts and string flags, everything after the version and revision
+#[derive(Debug)]
+struct ColumnHeader {
+    // the `format_spec` option bits, put together from the flags of a revision before 13
+    options: u64,
+    format_id: u16,
+    integers: u64,
+    booleans: u64,
+    all_ascii: bool,
+    front_coded: bool,
+    lz_strings: bool,
+    deduplicated_strings: bool,
+    strings: u64,
+    property_types: u64,
+    lengths: u64,
+    column_offsets: bool,
+    schema: bool,
+    provenance: bool,
+    raw_strings: bool,
+    padded: bool,
+    kind_markers: bool,
+    // whether a non-constant integer column has the frame of reference flag
+    framed_integers: bool,
+    // the integer column's `IntHeaderPlan`
+    int_plan: IntHeaderPlan,
+    section_checksums: bool,
+    absent: bool,
+    // `format_spec::tag_bits` of the revision, for the sections' unpackers
+    tag_bits: u8,
+}
+
+impl ColumnHeader {
+    fn read(unpacker: &mut BitUnpacker, revision: u64) -> Result<ColumnHeader, DecodeError> {
+        let truncated = DecodeError::Truncated(Section::Header);
+        // everything after the header reads tags as wide as this revision wrote them
+        unpacker.set_tag_bits(format_spec::tag_bits(revision));
+        let legacy_counts = revision < 12;
+        let legacy_options = revision < 13;
+        let count = |unpacker: &mut BitUnpacker| match legacy_counts {
+            true => u64::try_from(unpacker.read_int()?).ok(),
+            false => unpacker.read_count(),
+        };
+        // before revision 13 the format id flag came first and the rest followed the counts
+        let mut options = match legacy_options {
+            true => match unpacker.read_bit().ok_or(truncated.clone())? {
+                true => OPTION_FORMAT_ID,
+                false => 0,
+            },
+            false => unpacker.read_small_int().ok_or(truncated.clone())?,
+        };
+        if options & !KNOWN_OPTIONS != 0 {
+            return Err(DecodeError::ReservedOptions(options & !KNOWN_OPTIONS));
+        }
+        let mut read = || {
+            let format_id = match options & OPTION_FORMAT_ID != 0 {
+                true => u16::try_from(unpacker.read_small_int()?).ok()?,
+                false => 0,
+            };
+            let int_plan = match options & OPTION_INT_TABLE != 0 {
+                true => IntHeaderPlan::from_table(u8::try_from(unpacker.read_small_int()?).ok()?)?,
+                false => IntHeaderPlan::default(),
+            };
+            let int_plan = match options & OPTION_INT_WIDTHS != 0 {
+                // a table or custom widths, never both
+                true if options & OPTION_INT_TABLE != 0 => return None,
+                true => IntHeaderPlan::from_mask(unpacker.read_small_int()?)?,
+                false => int_plan,
+            };
+            let integers = count(unpacker)?;
+            let booleans = count(unpacker)?;
+            let all_ascii = unpacker.read_bit()?;
+            let front_coded = unpacker.read_bit()?;
+            let strings = count(unpacker)?;
+            let property_types = count(unpacker)?;
+            let lengths = match legacy_counts {
+                true => unpacker.read_small_int()?,
+                false => unpacker.read_count()?,
+            };
+            if legacy_options {
+                for option in [
+                    OPTION_COLUMN_OFFSETS,
+                    OPTION_SCHEMA,
+                    OPTION_PROVENANCE,
+                    OPTION_RAW_STRINGS,
+                    OPTION_PADDED,
+                ] {
+                    if unpacker.read_bit()? {
+                        options |= option;
+                    }
+                }
+            }
+            let option = |option| options & option != 0;
+            // the checksums are in the column offsets table
+            if option(OPTION_SECTION_CHECKSUMS) && !option(OPTION_COLUMN_OFFSETS) {
+                return None;
+            }
+            Some(ColumnHeader {
+                options,
+                format_id,
+                integers,
+                booleans,
+                all_ascii,
+                front_coded,
+                strings,
+                property_types,
+                lengths,
+                column_offsets: option(OPTION_COLUMN_OFFSETS),
+                schema: option(OPTION_SCHEMA),
+                provenance: option(OPTION_PROVENANCE),
+                raw_strings: option(OPTION_RAW_STRINGS),
+                padded: option(OPTION_PADDED),
+                kind_markers: option(OPTION_KIND_MARKERS),
+                lz_strings: option(OPTION_LZ_STRINGS),
+                deduplicated_strings: option(OPTION_DEDUPLICATED_STRINGS),
+                framed_integers: revision >= 14,
+                int_plan,
+                section_checksums: option(OPTION_SECTION_CHECKSUMS),
+                absent: option(OPTION_ABSENT),
+                tag_bits: unpacker.tag_bits(),
+            })
+        };
+        let header = read().ok_or(truncated)?;
+        if header.absent {
+            let columns = [
+                (Section::Integers, header.integers),
+                (Section::Booleans, header.booleans),
+                (Section::Strings, header.strings),
+                (Section::PropertyTypes, header.property_types),
+                (Section::Lengths, header.lengths),
+                (Section::RawStrings, header.raw_strings as u64),
+            ];
+            if let Some((section, _)) = columns.into_iter().find(|(_, count)| *count != 0) {
+                return Err(DecodeError::AbsentWithValues(section));
+            }
+        }
+        Ok(header)
+    }
+
+    // the fewest bits the declared values could be packed into
+    fn string_flags(&self) -> StringFlags {
+        StringFlags {
+            all_ascii: self.all_ascii,
+            front_coded: self.front_coded,
+            lz: self.lz_strings,
+            deduplicated: self.deduplicated_strings,
+        }
+    }
+
+    fn integer_flags(&self) -> (bool, &[u8]) {
+        (self.framed_integers, self.int_plan.widths())
+    }
+
+    // What's left of `max_decoded_bytes` for the strings once the fixed size values are counted,
+    // `None` if the header alone is already over one of the limits.
+    fn string_budget(&self, limits: &DecodeLimits) -> Option<u64> {
+        let counts = [
+            self.integers,
+            self.booleans,
+            self.strings,
+            self.property_types,
+            self.lengths,
+        ];
+        if counts.iter().any(|count| *count > limits.max_column_values) {
+            return None;
+        }
+        let fixed = self
+            .integers
+            .saturating_mul(8)
+            .saturating_add(self.booleans)
+            .saturating_add(self.property_types)
+            .saturating_add(self.lengths.saturating_mul(8));
+        limits.max_decoded_bytes.checked_sub(fixed)
+    }
+
+    fn value_count(&self) -> u64 {
+        [
+            self.integers,
+            self.booleans,
+            self.strings,
+            self.property_types,
+            self.lengths,
+        ]
+        .into_iter()
+        .fold(0, u64::saturating_add)
+    }
+
+    fn minimum_bits(&self) -> u64 {
+        // the narrowest int is its 0 prefix bit and the first width
+        let narrowest_int = 1 + INT_HEADER_WIDTHS[0] as u64;
+        // a constant column is its flag and a single int
+        let narrowest_column_int = 1 + self.int_plan.widths()[0] as u64;
+        let integers = match self.integers {
+            0 | 1 => self.integers * narrowest_column_int,
+            _ => 1 + narrowest_column_int,
+        };
+        // every string has at least its length prefix
+        let strings = self.strings.saturating_mul(narrowest_int);
+        integers
+            .saturating_add(self.booleans)
+            .saturating_add(strings)
+            .saturating_add(self.property_types.saturating_mul(self.tag_bits as u64))
+            .saturating_add(self.lengths.saturating_mul(SMALL_INT_HEADER_BITS as u64))
+    }
+}
+
+/// Checks that `bytes` is a complete, well formed buffer for `version` without building any of the
+/// values, strings are decoded through a single scratch buffer as long as the longest one.
+///
+/// Stricter than `Deserializer::read_bytes`, which ignores anything after the last section.
+pub fn verify(bytes: &[u8], version: u8) -> Result<VerifyReport, DecodeError> {
+    let mut unpacker = BitUnpacker::new(bytes);
+    let truncated = DecodeError::Truncated;
+
+    let found = unpacker.read_byte().ok_or(truncated(Section::Header))?;
+    if found != version {
+        return Err(DecodeError::VersionMismatch {
+            expected: version,
+            found,
+        });
+    }
+    let revision = unpacker
+        .read_small_int()
+        .ok_or(truncated(Section::Header))?;
+    if !(OLDEST_READABLE_REVISION..=FORMAT_REVISION).contains(&revision) {
+        return Err(DecodeError::UnsupportedRevision(revision));
+    }
+
+    let header = ColumnHeader::read(&mut unpacker, revision)?;
+    let minimum_bits = header.minimum_bits();
+    let available_bits = unpacker.remaining_bits();
+    if minimum_bits > available_bits {
+        return Err(DecodeError::CountsExceedInput {
+            minimum_bits,
+            available_bits,
+        });
+    }
+
+    let mut report = VerifyReport {
+        counts: ColumnCounts {
+            integers: column_count(header.integers)?,
+            booleans: column_count(header.booleans)?,
+            strings: column_count(header.strings)?,
+            property_types: column_count(header.property_types)?,
+            lengths: column_count(header.lengths)?,
+        },
+        tail_values: 0,
+        // constant integer columns aren't bounded by the input size
+        decoded_bytes: header
+            .integers
+            .saturating_add(header.lengths)
+            .saturating_mul(8)
+            .saturating_add(header.booleans + header.property_types),
+    };
+
+    let mut scratch = Vec::new();
+    if header.column_offsets {
+        let sections = Sections::read(&mut unpacker, header.strings, header.section_checksums)
+            .ok_or(truncated(Section::Header))?;
+        if let Some(section) = sections.corrupt().first() {
+            return Err(DecodeError::ChecksumMismatch(*section));
+        }
+        report.decoded_bytes += verify_sections(&header, &sections, &mut scratch)?;
+    } else {
+        columns::skip_integers(&mut unpacker, header.integers, header.integer_flags())
+            .ok_or(truncated(Section::Integers))?;
+        columns::skip_booleans(&mut unpacker, header.booleans)
+            .ok_or(truncated(Section::Booleans))?;
+        report.decoded_bytes += columns::skip_strings(
+            &mut unpacker,
+            0,
+            header.strings,
+            header.string_flags(),
+            &mut scratch,
+        )?;
+        columns::skip_property_types(&mut unpacker, header.property_types)?;
+        columns::skip_lengths(&mut unpacker, header.lengths).ok_or(truncated(Section::Lengths))?;
+    }
+    if header.raw_strings {
+        let mut last = 0;
+        let mut decoded = 0;
+        // already counted against the input, verify has no limits of its own
+        let mut budget = u64::MAX;
+        let count = columns::read_raw_strings(
+            &mut unpacker,
+            &mut scratch,
+            &mut budget,
+            |position, bytes| {
+                last = position;
+                decoded += bytes.len() as u64;
+                Some(())
+            },
+        )
+        .ok_or(truncated(Section::RawStrings))?;
+        // past the end of the string column once the raw strings are slotted in
+        if last as u64 >= header.strings.saturating_add(count) {
+            return Err(truncated(Section::RawStrings));
+        }
+        report.counts.strings += column_count(count)?;
+        report.decoded_bytes += decoded;
+    }
+    if header.kind_markers {
+        let count = unpacker
+            .read_count()
+            .ok_or(truncated(Section::KindMarkers))?;
+        if count.saturating_mul(unpacker.tag_bits() as u64) > unpacker.remaining_bits() {
+            return Err(truncated(Section::KindMarkers));
+        }
+        columns::skip_property_types(&mut unpacker, count)?;
+    }
+
+    let names =
+        columns::skip_names(&mut unpacker, &mut scratch).ok_or(truncated(Section::Names))?;
+    if header.absent && names != 0 {
+        return Err(DecodeError::AbsentWithValues(Section::Names));
+    }
+    if header.provenance {
+        Provenance::read(&mut unpacker, &mut scratch).ok_or(truncated(Section::Provenance))?;
+    }
+
+    if unpacker.read_bit().ok_or(truncated(Section::Tail))? {
+        if header.absent {
+            return Err(DecodeError::AbsentWithValues(Section::Tail));
+        }
+        unpacker.align_to_byte();
+        let (values, decoded) = appender::skip_tail(&mut unpacker, &mut scratch)?;
+        report.tail_values = values;
+        report.decoded_bytes += decoded;
+    }
+
+    let rest = unpacker.remaining_bytes();
+    if header.padded {
+        // zeros and then their count, see `Serializer::finish_aligned`
+        let (&length, zeros) = rest.split_last().ok_or(truncated(Section::Padding))?;
+        if length as usize != rest.len() || zeros.iter().any(|byte| *byte != 0) {
+            return Err(truncated(Section::Padding));
+        }
+    } else if !rest.is_empty() {
+        return Err(DecodeError::TrailingBytes(rest.len()));
+    }
+
+    Ok(report)
+}
+
+/// What `peek_header` reads from the start of a buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub revision: u64,
+    /// The `format_spec` option bits.
+    pub flags: u64,
+    pub format_id: u16,
+    pub int_count: u64,
+    pub bool_count: u64,
+    /// Strings in the string column, not counting any `StringCodec::Raw` ones.
+    pub string_count: u64,
+    pub tag_count: u64,
+    pub length_count: u64,
+    /// Where the columns (or the column offsets table) start.
+    pub header_bits: u64,
+    /// The rest of the buffer is too short for the counts, which `verify` and the deserializer
+    /// refuse with `DecodeError::CountsExceedInput`.
+    pub counts_exceed_input: bool,
+}
+
+impl Header {
+    pub fn has(&self, option: u64) -> bool {
+        self.flags & option != 0
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "version: {}", self.version)?;
+        writeln!(f, "revision: {}", self.revision)?;
+        let options: Vec<&str> = OPTIONS
+            .iter()
+            .filter(|(_, option)| self.has(*option))
+            .map(|(name, _)| *name)
+            .collect();
+        match options.is_empty() {
+            true => writeln!(f, "options: none")?,
+            false => writeln!(f, "options: {}", options.join(", "))?,
+        }
+        writeln!(f, "format id: {}", self.format_id)?;
+        let counts = [
+            ("integers", self.int_count),
+            ("booleans", self.bool_count),
+            ("strings", self.string_count),
+            ("property types", self.tag_count),
+            ("lengths", self.length_count),
+        ];
+        for (name, count) in counts {
+            writeln!(f, "{name}: {count} values")?;
+        }
+        write!(f, "header: {} bits", self.header_bits)?;
+        if self.counts_exceed_input {
+            writeln!(f)?;
+            write!(f, "the counts are more than the buffer holds")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the version byte and the header without touching any of the columns or allocating, for
+/// routing a buffer before paying for its decode. Whatever version it was written with is
+/// accepted, only a header that can't be read (or a revision this can't read) is an error.
+pub fn peek_header(bytes: &[u8]) -> Result<Header, DecodeError> {
+    let mut unpacker = BitUnpacker::new(bytes);
+    let truncated = DecodeError::Truncated(Section::Header);
+    let version = unpacker.read_byte().ok_or(truncated.clone())?;
+    let revision = unpacker.read_small_int().ok_or(truncated)?;
+    if !(OLDEST_READABLE_REVISION..=FORMAT_REVISION).contains(&revision) {
+        return Err(DecodeError::UnsupportedRevision(revision));
+    }
+    let header = ColumnHeader::read(&mut unpacker, revision)?;
+    Ok(Header {
+        version,
+        revision,
+        flags: header.options,
+        format_id: header.format_id,
+        int_count: header.integers,
+        bool_count: header.booleans,
+        string_count: header.strings,
+        tag_count: header.property_types,
+        length_count: header.lengths,
+        header_bits: unpacker.bit_position(),
+        counts_exceed_input: header.minimum_bits() > unpacker.remaining_bits(),
+    })
+}
+
+/// Every column of a buffer written with `Serializer::set_section_checksums` that doesn't match
+/// its checksum, empty when they all do (or the buffer has no checksums). Nothing is decoded, so
+/// unlike `verify` this doesn't stop at the first one.
+pub fn check_sections(bytes: &[u8], version: u8) -> Result<Vec<Section>, DecodeError> {
+    let mut unpacker = BitUnpacker::new(bytes);
+    let truncated = DecodeError::Truncated(Section::Header);
+    let found = unpacker.read_byte().ok_or(truncated.clone())?;
+    if found != version {
+        return Err(DecodeError::VersionMismatch {
+            expected: version,
+            found,
+        });
+    }
+    let revision = unpacker.read_small_int().ok_or(truncated.clone())?;
+    if !(OLDEST_READABLE_REVISION..=FORMAT_REVISION).contains(&revision) {
+        return Err(DecodeError::UnsupportedRevision(revision));
+    }
+    let header = ColumnHeader::read(&mut unpacker, revision)?;
+    if !header.section_checksums {
+        return Ok(Vec::new());
+    }
+    let sections = Sections::read(&mut unpacker, header.strings, true).ok_or(truncated)?;
+    Ok(sections.corrupt())
+}
+
+/// The buffer decoded and written again in the one encoding every buffer with the same values
+/// has, so canonical buffers can be compared or hashed byte for byte. The canonical form:
+///
+/// - is the current `FORMAT_REVISION`, whatever revision `bytes` was
+/// - has no column offsets, section checksums, kind markers or padding, and the strings aren't
+///   front coded or LZ coded (`finish` still picks their codec by size, and deduplicates repeats
+///   when that's smaller)
+/// - writes `StringCodec::Raw` strings into the string column with the rest
+/// - uses the `analyze_ints` plan for its integers
+/// - lists the named values by name
+///
+/// The format id, schema, provenance, names, tail array and absent marker are all kept. `bytes[0]`
+/// is taken as the version, which is kept too.
+pub fn canonicalize(bytes: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let version = *bytes
+        .first()
+        .ok_or(DecodeError::Truncated(Section::Header))?;
+    let mut deserializer = Deserializer::new();
+    deserializer.load(bytes, version)?;
+
+    let mut names: Vec<_> = deserializer.names.drain().collect();
+    names.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    let tail = deserializer.tail.take();
+    let mut serializer = match &deserializer.schema {
+        Some(schema) => Serializer::with_schema(schema),
+        None => Serializer::new(),
+    };
+    serializer.set_format_id(deserializer.format_id);
+    serializer.absent = deserializer.absent;
+    if let Some(provenance) = &deserializer.provenance {
+        serializer
+            .set_provenance(provenance.timestamp, &provenance.producer)
+            .expect("read from a buffer, so it was short enough to write");
+    }
+    serializer.integers.extend(deserializer.integers.drain());
+    serializer.set_int_header_plan(int_plan::analyze_ints(&serializer.integers));
+    serializer.booleans.extend(deserializer.booleans.drain());
+    serializer
+        .property_types
+        .extend(deserializer.property_types.drain());
+    serializer.lengths.extend(deserializer.lengths.drain());
+    // by position, so the raw strings land back where they were among the others
+    let strings = deserializer.strings.len() + deserializer.raw_strings.len();
+    for _ in 0..strings {
+        let string = deserializer
+            .take_unmarked_str()
+            .expect("counted from the columns");
+        serializer.strings.push(Cow::Owned(string.to_owned()));
+    }
+    serializer.names = names
+        .iter()
+        .map(|(name, (kind, index))| (name.as_str(), *kind, *index))
+        .collect();
+    if let Some(tail) = &tail {
+        serializer.write_tail_array(tail);
+    }
+
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, version);
+    Ok(buffer)
+}
+
+/// Whether `bytes` is already what `canonicalize` would write, `false` if it doesn't decode.
+pub fn is_canonical(bytes: &[u8]) -> bool {
+    canonicalize(bytes).is_ok_and(|canonical| canonical == bytes)
+}
+
+// every section has to be consumed up to its final padding, returns the decoded string size
+fn verify_sections(
+    header: &ColumnHeader,
+    sections: &Sections,
+    scratch: &mut Vec<u8>,
+) -> Result<u64, DecodeError> {
+    fn section<T>(
+        bytes: &[u8],
+        skip: impl FnOnce(&mut BitUnpacker) -> Result<T, DecodeError>,
+    ) -> Result<T, DecodeError> {
+        let mut unpacker = BitUnpacker::new(bytes);
+        let result = skip(&mut unpacker)?;
+        match unpacker.remaining_bytes().len() {
+            0 => Ok(result),
+            trailing => Err(DecodeError::TrailingBytes(trailing)),
+        }
+    }
+    let truncated = DecodeError::Truncated;
+
+    section(sections.integers, |unpacker| {
+        columns::skip_integers(unpacker, header.integers, header.integer_flags())
+            .ok_or(truncated(Section::Integers))
+    })?;
+    section(sections.booleans, |unpacker| {
+        columns::skip_booleans(unpacker, header.booleans).ok_or(truncated(Section::Booleans))
+    })?;
+    let mut decoded = 0;
+    for (index, bytes) in sections.strings.iter().enumerate() {
+        decoded += section(bytes, |unpacker| {
+            columns::skip_strings(
+                unpacker,
+                index * STRING_SECTION_LEN,
+                columns::section_strings(header.strings, index),
+                header.string_flags(),
+                scratch,
+            )
+        })?;
+    }
+    section(sections.property_types, |unpacker| {
+        unpacker.set_tag_bits(header.tag_bits);
+        columns::skip_property_types(unpacker, header.property_types)
+    })?;
+    section(sections.lengths, |unpacker| {
+        columns::skip_lengths(unpacker, header.lengths).ok_or(truncated(Section::Lengths))
+    })?;
+    Ok(decoded)
+}
+
+// bit position of the first section, the header and offsets table before it count as the header
+fn sections_start(sections: &Sections, bytes: &[u8]) -> u64 {
+    (sections.integers.as_ptr() as usize - bytes.as_ptr() as usize) as u64 * 8
+}
+
+pub use crate::diff::{FormatDiff, diff};
+pub use crate::roundtrip::{RoundtripError, assert_bytes_eq, assert_roundtrip, check_roundtrip};
+
+pub fn is_32_127(string: &str) -> bool {
+    string.chars().all(|c| c as u32 >= 32 && c as u32 <= 127)
+}
+
+// Where a `Deserializer` is between buffers:
+//
+// Empty --read_bytes--> Loaded --take_*--> Drained --read_bytes--> Loaded ...
+//
+// `read_bytes` on a `Loaded` deserializer fails rather than mixing a second buffer in behind the
+// first one's unread values, `clear` drops them and goes back to `Empty`. A failed `read_bytes`
+// also leaves it `Empty`. Reading from an `Empty` deserializer gives `DecodeError::NotLoaded`
+// from the `Result` returning readers (cursors, `take_array_checked`, `events`), `None` otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadState {
+    Empty,
+    // values from the last buffer are still unread
+    Loaded,
+    // everything from the last buffer, tail included, has been taken
+    Drained,
+}
+
+#[derive(Debug, Default)]
+pub struct Deserializer {
+    pub(crate) integers: Column<i64>,
+    pub(crate) strings: TextColumn,
+    // each string is decoded into here before it's copied onto the end of `strings`
+    pub(crate) scratch: Vec<u8>,
+    // `StringCodec::Raw` strings and where each one sits among all of the strings, see
+    // `read_raw_strings`
+    raw_strings: TextColumn,
+    raw_positions: Column<usize>,
+    pub(crate) booleans: Column<bool>,
+    pub(crate) property_types: Column<PropertyType>,
+    pub(crate) lengths: Column<u64>,
+    // `Serializer::set_kind_markers`, what's left of them and how many were taken
+    pub(crate) kinds: Column<PropertyType>,
+    kinds_taken: usize,
+    // the last `take_*` refused for not matching its marker, see `take_kind_mismatch`
+    kind_mismatch: Option<DecodeError>,
+    pub(crate) tail: Option<Vec<PropertyValue>>,
+    // name -> (pool, index within everything that was written to that pool)
+    names: HashMap<String, (PropertyType, usize)>,
+    // the (pool, index) of the named values taken, still in the pool but no longer remaining
+    named_taken: Vec<(PropertyType, usize)>,
+    pub(crate) stats: DecodeStats,
+    // byte offset of the tail array's length prefix within the last buffer read
+    pub(crate) tail_offset: Option<usize>,
+    // written with `Serializer::finish_aligned`
+    pub(crate) padded: bool,
+    // width of the last buffer's property types, which depends on its revision
+    pub(crate) tag_bits: u8,
+    format_id: u16,
+    version: u8,
+    schema: Option<Schema>,
+    provenance: Option<Provenance>,
+    // see `Serializer::write_absent`
+    absent: bool,
+    pub(crate) limits: DecodeLimits,
+    // `set_progress`, kept by `clear`
+    progress: Progress,
+    // the last `read_bytes` succeeded and `clear` hasn't been called since, see `LoadState`
+    pub(crate) loaded: bool,
+    // what's left of `DecodeLimits::max_work` for the last buffer, and whether it ran out
+    work_left: u64,
+    // the limit or version mismatch the last `read_bytes` stopped at, for `load` to report
+    refused: Option<DecodeError>,
+}
+
+impl Deserializer {
+    // Uses `DecodeLimits::relaxed`, which is meant for trusted data, see `new_untrusted`.
+    pub fn new() -> Self {
+        Self {
+            integers: Default::default(),
+            strings: Default::default(),
+            scratch: Vec::new(),
+            raw_strings: Default::default(),
+            raw_positions: Default::default(),
+            booleans: Default::default(),
+            property_types: Default::default(),
+            lengths: Default::default(),
+            kinds: Default::default(),
+            kinds_taken: 0,
+            kind_mismatch: None,
+            tail: None,
+            names: HashMap::new(),
+            named_taken: Vec::new(),
+            stats: DecodeStats::default(),
+            tail_offset: None,
+            padded: false,
+            tag_bits: TAG_BITS,
+            format_id: 0,
+            version: 0,
+            schema: None,
+            provenance: None,
+            absent: false,
+            limits: DecodeLimits::relaxed(),
+            progress: Progress::default(),
+            loaded: false,
+            work_left: 0,
+            refused: None,
+        }
+    }
+
+    // `new` with `DecodeLimits::strict`, for input from outside
+    pub fn new_untrusted() -> Self {
+        Self::with_limits(DecodeLimits::strict())
+    }
+
+    pub fn with_limits(limits: DecodeLimits) -> Self {
+        Self {
+            limits,
+            ..Self::new()
+        }
+    }
+
+    // Called as `read_bytes` (and `load`, `read_schema`, ...) decodes a buffer, with the number of
+    // columns done, the bits consumed so far and the bits in the buffer. It's called at the end of
+    // each of the five columns and every 4096 values within one. Returning `Break`
+    // stops the decode: `read_bytes` gives `None`, `load` gives `DecodeError::Cancelled`, and the
+    // deserializer is left empty as after any failed read.
+    //
+    // Buffers written with `Serializer::set_column_offsets` read their columns together, so the
+    // hook is only called once they're all done.
+    pub fn set_progress(
+        &mut self,
+        hook: impl FnMut(usize, u64, u64) -> ControlFlow<()> + Send + 'static,
+    ) {
+        self.progress.set(Some(Box::new(hook)));
+    }
+
+    pub fn clear_progress(&mut self) {
+        self.progress.set(None);
+    }
+
+    // drops whatever is left of the last buffer, see `LoadState`
+    pub fn clear(&mut self) {
+        self.loaded = false;
+        self.integers.clear();
+        self.strings.clear();
+        self.raw_strings.clear();
+        self.raw_positions.clear();
+        self.booleans.clear();
+        self.property_types.clear();
+        self.lengths.clear();
+        self.kinds.clear();
+        self.kinds_taken = 0;
+        self.kind_mismatch = None;
+        self.tail = None;
+        self.names.clear();
+        self.named_taken.clear();
+        self.stats = DecodeStats::default();
+        self.tail_offset = None;
+        self.padded = false;
+        self.format_id = 0;
+        self.version = 0;
+        self.schema = None;
+        self.provenance = None;
+        self.absent = false;
+    }
+
+    pub fn state(&self) -> LoadState {
+        if !self.loaded {
+            LoadState::Empty
+        } else if self.remaining_counts().is_empty() && self.tail.is_none() {
+            LoadState::Drained
+        } else {
+            LoadState::Loaded
+        }
+    }
+
+    // `Err(NotLoaded)` for the `Result` returning readers
+    pub(crate) fn check_loaded(&self) -> Result<(), DecodeError> {
+        match self.loaded {
+            true => Ok(()),
+            false => Err(DecodeError::NotLoaded),
+        }
+    }
+
+    // `read_bytes` with the reason it failed. Buffers that pass `verify` but still don't decode
+    // went over one of the deserializer's limits.
+    pub fn load(&mut self, bytes: &[u8], version: u8) -> Result<(), DecodeError> {
+        if self.state() == LoadState::Loaded {
+            return Err(DecodeError::AlreadyLoaded {
+                remaining: self.remaining_counts(),
+            });
+        }
+        if self.read_bytes(bytes, version).is_some() {
+            return Ok(());
+        }
+        if self.progress.cancelled() {
+            return Err(DecodeError::Cancelled);
+        }
+        if let Some(error) = self.refused.clone() {
+            return Err(error);
+        }
+        let report = verify(bytes, version)?;
+        let counts = report.counts;
+        let most = [
+            counts.integers,
+            counts.booleans,
+            counts.strings,
+            counts.property_types,
+            counts.lengths,
+        ]
+        .into_iter()
+        .max()
+        .unwrap_or_default();
+        Err(if most as u64 > self.limits.max_column_values {
+            DecodeError::LimitExceeded {
+                limit: Limit::ColumnValues,
+                max: self.limits.max_column_values,
+            }
+        } else {
+            DecodeError::LimitExceeded {
+                limit: Limit::DecodedBytes,
+                max: self.limits.max_decoded_bytes,
+            }
+        })
+    }
+
+    // the `Serializer::set_format_id` of the last buffer read
+    pub fn format_id(&self) -> u16 {
+        self.format_id
+    }
+
+    // the version byte of the last buffer read
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    // the `Serializer::with_schema` of the last buffer read
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    // the `Serializer::set_provenance` of the last buffer read
+    pub fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+
+    // the last buffer read was written with `Serializer::write_absent`
+    pub fn is_absent(&self) -> bool {
+        self.absent
+    }
+
+    // `read_bytes` for buffers written with `Serializer::with_schema`, `None` if the buffer
+    // doesn't decode or wasn't written with one. The values after the schema are left to `take_*`.
+    pub fn read_schema(&mut self, bytes: &[u8], version: u8) -> Option<Schema> {
+        self.read_bytes(bytes, version)?;
+        self.schema.clone()
+    }
+
+    fn take_schema(&mut self) -> Option<Schema> {
+        let count = usize::try_from(self.lengths.pop_front()?).ok()?;
+        if count > self.property_types.len() {
+            return None;
+        }
+        let fields = (0..count)
+            .map(|_| {
+                let name = self.take_unmarked_str()?.to_owned();
+                Some((name, self.take_property_type()?))
+            })
+            .collect::<Option<_>>()?;
+        Some(Schema { fields })
+    }
+
+    // ideally a `Result`
+    pub fn read_bytes(&mut self, bytes: &[u8], version: u8) -> Option<()> {
+        self.read_bytes_with(bytes, version, false)
+    }
+
+    // Decodes each column on the rayon pool when the buffer was written with
+    // `Serializer::set_column_offsets`, otherwise (or for small buffers, or a pool of a single
+    // thread, where it's only overhead) the same as `read_bytes`. `benches/parallel_decode` times
+    // both across buffer and pool sizes.
+    #[cfg(feature = "rayon")]
+    pub fn read_bytes_parallel(&mut self, bytes: &[u8], version: u8) -> Option<()> {
+        let parallel = bytes.len() >= PARALLEL_MIN_BYTES && rayon::current_num_threads() > 1;
+        self.read_bytes_with(bytes, version, parallel)
+    }
+
+    fn read_bytes_with(&mut self, bytes: &[u8], version: u8, parallel: bool) -> Option<()> {
+        if self.state() == LoadState::Loaded {
+            return None;
+        }
+        self.clear();
+        if self.read_buffer(bytes, version, parallel).is_none() {
+            // nothing from a buffer that didn't decode (or was cancelled) is left to take
+            self.clear();
+            return None;
+        }
+        self.loaded = true;
+        Some(())
+    }
+
+    fn read_buffer(&mut self, bytes: &[u8], version: u8, parallel: bool) -> Option<()> {
+        self.stats.total_bytes = bytes.len() as u64;
+        self.progress.start(bytes.len() as u64 * 8);
+        self.start_work();
+        let mut unpacker = BitUnpacker::new(bytes);
+
+        let found = unpacker.read_byte()?;
+        if found != version {
+            self.refused = Some(DecodeError::VersionMismatch {
+                expected: version,
+                found,
+            });
+            return None;
+        }
+        self.version = version;
+
+        let revision = unpacker.read_small_int()?;
+        if !(OLDEST_READABLE_REVISION..=FORMAT_REVISION).contains(&revision) {
+            return None;
+        }
+
+        let header = ColumnHeader::read(&mut unpacker, revision).ok()?;
+        // fail before looping over (and allocating for) counts the input can't hold
+        if header.minimum_bits() > unpacker.remaining_bits() {
+            return None;
+        }
+        let mut string_budget = header.string_budget(&self.limits)?;
+        self.charge_work(header.value_count())?;
+        self.reserve(&header, unpacker.remaining_bits())?;
+        #[cfg(feature = "trace")]
+        log::debug!("decoding version {version} revision {revision}: {header:?}");
+        self.format_id = header.format_id;
+        self.absent = header.absent;
+        self.padded = header.padded;
+        self.tag_bits = header.tag_bits;
+        self.stats.all_ascii = header.all_ascii;
+        self.stats.front_coded = header.front_coded;
+        self.stats.lz_strings = header.lz_strings;
+        self.stats.deduplicated_strings = header.deduplicated_strings;
+
+        if header.column_offsets {
+            let sections = Sections::read(&mut unpacker, header.strings, header.section_checksums)?;
+            if !sections.corrupt().is_empty() {
+                return None;
+            }
+            self.stats.header_bits = sections_start(&sections, bytes);
+            self.read_sections(&header, &sections, parallel, &mut string_budget)?;
+        } else {
+            self.stats.header_bits = unpacker.bit_position();
+            self.read_packed_columns(&mut unpacker, &header, &mut string_budget)?;
+        }
+        if header.raw_strings {
+            self.read_raw_strings(&mut unpacker, &mut string_budget)?;
+            self.charge_work(self.raw_strings.len() as u64)?;
+        }
+        if header.kind_markers {
+            self.read_kind_markers(&mut unpacker)?;
+        }
+        if header.schema {
+            self.schema = Some(self.take_schema()?);
+        }
+
+        let start = unpacker.bit_position();
+        columns::read_names(&mut unpacker, &mut self.names)?;
+        if header.absent && !self.names.is_empty() {
+            return None;
+        }
+        let stats = &mut self.stats;
+        stats.names = column_stats(self.names.len(), start, unpacker.bit_position());
+        if header.provenance {
+            self.provenance = Some(Provenance::read(&mut unpacker, &mut self.scratch)?);
+        }
+
+        let start = unpacker.bit_position();
+        let has_tail = unpacker.read_bit()?;
+        if has_tail {
+            if header.absent {
+                return None;
+            }
+            unpacker.align_to_byte();
+            self.tail_offset = Some(usize::try_from(unpacker.bit_position() / 8).ok()?);
+            let budget = &mut TailBudget::new(self.limits, string_budget, self.work_left);
+            let tail = match appender::read_tail(&mut unpacker, budget) {
+                Ok(tail) => tail,
+                Err(error) => {
+                    // anything else `verify` finds again for `load`
+                    if matches!(
+                        error,
+                        DecodeError::LimitExceeded { .. } | DecodeError::WorkBudgetExceeded { .. }
+                    ) {
+                        self.refused = Some(error);
+                    }
+                    return None;
+                }
+            };
+            self.work_left = budget.work_left();
+            let stats = &mut self.stats;
+            stats.tail = column_stats(tail.len(), start, unpacker.bit_position());
+            self.tail = Some(tail);
+        } else {
+            stats.tail = column_stats(0, start, unpacker.bit_position());
+        }
+
+        Some(())
+    }
+
+    fn read_packed_columns(
+        &mut self,
+        unpacker: &mut BitUnpacker,
+        header: &ColumnHeader,
+        string_budget: &mut u64,
+    ) -> Option<()> {
+        let stats = &mut self.stats;
+        let progress = &mut self.progress;
+
+        let start = unpacker.bit_position();
+        stats.constant_integers = columns::read_integers(
+            unpacker,
+            header.integers,
+            header.integer_flags(),
+            &mut self.integers,
+            progress,
+        )?;
+        stats.integers = column_stats(self.integers.len(), start, unpacker.bit_position());
+        progress.column_done(unpacker.bit_position())?;
+
+        let start = unpacker.bit_position();
+        columns::read_booleans(unpacker, header.booleans, &mut self.booleans, progress)?;
+        stats.booleans = column_stats(self.booleans.len(), start, unpacker.bit_position());
+        progress.column_done(unpacker.bit_position())?;
+
+        let start = unpacker.bit_position();
+        columns::read_strings(
+            unpacker,
+            header.strings,
+            header.string_flags(),
+            &mut self.strings,
+            &mut self.scratch,
+            string_budget,
+            progress,
+        )?;
+        stats.strings = column_stats(self.strings.len(), start, unpacker.bit_position());
+        progress.column_done(unpacker.bit_position())?;
+
+        let start = unpacker.bit_position();
+        let tags = &mut self.property_types;
+        columns::read_property_types(unpacker, header.property_types, tags, progress)?;
+        stats.property_types =
+            column_stats(self.property_types.len(), start, unpacker.bit_position());
+        progress.column_done(unpacker.bit_position())?;
+
+        let start = unpacker.bit_position();
+        columns::read_lengths(unpacker, header.lengths, &mut self.lengths, progress)?;
+        stats.lengths = column_stats(self.lengths.len(), start, unpacker.bit_position());
+        progress.column_done(unpacker.bit_position())
+    }
+
+    // every section gets its own unpacker, so they can run in any order (or all at once)
+    fn read_sections(
+        &mut self,
+        header: &ColumnHeader,
+        sections: &Sections,
+        parallel: bool,
+        string_budget: &mut u64,
+    ) -> Option<()> {
+        fn section<T>(
+            bytes: &[u8],
+            read: impl FnOnce(&mut BitUnpacker) -> Option<T>,
+        ) -> Option<(T, u64)> {
+            let mut unpacker = BitUnpacker::new(bytes);
+            let result = read(&mut unpacker)?;
+            Some((result, unpacker.bit_position()))
+        }
+
+        let Deserializer {
+            integers,
+            booleans,
+            property_types,
+            lengths,
+            ..
+        } = self;
+        let section_budget = *string_budget;
+
+        let read_integers = || {
+            section(sections.integers, |unpacker| {
+                let flags = header.integer_flags();
+                let progress = &mut Progress::default();
+                columns::read_integers(unpacker, header.integers, flags, integers, progress)
+            })
+        };
+        let read_booleans = || {
+            section(sections.booleans, |unpacker| {
+                columns::read_booleans(
+                    unpacker,
+                    header.booleans,
+                    booleans,
+                    &mut Progress::default(),
+                )
+            })
+        };
+        // each section of strings decodes into its own queue, they're appended in order after
+        let read_strings = || {
+            columns::map_sections(parallel, &sections.strings, |index, bytes| {
+                let mut strings = TextColumn::default();
+                let count = columns::section_strings(header.strings, index);
+                // each section can use all of the budget, it's checked across all of them after
+                let mut budget = section_budget;
+                let ((), bits) = section(bytes, |unpacker| {
+                    let flags = header.string_flags();
+                    let scratch = &mut Vec::new();
+                    columns::read_strings(
+                        unpacker,
+                        count,
+                        flags,
+                        &mut strings,
+                        scratch,
+                        &mut budget,
+                        &mut Progress::default(),
+                    )
+                })?;
+                Some((strings, bits, section_budget - budget))
+            })
+        };
+        let read_property_types = || {
+            section(sections.property_types, |unpacker| {
+                unpacker.set_tag_bits(header.tag_bits);
+                let progress = &mut Progress::default();
+                columns::read_property_types(
+                    unpacker,
+                    header.property_types,
+                    property_types,
+                    progress,
+                )
+            })
+        };
+        let read_lengths = || {
+            section(sections.lengths, |unpacker| {
+                columns::read_lengths(unpacker, header.lengths, lengths, &mut Progress::default())
+            })
+        };
+
+        use columns::join;
+        let ((integers_read, booleans_read), (strings_read, (tags_read, lengths_read))) = join(
+            parallel,
+            || join(parallel, read_integers, read_booleans),
+            || {
+                join(parallel, read_strings, || {
+                    join(parallel, read_property_types, read_lengths)
+                })
+            },
+        );
+
+        // The sections are read together, possibly on other threads, so the hook only hears about
+        // them once they're all done. Consumed is the end of each column's sections.
+        let progress = &mut self.progress;
+        let mut consumed = self.stats.header_bits;
+        let mut column_done = |sections: &[&[u8]]| {
+            consumed += sections
+                .iter()
+                .map(|bytes| bytes.len() as u64 * 8)
+                .sum::<u64>();
+            progress.column_done(consumed)
+        };
+
+        let stats = &mut self.stats;
+        let (constant, bits) = integers_read?;
+        stats.constant_integers = constant;
+        stats.integers = column_stats(self.integers.len(), 0, bits);
+        column_done(&[sections.integers])?;
+        let ((), bits) = booleans_read?;
+        stats.booleans = column_stats(self.booleans.len(), 0, bits);
+        column_done(&[sections.booleans])?;
+        let mut string_bits = 0;
+        let mut string_bytes = 0u64;
+        for (mut section, bits, bytes) in strings_read? {
+            self.strings.append(&mut section);
+            string_bits += bits;
+            string_bytes = string_bytes.saturating_add(bytes);
+        }
+        *string_budget = string_budget.checked_sub(string_bytes)?;
+        stats.strings = column_stats(self.strings.len(), 0, string_bits);
+        column_done(&sections.strings)?;
+        let ((), bits) = tags_read?;
+        stats.property_types = column_stats(self.property_types.len(), 0, bits);
+        column_done(&[sections.property_types])?;
+        let ((), bits) = lengths_read?;
+        stats.lengths = column_stats(self.lengths.len(), 0, bits);
+        column_done(&[sections.lengths])
+    }
+
+    // Sizes the columns for the header's counts up front rather than growing them value by value,
+    // after `minimum_bits` has checked they fit in the input. A constant integer column is the
+    // exception, its count can be far more than the input could otherwise hold, so it's only
+    // reserved once it's been read.
+    fn reserve(&mut self, header: &ColumnHeader, available_bits: u64) -> Option<()> {
+        let count = |count: u64| usize::try_from(count).ok();
+        let narrowest_int = 1 + header.int_plan.widths()[0] as u64;
+        let integers = header.integers.min(available_bits / narrowest_int);
+        self.integers.try_reserve(count(integers)?)?;
+        self.booleans.try_reserve(count(header.booleans)?)?;
+        self.strings.try_reserve(count(header.strings)?)?;
+        self.property_types
+            .try_reserve(count(header.property_types)?)?;
+        self.lengths.try_reserve(count(header.lengths)?)
+    }
+
+    fn read_kind_markers(&mut self, unpacker: &mut BitUnpacker) -> Option<()> {
+        let count = unpacker.read_count()?;
+        if count > self.limits.max_column_values
+            || count.saturating_mul(unpacker.tag_bits() as u64) > unpacker.remaining_bits()
+        {
+            return None;
+        }
+        self.charge_work(count)?;
+        self.kinds.try_reserve(usize::try_from(count).ok()?)?;
+        columns::read_property_types(unpacker, count, &mut self.kinds, &mut Progress::default())
+    }
+
+    // The strings written with `StringCodec::Raw` are kept in their own column rather than
+    // merged into `strings`, the `take_*` string methods slot them back in by position.
+    fn read_raw_strings(
+        &mut self,
+        unpacker: &mut BitUnpacker,
+        string_budget: &mut u64,
+    ) -> Option<()> {
+        let start = unpacker.bit_position();
+        let (raw_strings, raw_positions) = (&mut self.raw_strings, &mut self.raw_positions);
+        let count = columns::read_raw_strings(
+            unpacker,
+            &mut self.scratch,
+            string_budget,
+            |position, bytes| {
+                raw_strings.push(bytes);
+                raw_positions.push_back(position);
+                Some(())
+            },
+        )?;
+        let count = usize::try_from(count).ok()?;
+        self.stats.raw_strings = column_stats(count, start, unpacker.bit_position());
+
+        let total = self.strings.len() + raw_strings.len();
+        if total as u64 > self.limits.max_column_values {
+            return None;
+        }
+        // positions only increase, so only the last one can be past the end
+        match raw_positions.back() {
+            Some(&last) if last >= total => None,
+            _ => Some(()),
+        }
+    }
+
+    // stats for the last `read_bytes`, only partially filled in if it failed
+    pub fn last_stats(&self) -> &DecodeStats {
+        &self.stats
+    }
+
+    pub fn cursor(&mut self) -> ReadCursor<'_> {
+        ReadCursor::new(self)
+    }
+
+    // what's left in the pools less the named values already taken from them
+    pub(crate) fn remaining_counts(&self) -> ColumnCounts {
+        let named = |kind| {
+            let taken = self.taken_count(kind);
+            self.named_taken
+                .iter()
+                .filter(|&&(named, index)| named == kind && index >= taken)
+                .count()
+        };
+        ColumnCounts {
+            integers: self.integers.len() - named(PropertyType::Integer),
+            booleans: self.booleans.len() - named(PropertyType::Bool),
+            strings: self.pooled_strings() - named(PropertyType::String),
+            property_types: self.property_types.len(),
+            lengths: self.lengths.len(),
+        }
+    }
+
+    // the strings left for the positional takes, named ones included
+    pub(crate) fn pooled_strings(&self) -> usize {
+        self.strings.len() + self.raw_strings.len()
+    }
+
+    pub fn take_int(&mut self) -> Option<i64> {
+        self.check_kind(PropertyType::Integer)?;
+        self.integers.pop_front()
+    }
+
+    pub fn take_bool(&mut self) -> Option<bool> {
+        self.check_kind(PropertyType::Bool)?;
+        self.booleans.pop_front()
+    }
+
+    // Only for buffers written with `Serializer::set_kind_markers`: the next value taken has to
+    // be the kind the writer wrote next, otherwise nothing is taken and the `take_*` returns
+    // `None` with the mismatch kept for `take_kind_mismatch`.
+    fn check_kind(&mut self, expected: PropertyType) -> Option<()> {
+        let Some(&found) = self.kinds.get(0) else {
+            return Some(());
+        };
+        if found != expected {
+            self.kind_mismatch = Some(DecodeError::KindMismatch {
+                index: self.kinds_taken,
+                expected,
+                found,
+            });
+            return None;
+        }
+        self.kinds.pop_front();
+        self.kinds_taken += 1;
+        Some(())
+    }
+
+    // Why the last `take_*` returned `None` if it was a kind marker mismatch, `index` counts every
+    // value taken so far, array lengths and elements included.
+    pub fn take_kind_mismatch(&mut self) -> Option<DecodeError> {
+        self.kind_mismatch.take()
+    }
+
+    // what a `take_*` that came up empty reports, the kind mismatch if that's why
+    fn missing(&mut self, section: Section) -> DecodeError {
+        self.take_kind_mismatch()
+            .unwrap_or(DecodeError::Truncated(section))
+    }
+
+    // outer `None` is a decode failure, inner `None` is an absent value
+    pub fn take_optional<T>(
+        &mut self,
+        take: impl FnOnce(&mut Self) -> Option<T>,
+    ) -> Option<Option<T>> {
+        if self.take_bool()? {
+            take(self).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    pub fn take_string(&mut self) -> Option<String> {
+        self.take_str().map(str::to_owned)
+    }
+
+    // short strings are stored inline, so unlike `take_string` this never allocates for them
+    #[cfg(feature = "small-string")]
+    pub fn take_small_string(&mut self) -> Option<crate::small_string::SmallString> {
+        self.take_str().map(Into::into)
+    }
+
+    // Every string is decoded into the deserializer's own buffer, so this borrows from it rather
+    // than allocating. Still a `Cow` so callers don't break if some strings stop being kept that
+    // way.
+    pub fn take_string_cow(&mut self) -> Option<Cow<'_, str>> {
+        self.take_str().map(Cow::Borrowed)
+    }
+
+    fn take_str(&mut self) -> Option<&str> {
+        self.check_kind(PropertyType::String)?;
+        self.take_unmarked_str()
+    }
+
+    fn take_unmarked_str(&mut self) -> Option<&str> {
+        if self.raw_positions.get(0) == Some(&self.strings_taken()) {
+            self.raw_positions.pop_front();
+            return self.raw_strings.pop_front();
+        }
+        self.strings.pop_front()
+    }
+
+    // strings taken so far, by position or by name
+    fn strings_taken(&self) -> usize {
+        self.taken_count(PropertyType::String)
+    }
+
+    // the `index`th string that hasn't been taken yet
+    pub(crate) fn string_at(&self, index: usize) -> Option<&str> {
+        let position = self.strings_taken() + index;
+        let before = self
+            .raw_positions
+            .as_slice()
+            .partition_point(|&raw| raw < position);
+        match self.raw_positions.get(before) {
+            Some(&raw) if raw == position => self.raw_strings.get(before),
+            _ => self.strings.get(index - before),
+        }
+    }
+
+    // Named values are looked up by where they were written, so they're found regardless of the
+    // order the writer used. Each can be taken by name once, after which it no longer counts as
+    // remaining, but it stays in the pool for the positional `take_*`. Buffers without names fall
+    // back to the positional `take_*`. `None` if the name is missing, was written as a different
+    // kind or was already taken.
+    pub fn take_named_int(&mut self, name: &str) -> Option<i64> {
+        if self.names.is_empty() {
+            return self.take_int();
+        }
+        let (slot, index) = self.named_index(name, PropertyType::Integer)?;
+        let value = self.integers.get(index).copied()?;
+        self.named_taken.push((PropertyType::Integer, slot));
+        Some(value)
+    }
+
+    pub fn take_named_string(&mut self, name: &str) -> Option<String> {
+        if self.names.is_empty() {
+            return self.take_string();
+        }
+        let (slot, index) = self.named_index(name, PropertyType::String)?;
+        let value = self.string_at(index).map(str::to_owned)?;
+        self.named_taken.push((PropertyType::String, slot));
+        Some(value)
+    }
+
+    pub fn take_named_bool(&mut self, name: &str) -> Option<bool> {
+        if self.names.is_empty() {
+            return self.take_bool();
+        }
+        let (slot, index) = self.named_index(name, PropertyType::Bool)?;
+        let value = self.booleans.get(index).copied()?;
+        self.named_taken.push((PropertyType::Bool, slot));
+        Some(value)
+    }
+
+    // the index it was written at and the index into the pool as it is now, after any positional
+    // takes, `None` once it's been taken by name
+    fn named_index(&self, name: &str, kind: PropertyType) -> Option<(usize, usize)> {
+        let &(written_kind, slot) = self.names.get(name)?;
+        if written_kind != kind || self.named_taken.contains(&(kind, slot)) {
+            return None;
+        }
+        Some((slot, slot.checked_sub(self.taken_count(kind))?))
+    }
+
+    // how many values of `kind` were taken positionally
+    fn taken_count(&self, kind: PropertyType) -> usize {
+        let (written, remaining) = match kind {
+            PropertyType::Integer => (self.stats.integers.values, self.integers.len()),
+            PropertyType::String => (
+                self.stats.strings.values + self.stats.raw_strings.values,
+                self.pooled_strings(),
+            ),
+            PropertyType::Bool => (self.stats.booleans.values, self.booleans.len()),
+            PropertyType::Array => return 0,
+        };
+        stat_count(written) - remaining
+    }
+
+    pub fn take_tail_array(&mut self) -> Option<Vec<PropertyValue>> {
+        self.tail.take()
+    }
+
+    pub fn take_property_type(&mut self) -> Option<PropertyType> {
+        self.property_types.pop_front()
+    }
+
+    pub fn take_array(&mut self) -> Option<Vec<PropertyValue>> {
+        self.take_array_checked().ok()
+    }
+
+    // `take_array` with the reason it failed.
+    //
+    // Nested arrays are decoded with an explicit stack rather than recursion, so the depth of the
+    // input costs heap rather than the call stack and any nesting the tags can describe decodes.
+    pub fn take_array_checked(&mut self) -> Result<Vec<PropertyValue>, DecodeError> {
+        self.check_loaded()?;
+        // values the arrays on the stack are still waiting on, each needs a tag of its own
+        let mut pending = 0;
+        // arrays being filled (innermost last) and how many values each still needs
+        let mut stack = vec![self.take_array_start(&mut pending)?];
+        loop {
+            let (values, remaining) = stack.last_mut().expect("stack is never left empty");
+            if *remaining == 0 {
+                let (values, _) = stack.pop().expect("stack is never left empty");
+                match stack.last_mut() {
+                    Some((parent, _)) => parent.push(PropertyValue::Array(values)),
+                    None => return Ok(values),
+                }
+                continue;
+            }
+
+            *remaining -= 1;
+            pending -= 1;
+            self.charge_work(1)
+                .ok_or_else(|| self.work_budget_exceeded())?;
+            let tag = self
+                .take_property_type()
+                .ok_or(DecodeError::Truncated(Section::PropertyTypes))?;
+            match tag {
+                PropertyType::Array => {
+                    self.check_depth(stack.len() + 1)?;
+                    let array = self.take_array_start(&mut pending)?;
+                    stack.push(array);
+                }
+                tag => values.push(self.take_tagged_value(tag)?),
+            }
+        }
+    }
+
+    // counterpart to `Serializer::write_map_sorted_keys`, entries come back sorted by key
+    pub fn take_map(&mut self) -> Option<Vec<(String, PropertyValue)>> {
+        self.check_loaded().ok()?;
+        // every value has a tag, which bounds the length like an array's
+        let length = self.take_array_length(&mut 0).ok()?;
+        let keys: Vec<String> = (0..length)
+            .map(|_| self.take_string())
+            .collect::<Option<_>>()?;
+        keys.into_iter()
+            .map(|key| Some((key, self.take_value()?)))
+            .collect()
+    }
+
+    // a fresh `DecodeLimits::max_work` for the buffer about to be read
+    pub(crate) fn start_work(&mut self) {
+        self.work_left = self.limits.max_work;
+        self.refused = None;
+    }
+
+    pub(crate) fn charge_work(&mut self, values: u64) -> Option<()> {
+        match self.work_left.checked_sub(values) {
+            Some(left) => self.work_left = left,
+            None => self.refused = Some(self.work_budget_exceeded()),
+        }
+        self.refused.is_none().then_some(())
+    }
+
+    pub(crate) fn work_budget_exceeded(&self) -> DecodeError {
+        DecodeError::WorkBudgetExceeded {
+            max: self.limits.max_work,
+        }
+    }
+
+    // `depth` counts the outermost array as 1
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), DecodeError> {
+        if depth > self.limits.max_depth {
+            return Err(DecodeError::LimitExceeded {
+                limit: Limit::Depth,
+                max: self.limits.max_depth as u64,
+            });
+        }
+        Ok(())
+    }
+
+    fn take_array_start(
+        &mut self,
+        pending: &mut usize,
+    ) -> Result<(Vec<PropertyValue>, usize), DecodeError> {
+        let length = self.take_array_length(pending)?;
+        Ok((Vec::with_capacity(length), length))
+    }
+
+    // `pending` is how many tags the arrays already started still need, the new array's length is
+    // added to it
+    pub(crate) fn take_array_length(&mut self, pending: &mut usize) -> Result<usize, DecodeError> {
+        let declared = match self.take_length() {
+            Some(declared) => declared,
+            None => return Err(self.missing(Section::Lengths)),
+        };
+        // every value has its own tag, so a length past the tags left (less the ones the
+        // enclosing arrays still need) is corrupt, and shouldn't be allocated for. That also keeps
+        // the capacity of every array in progress within the tag count however deep they nest.
+        let available = self.property_types.len() - *pending;
+        let length = usize::try_from(declared)
+            .ok()
+            .filter(|length| *length <= available)
+            .ok_or(DecodeError::ArrayLength {
+                declared,
+                available,
+            })?;
+        #[cfg(feature = "trace")]
+        log::debug!("array of {length} values");
+
+        *pending += length;
+        Ok(length)
+    }
+
+    // an array length, or the count of whatever else was written with `write_length`
+    pub(crate) fn take_length(&mut self) -> Option<u64> {
+        self.check_kind(PropertyType::Array)?;
+        self.lengths.pop_front()
+    }
+
+    // counterpart to `Serializer::write_value`, a tag followed by its value
+    pub fn take_value(&mut self) -> Option<PropertyValue> {
+        self.take_value_checked().ok()
+    }
+
+    fn take_value_checked(&mut self) -> Result<PropertyValue, DecodeError> {
+        let tag = self
+            .take_property_type()
+            .ok_or(DecodeError::Truncated(Section::PropertyTypes))?;
+        self.take_tagged_value(tag)
+    }
+
+    pub(crate) fn take_tagged_value(
+        &mut self,
+        tag: PropertyType,
+    ) -> Result<PropertyValue, DecodeError> {
+        let (value, section) = match tag {
+            PropertyType::String => (
+                self.take_string().map(PropertyValue::String),
+                Section::Strings,
+            ),
+            PropertyType::Bool => (self.take_bool().map(PropertyValue::Bool), Section::Booleans),
+            PropertyType::Integer => (
+                self.take_int().map(PropertyValue::Integer),
+                Section::Integers,
+            ),
+            PropertyType::Array => return self.take_array_checked().map(PropertyValue::Array),
+        };
+        value.ok_or_else(|| self.missing(section))
+    }
+}
+
+pub trait IntoFormat {
+    // Identifies the type in buffers written through `pool::to_bytes`, so buffers of different
+    // types can share a version byte and still be told apart (see `Registry`). 0 is unidentified
+    // and isn't checked when decoding.
+    fn format_id() -> u16
+    where
+        Self: Sized,
+    {
+        0
+    }
+    fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>)
+    where
         Self: Sized;
     fn take(deserializer: &mut Deserializer) -> Option<Self>
     where
@@ -340,7 +2891,2119 @@ pub trait IntoFormat {
     where
         Self: Sized,
     {
+        // whatever the last message left unread would make `read_bytes` refuse this one
+        deserializer.clear();
         deserializer.read_bytes(data, version)?;
+        let id = Self::format_id();
+        if id != 0 && deserializer.format_id() != 0 && deserializer.format_id() != id {
+            return None;
+        }
         Self::take(deserializer)
     }
+
+    // `deserialize` for a value that may not be there at all, `Ok(None)` for a buffer written with
+    // `Serializer::write_absent` (see `pool::optional_to_bytes`). A record with no fields is
+    // still `Some`.
+    fn deserialize_optional(
+        data: &[u8],
+        deserializer: &mut Deserializer,
+        version: u8,
+    ) -> Result<Option<Self>, DecodeError>
+    where
+        Self: Sized,
+    {
+        deserializer.clear();
+        deserializer.load(data, version)?;
+        if deserializer.is_absent() {
+            return Ok(None);
+        }
+        let (expected, found) = (Self::format_id(), deserializer.format_id());
+        if expected != 0 && found != 0 && found != expected {
+            deserializer.clear();
+            return Err(DecodeError::FormatIdMismatch { expected, found });
+        }
+        match Self::take(deserializer) {
+            Some(value) => Ok(Some(value)),
+            None => {
+                let remaining = deserializer.remaining_counts();
+                let error = deserializer
+                    .take_kind_mismatch()
+                    .unwrap_or(DecodeError::TakeFailed { remaining });
+                deserializer.clear();
+                Err(error)
+            }
+        }
+    }
+}
+
+// a bare value as the message, see `PropertyValue::to_bytes`
+impl IntoFormat for PropertyValue {
+    fn serialize<'a>(&'a self, serializer: &mut Serializer<'a>) {
+        serializer.write_borrowed_value(self);
+    }
+
+    fn take(deserializer: &mut Deserializer) -> Option<Self> {
+        deserializer.take_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::{
+        bit_packer::{int_encoded_bits, small_int_encoded_bits},
+        format_spec::LEGACY_TAG_BITS,
+    };
+
+    #[test]
+    pub fn lengths_do_not_interleave_integers() {
+        let array = vec![
+            PropertyValue::Integer(7),
+            PropertyValue::Array(vec![PropertyValue::Integer(9), PropertyValue::Bool(true)]),
+            PropertyValue::Array(Vec::new()),
+        ];
+
+        let mut serializer = Serializer::new();
+        serializer.write_int(5);
+        serializer.write_array(&array);
+        serializer.write_int(11);
+
+        assert_eq!(serializer.integers, vec![5, 7, 9, 11]);
+        assert_eq!(serializer.lengths, vec![3, 2, 0]);
+
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.integers.as_slice(), [5, 7, 9, 11]);
+        assert_eq!(deserializer.lengths.as_slice(), [3, 2, 0]);
+
+        assert_eq!(deserializer.take_int(), Some(5));
+        assert_eq!(deserializer.take_array(), Some(array));
+        assert_eq!(deserializer.take_int(), Some(11));
+    }
+
+    #[test]
+    pub fn strings_containing_nul_roundtrip() {
+        let strings = ["a\0b", "\0", "", "trailing\0\0", "plain"];
+
+        let mut serializer = Serializer::new();
+        for string in strings {
+            serializer.write_string(string);
+        }
+
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        for string in strings {
+            assert_eq!(deserializer.take_string().as_deref(), Some(string));
+        }
+    }
+
+    // An empty value is only what says it's empty, a zero length (or an empty string's length).
+    // Nothing else goes in the columns and an empty column adds nothing past its header count.
+    #[test]
+    pub fn empty_values_are_only_their_length() {
+        type Write = fn(&mut Serializer);
+        type Take = fn(&mut Deserializer) -> bool;
+        let cases: [(&str, Write, Take); 13] = [
+            (
+                "array",
+                |serializer| serializer.write_array(&[]),
+                |deserializer| deserializer.take_array() == Some(Vec::new()),
+            ),
+            (
+                "map",
+                |serializer| serializer.write_map_sorted_keys([]),
+                |deserializer| deserializer.take_map() == Some(Vec::new()),
+            ),
+            (
+                "int vec",
+                |serializer| serializer.write_int_vec(&[]),
+                |deserializer| deserializer.take_int_vec() == Some(Vec::new()),
+            ),
+            (
+                "bool vec",
+                |serializer| serializer.write_bool_vec(&[]),
+                |deserializer| deserializer.take_bool_vec() == Some(Vec::new()),
+            ),
+            (
+                "string vec",
+                |serializer| serializer.write_string_vec::<&str>(&[]),
+                |deserializer| deserializer.take_string_vec() == Some(Vec::new()),
+            ),
+            (
+                "ints",
+                |serializer| serializer.write_ints([]),
+                |deserializer| deserializer.take_int_vec() == Some(Vec::new()),
+            ),
+            (
+                "bools",
+                |serializer| serializer.write_bools([]),
+                |deserializer| deserializer.take_bool_vec() == Some(Vec::new()),
+            ),
+            (
+                "strs",
+                |serializer| serializer.write_strs::<&str>([]),
+                |deserializer| deserializer.take_string_vec() == Some(Vec::new()),
+            ),
+            (
+                "float array",
+                |serializer| serializer.write_float_array(&[]),
+                |deserializer| deserializer.take_float_array() == Some(Vec::new()),
+            ),
+            (
+                "index set",
+                |serializer| serializer.write_index_set(&[]).unwrap(),
+                |deserializer| deserializer.take_index_set() == Some(Vec::new()),
+            ),
+            (
+                "string",
+                |serializer| serializer.write_string(""),
+                |deserializer| deserializer.take_string().as_deref() == Some(""),
+            ),
+            (
+                "owned string",
+                |serializer| serializer.write_string_owned(String::new()),
+                |deserializer| deserializer.take_string().as_deref() == Some(""),
+            ),
+            (
+                "raw string",
+                |serializer| serializer.write_string_with("", StringCodec::Raw),
+                |deserializer| deserializer.take_string().as_deref() == Some(""),
+            ),
+        ];
+        let mut empty = Vec::new();
+        let nothing = Serializer::new().finish(&mut empty, 0);
+        let length_bits = 1 + INT_HEADER_WIDTHS[0] as u64;
+        for (name, write, take) in cases {
+            let mut serializer = Serializer::new();
+            write(&mut serializer);
+            let mut buffer = Vec::new();
+            let stats = serializer.finish(&mut buffer, 0);
+
+            let column = |values, bits| ColumnStats { values, bits };
+            // a count in the header going from 0 to 1, or the raw strings' option
+            let count = length_bits;
+            let option = small_int_encoded_bits(OPTION_RAW_STRINGS) - small_int_encoded_bits(0);
+            let (lengths, strings, raw_strings, header) = match name {
+                // the adaptive codec's bit and the length
+                "string" | "owned string" => {
+                    let strings = column(1, 1 + length_bits);
+                    (0, strings, nothing.raw_strings, count)
+                }
+                // their count, the gap and the length
+                "raw string" => {
+                    let bits = small_int_encoded_bits(1) + small_int_encoded_bits(0) + length_bits;
+                    (0, nothing.strings, column(1, bits), option)
+                }
+                _ => (1, nothing.strings, nothing.raw_strings, count),
+            };
+            assert_eq!(
+                stats.lengths,
+                column(lengths, lengths * SMALL_INT_HEADER_BITS as u64),
+                "{name}"
+            );
+            assert_eq!(stats.strings, strings, "{name}");
+            assert_eq!(stats.raw_strings, raw_strings, "{name}");
+            assert_eq!(stats.integers, nothing.integers, "{name}");
+            assert_eq!(stats.booleans, nothing.booleans, "{name}");
+            assert_eq!(stats.property_types, nothing.property_types, "{name}");
+            assert_eq!(stats.header_bits, nothing.header_bits + header, "{name}");
+            let bits = stats.header_bits
+                + stats.lengths.bits
+                + stats.strings.bits
+                + stats.raw_strings.bits
+                + stats.names.bits
+                + stats.tail.bits;
+            assert_eq!(stats.total_bytes, bits.div_ceil(8), "{name}");
+
+            let mut deserializer = Deserializer::new();
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            assert!(take(&mut deserializer), "{name}");
+            assert_eq!(deserializer.state(), LoadState::Drained, "{name}");
+        }
+
+        // an empty tail is its presence bit and then a zero length
+        let mut serializer = Serializer::new();
+        serializer.write_tail_array(&[]);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        assert_eq!(buffer.len(), EMPTY_BUFFER_LEN + 4);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_tail_array(), Some(Vec::new()));
+    }
+
+    #[test]
+    pub fn empty_messages_roundtrip() {
+        let mut serializer = Serializer::new();
+        assert!(serializer.is_empty());
+        serializer.set_format_id(3);
+        assert!(serializer.is_empty());
+        let mut buffer = Vec::new();
+        let stats = Serializer::new().finish(&mut buffer, 0);
+        assert_eq!(buffer.len(), EMPTY_BUFFER_LEN);
+        assert_eq!(stats.total_bytes, EMPTY_BUFFER_LEN as u64);
+        // every byte carries header bits, none is left over from the packer's first push
+        assert_eq!(
+            (stats.tail.bits + stats.names.bits + stats.header_bits).div_ceil(8),
+            EMPTY_BUFFER_LEN as u64
+        );
+
+        assert!(verify(&buffer, 0).unwrap().counts.is_empty());
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.last_stats(), &stats);
+        assert_eq!(deserializer.take_int(), None);
+        assert_eq!(deserializer.take_string(), None);
+        assert_eq!(deserializer.take_array(), None);
+        assert_eq!(deserializer.cursor().finish(), Ok(()));
+
+        for len in 0..EMPTY_BUFFER_LEN {
+            assert!(
+                deserializer.read_bytes(&buffer[..len], 0).is_none(),
+                "{len}"
+            );
+        }
+
+        serializer.write_bool(false);
+        assert!(!serializer.is_empty());
+    }
+
+    #[test]
+    pub fn raw_strings_keep_their_place() {
+        let prose = [
+            "the quick brown fox",
+            "jumps over the lazy dog",
+            "service is healthy",
+        ];
+        let uuid = "3f2b8c1e-9d4a-4e7b-a6c5-0b1d2e3f4a5b";
+        let written = |token: &'static str, codec| {
+            let mut serializer = Serializer::new();
+            // the token is written twice, which would otherwise be deduplicated
+            serializer.set_dedup_strings(false);
+            serializer.write_string(prose[0]);
+            serializer.write_string_with(token, codec);
+            serializer.write_named_string("status", prose[1]);
+            serializer.write_string_with(token, codec);
+            serializer.write_string(prose[2]);
+            let mut buffer = Vec::new();
+            let stats = serializer.finish(&mut buffer, 0);
+            (buffer, stats)
+        };
+
+        let (buffer, stats) = written(uuid, StringCodec::Raw);
+        assert_eq!(stats.strings.values, 3);
+        assert_eq!(stats.raw_strings.values, 2);
+        assert_eq!(verify(&buffer, 0).unwrap().counts.strings, 5);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.last_stats(), &stats);
+        assert_eq!(
+            deserializer.take_named_string("status").as_deref(),
+            Some(prose[1])
+        );
+        for string in [prose[0], uuid, prose[1], uuid, prose[2]] {
+            assert_eq!(deserializer.take_string().as_deref(), Some(string));
+        }
+
+        // the prose is encoded the same as it would be on its own
+        let mut serializer = Serializer::new();
+        prose
+            .iter()
+            .for_each(|string| serializer.write_string(string));
+        let alone = serializer.finish(&mut Vec::new(), 0);
+        assert_eq!(stats.strings.bits, alone.strings.bits);
+
+        // an opaque token outside of 32..=127 would otherwise take the prose off the ascii path
+        let token = "ÿþ\u{1}Ç¬Ö×ÿ\u{7}ÆÐÑ";
+        let (raw, raw_stats) = written(token, StringCodec::Raw);
+        let (auto, auto_stats) = written(token, StringCodec::Auto);
+        assert!(raw_stats.all_ascii && !auto_stats.all_ascii);
+        assert!(
+            raw.len() < auto.len(),
+            "{} raw, {} auto",
+            raw.len(),
+            auto.len()
+        );
+        assert!(raw_stats.strings.bits + raw_stats.raw_strings.bits < auto_stats.strings.bits);
+    }
+
+    #[test]
+    pub fn strings_are_borrowed() {
+        let token = "3f2b8c1e-9d4a-4e7b-a6c5-0b1d2e3f4a5b";
+        let mut serializer = Serializer::new();
+        serializer.write_string_with(token, StringCodec::Raw);
+        serializer.write_string("healthy");
+        serializer.write_string_with("ünïcode", StringCodec::Raw);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.load(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.remaining_view().strings().collect::<Vec<_>>(),
+            [token, "healthy", "ünïcode"]
+        );
+
+        // raw or coded, they're all read out of the deserializer's own buffers
+        let allocations = crate::alloc_counter::count(|| {
+            for expected in [token, "healthy", "ünïcode"] {
+                let string = deserializer.take_string_cow().unwrap();
+                assert!(matches!(string, Cow::Borrowed(_)));
+                assert_eq!(string, expected);
+            }
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(deserializer.take_string_cow(), None);
+        assert_eq!(deserializer.state(), LoadState::Drained);
+    }
+
+    #[test]
+    pub fn raw_string_lengths_are_checked_against_the_input() {
+        let with_length = |len: i64| {
+            let mut buffer = Vec::new();
+            let mut packer = BitPacker::new(&mut buffer);
+            packer.write_byte(0);
+            packer.write_small_int(FORMAT_REVISION);
+            packer.write_small_int(OPTION_RAW_STRINGS);
+            packer.write_count(0);
+            packer.write_count(0);
+            packer.write_bit(true);
+            packer.write_bit(false);
+            for _ in 0..3 {
+                packer.write_count(0);
+            }
+            packer.write_small_int(1);
+            packer.write_small_int(0);
+            packer.write_int(len);
+            packer.write_bytes(b"abc");
+            packer.write_small_int(0);
+            packer.write_bit(false);
+            buffer
+        };
+
+        let mut deserializer = Deserializer::new();
+        deserializer.load(&with_length(3), 0).unwrap();
+        assert_eq!(deserializer.take_string().as_deref(), Some("abc"));
+
+        // claims a terabyte, fails before reserving any of it
+        let buffer = with_length(1 << 40);
+        assert_eq!(
+            verify(&buffer, 0),
+            Err(DecodeError::Truncated(Section::RawStrings))
+        );
+        let allocations = crate::alloc_counter::count(|| {
+            assert_eq!(
+                deserializer.load(&buffer, 0),
+                Err(DecodeError::Truncated(Section::RawStrings))
+            );
+        });
+        assert!(allocations < 8, "{allocations} allocations");
+        assert_eq!(deserializer.state(), LoadState::Empty);
+    }
+
+    #[test]
+    pub fn aligned_buffers_decode_at_their_offset() {
+        let mut serializer = Serializer::new();
+        serializer.write_int(4);
+        serializer.write_string("Nice");
+        serializer.write_tail_array(&[]);
+
+        let mut buffer = Vec::new();
+        let stats = serializer.finish_aligned(&mut buffer, 3, 8);
+        assert_eq!(buffer.len() % 8, 0);
+        assert_eq!(stats.total_bytes, buffer.len() as u64);
+        verify(&buffer, 3).unwrap();
+
+        // whatever came before is padded so the message starts aligned too
+        let mut embedded = b"hdr".to_vec();
+        let stats = serializer.finish_aligned(&mut embedded, 3, 8);
+        assert_eq!(&embedded[..3], b"hdr");
+        assert_eq!(embedded.len(), 8 + buffer.len());
+        let start = embedded.len() - stats.total_bytes as usize;
+        assert_eq!(start, 8);
+        assert_eq!(&embedded[start..], &buffer[..]);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&embedded[start..], 3).unwrap();
+        assert_eq!(deserializer.last_stats(), &stats);
+        assert_eq!(deserializer.take_int(), Some(4));
+        assert_eq!(deserializer.take_string().as_deref(), Some("Nice"));
+        assert_eq!(deserializer.take_tail_array(), Some(Vec::new()));
+        assert!(crate::appender::ArrayAppender::new(&mut buffer, 3).is_none());
+
+        // the padding is checked like the rest
+        let mut corrupt = buffer.clone();
+        let len = corrupt.len();
+        corrupt[len - 2] = 1;
+        assert_eq!(
+            verify(&corrupt, 3),
+            Err(DecodeError::Truncated(Section::Padding))
+        );
+        let mut short = buffer.clone();
+        short.pop();
+        assert!(verify(&short, 3).is_err());
+    }
+
+    #[test]
+    pub fn load_states() {
+        let written = |value| {
+            let mut serializer = Serializer::new();
+            serializer.write_int(value);
+            serializer.write_length(0);
+            let mut buffer = Vec::new();
+            serializer.finish(&mut buffer, 0);
+            buffer
+        };
+        let (first, second) = (written(1), written(2));
+
+        // nothing to read before a load
+        let mut deserializer = Deserializer::new();
+        assert_eq!(deserializer.state(), LoadState::Empty);
+        assert_eq!(deserializer.take_int(), None);
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::NotLoaded)
+        );
+        assert_eq!(deserializer.cursor().int(), Err(DecodeError::NotLoaded));
+        assert_eq!(deserializer.cursor().finish(), Err(DecodeError::NotLoaded));
+        assert!(matches!(
+            deserializer.events().next(),
+            Some(Err(DecodeError::NotLoaded))
+        ));
+
+        // a second buffer isn't mixed in behind the first one's unread values
+        deserializer.load(&first, 0).unwrap();
+        assert_eq!(deserializer.state(), LoadState::Loaded);
+        assert_eq!(
+            deserializer.load(&second, 0),
+            Err(DecodeError::AlreadyLoaded {
+                remaining: ColumnCounts {
+                    integers: 1,
+                    lengths: 1,
+                    ..ColumnCounts::default()
+                }
+            })
+        );
+        assert!(deserializer.read_bytes(&second, 0).is_none());
+        assert_eq!(deserializer.take_int(), Some(1));
+        assert_eq!(deserializer.state(), LoadState::Loaded);
+
+        // until they've been read
+        assert_eq!(deserializer.take_array(), Some(Vec::new()));
+        assert_eq!(deserializer.state(), LoadState::Drained);
+        deserializer.load(&second, 0).unwrap();
+        assert_eq!(deserializer.take_int(), Some(2));
+
+        // or dropped
+        deserializer.clear();
+        assert_eq!(deserializer.state(), LoadState::Empty);
+        assert_eq!(deserializer.take_array(), None);
+        deserializer.load(&first, 0).unwrap();
+        assert_eq!(deserializer.take_int(), Some(1));
+
+        // a failed load leaves nothing behind
+        deserializer.clear();
+        assert_eq!(
+            deserializer.load(&first[..2], 0),
+            Err(DecodeError::Truncated(Section::Header))
+        );
+        assert_eq!(deserializer.state(), LoadState::Empty);
+        let mut strict = Deserializer::with_limits(DecodeLimits::strict().max_column_values(0));
+        assert_eq!(
+            strict.load(&first, 0),
+            Err(DecodeError::LimitExceeded {
+                limit: Limit::ColumnValues,
+                max: 0
+            })
+        );
+        assert_eq!(strict.state(), LoadState::Empty);
+    }
+
+    #[test]
+    pub fn sequential_reads_keep_their_order() {
+        let mut serializer = Serializer::new();
+        for i in 0..50 {
+            serializer.write_int(i);
+            serializer.write_string_owned(format!("field {i}"));
+            serializer.write_bool(i % 3 == 0);
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        // read twice with the same deserializer, the second buffer reuses the first's columns
+        let mut deserializer = Deserializer::new();
+        for _ in 0..2 {
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            for i in 0..50 {
+                assert_eq!(deserializer.take_int(), Some(i));
+                assert_eq!(deserializer.take_string(), Some(format!("field {i}")));
+                assert_eq!(deserializer.take_bool(), Some(i % 3 == 0));
+                assert_eq!(deserializer.remaining_counts().integers, 49 - i as usize);
+            }
+            assert_eq!(deserializer.take_int(), None);
+            assert_eq!(deserializer.take_string(), None);
+            assert_eq!(deserializer.take_bool(), None);
+        }
+    }
+
+    fn integer_roundtrip(integers: &[i64]) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        for integer in integers {
+            serializer.write_int(*integer);
+        }
+
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.integers.as_slice(), integers);
+        buffer
+    }
+
+    #[test]
+    pub fn constant_integer_column() {
+        let zeros = integer_roundtrip(&[0; 100]);
+        let forty_twos = integer_roundtrip(&[42; 100]);
+        let mut almost = [42; 100];
+        almost[99] = 43;
+        let almost = integer_roundtrip(&almost);
+
+        // the count, the constant flag and one value on top of an empty buffer's header
+        let empty = integer_roundtrip(&[]);
+        assert!(zeros.len() <= empty.len() + 3, "{} bytes", zeros.len());
+        assert!(
+            forty_twos.len() <= empty.len() + 3,
+            "{} bytes",
+            forty_twos.len()
+        );
+        // framed, but every value is still written as at least the narrowest int
+        assert!(almost.len() >= 50, "{} bytes", almost.len());
+
+        // the count only grows the header
+        let thousand = integer_roundtrip(&[42; 1000]);
+        assert!(
+            thousand.len() <= empty.len() + 4,
+            "{} bytes",
+            thousand.len()
+        );
+
+        let mut serializer = Serializer::new();
+        serializer.write_int(42);
+        serializer.write_int(42);
+        serializer.write_int(43);
+        assert_eq!(serializer.constant_integer(), None);
+        assert!(!serializer.finish(&mut Vec::new(), 0).constant_integers);
+    }
+
+    #[test]
+    pub fn frame_of_reference_integer_column() {
+        let clustered = [1_000_000, 1_000_003, 1_000_001];
+        integer_roundtrip(&clustered);
+        let mut serializer = Serializer::new();
+        for integer in clustered {
+            serializer.write_int(integer);
+        }
+        let stats = serializer.finish(&mut Vec::new(), 0);
+        // 29 bits each as they are, against the minimum once and 4 bits each
+        let plain: u64 = clustered.iter().map(|int| int_encoded_bits(*int)).sum();
+        assert_eq!(plain, 87);
+        assert!(stats.integers.bits * 2 < plain, "{:?}", stats.integers);
+
+        // negative values, and a span past `i64::MAX` that wraps
+        integer_roundtrip(&[-1_000_003, -1_000_000, -1_000_001]);
+        integer_roundtrip(&[-5, -1, 0, 7]);
+        integer_roundtrip(&[i64::MIN, i64::MAX, 0]);
+        // not worth framing, the flag is all it costs
+        integer_roundtrip(&[0, 1_000_000]);
+    }
+
+    #[test]
+    pub fn reused_deserializers_decode_without_allocating() {
+        let strings = ["host", "/usr/local/bin/test", "ünïcode"];
+
+        let mut serializer = Serializer::new();
+        for string in strings {
+            serializer.write_string(string);
+        }
+        serializer.write_int(8080);
+        serializer.write_bool(true);
+        serializer.write_array(&[PropertyValue::Integer(1)]);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        deserializer.clear();
+        let allocations =
+            crate::alloc_counter::count(|| deserializer.read_bytes(&buffer, 0).unwrap());
+        assert_eq!(allocations, 0);
+    }
+
+    #[test]
+    pub fn reuse_keeps_column_capacity() {
+        let keys: Vec<String> = (0..40).map(|i| format!("key.{i}")).collect();
+        let mut serializer = Serializer::new();
+        for key in &keys {
+            serializer.write_string(key);
+            serializer.write_string_with(key, StringCodec::Raw);
+            serializer.write_int(1);
+        }
+        serializer.write_named_int("port", 8080);
+        let capacities = |serializer: &Serializer| {
+            [
+                serializer.strings.capacity(),
+                serializer.raw_strings.capacity(),
+                serializer.names.capacity(),
+                serializer.integers.capacity(),
+            ]
+        };
+        let before = capacities(&serializer);
+
+        // twice, so the second one starts from a serializer that was already reused
+        let mut reused: Serializer<'static> = serializer.reuse();
+        drop(keys);
+        for _ in 0..2 {
+            assert_eq!(capacities(&reused), before);
+            assert!(reused.strings.is_empty() && reused.names.is_empty());
+            reused = reused.reuse();
+        }
+    }
+
+    #[test]
+    pub fn clear_keeps_capacities() {
+        let keys: Vec<String> = (0..40).map(|i| format!("pool.member_{i}")).collect();
+        let array = [PropertyValue::Integer(4), PropertyValue::Bool(true)];
+        fn write<'a>(
+            serializer: &mut Serializer<'a>,
+            keys: &'a [String],
+            array: &'a [PropertyValue],
+        ) {
+            for (i, key) in keys.iter().enumerate() {
+                serializer.write_int(i as i64);
+                serializer.write_bool(i % 2 == 0);
+                serializer.write_string(key);
+                serializer.write_string_with(key, StringCodec::Raw);
+            }
+            serializer.write_array(array);
+            serializer.write_named_int("weight", 10);
+            serializer.set_format_id(7);
+        }
+
+        let mut serializer = Serializer::new();
+        serializer.set_column_offsets(true);
+        serializer.set_kind_markers(true);
+        write(&mut serializer, &keys, &array);
+        let mut expected = Vec::new();
+        serializer.finish(&mut expected, 0);
+        let written = serializer.capacities();
+        assert!(written.strings >= 40 && written.kinds >= 160 && written.sections > 0);
+
+        let mut buffer = Vec::new();
+        for _ in 0..3 {
+            serializer.clear();
+            assert!(serializer.is_empty());
+            assert_eq!(serializer.capacities(), written);
+            write(&mut serializer, &keys, &array);
+            serializer.finish(&mut buffer, 0);
+            assert_eq!(serializer.capacities(), written);
+            assert_eq!(buffer, expected);
+        }
+        assert_eq!(ColumnCapacities::default(), Serializer::new().capacities());
+    }
+
+    #[test]
+    pub fn reused_serializers_pack_sections_without_allocating() {
+        let keys: Vec<String> = (0..40).map(|i| format!("service.key_{i}")).collect();
+        fn write<'a>(serializer: &mut Serializer<'a>, keys: &'a [String]) {
+            for (i, key) in keys.iter().enumerate() {
+                serializer.write_int(i as i64);
+                serializer.write_string(key);
+            }
+            serializer.write_array(&[PropertyValue::Bool(true)]);
+        }
+
+        let mut serializer = Serializer::new();
+        serializer.set_column_offsets(true);
+        write(&mut serializer, &keys);
+        let mut expected = Vec::new();
+        serializer.finish(&mut expected, 0);
+
+        let mut buffer = Vec::with_capacity(expected.len());
+        let mut serializer = Some(serializer.reuse());
+        let allocations = crate::alloc_counter::count(|| {
+            for _ in 0..10 {
+                let mut reused = serializer.take().unwrap().reuse();
+                write(&mut reused, &keys);
+                reused.finish(&mut buffer, 0);
+                assert_eq!(buffer, expected);
+                serializer = Some(reused);
+            }
+        });
+        assert_eq!(allocations, 0);
+    }
+
+    #[test]
+    pub fn decode_stats_match_encode_stats() {
+        let array = vec![
+            PropertyValue::String("1920x1080".to_owned()),
+            PropertyValue::Bool(false),
+            PropertyValue::Array(vec![PropertyValue::Integer(3)]),
+        ];
+        let tail = vec![PropertyValue::Integer(1)];
+
+        let mut serializer = Serializer::new();
+        serializer.write_int(7);
+        serializer.write_int(7);
+        serializer.write_string("Nice");
+        serializer.write_bool(true);
+        serializer.write_array(&array);
+        serializer.write_tail_array(&tail);
+
+        let mut buffer = Vec::new();
+        let encode_stats = serializer.finish(&mut buffer, 0);
+        assert_eq!(encode_stats.total_bytes, buffer.len() as u64);
+        assert_eq!(encode_stats.integers.values, 3);
+        assert_eq!(encode_stats.tail.values, 1);
+        assert!(encode_stats.all_ascii);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.last_stats(), &encode_stats);
+
+        let mut serializer = Serializer::new();
+        serializer.write_int(7);
+        serializer.write_int(7);
+        let encode_stats = serializer.finish(&mut buffer, 0);
+        assert!(encode_stats.constant_integers);
+        deserializer.clear();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.last_stats(), &encode_stats);
+    }
+
+    #[test]
+    pub fn native_is_little_endian() {
+        let mut serializer = Serializer::new();
+        serializer.write_int(0x0102_0304);
+
+        let mut buffer = Vec::new();
+        serializer.finish_native(&mut buffer, 7);
+        // version, then the 5 column counts
+        assert_eq!(buffer[..9], [7, 1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(buffer[41..49], [4, 3, 2, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    pub fn incremental_hash_matches_post_hoc() {
+        use std::hash::DefaultHasher;
+
+        let array = vec![
+            PropertyValue::String("ünïcode".to_owned()),
+            PropertyValue::Array(vec![PropertyValue::Integer(300)]),
+        ];
+        let tail = vec![PropertyValue::Bool(true), PropertyValue::Integer(9)];
+        let long = "/usr/local/bin/test ".repeat(20);
+
+        let empty = Serializer::new();
+        let mut single = Serializer::new();
+        single.write_int(5);
+        let mut mixed = Serializer::new();
+        mixed.write_int(1);
+        mixed.write_bool(false);
+        mixed.write_string(&long);
+        mixed.write_array(&array);
+        let mut with_tail = Serializer::new();
+        with_tail.write_string("Nice");
+        with_tail.write_tail_array(&tail);
+
+        for serializer in [empty, single, mixed, with_tail] {
+            let mut buffer = Vec::new();
+            let mut hasher = DefaultHasher::new();
+            let stats = serializer.finish_hashed(&mut buffer, 3, &mut hasher);
+
+            let mut post_hoc = DefaultHasher::new();
+            post_hoc.write(&buffer);
+            assert_eq!(stats.digest, Some(post_hoc.finish()));
+        }
+    }
+
+    #[test]
+    pub fn revision_11_headers_still_decode() {
+        // a single int, written the way revision 11 did
+        let mut legacy = Vec::new();
+        let mut packer = BitPacker::new(&mut legacy);
+        packer.write_byte(0);
+        packer.write_small_int(11);
+        packer.write_bit(false);
+        packer.write_int(1);
+        packer.write_int(0);
+        packer.write_bit(true);
+        packer.write_bit(false);
+        packer.write_int(0);
+        packer.write_int(0);
+        packer.write_small_int(0);
+        for _ in 0..5 {
+            packer.write_bit(false);
+        }
+        packer.write_int(8080);
+        packer.write_small_int(0);
+        packer.write_bit(false);
+
+        let mut serializer = Serializer::new();
+        serializer.write_int(8080);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        // four empty columns are a bit each rather than an int each
+        assert_eq!(legacy.len(), 9);
+        assert_eq!(buffer.len(), 7);
+
+        for buffer in [&legacy, &buffer] {
+            verify(buffer, 0).unwrap();
+            let mut deserializer = Deserializer::new();
+            deserializer.read_bytes(buffer, 0).unwrap();
+            assert_eq!(deserializer.take_int(), Some(8080));
+            assert_eq!(deserializer.state(), LoadState::Drained);
+        }
+
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(OLDEST_READABLE_REVISION - 1);
+        packer.write_bytes(&[0; 8]);
+        assert_eq!(
+            verify(&buffer, 0),
+            Err(DecodeError::UnsupportedRevision(
+                OLDEST_READABLE_REVISION - 1
+            ))
+        );
+    }
+
+    fn progress_calls(deserializer: &mut Deserializer) -> Arc<Mutex<Vec<(usize, u64, u64)>>> {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&calls);
+        deserializer.set_progress(move |columns, consumed, total| {
+            recorded.lock().unwrap().push((columns, consumed, total));
+            ControlFlow::Continue(())
+        });
+        calls
+    }
+
+    #[test]
+    pub fn progress_is_reported_per_column() {
+        let keys: Vec<String> = (0..10_000).map(|i| format!("key.{i}")).collect();
+        for column_offsets in [false, true] {
+            let mut serializer = Serializer::new();
+            serializer.set_column_offsets(column_offsets);
+            for (i, key) in keys.iter().enumerate() {
+                serializer.write_int(i as i64);
+                serializer.write_string(key);
+            }
+            let mut buffer = Vec::new();
+            serializer.finish(&mut buffer, 0);
+
+            let mut deserializer = Deserializer::new();
+            let calls = progress_calls(&mut deserializer);
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            let calls = calls.lock().unwrap();
+            let total = buffer.len() as u64 * 8;
+            assert!(calls.iter().all(|call| call.2 == total));
+            assert!(calls.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+            let boundaries: Vec<usize> = calls.iter().map(|call| call.0).collect();
+            if column_offsets {
+                assert_eq!(boundaries, [1, 2, 3, 4, 5]);
+            } else {
+                // two calls within each of the integers and strings
+                assert_eq!(boundaries, [0, 0, 1, 2, 2, 2, 3, 4, 5]);
+            }
+            assert!(calls.last().unwrap().1 <= total);
+        }
+    }
+
+    #[test]
+    pub fn cancelled_decodes_leave_nothing_behind() {
+        let mut serializer = Serializer::new();
+        for i in 0..10_000 {
+            serializer.write_int(i);
+            serializer.write_bool(i % 2 == 0);
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        // stops partway through the integers
+        let mut deserializer = Deserializer::new();
+        deserializer.set_progress(|columns, _, _| match columns {
+            0 => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        });
+        assert_eq!(deserializer.load(&buffer, 0), Err(DecodeError::Cancelled));
+        assert_eq!(deserializer.state(), LoadState::Empty);
+        assert!(deserializer.remaining_counts().is_empty());
+        assert_eq!(deserializer.take_int(), None);
+        assert_eq!(deserializer.read_bytes(&buffer, 0), None);
+
+        deserializer.clear();
+        deserializer.clear_progress();
+        deserializer.load(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_int(), Some(0));
+        assert_eq!(deserializer.take_bool(), Some(true));
+    }
+
+    #[test]
+    pub fn invalid_tags_are_named() {
+        const INVALID: u8 = 5;
+        assert_eq!(PropertyType::from_bits(INVALID), None);
+        let array = [PropertyValue::Integer(7)];
+        let invalid = Err(DecodeError::InvalidPropertyTag(INVALID));
+
+        // the tag column, in a section of its own so it's easy to find
+        let mut serializer = Serializer::new();
+        serializer.set_column_offsets(true);
+        serializer.write_array(&array);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        let tags = (stats.header_bits / 8 + stats.integers.bits.div_ceil(8)) as usize;
+        assert_eq!(buffer[tags] >> (8 - TAG_BITS), 2);
+        buffer[tags] = INVALID << (8 - TAG_BITS);
+        assert_eq!(verify(&buffer, 0).map(|_| ()), invalid);
+        assert_eq!(Deserializer::new().load(&buffer, 0), invalid);
+
+        // the tail array, where each element carries its own tag
+        let mut serializer = Serializer::new();
+        serializer.write_tail_array(&array);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let element = buffer.len() - 1;
+        assert_eq!(buffer[element] >> (8 - TAG_BITS), 2);
+        buffer[element] = buffer[element] & 0x1f | INVALID << (8 - TAG_BITS);
+        assert_eq!(verify(&buffer, 0).map(|_| ()), invalid);
+        assert_eq!(Deserializer::new().load(&buffer, 0), invalid);
+    }
+
+    #[test]
+    pub fn revision_14_tags_still_decode() {
+        // the "tagged array" conformance vector as revision 14 wrote it, with 2 bit tags
+        let legacy: Vec<u8> = (0..42)
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&"015c230a15907fffffffffffffffff63ed0f690c00"[i..i + 2], 16)
+            })
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let array = vec![
+            PropertyValue::Integer(3),
+            PropertyValue::String("x".to_owned()),
+            PropertyValue::Bool(true),
+            PropertyValue::Array(vec![
+                PropertyValue::Integer(-2),
+                PropertyValue::Array(Vec::new()),
+            ]),
+        ];
+        let mut serializer = Serializer::new();
+        serializer.write_array(&array);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 1);
+
+        let mut deserializer = Deserializer::new();
+        for (buffer, tag_bits) in [(&legacy, LEGACY_TAG_BITS), (&buffer, TAG_BITS)] {
+            verify(buffer, 1).unwrap();
+            deserializer.read_bytes(buffer, 1).unwrap();
+            assert_eq!(
+                deserializer.last_stats().property_types.bits,
+                6 * tag_bits as u64
+            );
+            assert_eq!(deserializer.take_array(), Some(array.clone()));
+        }
+    }
+
+    #[test]
+    pub fn rejects_other_revisions() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION + 1);
+        packer.write_bytes(&[0; 8]);
+
+        let mut deserializer = Deserializer::new();
+        assert_eq!(deserializer.read_bytes(&buffer, 0), None);
+    }
+
+    #[test]
+    pub fn reserved_options_are_rejected() {
+        let mut serializer = Serializer::new();
+        serializer.set_format_id(3);
+        serializer.write_int(8080);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.format_id(), 3);
+        assert_eq!(deserializer.take_int(), Some(8080));
+
+        let with_options = |options: u64| {
+            let mut buffer = Vec::new();
+            let mut packer = BitPacker::new(&mut buffer);
+            packer.write_byte(0);
+            packer.write_small_int(FORMAT_REVISION);
+            packer.write_small_int(options);
+            for _ in 0..2 {
+                packer.write_count(0);
+            }
+            packer.write_bit(true);
+            packer.write_bit(false);
+            for _ in 0..3 {
+                packer.write_count(0);
+            }
+            packer.write_small_int(0);
+            packer.write_bit(false);
+            buffer
+        };
+        verify(&with_options(0), 0).unwrap();
+        let reserved = with_options(KNOWN_OPTIONS + 1);
+        assert_eq!(
+            verify(&reserved, 0),
+            Err(DecodeError::ReservedOptions(KNOWN_OPTIONS + 1))
+        );
+        assert!(Deserializer::new().read_bytes(&reserved, 0).is_none());
+        assert_eq!(
+            Deserializer::new().load(&reserved, 0),
+            Err(DecodeError::ReservedOptions(KNOWN_OPTIONS + 1))
+        );
+    }
+
+    #[test]
+    pub fn deserialize_drops_the_last_message() {
+        let mut buffer = Vec::new();
+        let mut serializer = Serializer::new();
+        serializer.write_value(&PropertyValue::Integer(1));
+        serializer.write_value(&PropertyValue::String("left over".to_owned()));
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        assert_eq!(
+            PropertyValue::deserialize(&buffer, &mut deserializer, 0),
+            Some(PropertyValue::Integer(1))
+        );
+        assert_eq!(deserializer.state(), LoadState::Loaded);
+
+        let mut buffer = Vec::new();
+        let mut serializer = Serializer::new();
+        serializer.write_value(&PropertyValue::Bool(true));
+        serializer.finish(&mut buffer, 0);
+        assert_eq!(
+            PropertyValue::deserialize(&buffer, &mut deserializer, 0),
+            Some(PropertyValue::Bool(true))
+        );
+        assert_eq!(deserializer.state(), LoadState::Drained);
+    }
+
+    #[test]
+    pub fn absent_buffers_with_values_are_errors() {
+        // an absent header followed by `booleans` bits, `names` and maybe a tail
+        let absent = |booleans: u64, names: &[FieldName], tail: Option<&[PropertyValue]>| {
+            let mut buffer = Vec::new();
+            let mut packer = BitPacker::new(&mut buffer);
+            packer.write_byte(0);
+            packer.write_small_int(FORMAT_REVISION);
+            packer.write_small_int(OPTION_ABSENT);
+            packer.write_count(0);
+            packer.write_count(booleans);
+            packer.write_bit(true);
+            packer.write_bit(false);
+            for _ in 0..3 {
+                packer.write_count(0);
+            }
+            for _ in 0..booleans {
+                packer.write_bit(true);
+            }
+            columns::write_names(&mut packer, names);
+            packer.write_bit(tail.is_some());
+            let buffer = packer.into_inner();
+            if let Some(tail) = tail {
+                appender::write_tail(buffer, tail);
+            }
+            buffer.clone()
+        };
+        let empty = absent(0, &[], None);
+        verify(&empty, 0).unwrap();
+        assert_eq!(canonicalize(&empty), Ok(empty.clone()));
+
+        let tail = [PropertyValue::Integer(1)];
+        for (buffer, section) in [
+            (absent(1, &[], None), Section::Booleans),
+            (
+                absent(0, &[("a", PropertyType::Bool, 0)], None),
+                Section::Names,
+            ),
+            (absent(0, &[], Some(&tail)), Section::Tail),
+        ] {
+            let error = Err(DecodeError::AbsentWithValues(section));
+            assert_eq!(verify(&buffer, 0).map(|_| ()), error);
+            assert!(Deserializer::new().read_bytes(&buffer, 0).is_none());
+            assert_eq!(Deserializer::new().load(&buffer, 0), error);
+            assert_eq!(canonicalize(&buffer).map(|_| ()), error);
+        }
+    }
+
+    fn strings_to_bytes(strings: &[&str], front_coding: bool) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        serializer.set_front_coding(front_coding);
+        for string in strings {
+            serializer.write_string(string);
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        buffer
+    }
+
+    fn take_strings(buffer: &[u8], count: usize) -> Vec<String> {
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(buffer, 0).unwrap();
+        (0..count)
+            .map(|_| deserializer.take_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    pub fn front_coding_sorted_keys() {
+        let keys = [
+            "server.http.bind_address",
+            "server.http.max_connections",
+            "server.http.request_timeout",
+            "server.http.tls.certificate",
+            "server.http.tls.private_key",
+            "server.logging.format",
+            "server.logging.level",
+            "server.logging.output",
+        ];
+        let plain = strings_to_bytes(&keys, false);
+        let front_coded = strings_to_bytes(&keys, true);
+        assert!(
+            front_coded.len() * 3 < plain.len() * 2,
+            "{} vs {}",
+            front_coded.len(),
+            plain.len()
+        );
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&front_coded, 0).unwrap();
+        assert!(deserializer.last_stats().front_coded);
+        assert_eq!(take_strings(&front_coded, keys.len()), keys);
+    }
+
+    #[test]
+    pub fn front_coding_unsorted_roundtrip() {
+        // shares some prefixes, including partial multi-byte characters
+        let strings = [
+            "zeta",
+            "alpha",
+            "alphabet",
+            "ünïcode",
+            "ünï",
+            "üx",
+            "",
+            "alpha",
+        ];
+        let buffer = strings_to_bytes(&strings, true);
+        assert_eq!(take_strings(&buffer, strings.len()), strings);
+
+        // nothing shared, so the flag stays off
+        let strings = ["zeta", "alpha", "mu"];
+        let buffer = strings_to_bytes(&strings, true);
+        assert_eq!(buffer, strings_to_bytes(&strings, false));
+        assert_eq!(take_strings(&buffer, strings.len()), strings);
+    }
+
+    #[test]
+    pub fn repeated_strings_are_stored_once() {
+        // ten listeners on the same upstream
+        const UPSTREAM: &str = "backend.internal:443";
+        let written = |repeats: i64, column_offsets| {
+            let mut serializer = Serializer::new();
+            serializer.set_column_offsets(column_offsets);
+            for port in 0..repeats {
+                serializer.write_int(8000 + port);
+                serializer.write_string(UPSTREAM);
+            }
+            let mut buffer = Vec::new();
+            let stats = serializer.finish(&mut buffer, 0);
+            (buffer, stats)
+        };
+
+        let (_, once) = written(1, false);
+        assert!(!once.deduplicated_strings);
+        // a bit for each string and the distance back for each repeat
+        let references = 1 + 9 * (1 + small_int_encoded_bits(0));
+        for column_offsets in [false, true] {
+            let (buffer, stats) = written(10, column_offsets);
+            assert!(stats.deduplicated_strings);
+            assert_eq!(stats.strings.values, 10);
+            assert_eq!(stats.strings.bits, once.strings.bits + references);
+
+            verify(&buffer, 0).unwrap();
+            let mut deserializer = Deserializer::new();
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            assert!(deserializer.last_stats().deduplicated_strings);
+            for port in 0..10 {
+                assert_eq!(deserializer.take_int(), Some(8000 + port));
+                assert_eq!(deserializer.take_string().as_deref(), Some(UPSTREAM));
+            }
+            assert_eq!(deserializer.state(), LoadState::Drained);
+        }
+    }
+
+    #[test]
+    pub fn map_keys_are_sorted_and_front_coded() {
+        let values = [
+            PropertyValue::Integer(7),
+            PropertyValue::String("ada".to_owned()),
+            PropertyValue::String("ada@example.com".to_owned()),
+        ];
+        let keys = ["user_id", "user_name", "user_email"];
+        let mut serializer = Serializer::new();
+        serializer.write_map_sorted_keys(keys.into_iter().zip(&values));
+        serializer.write_int(1);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        assert!(stats.front_coded);
+
+        // the same entries in the order given, without front coding
+        let mut serializer = Serializer::new();
+        serializer.write_length(keys.len());
+        for key in keys {
+            serializer.write_string(key);
+        }
+        for value in &values {
+            serializer.write_value(value);
+        }
+        serializer.write_int(1);
+        let mut plain = Vec::new();
+        serializer.finish(&mut plain, 0);
+        assert!(buffer.len() < plain.len(), "{buffer:?} vs {plain:?}");
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_map().unwrap(),
+            [
+                ("user_email".to_owned(), values[2].clone()),
+                ("user_id".to_owned(), values[0].clone()),
+                ("user_name".to_owned(), values[1].clone()),
+            ]
+        );
+        assert_eq!(deserializer.take_int(), Some(1));
+        assert_eq!(deserializer.take_map(), None);
+    }
+
+    #[test]
+    pub fn bare_values_roundtrip() {
+        let mut nested = PropertyValue::Array(vec![PropertyValue::Integer(1)]);
+        for depth in 0..500 {
+            nested = PropertyValue::Array(vec![
+                PropertyValue::String(format!("level {depth}")),
+                nested,
+                PropertyValue::Bool(depth % 2 == 0),
+            ]);
+        }
+        for value in [
+            PropertyValue::String("ünïcode root".to_owned()),
+            PropertyValue::Integer(i64::MAX),
+            PropertyValue::Integer(i64::MIN),
+            PropertyValue::Array(Vec::new()),
+            nested,
+        ] {
+            let buffer = value.to_bytes(7);
+            assert_eq!(PropertyValue::from_bytes(&buffer, 7), Ok(value.clone()));
+            assert_eq!(
+                PropertyValue::from_bytes(&buffer, 6),
+                Err(DecodeError::VersionMismatch {
+                    expected: 6,
+                    found: 7
+                })
+            );
+
+            // the serializer's flags don't change how it reads back
+            let mut serializer = Serializer::new();
+            serializer.set_front_coding(true);
+            serializer.set_column_offsets(true);
+            serializer.set_provenance(1, "tooling").unwrap();
+            value.serialize(&mut serializer);
+            let mut buffer = Vec::new();
+            serializer.finish_aligned(&mut buffer, 7, 8);
+            assert_eq!(PropertyValue::from_bytes(&buffer, 7), Ok(value));
+        }
+
+        // more than one value isn't a bare value
+        let mut serializer = Serializer::new();
+        serializer.write_value(&PropertyValue::Integer(1));
+        serializer.write_int(2);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        assert!(matches!(
+            PropertyValue::from_bytes(&buffer, 0),
+            Err(DecodeError::UnconsumedFields { fields: 1, .. })
+        ));
+    }
+
+    fn verify_fixture() -> Vec<u8> {
+        let array = vec![
+            PropertyValue::String("ünïcode".to_owned()),
+            PropertyValue::Array(vec![PropertyValue::Integer(-3), PropertyValue::Bool(true)]),
+        ];
+        let tail = vec![
+            PropertyValue::String("tail".to_owned()),
+            PropertyValue::Integer(12),
+        ];
+        let mut serializer = Serializer::new();
+        serializer.set_front_coding(true);
+        serializer.write_int(1920);
+        serializer.write_int(1080);
+        serializer.write_bool(false);
+        serializer.write_string("window.title");
+        serializer.write_string("window.width");
+        serializer.write_array(&array);
+        serializer.write_tail_array(&tail);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 3);
+        buffer
+    }
+
+    #[test]
+    pub fn verify_accepts_valid_buffers() {
+        let buffer = verify_fixture();
+        let report = verify(&buffer, 3).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 3).unwrap();
+        assert_eq!(report.counts, deserializer.remaining_counts());
+        assert_eq!(report.tail_values, 2);
+        assert!(report.decoded_bytes > 0);
+
+        let mut empty = Vec::new();
+        Serializer::new().finish(&mut empty, 0);
+        assert_eq!(verify(&empty, 0).unwrap(), VerifyReport::default());
+    }
+
+    #[test]
+    pub fn peek_header_reads_only_the_prefix() {
+        let mut buffer = verify_fixture();
+        // the version it was written with, nothing to compare it against
+        buffer[0] = 9;
+        let mut header = None;
+        let allocations = crate::alloc_counter::count(|| header = Some(peek_header(&buffer)));
+        assert_eq!(allocations, 0);
+        let header = header.unwrap().unwrap();
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 9).unwrap();
+        let counts = deserializer.remaining_counts();
+        assert_eq!((header.version, header.revision), (9, FORMAT_REVISION));
+        assert_eq!(header.flags, 0);
+        assert_eq!(header.int_count, counts.integers as u64);
+        assert_eq!(header.bool_count, counts.booleans as u64);
+        assert_eq!(header.string_count, counts.strings as u64);
+        assert_eq!(header.tag_count, counts.property_types as u64);
+        assert_eq!(header.length_count, counts.lengths as u64);
+        assert_eq!(header.header_bits, deserializer.last_stats().header_bits);
+        assert!(!header.counts_exceed_input);
+
+        let mut serializer = Serializer::new();
+        serializer.set_format_id(12);
+        serializer.set_column_offsets(true);
+        serializer.write_int(1);
+        serializer.finish(&mut buffer, 0);
+        let header = peek_header(&buffer).unwrap();
+        assert_eq!(header.format_id, 12);
+        assert!(header.has(OPTION_FORMAT_ID) && header.has(OPTION_COLUMN_OFFSETS));
+
+        // cut off partway through the counts
+        assert_eq!(
+            peek_header(&buffer[..2]),
+            Err(DecodeError::Truncated(Section::Header))
+        );
+        assert_eq!(
+            peek_header(&[]),
+            Err(DecodeError::Truncated(Section::Header))
+        );
+
+        // a million booleans declared in a handful of bytes still has a header to route on
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION);
+        packer.write_small_int(0);
+        packer.write_count(0);
+        packer.write_count(1_000_000);
+        for _ in 0..32 {
+            packer.write_bit(false);
+        }
+        let header = peek_header(&buffer).unwrap();
+        assert_eq!(header.bool_count, 1_000_000);
+        assert!(header.counts_exceed_input);
+        assert!(matches!(
+            verify(&buffer, 0),
+            Err(DecodeError::CountsExceedInput { .. })
+        ));
+    }
+
+    #[test]
+    pub fn verify_rejects_corruption() {
+        let buffer = verify_fixture();
+
+        let mismatch = Err(DecodeError::VersionMismatch {
+            expected: 4,
+            found: 3,
+        });
+        assert_eq!(verify(&buffer, 4).map(|_| ()), mismatch);
+        let mut deserializer = Deserializer::new();
+        assert!(deserializer.read_bytes(&buffer, 4).is_none());
+        assert_eq!(deserializer.load(&buffer, 4), mismatch);
+        assert_eq!(deserializer.load(&buffer, 3), Ok(()));
+
+        for len in 0..buffer.len() {
+            assert!(verify(&buffer[..len], 3).is_err(), "{len} byte prefix");
+        }
+
+        let mut trailing = buffer.clone();
+        trailing.push(0);
+        assert_eq!(verify(&trailing, 3), Err(DecodeError::TrailingBytes(1)));
+
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION + 1);
+        assert_eq!(
+            verify(&buffer, 0),
+            Err(DecodeError::UnsupportedRevision(FORMAT_REVISION + 1))
+        );
+
+        // a million booleans declared in a handful of bytes
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION);
+        packer.write_small_int(0);
+        packer.write_count(0);
+        packer.write_count(1_000_000);
+        for _ in 0..32 {
+            packer.write_bit(false);
+        }
+        assert!(matches!(
+            verify(&buffer, 0),
+            Err(DecodeError::CountsExceedInput { .. })
+        ));
+
+        // second string claims to share more than the first one has
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION);
+        packer.write_small_int(0);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_bit(true);
+        packer.write_bit(true);
+        packer.write_count(2);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_small_int(0);
+        packer.write_ascii_string_adaptive("ab").unwrap();
+        packer.write_small_int(3);
+        packer.write_ascii_string_adaptive("c").unwrap();
+        packer.write_bit(false);
+        assert_eq!(
+            verify(&buffer, 0),
+            Err(DecodeError::InvalidPrefix { string: 1 })
+        );
+    }
+
+    #[test]
+    pub fn oversized_counts_fail_cleanly() {
+        // a million integers declared in a handful of bytes
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION);
+        packer.write_small_int(0);
+        packer.write_count(1_000_000);
+        packer.write_count(0);
+        packer.write_bit(true);
+        packer.write_bit(false);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_count(0);
+        for _ in 0..32 {
+            packer.write_bit(false);
+        }
+        assert!(Deserializer::new().read_bytes(&buffer, 0).is_none());
+        // could still have been a constant column, so this only shows up once the ints run out
+        assert_eq!(
+            verify(&buffer, 0),
+            Err(DecodeError::Truncated(Section::Integers))
+        );
+
+        // a constant column only takes a few bits whatever the count, so the count itself is
+        // the only thing that can be wrong
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION);
+        packer.write_small_int(0);
+        packer.write_count(i64::MAX as u64);
+        packer.write_count(0);
+        packer.write_bit(true);
+        packer.write_bit(false);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_bit(true);
+        packer.write_int(7);
+        packer.write_small_int(0);
+        packer.write_bit(false);
+        assert!(Deserializer::new().read_bytes(&buffer, 0).is_none());
+    }
+
+    #[test]
+    pub fn temporary_values_match_borrowed_arrays() {
+        let array = vec![
+            PropertyValue::String("kept".to_owned()),
+            PropertyValue::Array(vec![PropertyValue::String("nested".to_owned())]),
+            PropertyValue::Integer(3),
+        ];
+        let mut borrowed = Serializer::new();
+        borrowed.write_array(&array);
+        let mut expected = Vec::new();
+        borrowed.finish(&mut expected, 0);
+
+        let mut copied = Serializer::new();
+        copied.write_length(array.len());
+        for value in array.clone() {
+            copied.write_value(&value);
+        }
+        let mut buffer = Vec::new();
+        copied.finish(&mut buffer, 0);
+        assert_eq!(buffer, expected);
+    }
+
+    #[test]
+    pub fn oversized_array_length_fails_cleanly() {
+        let mut serializer = Serializer::new();
+        serializer.write_length(usize::MAX);
+        serializer.write_property_type(PropertyType::Integer);
+        serializer.write_int(1);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_array(), None);
+    }
+
+    #[test]
+    pub fn array_lengths_are_checked_against_tags() {
+        // an outer array of one array that claims far more values than there are tags left
+        let mut serializer = Serializer::new();
+        serializer.write_length(1);
+        serializer.write_property_type(PropertyType::Array);
+        serializer.write_length(10_000);
+        serializer.write_property_type(PropertyType::Integer);
+        serializer.write_int(1);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        let err = deserializer.take_array_checked().unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::ArrayLength {
+                declared: 10_000,
+                available: 1
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "array declares 10000 values but only 1 property types remain"
+        );
+
+        // one tag too few is missing a value rather than over-declared
+        let mut serializer = Serializer::new();
+        serializer.write_length(1);
+        serializer.write_property_type(PropertyType::String);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        deserializer.clear();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::Truncated(Section::Strings))
+        );
+    }
+
+    #[test]
+    pub fn deeply_nested_arrays_decode_iteratively() {
+        // far deeper than a recursive decode gets on a test thread's stack, built by hand since
+        // writing (and dropping) a `PropertyValue` this deep recurses too
+        const DEPTH: usize = 100_000;
+        let mut serializer = Serializer::new();
+        serializer.write_length(1);
+        for level in 1..DEPTH {
+            serializer.write_property_type(PropertyType::Array);
+            serializer.write_length(if level + 1 < DEPTH { 1 } else { 0 });
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::with_limits(DecodeLimits::unlimited());
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        let mut array = deserializer.take_array().unwrap();
+        let mut depth = 1;
+        while let Some(value) = array.pop() {
+            let PropertyValue::Array(inner) = value else {
+                panic!("{value:?} at depth {depth}");
+            };
+            array = inner;
+            depth += 1;
+        }
+        assert_eq!(depth, DEPTH);
+        assert_eq!(deserializer.remaining_counts(), ColumnCounts::default());
+
+        // the tags still owed to the outer array count against the inner one's length
+        let mut serializer = Serializer::new();
+        serializer.write_length(2);
+        serializer.write_property_type(PropertyType::Array);
+        serializer.write_length(3);
+        for int in 0..3 {
+            serializer.write_property_type(PropertyType::Integer);
+            serializer.write_int(int);
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::ArrayLength {
+                declared: 3,
+                available: 2
+            })
+        );
+    }
+
+    #[test]
+    pub fn invalid_utf8_is_decoded_lossily() {
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION);
+        packer.write_small_int(0);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_bit(false);
+        packer.write_bit(false);
+        packer.write_count(2);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_unicode_huffman_string("ünïcode");
+        // every byte escaped, a lone continuation byte in the middle
+        let bytes = [b'o', 0x80, b'k'];
+        packer.write_int(bytes.len() as i64);
+        for byte in bytes {
+            packer.write_bit(true);
+            packer.write_byte(byte);
+        }
+        packer.write_small_int(0);
+        packer.write_bit(false);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_string().unwrap(), "ünïcode");
+        assert_eq!(
+            deserializer.take_string().unwrap(),
+            String::from_utf8_lossy(&bytes)
+        );
+    }
+
+    #[test]
+    pub fn hostile_lengths_are_errors() {
+        // a 2^40 byte string, which is 0 once truncated to a 32 bit usize, and a few bytes after
+        let mut buffer = Vec::new();
+        let mut packer = BitPacker::new(&mut buffer);
+        packer.write_byte(0);
+        packer.write_small_int(FORMAT_REVISION);
+        packer.write_small_int(0);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_bit(false);
+        packer.write_bit(false);
+        packer.write_count(1);
+        packer.write_count(0);
+        packer.write_count(0);
+        packer.write_int(1 << 40);
+        for byte in *b"tail" {
+            packer.write_bit(true);
+            packer.write_byte(byte);
+        }
+        packer.write_small_int(0);
+        packer.write_bit(false);
+        assert!(Deserializer::new().read_bytes(&buffer, 0).is_none());
+        assert_eq!(
+            verify(&buffer, 0),
+            Err(DecodeError::Truncated(Section::Strings))
+        );
+
+        let mut serializer = Serializer::new();
+        serializer.write_length(1 << 40);
+        serializer.write_property_type(PropertyType::Integer);
+        serializer.write_int(1);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::ArrayLength {
+                declared: 1 << 40,
+                available: 1
+            })
+        );
+    }
+
+    #[test]
+    pub fn checked_conversions() {
+        assert_eq!(length_int(1 << 40), 1 << 40);
+        assert_eq!(stat_count(1 << 20), 1 << 20);
+        assert_eq!(column_count(1 << 20), Ok(1 << 20));
+        if usize::BITS < 64 {
+            assert!(column_count(1 << 40).is_err());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "length doesn't fit in an int")]
+    pub fn lengths_past_an_int_panic() {
+        length_int(usize::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "count doesn't fit in an int")]
+    pub fn counts_past_an_int_panic() {
+        let mut buffer = Vec::new();
+        BitPacker::new(&mut buffer).write_count(u64::MAX);
+    }
+
+    #[test]
+    pub fn string_heavy_decode_allocates_a_constant() {
+        let strings: Vec<String> = (0..500)
+            .map(|i| format!("section{}.key_{i}", i % 7))
+            .collect();
+        let mut serializer = Serializer::new();
+        for string in &strings {
+            serializer.write_string(string);
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        // the spans are sized from the header, only the text grows while it's decoded
+        let mut deserializer = Deserializer::new();
+        let allocations =
+            crate::alloc_counter::count(|| deserializer.read_bytes(&buffer, 0).unwrap());
+        assert!(allocations <= 16, "{allocations} allocations");
+        deserializer.clear();
+        let allocations =
+            crate::alloc_counter::count(|| deserializer.read_bytes(&buffer, 0).unwrap());
+        assert_eq!(allocations, 0);
+        for string in &strings {
+            assert_eq!(&deserializer.take_string().unwrap(), string);
+        }
+    }
+
+    fn sectioned_fixture(strings: &[String], column_offsets: bool) -> Vec<u8> {
+        let array = vec![
+            PropertyValue::Integer(-4),
+            PropertyValue::Array(vec![PropertyValue::Bool(true)]),
+        ];
+        let tail = vec![PropertyValue::String("tail".to_owned())];
+        let mut serializer = Serializer::new();
+        serializer.set_front_coding(true);
+        serializer.set_column_offsets(column_offsets);
+        for (i, string) in strings.iter().enumerate() {
+            serializer.write_int(i as i64);
+            serializer.write_bool(i % 3 == 0);
+            serializer.write_string(string);
+        }
+        serializer.write_array(&array);
+        serializer.write_tail_array(&tail);
+        let mut buffer = Vec::new();
+        let encode_stats = serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.last_stats(), &encode_stats);
+        buffer
+    }
+
+    fn sorted_keys(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| format!("section{:03}.key_{i}", i / 100))
+            .collect()
+    }
+
+    #[test]
+    pub fn column_offsets_roundtrip() {
+        // spans a few string sections, the last one partially filled
+        let strings = sorted_keys(STRING_SECTION_LEN * 2 + 10);
+        let buffer = sectioned_fixture(&strings, true);
+        let plain = sectioned_fixture(&strings, false);
+        assert_ne!(buffer, plain);
+
+        let mut sectioned = Deserializer::new();
+        sectioned.read_bytes(&buffer, 0).unwrap();
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&plain, 0).unwrap();
+        assert_eq!(
+            format!(
+                "{:?}",
+                (&sectioned.integers, &sectioned.booleans, &sectioned.strings)
+            ),
+            format!(
+                "{:?}",
+                (
+                    &deserializer.integers,
+                    &deserializer.booleans,
+                    &deserializer.strings
+                )
+            )
+        );
+        assert_eq!(sectioned.take_tail_array(), deserializer.take_tail_array());
+
+        assert_eq!(verify(&buffer, 0).unwrap(), verify(&plain, 0).unwrap());
+        for len in (0..buffer.len()).step_by(97) {
+            assert!(verify(&buffer[..len], 0).is_err(), "{len} byte prefix");
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    pub fn parallel_matches_serial() {
+        let strings = sorted_keys(20_000);
+        let buffer = sectioned_fixture(&strings, true);
+        assert!(buffer.len() > 64 * 1024);
+
+        let mut serial = Deserializer::new();
+        serial.read_bytes(&buffer, 0).unwrap();
+        let mut parallel = Deserializer::new();
+        parallel.read_bytes_parallel(&buffer, 0).unwrap();
+        assert_eq!(format!("{serial:?}"), format!("{parallel:?}"));
+
+        // no offsets to split on, falls back to the serial path
+        let plain = sectioned_fixture(&strings, false);
+        parallel.clear();
+        serial.clear();
+        parallel.read_bytes_parallel(&plain, 0).unwrap();
+        serial.read_bytes(&plain, 0).unwrap();
+        assert_eq!(format!("{serial:?}"), format!("{parallel:?}"));
+    }
+
+    #[test]
+    pub fn named_fields_in_any_order() {
+        let mut serializer = Serializer::new();
+        serializer.write_named_string("name", "primary");
+        serializer.write_named_int("data", 7);
+        serializer.write_named_int("retries", 3);
+        serializer.write_named_bool("enabled", true);
+        serializer.write_int(99);
+        let mut buffer = Vec::new();
+        let encode_stats = serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.last_stats(), &encode_stats);
+        assert_eq!(deserializer.take_named_int("retries"), Some(3));
+        assert_eq!(deserializer.take_named_bool("enabled"), Some(true));
+        assert_eq!(deserializer.take_named_int("data"), Some(7));
+        assert_eq!(
+            deserializer.take_named_string("name").as_deref(),
+            Some("primary")
+        );
+        assert_eq!(deserializer.take_named_int("name"), None);
+        assert_eq!(deserializer.take_named_int("missing"), None);
+
+        assert_eq!(deserializer.take_named_int("retries"), None);
+        let remaining = deserializer.remaining_counts();
+        assert_eq!((remaining.integers, remaining.strings), (1, 0));
+        assert_eq!(deserializer.state(), LoadState::Loaded);
+
+        // positional takes still see every value, named ones adjust for them
+        assert_eq!(deserializer.take_int(), Some(7));
+        assert_eq!(deserializer.remaining_counts().integers, 1);
+        assert_eq!(deserializer.take_named_int("data"), None);
+
+        // a reader that only takes by name drains the buffer
+        let mut serializer = Serializer::new();
+        serializer.write_named_string("name", "primary");
+        serializer.write_named_int("retries", 3);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        deserializer.clear();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_named_int("retries"), Some(3));
+        assert_eq!(deserializer.state(), LoadState::Loaded);
+        assert_eq!(
+            deserializer.take_named_string("name").as_deref(),
+            Some("primary")
+        );
+        assert_eq!(deserializer.state(), LoadState::Drained);
+        assert_eq!(deserializer.take_named_string("name"), None);
+    }
+
+    #[test]
+    pub fn unnamed_buffers_take_positionally() {
+        let mut serializer = Serializer::new();
+        serializer.write_int(7);
+        serializer.write_string("primary");
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_named_string("name").as_deref(),
+            Some("primary")
+        );
+        assert_eq!(deserializer.take_named_int("data"), Some(7));
+    }
+
+    #[test]
+    pub fn kind_markers_catch_swapped_takes() {
+        let array = vec![PropertyValue::Integer(3), PropertyValue::Bool(false)];
+        let write = |markers: bool| {
+            let mut serializer = Serializer::new();
+            serializer.set_kind_markers(markers);
+            serializer.write_int(8080);
+            serializer.write_bool(true);
+            serializer.write_int(30);
+            serializer.write_array(&array);
+            let mut buffer = Vec::new();
+            serializer.finish(&mut buffer, 0);
+            buffer
+        };
+
+        // a forgotten `take_bool` goes unnoticed, the ints are still there in order
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&write(false), 0).unwrap();
+        assert_eq!(deserializer.take_int(), Some(8080));
+        assert_eq!(deserializer.take_int(), Some(30));
+        assert_eq!(deserializer.take_kind_mismatch(), None);
+
+        let marked = write(true);
+        assert!(verify(&marked, 0).is_ok());
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&marked, 0).unwrap();
+        assert_eq!(deserializer.take_int(), Some(8080));
+        assert_eq!(deserializer.take_int(), None);
+        let mismatch = deserializer.take_kind_mismatch().unwrap();
+        assert_eq!(
+            mismatch,
+            DecodeError::KindMismatch {
+                index: 1,
+                expected: PropertyType::Integer,
+                found: PropertyType::Bool,
+            }
+        );
+        assert_eq!(
+            mismatch.to_string(),
+            "field 1 was read as Integer but written as Bool"
+        );
+        // nothing was taken, reading in the right order carries on
+        assert_eq!(deserializer.take_bool(), Some(true));
+        assert_eq!(deserializer.take_int(), Some(30));
+        assert_eq!(deserializer.take_array(), Some(array.clone()));
+
+        // inside an array the mismatch comes out of `take_array_checked`
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&marked, 0).unwrap();
+        assert_eq!(
+            deserializer.take_array_checked(),
+            Err(DecodeError::KindMismatch {
+                index: 0,
+                expected: PropertyType::Array,
+                found: PropertyType::Integer,
+            })
+        );
+    }
+
+    // every option that makes a buffer bigger without changing what it holds
+    fn wide(values: &[i64], strings: &[&str], tail: &[PropertyValue]) -> Vec<u8> {
+        let mut serializer = Serializer::new();
+        serializer.set_column_offsets(true);
+        serializer.set_section_checksums(true);
+        serializer.set_kind_markers(true);
+        serializer.set_front_coding(true);
+        serializer.set_int_header_plan(IntHeaderPlan::from_table(3).unwrap());
+        serializer.set_format_id(42);
+        serializer.set_provenance(1_700_000_000, "ingest").unwrap();
+        for (value, string) in values.iter().zip(strings) {
+            serializer.write_int(*value);
+            serializer.write_string_with(string, StringCodec::Raw);
+            serializer.write_bool(value % 2 == 0);
+        }
+        serializer.write_array(&[PropertyValue::Integer(1), PropertyValue::Bool(false)]);
+        serializer.write_named_string("zone", "eu-west");
+        serializer.write_named_int("replicas", 3);
+        serializer.write_tail_array(tail);
+        let mut buffer = Vec::new();
+        serializer.finish_aligned(&mut buffer, 0, 64);
+        buffer
+    }
+
+    #[test]
+    pub fn canonicalize_shrinks_wide_buffers() {
+        let values: Vec<i64> = (0..40).map(|i| i * 7 % 13).collect();
+        let strings: Vec<String> = (0..40).map(|i| format!("shard-{}", i % 5)).collect();
+        let strings: Vec<&str> = strings.iter().map(String::as_str).collect();
+        let tail = [PropertyValue::Integer(9)];
+        let wide = wide(&values, &strings, &tail);
+        assert!(!is_canonical(&wide));
+
+        let canonical = canonicalize(&wide).unwrap();
+        assert!(canonical.len() < wide.len(), "{} bytes", canonical.len());
+        assert!(is_canonical(&canonical));
+        assert_eq!(canonicalize(&canonical).unwrap(), canonical);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&canonical, 0).unwrap();
+        assert_eq!(deserializer.format_id(), 42);
+        assert_eq!(deserializer.provenance().unwrap().producer, "ingest");
+        assert_eq!(
+            deserializer.take_named_string("zone").as_deref(),
+            Some("eu-west")
+        );
+        assert_eq!(deserializer.take_named_int("replicas"), Some(3));
+        for (value, string) in values.iter().zip(&strings) {
+            assert_eq!(deserializer.take_int(), Some(*value));
+            assert_eq!(deserializer.take_string().as_deref(), Some(*string));
+            assert_eq!(deserializer.take_bool(), Some(value % 2 == 0));
+        }
+        assert_eq!(
+            deserializer.take_array(),
+            Some(vec![PropertyValue::Integer(1), PropertyValue::Bool(false)])
+        );
+        assert_eq!(deserializer.take_tail_array(), Some(tail.to_vec()));
+
+        // the names come out of a map, so their order can't depend on it
+        for _ in 0..8 {
+            assert_eq!(canonicalize(&wide).unwrap(), canonical);
+        }
+        assert_eq!(
+            canonicalize(&[]),
+            Err(DecodeError::Truncated(Section::Header))
+        );
+        assert!(!is_canonical(&canonical[..canonical.len() - 1]));
+    }
 }
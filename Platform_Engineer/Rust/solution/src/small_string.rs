@@ -0,0 +1,217 @@
+use std::{borrow::Borrow, fmt, ops::Deref};
+
+// Decoded config strings are mostly short keys and values, keeping anything up to as long as a
+// `String` is wide inline means taking them out of a deserializer doesn't touch the allocator.
+// The enum is a word wider than a `String` for it (32 bytes on 64 bit targets against 24), the
+// inline bytes leave no room for the tag in a `String`'s spare bits.
+pub const INLINE_CAPACITY: usize = 23;
+
+#[derive(Clone)]
+pub enum SmallString {
+    Inline {
+        len: u8,
+        bytes: [u8; INLINE_CAPACITY],
+    },
+    Heap(String),
+}
+
+impl SmallString {
+    pub fn new(string: &str) -> Self {
+        if string.len() <= INLINE_CAPACITY {
+            let mut bytes = [0u8; INLINE_CAPACITY];
+            bytes[..string.len()].copy_from_slice(string.as_bytes());
+            SmallString::Inline {
+                len: string.len() as u8,
+                bytes,
+            }
+        } else {
+            SmallString::Heap(string.to_owned())
+        }
+    }
+
+    pub fn is_inline(&self) -> bool {
+        matches!(self, SmallString::Inline { .. })
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            // SAFETY: inline bytes are only ever copied from a `&str` in `new`, and `len` is never
+            // modified afterwards so this is always a full, valid utf-8 string.
+            SmallString::Inline { len, bytes } => unsafe {
+                std::str::from_utf8_unchecked(&bytes[..*len as usize])
+            },
+            SmallString::Heap(string) => string.as_str(),
+        }
+    }
+}
+
+impl Default for SmallString {
+    fn default() -> Self {
+        SmallString::new("")
+    }
+}
+
+impl Deref for SmallString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for SmallString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for SmallString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq for SmallString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SmallString {}
+
+impl PartialEq<str> for SmallString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SmallString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl From<&str> for SmallString {
+    fn from(string: &str) -> Self {
+        SmallString::new(string)
+    }
+}
+
+impl From<String> for SmallString {
+    fn from(string: String) -> Self {
+        if string.len() <= INLINE_CAPACITY {
+            SmallString::new(&string)
+        } else {
+            SmallString::Heap(string)
+        }
+    }
+}
+
+impl From<SmallString> for String {
+    fn from(string: SmallString) -> Self {
+        match string {
+            SmallString::Inline { .. } => string.as_str().to_owned(),
+            SmallString::Heap(string) => string,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        alloc_counter,
+        serializer::{Deserializer, Serializer},
+    };
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    pub fn a_word_wider_than_a_string() {
+        assert_eq!(size_of::<String>(), 24);
+        assert_eq!(size_of::<SmallString>(), 32);
+        assert_eq!(INLINE_CAPACITY + 1, size_of::<String>());
+    }
+
+    #[test]
+    pub fn inline_boundary() {
+        let fits = "a".repeat(INLINE_CAPACITY);
+        let spills = "a".repeat(INLINE_CAPACITY + 1);
+        assert!(SmallString::new(&fits).is_inline());
+        assert!(!SmallString::new(&spills).is_inline());
+        assert_eq!(SmallString::new(&spills), spills.as_str());
+        assert_eq!(String::from(SmallString::new("ключ")), "ключ");
+    }
+
+    #[test]
+    pub fn unicode_at_the_inline_boundary() {
+        // the capacity is in bytes, a character can't be split across it
+        let two_byte = format!("{}é", "a".repeat(INLINE_CAPACITY - 2));
+        let spills = format!("{}é", "a".repeat(INLINE_CAPACITY - 1));
+        let four_byte = format!("{}🦀", "a".repeat(INLINE_CAPACITY - 4));
+        let cyrillic = "ключ".repeat(3);
+        assert_eq!(two_byte.len(), INLINE_CAPACITY);
+        assert_eq!(spills.len(), INLINE_CAPACITY + 1);
+        assert_eq!(cyrillic.len(), 24);
+
+        let mut serializer = Serializer::new();
+        for string in [&two_byte, &spills, &four_byte, &cyrillic] {
+            serializer.write_string(string);
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        for (string, inline) in [
+            (&two_byte, true),
+            (&spills, false),
+            (&four_byte, true),
+            (&cyrillic, false),
+        ] {
+            let small = deserializer.take_small_string().unwrap();
+            assert_eq!(small, string.as_str());
+            assert_eq!(small.is_inline(), inline, "{string}");
+            assert_eq!(small.chars().last(), string.chars().last());
+        }
+    }
+
+    #[test]
+    pub fn short_keys_decode_without_allocating() {
+        let keys = [
+            "host",
+            "port",
+            "timeout_ms",
+            "retries",
+            "log.level",
+            "Tokyo",
+        ];
+
+        let mut serializer = Serializer::new();
+        for key in keys {
+            serializer.write_string(key);
+        }
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        // first decode sizes the columns and scratch space
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        deserializer.clear();
+
+        let mut decoded = Vec::with_capacity(keys.len());
+        let allocations = alloc_counter::count(|| {
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            while let Some(key) = deserializer.take_small_string() {
+                decoded.push(key);
+            }
+        });
+
+        assert_eq!(allocations, 0);
+        assert_eq!(decoded, keys);
+    }
+}
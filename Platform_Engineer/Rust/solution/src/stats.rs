@@ -0,0 +1,98 @@
+use std::fmt;
+
+use crate::error::ColumnCounts;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColumnStats {
+    pub values: u64,
+    pub bits: u64,
+}
+
+// How many values each of a `Serializer`'s columns holds before it has to grow, see
+// `Serializer::capacities`. A hot loop that `clear`s (or `reuse`s) one serializer should see
+// these stop changing once it has written its largest message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColumnCapacities {
+    pub integers: usize,
+    pub booleans: usize,
+    pub strings: usize,
+    pub raw_strings: usize,
+    pub property_types: usize,
+    pub lengths: usize,
+    pub names: usize,
+    pub kinds: usize,
+    // bytes of scratch `finish` packs the column offsets sections into
+    pub sections: usize,
+}
+
+// Sizes and codec choices for a single buffer, the same shape is produced by `Serializer::finish`
+// and `Deserializer::read_bytes` so the two can be compared directly.
+//
+// Only counters, nothing timing related so these are deterministic for a given buffer.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub total_bytes: u64,
+    pub header_bits: u64,
+    pub integers: ColumnStats,
+    pub booleans: ColumnStats,
+    pub strings: ColumnStats,
+    pub property_types: ColumnStats,
+    pub lengths: ColumnStats,
+    // written with `StringCodec::Raw`, not counted in `strings`
+    pub raw_strings: ColumnStats,
+    // including the count, which is there even if nothing was named
+    pub names: ColumnStats,
+    // includes the presence bit and the padding up to the byte-aligned section
+    pub tail: ColumnStats,
+    pub all_ascii: bool,
+    pub front_coded: bool,
+    // `Serializer::set_lz_strings`
+    pub lz_strings: bool,
+    // `Serializer::set_dedup_strings`, when it saved anything
+    pub deduplicated_strings: bool,
+    pub constant_integers: bool,
+    // only set by `Serializer::finish_hashed`
+    pub digest: Option<u64>,
+}
+
+pub type EncodeStats = Stats;
+pub type DecodeStats = Stats;
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "total: {} bytes", self.total_bytes)?;
+        writeln!(f, "header: {} bits", self.header_bits)?;
+        let columns = [
+            ("integers", self.integers),
+            ("booleans", self.booleans),
+            ("strings", self.strings),
+            ("property types", self.property_types),
+            ("lengths", self.lengths),
+            ("raw strings", self.raw_strings),
+            ("names", self.names),
+            ("tail", self.tail),
+        ];
+        for (name, column) in columns {
+            writeln!(f, "{name}: {} values, {} bits", column.values, column.bits)?;
+        }
+        writeln!(f, "all ascii: {}", self.all_ascii)?;
+        writeln!(f, "front coded: {}", self.front_coded)?;
+        writeln!(f, "lz strings: {}", self.lz_strings)?;
+        writeln!(f, "deduplicated strings: {}", self.deduplicated_strings)?;
+        write!(f, "constant integers: {}", self.constant_integers)?;
+        if let Some(digest) = self.digest {
+            write!(f, "\ndigest: {digest:016x}")?;
+        }
+        Ok(())
+    }
+}
+
+// Result of `serializer::verify`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub counts: ColumnCounts,
+    pub tail_values: usize,
+    // rough in-memory size of everything that would be decoded: 8 bytes per integer and length,
+    // 1 per boolean and tag, plus the string contents.
+    pub decoded_bytes: u64,
+}
@@ -0,0 +1,201 @@
+use crate::{
+    error::ColumnCounts,
+    serializer::{Deserializer, IntoFormat, Serializer},
+};
+
+// An array of records of one type, written field by field rather than record by record. Two
+// string fields of a record end up next to each other in the string column, so a hundred records
+// alternate hosts and paths and front coding (or a compressor run afterwards) keeps having to start
+// over. `write_struct_array` writes every record's first string, then every record's second,
+// which puts the hosts together and the paths together.
+//
+// That needs every record to write the same number of values to a column. The array's count goes
+// in the length column, then each column's stride in `ColumnKind::ALL` order: the values each
+// record wrote to it plus 1, or 0 where the records differ (a `Vec` field of different lengths)
+// and the column is left in record order. The records follow. `take_struct_array` puts the values
+// back in record order in the deserializer's columns and takes the records from there as usual.
+//
+// Records can't use `write_named_*`, `write_tail_array` or raw strings.
+
+impl<'a> Serializer<'a> {
+    pub fn write_struct_array<T: IntoFormat>(&mut self, items: &'a [T]) {
+        let mut fields = self.scratch();
+        let mut strides: Option<[Option<usize>; 5]> = None;
+        let mut before = by_kind(fields.written_counts());
+        for item in items {
+            item.serialize(&mut fields);
+            let after = by_kind(fields.written_counts());
+            let wrote = std::array::from_fn(|kind| after[kind] - before[kind]);
+            let strides = strides.get_or_insert(wrote.map(Some));
+            for (stride, wrote) in strides.iter_mut().zip(wrote) {
+                if *stride != Some(wrote) {
+                    *stride = None;
+                }
+            }
+            before = after;
+        }
+
+        let strides = strides.unwrap_or_default();
+        self.write_length(items.len());
+        for stride in strides {
+            self.write_length(stride.map_or(0, |stride| stride + 1));
+        }
+        self.append_transposed(&mut fields, items.len(), strides);
+    }
+}
+
+fn by_kind(counts: ColumnCounts) -> [usize; 5] {
+    [
+        counts.integers,
+        counts.booleans,
+        counts.strings,
+        counts.property_types,
+        counts.lengths,
+    ]
+}
+
+impl Deserializer {
+    // The records of `write_struct_array`, `None` if any of them can't be taken.
+    pub fn take_struct_array<T: IntoFormat>(&mut self) -> Option<Vec<T>> {
+        let items = usize::try_from(self.take_length()?).ok()?;
+        let mut strides = [None; 5];
+        for stride in &mut strides {
+            *stride = match self.take_length()? {
+                0 => None,
+                code => Some(usize::try_from(code - 1).ok()?),
+            };
+        }
+        let [integers, booleans, strings, property_types, lengths] = strides;
+        if let Some(stride) = integers {
+            self.integers.interleave_front(items, stride)?;
+        }
+        if let Some(stride) = booleans {
+            self.booleans.interleave_front(items, stride)?;
+        }
+        if let Some(stride) = strings {
+            self.strings.interleave_front(items, stride)?;
+        }
+        if let Some(stride) = property_types {
+            self.property_types.interleave_front(items, stride)?;
+        }
+        if let Some(stride) = lengths {
+            self.lengths.interleave_front(items, stride)?;
+        }
+        // collected without reserving for the count, which comes from the buffer
+        (0..items).map(|_| T::take(self)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{derive_format, serializer::LoadState};
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Route {
+            host: String,
+            path: String,
+        }
+    }
+
+    fn routes() -> Vec<Route> {
+        (0..100)
+            .map(|i| Route {
+                host: format!("node{:02}.eu-west.internal", i % 10),
+                path: format!("/api/v2/accounts/{}/settings", i * 7),
+            })
+            .collect()
+    }
+
+    #[test]
+    pub fn fields_together_compress_better() {
+        let routes = routes();
+        let finish = |serializer: &mut Serializer| {
+            serializer.set_front_coding(true);
+            let mut buffer = Vec::new();
+            serializer.finish(&mut buffer, 0);
+            buffer
+        };
+
+        let mut serializer = Serializer::new();
+        serializer.write_struct_array(&routes);
+        let soa = finish(&mut serializer);
+        // the same records one after the other
+        let mut serializer = Serializer::new();
+        serializer.write_length(routes.len());
+        for route in &routes {
+            route.serialize(&mut serializer);
+        }
+        let aos = finish(&mut serializer);
+
+        let compressed = |bytes: &[u8]| zstd::bulk::compress(bytes, 3).unwrap().len();
+        assert!(soa.len() < aos.len(), "{} against {}", soa.len(), aos.len());
+        assert!(compressed(&soa) < compressed(&aos));
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&soa, 0).unwrap();
+        assert_eq!(deserializer.take_struct_array(), Some(routes));
+        assert_eq!(deserializer.state(), LoadState::Drained);
+    }
+
+    derive_format! {
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        struct Service {
+            port: i64,
+            replicas: i64,
+            aliases: Vec<String>,
+            enabled: bool,
+        }
+    }
+
+    #[test]
+    pub fn uneven_records_roundtrip() {
+        let services: Vec<Service> = (0..50)
+            .map(|i| Service {
+                port: 8000 + i,
+                replicas: i % 4,
+                aliases: (0..i % 3).map(|alias| format!("svc-{i}-{alias}")).collect(),
+                enabled: i % 2 == 0,
+            })
+            .collect();
+        for kind_markers in [false, true] {
+            let mut serializer = Serializer::new();
+            serializer.set_kind_markers(kind_markers);
+            serializer.write_int(-1);
+            serializer.write_struct_array(&services);
+            serializer.write_struct_array::<Service>(&[]);
+            serializer.write_string("after");
+            let mut buffer = Vec::new();
+            serializer.finish(&mut buffer, 0);
+
+            let mut deserializer = Deserializer::new();
+            deserializer.read_bytes(&buffer, 0).unwrap();
+            assert_eq!(deserializer.take_int(), Some(-1));
+            assert_eq!(deserializer.take_struct_array(), Some(services.clone()));
+            assert_eq!(
+                deserializer.take_struct_array::<Service>(),
+                Some(Vec::new())
+            );
+            assert_eq!(deserializer.take_string().as_deref(), Some("after"));
+            assert_eq!(deserializer.state(), LoadState::Drained);
+        }
+    }
+
+    #[test]
+    pub fn strides_past_the_column_are_rejected() {
+        let mut serializer = Serializer::new();
+        serializer.write_length(3);
+        // two integers a record, but only one written
+        for code in [3, 0, 0, 0, 0] {
+            serializer.write_length(code);
+        }
+        serializer.write_int(1);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_struct_array::<Route>(), None);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::{
+    error::{ColumnCounts, DecodeError, Section},
+    serializer::{Deserializer, PropertyType},
+};
+
+// A pass over the tag and length columns alone, before any `PropertyValue` is built. Shifted or
+// corrupt columns otherwise only show up partway through `take_array`, after the values before
+// them were already taken.
+
+// What the tags and lengths describe, see `Deserializer::validate_structure`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Structure {
+    // arrays not inside another array, each started by a length without a tag
+    pub top_level_arrays: usize,
+    // tagged values outside of any array, written with `Serializer::write_value`
+    pub top_level_values: usize,
+    // the deepest array, the outermost counts as 1
+    pub max_depth: usize,
+    // values the tags account for in each column, the rest of each column was written untagged
+    pub tagged: ColumnCounts,
+}
+
+impl Deserializer {
+    // Checks that every tag has a value left in its column and that the arrays nest inside the
+    // tags there are, without taking anything. A value column with fewer values than its tags
+    // reference is a `DecodeError::ColumnTagMismatch`.
+    //
+    // Only meant for buffers of values and arrays written through `write_array` and `write_value`
+    // (and untagged ints, strings and bools alongside them), with any top-level `write_value` after
+    // the last top-level array. Maps, records, schemas and the other layouts that put untagged
+    // values inside an array length aren't described by their tags and fail here.
+    pub fn validate_structure(&self) -> Result<Structure, DecodeError> {
+        self.check_loaded()?;
+        let available = self.remaining_counts();
+        let mut structure = Structure::default();
+
+        let tagged = &mut structure.tagged;
+        for (index, tag) in self.property_types.iter().enumerate() {
+            match tag {
+                PropertyType::Integer => tagged.integers += 1,
+                PropertyType::Bool => tagged.booleans += 1,
+                PropertyType::String => tagged.strings += 1,
+                PropertyType::Array => {
+                    tagged.lengths += 1;
+                    if tagged.lengths > available.lengths {
+                        return Err(DecodeError::InconsistentTag { index, kind: *tag });
+                    }
+                }
+            }
+        }
+        tagged.property_types = self.property_types.len();
+        // all of each column's tags, so the error is about the buffer rather than where a reader
+        // would have run out
+        let columns = [
+            (Section::Integers, available.integers, tagged.integers),
+            (Section::Booleans, available.booleans, tagged.booleans),
+            (Section::Strings, available.strings, tagged.strings),
+        ];
+        for (column, declared, referenced) in columns {
+            if referenced > declared {
+                return Err(DecodeError::ColumnTagMismatch {
+                    column,
+                    declared,
+                    referenced,
+                });
+            }
+        }
+
+        // Pre-order, the same order `write_array` wrote them in. Each entry is an array's length
+        // and how many of its tags are still to come, a length that isn't claimed by an array tag
+        // starts a top-level array.
+        let mut lengths = self.lengths.iter();
+        let mut tags = self.property_types.iter().enumerate();
+        let mut open: Vec<(u64, u64)> = Vec::new();
+        loop {
+            match open.last_mut() {
+                Some((_, 0)) => {
+                    open.pop();
+                }
+                Some((declared, left)) => {
+                    let Some((index, tag)) = tags.next() else {
+                        return Err(DecodeError::ArrayLength {
+                            declared: *declared,
+                            available: (*declared - *left) as usize,
+                        });
+                    };
+                    *left -= 1;
+                    if *tag == PropertyType::Array {
+                        let length = *lengths
+                            .next()
+                            .ok_or(DecodeError::InconsistentTag { index, kind: *tag })?;
+                        open.push((length, length));
+                        structure.max_depth = structure.max_depth.max(open.len());
+                    }
+                }
+                None => match lengths.next() {
+                    Some(length) => {
+                        structure.top_level_arrays += 1;
+                        open.push((*length, *length));
+                        structure.max_depth = structure.max_depth.max(1);
+                    }
+                    None => break,
+                },
+            }
+        }
+
+        // whatever's left was written outside of any array, where an array would need a length
+        for (index, tag) in tags {
+            if *tag == PropertyType::Array {
+                return Err(DecodeError::InconsistentTag { index, kind: *tag });
+            }
+            structure.top_level_values += 1;
+        }
+        Ok(structure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serializer::{PropertyValue, Serializer};
+
+    fn fixture() -> Vec<PropertyValue> {
+        vec![
+            PropertyValue::Integer(1),
+            PropertyValue::String("eu-west".to_owned()),
+            PropertyValue::Array(vec![PropertyValue::Bool(true), PropertyValue::Integer(2)]),
+            PropertyValue::Integer(3),
+        ]
+    }
+
+    fn loaded(serializer: &Serializer) -> Deserializer {
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+        let mut deserializer = Deserializer::new();
+        deserializer.load(&buffer, 0).unwrap();
+        deserializer
+    }
+
+    #[test]
+    pub fn structure_of_arrays_and_values() {
+        let array = fixture();
+        let mut serializer = Serializer::new();
+        serializer.write_int(9);
+        serializer.write_array(&array);
+        serializer.write_array(&[]);
+        serializer.write_value(&PropertyValue::Bool(false));
+
+        let deserializer = loaded(&serializer);
+        assert_eq!(
+            deserializer.validate_structure(),
+            Ok(Structure {
+                top_level_arrays: 2,
+                top_level_values: 1,
+                max_depth: 2,
+                tagged: ColumnCounts {
+                    integers: 3,
+                    booleans: 2,
+                    strings: 1,
+                    property_types: 7,
+                    lengths: 1,
+                },
+            })
+        );
+        // nothing was taken
+        assert_eq!(deserializer.remaining_view().counts().integers, 4);
+    }
+
+    #[test]
+    pub fn corrupt_tag_is_caught_before_any_value() {
+        // `write_array` of [1, "eu-west", 3] with the last tag corrupted into a string there's no
+        // value for
+        let mut serializer = Serializer::new();
+        serializer.write_length(3);
+        serializer.write_property_type(PropertyType::Integer);
+        serializer.write_int(1);
+        serializer.write_property_type(PropertyType::String);
+        serializer.write_string("eu-west");
+        serializer.write_property_type(PropertyType::String);
+        serializer.write_int(3);
+
+        let mut deserializer = loaded(&serializer);
+        let err = deserializer.validate_structure().unwrap_err();
+        assert_eq!(
+            err,
+            DecodeError::ColumnTagMismatch {
+                column: Section::Strings,
+                declared: 1,
+                referenced: 2,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "property types reference 2 values in the string column but it only has 1"
+        );
+        assert_eq!(deserializer.remaining_view().counts().integers, 2);
+        // where `take_array` only notices once the values before it are built
+        assert!(deserializer.take_array_checked().is_err());
+
+        // an array tag without a length
+        let mut serializer = Serializer::new();
+        serializer.write_length(1);
+        serializer.write_property_type(PropertyType::Array);
+        assert_eq!(
+            loaded(&serializer).validate_structure(),
+            Err(DecodeError::InconsistentTag {
+                index: 0,
+                kind: PropertyType::Array
+            })
+        );
+
+        // an array that claims more tags than there are
+        let array = fixture();
+        let mut serializer = Serializer::new();
+        serializer.write_array(&array);
+        serializer.write_length(3);
+        assert_eq!(
+            loaded(&serializer).validate_structure(),
+            Err(DecodeError::ArrayLength {
+                declared: 3,
+                available: 0
+            })
+        );
+    }
+
+    #[test]
+    pub fn columns_short_of_their_tags() {
+        // `write_array` of 5 values of `kind` where only 3 of them made it into their column
+        type Write = fn(&mut Serializer, usize);
+        let short = |kind, write: Write| {
+            let mut serializer = Serializer::new();
+            serializer.write_length(5);
+            for value in 0..5 {
+                serializer.write_property_type(kind);
+                if value < 3 {
+                    write(&mut serializer, value);
+                }
+            }
+            loaded(&serializer).validate_structure()
+        };
+        let cases: [(PropertyType, Write, Section); 3] = [
+            (
+                PropertyType::Integer,
+                |serializer, value| serializer.write_int(value as i64),
+                Section::Integers,
+            ),
+            (
+                PropertyType::Bool,
+                |serializer, value| serializer.write_bool(value % 2 == 0),
+                Section::Booleans,
+            ),
+            (
+                PropertyType::String,
+                |serializer, _| serializer.write_string("eu-west"),
+                Section::Strings,
+            ),
+        ];
+        for (kind, write, column) in cases {
+            assert_eq!(
+                short(kind, write),
+                Err(DecodeError::ColumnTagMismatch {
+                    column,
+                    declared: 3,
+                    referenced: 5,
+                }),
+                "{kind:?}"
+            );
+        }
+    }
+}
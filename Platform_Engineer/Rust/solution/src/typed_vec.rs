@@ -0,0 +1,240 @@
+use std::borrow::Cow;
+
+use crate::serializer::{Deserializer, Serializer};
+
+// Arrays of a single kind, where the type already says what every element is. The count goes in
+// the length column like an array's and the elements straight into their pool, without the
+// property type each element of a `write_array` needs.
+//
+// `write_ints`, `write_bools` and `write_strs` write the same thing from an iterator, without a
+// slice to collect it into first. The iterator doesn't need to know its length: the pools are
+// only packed in `finish`, so the length is pushed as a placeholder and filled in once the
+// iterator runs out. Nothing is buffered and the iterator is only run once.
+
+impl<'a> Serializer<'a> {
+    pub fn write_int_vec(&mut self, values: &[i64]) {
+        self.write_length(values.len());
+        for value in values {
+            self.write_int(*value);
+        }
+    }
+
+    // a bit each in the boolean column, next to each other
+    pub fn write_bool_vec(&mut self, values: &[bool]) {
+        self.write_length(values.len());
+        for value in values {
+            self.write_bool(*value);
+        }
+    }
+
+    // `&[&str]` or `&[String]`
+    pub fn write_string_vec<'b: 'a, S: AsRef<str>>(&mut self, values: &'b [S]) {
+        self.write_length(values.len());
+        for value in values {
+            self.write_string(value.as_ref());
+        }
+    }
+
+    pub fn write_ints(&mut self, values: impl IntoIterator<Item = i64>) {
+        self.write_counted_length(|serializer| {
+            values
+                .into_iter()
+                .map(|value| serializer.write_int(value))
+                .count()
+        });
+    }
+
+    pub fn write_bools(&mut self, values: impl IntoIterator<Item = bool>) {
+        self.write_counted_length(|serializer| {
+            values
+                .into_iter()
+                .map(|value| serializer.write_bool(value))
+                .count()
+        });
+    }
+
+    // borrowed `&str`s or `String`s built along the way
+    pub fn write_strs<S: Into<Cow<'a, str>>>(&mut self, values: impl IntoIterator<Item = S>) {
+        self.write_counted_length(|serializer| {
+            values
+                .into_iter()
+                .map(|value| match value.into() {
+                    Cow::Borrowed(value) => serializer.write_string(value),
+                    Cow::Owned(value) => serializer.write_string_owned(value),
+                })
+                .count()
+        });
+    }
+}
+
+impl Deserializer {
+    pub fn take_int_vec(&mut self) -> Option<Vec<i64>> {
+        let count = usize::try_from(self.take_length()?).ok()?;
+        if count > self.integers.len() {
+            return None;
+        }
+        (0..count).map(|_| self.take_int()).collect()
+    }
+
+    pub fn take_bool_vec(&mut self) -> Option<Vec<bool>> {
+        let count = usize::try_from(self.take_length()?).ok()?;
+        if count > self.booleans.len() {
+            return None;
+        }
+        (0..count).map(|_| self.take_bool()).collect()
+    }
+
+    pub fn take_string_vec(&mut self) -> Option<Vec<String>> {
+        let count = usize::try_from(self.take_length()?).ok()?;
+        if count > self.pooled_strings() {
+            return None;
+        }
+        (0..count).map(|_| self.take_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        derive_format,
+        roundtrip::assert_bytes_eq,
+        serializer::{Deserializer, IntoFormat, LoadState, Serializer},
+    };
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Listener {
+            ports: Vec<i64>,
+            hosts: Vec<String>,
+            enabled: bool,
+        }
+    }
+
+    #[test]
+    pub fn typed_vecs_have_no_property_types() {
+        let listener = Listener {
+            ports: vec![80, 443, 8080, -1],
+            hosts: vec!["localhost".to_owned(), "api.internal".to_owned()],
+            enabled: true,
+        };
+        let mut serializer = Serializer::new();
+        listener.serialize(&mut serializer);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        assert_eq!(stats.property_types.values, 0);
+        assert_eq!(stats.lengths.values, 2);
+        assert_eq!(stats.integers.values, 4);
+
+        let mut deserializer = Deserializer::new();
+        assert_eq!(
+            Listener::deserialize(&buffer, &mut deserializer, 0),
+            Some(listener)
+        );
+
+        let empty = Listener {
+            ports: Vec::new(),
+            hosts: Vec::new(),
+            enabled: false,
+        };
+        let mut serializer = Serializer::new();
+        empty.serialize(&mut serializer);
+        serializer.finish(&mut buffer, 0);
+        assert_eq!(
+            Listener::deserialize(&buffer, &mut deserializer, 0),
+            Some(empty)
+        );
+    }
+
+    derive_format! {
+        #[derive(Debug, PartialEq, Eq)]
+        struct Flags {
+            enabled: Vec<bool>,
+        }
+    }
+
+    #[test]
+    pub fn bool_vecs_are_a_bit_each() {
+        let flags = Flags {
+            enabled: (0..64).map(|i| i % 3 == 0 || i % 7 == 0).collect(),
+        };
+        let mut serializer = Serializer::new();
+        flags.serialize(&mut serializer);
+        let mut buffer = Vec::new();
+        let stats = serializer.finish(&mut buffer, 0);
+        assert_eq!(stats.property_types.values, 0);
+        assert_eq!(stats.lengths.values, 1);
+        assert_eq!((stats.booleans.values, stats.booleans.bits), (64, 64));
+
+        let mut deserializer = Deserializer::new();
+        assert_eq!(
+            Flags::deserialize(&buffer, &mut deserializer, 0),
+            Some(flags)
+        );
+    }
+
+    #[test]
+    pub fn iterators_write_typed_vecs() {
+        let ints = || (0..1000).map(|i| i * i - 500);
+        let bools = || (0..1000).map(|i| i % 3 == 0);
+        let names = || (0..1000).map(|i| format!("node{i}.eu-west"));
+        // doesn't know how many it'll give
+        let odd = || names().filter(|name| name.len() % 2 == 1);
+        let regions = ["eu-west", "us-east"];
+
+        let mut serializer = Serializer::new();
+        serializer.write_ints(ints());
+        serializer.write_bools(bools());
+        serializer.write_strs(names());
+        serializer.write_strs(odd());
+        serializer.write_strs(regions);
+        serializer.write_ints([]);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        // the same as collecting them first
+        let (ints_vec, bools_vec): (Vec<i64>, Vec<bool>) = (ints().collect(), bools().collect());
+        let (names_vec, odd_vec): (Vec<String>, Vec<String>) = (names().collect(), odd().collect());
+        let mut serializer = Serializer::new();
+        serializer.write_int_vec(&ints_vec);
+        serializer.write_bool_vec(&bools_vec);
+        serializer.write_string_vec(&names_vec);
+        serializer.write_string_vec(&odd_vec);
+        serializer.write_string_vec(&regions);
+        serializer.write_int_vec(&[]);
+        let mut collected = Vec::new();
+        serializer.finish(&mut collected, 0);
+        assert_bytes_eq(&buffer, &collected);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(deserializer.take_int_vec(), Some(ints_vec));
+        assert_eq!(deserializer.take_bool_vec(), Some(bools_vec));
+        assert_eq!(deserializer.take_string_vec(), Some(names_vec));
+        assert_eq!(deserializer.take_string_vec(), Some(odd_vec));
+        assert_eq!(deserializer.take_string_vec().unwrap(), regions);
+        assert_eq!(deserializer.take_int_vec(), Some(Vec::new()));
+        assert_eq!(deserializer.state(), LoadState::Drained);
+    }
+
+    #[test]
+    pub fn counts_past_the_pool_are_rejected() {
+        let mut serializer = Serializer::new();
+        serializer.write_string_vec(&["a", "b"]);
+        serializer.write_length(3);
+        serializer.write_int(1);
+        serializer.write_length(2);
+        serializer.write_bool(true);
+        let mut buffer = Vec::new();
+        serializer.finish(&mut buffer, 0);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.read_bytes(&buffer, 0).unwrap();
+        assert_eq!(
+            deserializer.take_string_vec(),
+            Some(vec!["a".to_owned(), "b".to_owned()])
+        );
+        assert_eq!(deserializer.take_int_vec(), None);
+        deserializer.take_int();
+        assert_eq!(deserializer.take_bool_vec(), None);
+    }
+}
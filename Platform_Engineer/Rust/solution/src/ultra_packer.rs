@@ -24,6 +24,9 @@ pub const fn bits_per_bundle(max_value: u64, bundle_size: u8) -> u8 {
     (64 - (max_bundle - 1).leading_zeros()) as u8
 }
 
+// upper bound on the bundle sizes `find_optimal_bundle` will search
+pub const MAX_BUNDLE_SIZE: u8 = 40;
+
 pub const fn find_optimal_bundle(max_value: u64) -> (u8, u8) {
     assert!(max_value > 0);
     let naive_bits = max_value.ilog2() + 1;
@@ -32,7 +35,7 @@ pub const fn find_optimal_bundle(max_value: u64) -> (u8, u8) {
     let mut best_bits_per_val = naive_bits as f64;
 
     let mut bundle_size = 1;
-    while bundle_size <= 40u8 {
+    while bundle_size <= MAX_BUNDLE_SIZE {
         // max_value^k - 1
         let Some(max_bundle) = max_value.checked_pow(bundle_size as u32) else {
             break;
@@ -74,6 +77,14 @@ pub fn decode(bundle_size: u8, max_value: u64, mut bundle: u64) -> Vec<u64> {
     values
 }
 
+// `decode` without the allocation, `values.len()` is the bundle size
+pub fn decode_into(max_value: u64, mut bundle: u64, values: &mut [u64]) {
+    for value in values.iter_mut().rev() {
+        *value = bundle % max_value;
+        bundle /= max_value;
+    }
+}
+
 pub fn write_bundle(packer: &mut BitPacker, bits_per_bundle: u8, bundle: u64) {
     let bytes = bundle.to_le_bytes();
     packer.write_bytes_width(&bytes, bits_per_bundle);
@@ -0,0 +1,359 @@
+// Every combination of the writer's options against every way of reading a buffer back. Each
+// corpus entry is written under each combination of `FLAGS` and has to decode to the same values
+// under every one of `READERS`, and to fail with the error `REFUSALS` expects from readers that
+// can't take it rather than decoding to something else. Buffers older revisions wrote (`legacy`)
+// go through the same readers.
+//
+// A new option is one more line in `FLAGS`, a new way of reading one more line in `READERS`.
+use solution::{
+    error::DecodeError,
+    format_spec::{FORMAT_SPEC, INT_HEADER_TABLES},
+    int_plan::IntHeaderPlan,
+    limits::{DecodeLimits, Limit},
+    provenance::Provenance,
+    record::Schema,
+    serializer::{
+        Deserializer, LoadState, PropertyType, PropertyValue, Serializer, StringCodec,
+        canonicalize, verify,
+    },
+};
+
+const VERSION: u8 = 1;
+const FORMAT_ID: u16 = 0x1234;
+const PRODUCER: &str = "compat-matrix";
+const TIMESTAMP: u64 = 1_700_000_000;
+
+enum Flag {
+    // one of the serializer's `set_*` options
+    Set(fn(&mut Serializer)),
+    // the strings are written with `StringCodec::Raw`
+    RawStrings,
+    // `finish_aligned` rather than `finish`
+    Padded,
+    // the values are written again as the tail array
+    Tail,
+    // `Serializer::with_schema`
+    Schema,
+}
+
+const FLAGS: &[(&str, Flag)] = &[
+    ("front coding", Flag::Set(|s| s.set_front_coding(true))),
+    ("lz strings", Flag::Set(|s| s.set_lz_strings(true))),
+    ("column offsets", Flag::Set(|s| s.set_column_offsets(true))),
+    (
+        "section checksums",
+        Flag::Set(|s| s.set_section_checksums(true)),
+    ),
+    ("kind markers", Flag::Set(|s| s.set_kind_markers(true))),
+    (
+        "int table",
+        Flag::Set(|s| s.set_int_header_plan(last_int_table())),
+    ),
+    ("format id", Flag::Set(|s| s.set_format_id(FORMAT_ID))),
+    (
+        "provenance",
+        Flag::Set(|s| s.set_provenance(TIMESTAMP, PRODUCER).unwrap()),
+    ),
+    ("raw strings", Flag::RawStrings),
+    ("padded", Flag::Padded),
+    ("tail", Flag::Tail),
+    ("schema", Flag::Schema),
+];
+
+type Reader = fn(&[u8]) -> Result<Deserializer, DecodeError>;
+
+const READERS: &[(&str, Reader)] = &[
+    ("load", |bytes| load(Deserializer::new(), bytes, VERSION)),
+    ("untrusted", |bytes| {
+        load(Deserializer::new_untrusted(), bytes, VERSION)
+    }),
+    ("verified", |bytes| {
+        verify(bytes, VERSION)?;
+        load(Deserializer::new(), bytes, VERSION)
+    }),
+    ("canonicalized", |bytes| {
+        load(Deserializer::new(), &canonicalize(bytes)?, VERSION)
+    }),
+];
+
+// Readers that mustn't accept the buffer and whether they refused it for the right reason. The
+// limits only refuse buffers with values in them.
+type Refusal = (&'static str, Reader, fn(&DecodeError) -> bool);
+
+const REFUSALS: &[Refusal] = &[
+    (
+        "another version",
+        |bytes| load(Deserializer::new(), bytes, VERSION + 1),
+        |error| {
+            *error
+                == DecodeError::VersionMismatch {
+                    expected: VERSION + 1,
+                    found: VERSION,
+                }
+        },
+    ),
+    (
+        "no column values",
+        |bytes| limited(DecodeLimits::relaxed().max_column_values(0), bytes),
+        |error| {
+            matches!(
+                error,
+                DecodeError::LimitExceeded {
+                    limit: Limit::ColumnValues,
+                    max: 0
+                }
+            )
+        },
+    ),
+    (
+        "no decoded bytes",
+        |bytes| limited(DecodeLimits::relaxed().max_decoded_bytes(0), bytes),
+        |error| {
+            matches!(
+                error,
+                DecodeError::LimitExceeded {
+                    limit: Limit::DecodedBytes,
+                    max: 0
+                }
+            )
+        },
+    ),
+    (
+        // verified, `load` doesn't look past the values for what's missing from the padding
+        "truncated",
+        |bytes| {
+            let bytes = &bytes[..bytes.len() - 1];
+            verify(bytes, VERSION)?;
+            load(Deserializer::new(), bytes, VERSION)
+        },
+        |error| matches!(error, DecodeError::Truncated(_)),
+    ),
+];
+
+fn load(
+    mut deserializer: Deserializer,
+    bytes: &[u8],
+    version: u8,
+) -> Result<Deserializer, DecodeError> {
+    deserializer.load(bytes, version)?;
+    Ok(deserializer)
+}
+
+fn limited(limits: DecodeLimits, bytes: &[u8]) -> Result<Deserializer, DecodeError> {
+    load(Deserializer::with_limits(limits), bytes, VERSION)
+}
+
+fn last_int_table() -> IntHeaderPlan {
+    IntHeaderPlan::from_table(INT_HEADER_TABLES.len() as u8 - 1).unwrap()
+}
+
+fn corpus() -> Vec<(&'static str, Vec<PropertyValue>)> {
+    use PropertyValue::{Array, Bool, Integer, String};
+    let config = vec![
+        Integer(8080),
+        String("service.example.internal".to_owned()),
+        Bool(true),
+        Array(vec![Integer(1), Integer(2), Integer(3)]),
+        String("service.example.external".to_owned()),
+    ];
+    let keys = (0..12)
+        .flat_map(|i| {
+            [
+                String(format!("metrics.host{:02}.cpu_{i}", i % 4)),
+                Integer(i64::from(i) * 1_000 - 50_000),
+            ]
+        })
+        .collect();
+    let edges = vec![
+        Integer(i64::MIN),
+        Integer(i64::MAX),
+        String(std::string::String::new()),
+        String("ünïcode 日本語 a\0b".to_owned()),
+        Array(vec![
+            Array(vec![Bool(false), Array(Vec::new())]),
+            String("x".to_owned()),
+        ]),
+    ];
+    vec![
+        ("config", config),
+        ("keys", keys),
+        ("edges", edges),
+        ("empty", Vec::new()),
+    ]
+}
+
+fn schema() -> Schema {
+    Schema::new([
+        ("port", PropertyType::Integer),
+        ("host", PropertyType::String),
+    ])
+}
+
+fn enabled(config: u32) -> impl Iterator<Item = &'static (&'static str, Flag)> {
+    FLAGS
+        .iter()
+        .enumerate()
+        .filter(move |(index, _)| config & 1 << index != 0)
+        .map(|(_, flag)| flag)
+}
+
+fn has(config: u32, wanted: fn(&Flag) -> bool) -> bool {
+    enabled(config).any(|(_, flag)| wanted(flag))
+}
+
+fn describe(config: u32) -> String {
+    let names: Vec<&str> = enabled(config).map(|(name, _)| *name).collect();
+    match names.is_empty() {
+        true => "default".to_owned(),
+        false => names.join(" + "),
+    }
+}
+
+fn write(values: &[PropertyValue], config: u32) -> Vec<u8> {
+    let mut serializer = match has(config, |flag| matches!(flag, Flag::Schema)) {
+        true => Serializer::with_schema(&schema()),
+        false => Serializer::new(),
+    };
+    for (_, flag) in enabled(config) {
+        if let Flag::Set(set) = flag {
+            set(&mut serializer);
+        }
+    }
+    let codec = match has(config, |flag| matches!(flag, Flag::RawStrings)) {
+        true => StringCodec::Raw,
+        false => StringCodec::Auto,
+    };
+    for value in values {
+        match value {
+            PropertyValue::Integer(int) => serializer.write_int(*int),
+            PropertyValue::Bool(bool) => serializer.write_bool(*bool),
+            PropertyValue::String(string) => serializer.write_string_with(string, codec),
+            PropertyValue::Array(array) => serializer.write_array(array),
+        }
+    }
+    if has(config, |flag| matches!(flag, Flag::Tail)) {
+        serializer.write_tail_array(values);
+    }
+    let mut buffer = Vec::new();
+    match has(config, |flag| matches!(flag, Flag::Padded)) {
+        true => serializer.finish_aligned(&mut buffer, VERSION, 16),
+        false => serializer.finish(&mut buffer, VERSION),
+    };
+    buffer
+}
+
+// everything a reader can tell about a buffer
+#[derive(Debug, PartialEq)]
+struct Decoded {
+    values: Vec<PropertyValue>,
+    tail: Option<Vec<PropertyValue>>,
+    format_id: u16,
+    provenance: Option<Provenance>,
+    schema: Option<Schema>,
+}
+
+fn expected(values: &[PropertyValue], config: u32) -> Decoded {
+    let set = |name| enabled(config).any(|(flag, _)| *flag == name);
+    Decoded {
+        values: values.to_vec(),
+        tail: set("tail").then(|| values.to_vec()),
+        format_id: if set("format id") { FORMAT_ID } else { 0 },
+        provenance: set("provenance").then(|| Provenance {
+            timestamp: TIMESTAMP,
+            producer: PRODUCER.to_owned(),
+        }),
+        schema: set("schema").then(schema),
+    }
+}
+
+// takes the values as the corpus entry says they were written
+fn decode(mut deserializer: Deserializer, written: &[PropertyValue]) -> Decoded {
+    let values = written
+        .iter()
+        .map_while(|value| match value {
+            PropertyValue::Integer(_) => deserializer.take_int().map(PropertyValue::Integer),
+            PropertyValue::Bool(_) => deserializer.take_bool().map(PropertyValue::Bool),
+            PropertyValue::String(_) => deserializer.take_string().map(PropertyValue::String),
+            PropertyValue::Array(_) => deserializer.take_array().map(PropertyValue::Array),
+        })
+        .collect();
+    let tail = deserializer.take_tail_array();
+    assert_eq!(deserializer.state(), LoadState::Drained);
+    Decoded {
+        values,
+        tail,
+        format_id: deserializer.format_id(),
+        provenance: deserializer.provenance().cloned(),
+        schema: deserializer.schema().cloned(),
+    }
+}
+
+#[test]
+fn every_writer_against_every_reader() {
+    for (entry, values) in corpus() {
+        for config in 0..1u32 << FLAGS.len() {
+            let buffer = write(&values, config);
+            let expected = expected(&values, config);
+            let case = || format!("{entry} written with {}", describe(config));
+
+            for (reader, read) in READERS {
+                let deserializer =
+                    read(&buffer).unwrap_or_else(|error| panic!("{}, {reader}: {error}", case()));
+                assert_eq!(
+                    decode(deserializer, &values),
+                    expected,
+                    "{}, {reader}",
+                    case()
+                );
+            }
+
+            let columns_empty =
+                values.is_empty() && !has(config, |flag| matches!(flag, Flag::Schema));
+            for (reader, read, refused) in REFUSALS {
+                if columns_empty && reader.starts_with("no ") {
+                    continue;
+                }
+                match read(&buffer) {
+                    Ok(_) => panic!("{}, {reader}: decoded", case()),
+                    Err(error) => assert!(refused(&error), "{}, {reader}: {error:?}", case()),
+                }
+            }
+        }
+    }
+}
+
+// `(revision, hex, the values as written)`
+fn legacy() -> Vec<(u64, &'static str, Vec<PropertyValue>)> {
+    use PropertyValue::{Array, Bool, Integer, String};
+    vec![(
+        // the "tagged array" conformance vector, with 2 bit tags
+        14,
+        "015c230a15907fffffffffffffffff63ed0f690c00",
+        vec![Array(vec![
+            Integer(3),
+            String("x".to_owned()),
+            Bool(true),
+            Array(vec![Integer(-2), Array(Vec::new())]),
+        ])],
+    )]
+}
+
+#[test]
+fn older_revisions_against_every_reader() {
+    for (revision, hex, values) in legacy() {
+        let buffer: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        assert!(revision < FORMAT_SPEC.revision);
+        for (reader, read) in READERS {
+            let deserializer = read(&buffer)
+                .unwrap_or_else(|error| panic!("revision {revision}, {reader}: {error}"));
+            assert_eq!(
+                decode(deserializer, &values),
+                expected(&values, 0),
+                "revision {revision}, {reader}"
+            );
+        }
+    }
+}
@@ -0,0 +1,52 @@
+// Decoding is a library call, it shouldn't write anything to stdout/stderr. Without a harness so
+// the decode can run in a child process where its output is captured for real (the test harness
+// only captures `print!` and friends, not writes to the file descriptors).
+use std::process::{Command, ExitCode};
+
+use solution::serializer::{Deserializer, PropertyValue, Serializer};
+
+const CHILD: &str = "QUIET_DECODE_CHILD";
+
+fn decode() {
+    let array: Vec<PropertyValue> = (0..10_000)
+        .map(|i| match i % 3 {
+            0 => PropertyValue::Integer(i),
+            1 => PropertyValue::String(format!("value {i}")),
+            _ => PropertyValue::Array(vec![PropertyValue::Bool(i % 2 == 0)]),
+        })
+        .collect();
+    let mut serializer = Serializer::new();
+    serializer.write_array(&array);
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, 0);
+
+    let mut deserializer = Deserializer::new();
+    deserializer.read_bytes(&buffer, 0).expect("decode failed");
+    assert_eq!(deserializer.take_array(), Some(array));
+}
+
+fn main() -> ExitCode {
+    if std::env::var_os(CHILD).is_some() {
+        decode();
+        return ExitCode::SUCCESS;
+    }
+
+    let exe = std::env::current_exe().expect("no path to the test binary");
+    let output = Command::new(exe)
+        .env(CHILD, "1")
+        .output()
+        .expect("failed to run the decode");
+    assert!(output.status.success(), "decode failed: {output:?}");
+    assert!(
+        output.stdout.is_empty(),
+        "decode wrote to stdout: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(
+        output.stderr.is_empty(),
+        "decode wrote to stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    println!("test decode_is_quiet ... ok");
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,212 @@
+// Conformance vectors for other implementations of the format: `tests/vectors/format_spec.json` is
+// `FORMAT_SPEC` and `tests/vectors/vectors.json` is a list of canonical inputs and the exact bytes
+// `finish` writes for them. Each input is the values in the order they were written, as
+// `{"int": n}`, `{"bool": b}`, `{"string": s}` or `{"array": [...]}`.
+//
+// Any change to what's written fails here until the files are regenerated on purpose with
+// `UPDATE_VECTORS=1 cargo test --test vectors`, and the diff to them is the format change.
+use std::{fmt::Write, fs, path::PathBuf};
+
+use solution::{
+    format_spec::{FORMAT_SPEC, INT_HEADER_WIDTHS},
+    serializer::{PropertyValue, Serializer},
+};
+
+const VERSION: u8 = 1;
+const UPDATE: &str = "UPDATE_VECTORS";
+
+struct Vector {
+    name: String,
+    input: Vec<PropertyValue>,
+}
+
+fn vector(name: impl Into<String>, input: Vec<PropertyValue>) -> Vector {
+    Vector {
+        name: name.into(),
+        input,
+    }
+}
+
+fn vectors() -> Vec<Vector> {
+    use PropertyValue::{Array, Bool, Integer, String};
+
+    let mut vectors = vec![vector("empty", Vec::new())];
+
+    // either side of every boundary between the int widths
+    let mut ints = vec![0, -1, i64::MIN, i64::MAX];
+    for width in INT_HEADER_WIDTHS.into_iter().filter(|width| *width < 63) {
+        ints.extend([(1 << width) - 1, 1 << width]);
+    }
+    for int in ints {
+        vectors.push(vector(format!("int {int}"), vec![Integer(int)]));
+    }
+    vectors.push(vector("constant ints", vec![Integer(42); 5]));
+    vectors.push(vector(
+        "frame of reference ints",
+        [1_000_000, 1_000_003, 1_000_001].map(Integer).to_vec(),
+    ));
+
+    vectors.push(vector(
+        "bool run",
+        [true, true, false, true, false, false, false, true, true]
+            .map(Bool)
+            .to_vec(),
+    ));
+
+    for string in ["", "config.timeout_ms", "hello world", "Zq!@XJ", "ünïcode"] {
+        vectors.push(vector(
+            format!("string {string:?}"),
+            vec![String(string.to_owned())],
+        ));
+    }
+
+    vectors.push(vector(
+        "tagged array",
+        vec![Array(vec![
+            Integer(3),
+            String("x".to_owned()),
+            Bool(true),
+            Array(vec![Integer(-2), Array(Vec::new())]),
+        ])],
+    ));
+    vectors.push(vector(
+        "mixed",
+        vec![
+            String("port".to_owned()),
+            Integer(8080),
+            Bool(false),
+            Array(vec![Bool(true)]),
+            String("host".to_owned()),
+        ],
+    ));
+    vectors
+}
+
+fn encode(input: &[PropertyValue]) -> Vec<u8> {
+    let mut serializer = Serializer::new();
+    for value in input {
+        match value {
+            PropertyValue::Integer(int) => serializer.write_int(*int),
+            PropertyValue::Bool(bool) => serializer.write_bool(*bool),
+            PropertyValue::String(string) => serializer.write_string(string),
+            PropertyValue::Array(array) => serializer.write_array(array),
+        }
+    }
+    let mut buffer = Vec::new();
+    serializer.finish(&mut buffer, VERSION);
+    buffer
+}
+
+fn value_json(value: &PropertyValue) -> String {
+    match value {
+        PropertyValue::Integer(int) => format!("{{\"int\": {int}}}"),
+        PropertyValue::Bool(bool) => format!("{{\"bool\": {bool}}}"),
+        PropertyValue::String(string) => format!("{{\"string\": {}}}", string_json(string)),
+        PropertyValue::Array(array) => format!("{{\"array\": {}}}", values_json(array)),
+    }
+}
+
+fn values_json(values: &[PropertyValue]) -> String {
+    let values: Vec<String> = values.iter().map(value_json).collect();
+    format!("[{}]", values.join(", "))
+}
+
+// non-ascii is left as it is, JSON is UTF-8
+fn string_json(string: &str) -> String {
+    let mut json = String::from("\"");
+    for c in string.chars() {
+        match c {
+            '"' | '\\' => {
+                json.push('\\');
+                json.push(c);
+            }
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+fn vectors_json() -> String {
+    let entries: Vec<String> = vectors()
+        .iter()
+        .map(|vector| {
+            format!(
+                "    {{\"name\": {}, \"input\": {}, \"hex\": \"{}\"}}",
+                string_json(&vector.name),
+                values_json(&vector.input),
+                hex(&encode(&vector.input))
+            )
+        })
+        .collect();
+    format!(
+        "{{\n  \"version\": {VERSION},\n  \"vectors\": [\n{}\n  ]\n}}\n",
+        entries.join(",\n")
+    )
+}
+
+fn check(file: &str, expected: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/vectors")
+        .join(file);
+    if std::env::var_os(UPDATE).is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, expected).unwrap();
+        return;
+    }
+    let found = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()));
+    // line by line so a failure names the vector that changed
+    for (found, expected) in found.lines().zip(expected.lines()) {
+        assert_eq!(
+            found, expected,
+            "{file} is out of date, regenerate it with {UPDATE}=1 if the format change is intended"
+        );
+    }
+    assert_eq!(
+        found.lines().count(),
+        expected.lines().count(),
+        "{file} is out of date, regenerate it with {UPDATE}=1 if the format change is intended"
+    );
+}
+
+#[test]
+fn format_spec_matches() {
+    check("format_spec.json", &FORMAT_SPEC.to_json());
+}
+
+#[test]
+fn vectors_match() {
+    check("vectors.json", &vectors_json());
+}
+
+#[test]
+fn vectors_decode() {
+    use solution::serializer::Deserializer;
+
+    for vector in vectors() {
+        let mut deserializer = Deserializer::new();
+        deserializer
+            .read_bytes(&encode(&vector.input), VERSION)
+            .unwrap_or_else(|| panic!("{} doesn't decode", vector.name));
+        for value in &vector.input {
+            let taken = match value {
+                PropertyValue::Integer(_) => deserializer.take_int().map(PropertyValue::Integer),
+                PropertyValue::Bool(_) => deserializer.take_bool().map(PropertyValue::Bool),
+                PropertyValue::String(_) => deserializer.take_string().map(PropertyValue::String),
+                PropertyValue::Array(_) => deserializer.take_array().map(PropertyValue::Array),
+            };
+            assert_eq!(taken.as_ref(), Some(value), "{}", vector.name);
+        }
+    }
+}